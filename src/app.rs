@@ -1,32 +1,49 @@
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::{self, BufRead, BufReader, Stdout};
+use std::io::{self, BufRead, BufReader, IsTerminal, Stdout, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
 use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use crossterm::cursor;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use crossterm::execute;
 use crossterm::terminal::{
-    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    self, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use ratatui::backend::CrosstermBackend;
-use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Position, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph};
-use ratatui::{Frame, Terminal};
+use ratatui::widgets::{
+    Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap,
+};
+use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use crate::model::{CommandCatalog, CommandEntry, CommandSource, ParamType, render_template};
-use crate::{config, provider};
+use crate::config::{
+    ColorMode, ExecTarget, ProvidersConfig, SecretsConfig, UsageScale, parse_timeout,
+};
+use crate::events::{EventSocket, RunEvent};
+use crate::history::{self, HistoryEntry};
+use crate::i18n::{self, Locale};
+use crate::model::{
+    CommandCatalog, CommandEntry, CommandGuard, CommandHooks, CommandSource, GuardVerdict,
+    ParamSpec, ParamType, RetryPolicy, insert_context_values, remote_command_line, render_template,
+};
+use crate::redact::{MASK, Redactor};
+use crate::theme::Theme;
+use crate::{config, favorites, provider, secrets, snapshots};
 
 const MAX_CHAT_LINES: usize = 600;
 const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
@@ -37,19 +54,135 @@ type TuiTerminal = Terminal<CrosstermBackend<Stdout>>;
 pub struct RankingSettings {
     pub usage_enabled: bool,
     pub usage_weight: i64,
+    pub usage_scale: UsageScale,
+    pub usage_cap: Option<i64>,
+    pub decay_half_life_days: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputSettings {
+    pub max_line_bytes: usize,
+    pub pretty_json: bool,
+    pub color: ColorMode,
+    /// `[output] pty`: stream session commands through a pseudo-terminal.
+    pub pty: bool,
+    /// `[output] timeout`, for commands without their own.
+    pub timeout: Option<Duration>,
+    /// `[output] target`, for commands without their own.
+    pub target: ExecTarget,
+    /// `[output] terminal`: the launcher for `target = "terminal"`.
+    pub terminal: Option<String>,
+}
+
+/// How the TUI session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionExit {
+    /// The user quit without handing off to a command.
+    Canceled,
+    /// A command was run on exit (`Option+Enter`); holds its exit code.
+    Executed(i32),
 }
 
 #[derive(Debug, Clone)]
 pub struct RuntimeContext {
     pub cwd: PathBuf,
     pub explicit_config_path: Option<PathBuf>,
+    /// The global config the loaded project config was merged over.
+    pub global_config_path: Option<PathBuf>,
+    pub provider_hints: Vec<String>,
+    /// Whether usage counts and recent projects are read from and written to disk.
+    pub persist_usage: bool,
+    pub color_support: ColorSupport,
+    /// Providers loaded on background threads once the TUI is up.
+    pub providers: ProvidersConfig,
+    /// Draw below the shell prompt instead of on the alternate screen.
+    pub inline: bool,
+    /// `[events] socket`: where JSON run events are served for this session.
+    pub events_socket: Option<PathBuf>,
+    pub poll: PollSettings,
+    /// `[ui] job_panes`: scheduled and background jobs shown in their own Session sub-pane.
+    pub job_panes: usize,
+    /// `[ui] mouse`: capture clicks and the scroll wheel.
+    pub mouse: bool,
+    pub theme: Theme,
+    pub locale: Locale,
+    pub secrets: SecretsConfig,
+    /// Global `[hooks]`; commands fall back to these per hook.
+    pub hooks: CommandHooks,
+    /// `[privacy] redact`: masks matching output before it is kept or sent anywhere.
+    pub redactor: Redactor,
+}
+
+/// Event poll intervals; the loop backs off to `idle_interval` once nothing has happened for
+/// [`IDLE_AFTER`].
+#[derive(Debug, Clone, Copy)]
+pub struct PollSettings {
+    pub interval: Duration,
+    pub idle_interval: Duration,
+}
+
+const IDLE_AFTER: Duration = Duration::from_secs(2);
+
+/// Colors the terminal can show; RGB colors are downgraded to fit when drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    /// `NO_COLOR` or a dumb terminal: no colors, selection shown reversed.
+    Monochrome,
+}
+
+impl ColorSupport {
+    pub fn detect() -> Self {
+        // Windows Terminal shows 24-bit color but sets no COLORTERM.
+        let colorterm = std::env::var("COLORTERM").ok().or_else(|| {
+            std::env::var_os("WT_SESSION")
+                .filter(|_| cfg!(windows))
+                .map(|_| "truecolor".to_string())
+        });
+        Self::from_env(
+            std::env::var("NO_COLOR").ok().as_deref(),
+            colorterm.as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+            cfg!(windows),
+        )
+    }
+
+    /// An unset `TERM` means a dumb terminal only on unix: Windows consoles never set it, and
+    /// render ANSI colors once crossterm turns on VT processing.
+    fn from_env(
+        no_color: Option<&str>,
+        colorterm: Option<&str>,
+        term: Option<&str>,
+        windows: bool,
+    ) -> Self {
+        if no_color.is_some_and(|value| !value.is_empty()) {
+            return ColorSupport::Monochrome;
+        }
+        if colorterm.is_some_and(|value| matches!(value, "truecolor" | "24bit")) {
+            return ColorSupport::TrueColor;
+        }
+        match term.unwrap_or_default() {
+            "" if windows => ColorSupport::Ansi16,
+            "" | "dumb" => ColorSupport::Monochrome,
+            term if term.contains("256color") => ColorSupport::Ansi256,
+            term if term.contains("direct") => ColorSupport::TrueColor,
+            _ => ColorSupport::Ansi16,
+        }
+    }
 }
 
 struct ReloadPayload {
     commands: Vec<CommandEntry>,
     config_path: Option<PathBuf>,
+    global_config_path: Option<PathBuf>,
     provider_aliases: HashMap<String, String>,
     ranking: RankingSettings,
+    output: OutputSettings,
+    guard: CommandGuard,
+    providers: ProvidersConfig,
+    hooks: CommandHooks,
 }
 
 enum InternalTaskResult {
@@ -58,6 +191,11 @@ enum InternalTaskResult {
         path: PathBuf,
         payload: ReloadPayload,
     },
+    Switched {
+        cwd: PathBuf,
+        payload: ReloadPayload,
+    },
+    Report(Vec<String>),
     Error(String),
 }
 
@@ -66,72 +204,541 @@ pub fn run_tui(
     config_path: Option<&Path>,
     provider_aliases: HashMap<String, String>,
     ranking: RankingSettings,
+    output: OutputSettings,
+    guard: CommandGuard,
     runtime: RuntimeContext,
-) -> Result<()> {
-    let mut terminal = init_terminal()?;
+) -> Result<SessionExit> {
+    if runtime.persist_usage {
+        let _ = record_recent_project(&project_root(&runtime.cwd));
+    }
+    let inline = runtime.inline;
+    let mut terminal = init_terminal(inline, runtime.mouse)?;
     let mut app = AppState::new(
         commands,
         config_path.map(Path::to_path_buf),
         provider_aliases,
         ranking,
+        output,
+        guard,
         runtime,
     );
+    app.start_provider_loads();
 
     match run_loop(&mut terminal, &mut app) {
         Ok(LoopExit::NeedsRestore) => {
-            restore_terminal(&mut terminal)?;
-            Ok(())
+            restore_terminal(&mut terminal, inline)?;
+            Ok(SessionExit::Canceled)
         }
-        Ok(LoopExit::AlreadyRestored) => Ok(()),
+        Ok(LoopExit::AlreadyRestored(code)) => Ok(SessionExit::Executed(code)),
         Err(err) => {
-            let _ = restore_terminal(&mut terminal);
+            let _ = restore_terminal(&mut terminal, inline);
             Err(err)
         }
     }
 }
 
-fn init_terminal() -> Result<TuiTerminal> {
+/// Average search latency for one query, as reported by `fzc bench`.
+pub struct MatchTiming {
+    pub query: String,
+    pub average: Duration,
+    pub matches: usize,
+}
+
+/// Runs the same search path as the TUI for each query, `iterations` times.
+pub fn measure_match_latency(
+    commands: Vec<CommandEntry>,
+    provider_aliases: HashMap<String, String>,
+    ranking: RankingSettings,
+    runtime: RuntimeContext,
+    queries: &[String],
+    iterations: usize,
+) -> Vec<MatchTiming> {
+    let mut app = AppState::new(
+        commands,
+        None,
+        provider_aliases,
+        ranking,
+        OutputSettings {
+            max_line_bytes: 0,
+            pretty_json: false,
+            color: ColorMode::default(),
+            pty: false,
+            timeout: None,
+            target: ExecTarget::Session,
+            terminal: None,
+        },
+        CommandGuard::default(),
+        runtime,
+    );
+    let iterations = iterations.max(1);
+
+    queries
+        .iter()
+        .map(|query| {
+            app.query = query.clone();
+            let started = Instant::now();
+            for _ in 0..iterations {
+                app.refresh_filtered();
+            }
+            MatchTiming {
+                query: query.clone(),
+                average: started.elapsed() / iterations as u32,
+                matches: app.filtered.len(),
+            }
+        })
+        .collect()
+}
+
+/// What `fzc run` asks for: a catalog command by name plus `--param` values.
+pub struct HeadlessRun {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub environment: Option<String>,
+    /// Runs commands matching `[safety] confirm` patterns without asking.
+    pub assume_yes: bool,
+}
+
+/// Runs one catalog command, then the ones it chains with `then`, without the TUI; missing params
+/// are asked for on stdin when it is a terminal. Returns the exit code of the last command run.
+pub fn run_headless(
+    commands: Vec<CommandEntry>,
+    ranking: RankingSettings,
+    output: OutputSettings,
+    guard: CommandGuard,
+    runtime: RuntimeContext,
+    run: HeadlessRun,
+) -> Result<i32> {
+    let interactive = io::stdin().is_terminal();
+    let mut app = AppState::new(
+        commands,
+        None,
+        HashMap::new(),
+        ranking,
+        output,
+        guard,
+        runtime,
+    );
+    let index = find_command_by_name(&app.commands, &run.name)?;
+    let command = &app.commands[index];
+
+    let names = || {
+        command
+            .environments
+            .iter()
+            .map(|environment| environment.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let environment = match &run.environment {
+        Some(name) => Some(
+            command
+                .environments
+                .iter()
+                .position(|environment| environment.name.eq_ignore_ascii_case(name))
+                .with_context(|| {
+                    format!(
+                        "command '{}' has no environment '{name}' (available: {})",
+                        command.name,
+                        names()
+                    )
+                })?,
+        ),
+        None if command.environments.is_empty() => None,
+        None if interactive => Some(ask_environment(command)?),
+        None => bail!(
+            "command '{}' needs --environment (one of: {})",
+            command.name,
+            names()
+        ),
+    };
+
+    let values = resolve_headless_values(command, environment, &run.params, &mut |param| {
+        if interactive {
+            ask_param(param).map(Some)
+        } else {
+            Ok(None)
+        }
+    })?;
+    let request = app
+        .resolve_run_request(index, values, environment, false)
+        .map_err(anyhow::Error::msg)?;
+
+    let (verdict, name, warning) = app.check_chain(&request);
+    if let Some(banner) = &warning {
+        eprintln!("{banner}");
+    }
+    match verdict {
+        GuardVerdict::Allow => {}
+        GuardVerdict::Warn(reason) => {
+            eprintln!("{}", warning_banner(name, &reason));
+            if interactive && !run.assume_yes {
+                read_stdin_line("Press Enter to run it, Ctrl+C to cancel: ")?;
+            }
+        }
+        GuardVerdict::Deny(pattern) => {
+            bail!("refusing to run '{name}': matches safety pattern '{pattern}'")
+        }
+        GuardVerdict::Confirm(_) if run.assume_yes => {}
+        GuardVerdict::Confirm(pattern) if interactive => {
+            let answer = read_stdin_line(&format!(
+                "'{name}' matches safety pattern '{pattern}'. Type yes to run: "
+            ))?;
+            if answer != "yes" {
+                bail!("run canceled");
+            }
+        }
+        GuardVerdict::Confirm(pattern) => {
+            bail!("'{name}' matches safety pattern '{pattern}'; pass --yes to run it")
+        }
+    }
+
+    let mut request = request;
+    let pipeline = request.pipeline.take();
+    if let Some(pipeline) = &pipeline {
+        app.record_usage(&pipeline.usage_key);
+    }
+    let then = std::mem::take(&mut request.then);
+    let total = then.len() + 1;
+    let mut code = 0;
+    let mut stop = false;
+    for (position, request) in std::iter::once(request).chain(then).enumerate() {
+        if stop {
+            eprintln!(
+                "fzc: stopped: {} remaining command(s) not run",
+                total - position
+            );
+            break;
+        }
+        match &pipeline {
+            Some(pipeline) => eprintln!(
+                "fzc: [{} {}/{total}] {}",
+                pipeline.name,
+                position + 1,
+                request.display_name
+            ),
+            None if position > 0 => eprintln!(
+                "fzc: [then {position}/{}] {}",
+                total - 1,
+                request.display_name
+            ),
+            None => {}
+        }
+        let continue_on_error = request.continue_on_error;
+        let request = app.with_secrets(request)?;
+        let started = Instant::now();
+        let (exit_code, timed_out) = run_inherit_with_retries(&request)?;
+        code = exit_code;
+        app.record_usage(&request.usage_key);
+        app.record_history(&request, Some(code), timed_out);
+        run_hook_inherit(&app.guard, &request, Some(code), started.elapsed());
+        stop = code != 0 && !continue_on_error;
+    }
+    Ok(code)
+}
+
+/// Finds a command by name, or by `provider::name` when several providers share it.
+fn find_command_by_name(commands: &[CommandEntry], name: &str) -> Result<usize> {
+    let matches: Vec<usize> = commands
+        .iter()
+        .enumerate()
+        .filter(|(_, command)| {
+            command.name.eq_ignore_ascii_case(name)
+                || command_usage_key(command).eq_ignore_ascii_case(name)
+        })
+        .map(|(index, _)| index)
+        .collect();
+    match matches.as_slice() {
+        [index] => Ok(*index),
+        [] => bail!("no command named '{name}'"),
+        _ => {
+            let keys: Vec<String> = matches
+                .iter()
+                .map(|index| command_usage_key(&commands[*index]))
+                .collect();
+            bail!("'{name}' is ambiguous; use one of: {}", keys.join(", "))
+        }
+    }
+}
+
+/// Fills param values from `--param` pairs, fixed values, `ask` (when it returns an answer), and
+/// defaults, in that order.
+fn resolve_headless_values(
+    command: &CommandEntry,
+    environment: Option<usize>,
+    given: &[(String, String)],
+    ask: &mut dyn FnMut(&ParamSpec) -> Result<Option<String>>,
+) -> Result<HashMap<String, String>> {
+    if let Some((key, _)) = given
+        .iter()
+        .find(|(key, _)| !command.params.iter().any(|param| param.name == *key))
+    {
+        bail!("command '{}' has no param '{key}'", command.name);
+    }
+
+    let mut values = environment
+        .and_then(|index| command.environments.get(index))
+        .map(|environment| environment.vars.clone())
+        .unwrap_or_default();
+    for param in &command.params {
+        if values.contains_key(&param.name) {
+            continue;
+        }
+        let given = given
+            .iter()
+            .rev()
+            .find(|(key, _)| *key == param.name)
+            .map(|(_, value)| value.clone());
+        let enabled = param_enabled(param, &values);
+
+        let value = match param.kind {
+            ParamType::Value | ParamType::Choice(_) | ParamType::Path(_) => {
+                match given.or_else(|| param.value_value.clone()) {
+                    Some(value) => value,
+                    None if !enabled => String::new(),
+                    None if param.requires_input() => {
+                        match ask(param)?.filter(|answer| !answer.is_empty()) {
+                            Some(answer) => answer,
+                            None => match &param.default_value {
+                                Some(default) => default.clone(),
+                                None if param.required => bail!(
+                                    "missing value for '{}': pass --param {}=<value>",
+                                    param.name,
+                                    param.name
+                                ),
+                                None => String::new(),
+                            },
+                        }
+                    }
+                    None => param.default_value.clone().unwrap_or_default(),
+                }
+            }
+            ParamType::Flag => {
+                let default = param.default_flag.unwrap_or(false);
+                let on = match (given, param.value_flag) {
+                    (Some(raw), _) => parse_flag_input(&raw, default).with_context(|| {
+                        format!("param '{}' expects yes or no, got '{raw}'", param.name)
+                    })?,
+                    (None, Some(fixed)) => fixed,
+                    (None, None) if !enabled => false,
+                    (None, None) => ask(param)?
+                        .and_then(|answer| parse_flag_input(&answer, default))
+                        .unwrap_or(default),
+                };
+                if on {
+                    param.flag_token()
+                } else {
+                    String::new()
+                }
+            }
+        };
+        if let ParamType::Choice(options) = &param.kind
+            && !value.is_empty()
+            && !options.contains(&value)
+        {
+            bail!(
+                "'{value}' is not an option for '{}': expected one of {}",
+                param.name,
+                options.join(", ")
+            );
+        }
+        values.insert(param.name.clone(), value);
+    }
+    Ok(values)
+}
+
+fn ask_param(param: &ParamSpec) -> Result<String> {
+    let hint = match &param.kind {
+        ParamType::Value | ParamType::Path(_) => param
+            .default_value
+            .as_deref()
+            .map(|default| format!(" [{default}]"))
+            .unwrap_or_default(),
+        ParamType::Flag if param.default_flag.unwrap_or(false) => " [Y/n]".to_string(),
+        ParamType::Flag => " [y/N]".to_string(),
+        ParamType::Choice(options) => {
+            let options = options
+                .iter()
+                .map(|option| {
+                    if param.default_value.as_ref() == Some(option) {
+                        format!("{option}*")
+                    } else {
+                        option.clone()
+                    }
+                })
+                .collect::<Vec<_>>();
+            format!(" [{}]", options.join("/"))
+        }
+    };
+    read_stdin_line(&format!("{}{hint}: ", param.prompt))
+}
+
+fn ask_environment(command: &CommandEntry) -> Result<usize> {
+    for (number, environment) in command.environments.iter().enumerate() {
+        eprintln!("  {}) {}", number + 1, environment.name);
+    }
+    let answer = read_stdin_line("Environment: ")?;
+    answer
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| number.checked_sub(1))
+        .filter(|index| *index < command.environments.len())
+        .or_else(|| {
+            command
+                .environments
+                .iter()
+                .position(|environment| environment.name.eq_ignore_ascii_case(&answer))
+        })
+        .with_context(|| format!("unknown environment '{answer}'"))
+}
+
+fn read_stdin_line(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .context("failed to read from stdin")?;
+    Ok(line.trim().to_string())
+}
+
+/// Share of the terminal used by `--inline`, like fzf's `--height 40%`.
+const INLINE_HEIGHT_PERCENT: u16 = 40;
+/// Rows the layout needs: session (8), commands (8), search bar, hint bar.
+const INLINE_MIN_HEIGHT: u16 = 18;
+
+fn inline_height(rows: u16) -> u16 {
+    (rows * INLINE_HEIGHT_PERCENT / 100)
+        .max(INLINE_MIN_HEIGHT)
+        .min(rows)
+}
+
+fn init_terminal(inline: bool, mouse: bool) -> Result<TuiTerminal> {
     enable_raw_mode().context("failed to enable raw mode")?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen).context("failed to enter alternate screen")?;
+    if mouse {
+        execute!(stdout, EnableMouseCapture).context("failed to enable mouse capture")?;
+    }
+    if inline {
+        execute!(stdout, EnableBracketedPaste).context("failed to enable bracketed paste")?;
+        let (_, rows) = terminal::size().context("failed to read terminal size")?;
+        let backend = CrosstermBackend::new(stdout);
+        return Terminal::with_options(
+            backend,
+            TerminalOptions {
+                viewport: Viewport::Inline(inline_height(rows)),
+            },
+        )
+        .context("failed to create terminal");
+    }
+
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)
+        .context("failed to enter alternate screen")?;
     let backend = CrosstermBackend::new(stdout);
     Terminal::new(backend).context("failed to create terminal")
 }
 
-fn restore_terminal(terminal: &mut TuiTerminal) -> Result<()> {
+fn restore_terminal(terminal: &mut TuiTerminal, inline: bool) -> Result<()> {
     disable_raw_mode().context("failed to disable raw mode")?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen, cursor::Show)
-        .context("failed to leave alternate screen")?;
+    if inline {
+        // Wipe the picker and leave the cursor where it started so the shell continues below.
+        terminal
+            .clear()
+            .context("failed to clear inline viewport")?;
+        let top = terminal.get_frame().area().y;
+        terminal
+            .set_cursor_position((0, top))
+            .context("failed to move cursor")?;
+        execute!(
+            terminal.backend_mut(),
+            DisableBracketedPaste,
+            DisableMouseCapture,
+            cursor::Show
+        )
+        .context("failed to disable bracketed paste")?;
+        return terminal.show_cursor().context("failed to show cursor");
+    }
+
+    execute!(
+        terminal.backend_mut(),
+        DisableBracketedPaste,
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        cursor::Show
+    )
+    .context("failed to leave alternate screen")?;
     terminal.show_cursor().context("failed to show cursor")
 }
 
 fn run_loop(terminal: &mut TuiTerminal, app: &mut AppState) -> Result<LoopExit> {
+    let mut dirty = true;
+    let mut mouse_captured = app.runtime.mouse;
     loop {
-        terminal.draw(|frame| draw_ui(frame, app))?;
+        let now = Instant::now();
+        dirty |= app.poll_provider_loads();
+        dirty |= app.poll_schedules(now);
+        dirty |= app.poll_hooks();
+        dirty |= app.poll_jobs();
+        dirty |= app.poll_preview(now);
+        if app.wants_mouse_capture() != mouse_captured {
+            mouse_captured = !mouse_captured;
+            if mouse_captured {
+                execute!(terminal.backend_mut(), EnableMouseCapture)
+            } else {
+                execute!(terminal.backend_mut(), DisableMouseCapture)
+            }
+            .context("failed to toggle mouse capture")?;
+        }
+        if dirty {
+            app.last_activity = now;
+            terminal.draw(|frame| draw_ui(frame, app))?;
+            dirty = false;
+        }
 
-        if event::poll(Duration::from_millis(100))? {
-            let Event::Key(key) = event::read()? else {
-                continue;
+        if event::poll(app.poll_timeout(Instant::now()))? {
+            let action = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => app.on_key(key),
+                // Motion is reported too; skip it so moving the mouse doesn't redraw.
+                Event::Mouse(mouse)
+                    if matches!(
+                        mouse.kind,
+                        MouseEventKind::Down(_)
+                            | MouseEventKind::ScrollUp
+                            | MouseEventKind::ScrollDown
+                    ) =>
+                {
+                    app.on_mouse(mouse, terminal.get_frame().area())
+                }
+                Event::Paste(text) => {
+                    app.on_paste(&text);
+                    UiAction::None
+                }
+                Event::Resize(..) => UiAction::None,
+                _ => continue,
             };
-            if key.kind != KeyEventKind::Press {
-                continue;
-            }
+            dirty = true;
 
-            match app.on_key(key) {
+            match action {
                 UiAction::None => {}
                 UiAction::Quit => break,
                 UiAction::Run(request) => {
                     // Force a redraw before execution so prompt popups disappear immediately.
                     terminal.draw(|frame| draw_ui(frame, app))?;
-                    match execute_command(terminal, app, request)? {
+                    match execute_command(terminal, app, *request)? {
                         CommandExec::Continue => {}
-                        CommandExec::ExitAlreadyRestored => return Ok(LoopExit::AlreadyRestored),
+                        CommandExec::ExitAlreadyRestored(code) => {
+                            return Ok(LoopExit::AlreadyRestored(code));
+                        }
                     }
                 }
+                UiAction::RunQueue(requests) => {
+                    terminal.draw(|frame| draw_ui(frame, app))?;
+                    execute_queue(terminal, app, requests, "queue")?;
+                }
                 UiAction::RunInternal(request) => {
                     terminal.draw(|frame| draw_ui(frame, app))?;
                     execute_internal_command(terminal, app, request)?;
                 }
+                UiAction::ForegroundJob(id) => foreground_job(terminal, app, id)?,
             }
         }
     }
@@ -145,119 +752,574 @@ fn execute_command(
     request: RunRequest,
 ) -> Result<CommandExec> {
     app.mode = Mode::Search;
-
-    if !request.return_to_tui {
-        restore_terminal(terminal)?;
-
-        println!();
-        println!("fzc: {}", request.display_name);
-        if let Some(dir) = &request.working_dir {
-            println!("working directory: {}", dir.display());
+    if request.return_to_tui
+        && !request.background
+        && (request.pipeline.is_some() || !request.then.is_empty())
+    {
+        let label = match &request.pipeline {
+            Some(pipeline) => pipeline.name.clone(),
+            None => "chain".to_string(),
+        };
+        execute_queue(terminal, app, vec![request], &label)?;
+        return Ok(CommandExec::Continue);
+    }
+    let request = match app.with_secrets(request) {
+        Ok(request) => request,
+        Err(err) => {
+            app.push_error(format!("{err:#}"));
+            return Ok(CommandExec::Continue);
         }
-        println!("$ {}", request.command_line);
-        println!();
+    };
 
-        let run_result =
-            run_shell_command_inherit(&request.command_line, request.working_dir.as_deref());
-        match &run_result {
-            Ok(code) => println!("exit code: {code}"),
-            Err(err) => println!("execution failed: {err:#}"),
+    if request.background && request.return_to_tui {
+        if !request.then.is_empty() {
+            app.push_info(format!(
+                "{}: `then` commands do not run after background jobs",
+                request.display_name
+            ));
         }
-        app.record_usage(&request.usage_key);
-
-        return Ok(CommandExec::ExitAlreadyRestored);
+        app.start_background_job(request);
+        return Ok(CommandExec::Continue);
     }
-
-    app.push_command(request.command_line.clone());
-    if let Some(dir) = &request.working_dir {
-        app.push_info(format!("working directory: {}", dir.display()));
+    if request.target != ExecTarget::Session && request.return_to_tui {
+        app.launch_in_target(request);
+        return Ok(CommandExec::Continue);
     }
-    app.start_loading(&request.display_name);
-    terminal.draw(|frame| draw_ui(frame, app))?;
+    if !request.return_to_tui {
+        restore_terminal(terminal, app.runtime.inline)?;
 
-    let run_result = run_shell_command_streaming(
-        terminal,
-        app,
-        &request.command_line,
-        request.working_dir.as_deref(),
-    );
-    match run_result {
-        Ok(result) => {
-            if result.interrupted {
-                app.push_info("Interrupted by user (Escape)");
-            } else {
-                app.push_info(format!("exit code: {}", result.exit_code));
+        let mut request = request;
+        if let Some(pipeline) = &request.pipeline {
+            app.record_usage(&pipeline.usage_key);
+        }
+        let then = std::mem::take(&mut request.then);
+        let remaining = then.len();
+        let mut code = run_reported_inherit(app, &request);
+        let mut stop = code != 0 && !request.continue_on_error;
+        for (position, next) in then.into_iter().enumerate() {
+            if stop {
+                println!(
+                    "stopped: {} remaining command(s) not run",
+                    remaining - position
+                );
+                break;
             }
+            let continue_on_error = next.continue_on_error;
+            code = match app.with_secrets(next) {
+                Ok(next) => run_reported_inherit(app, &next),
+                Err(err) => {
+                    println!("{err:#}");
+                    1
+                }
+            };
+            stop = code != 0 && !continue_on_error;
         }
-        Err(err) => app.push_error(format!("execution failed: {err:#}")),
+        return Ok(CommandExec::ExitAlreadyRestored(code));
     }
-    app.stop_loading();
-    app.record_usage(&request.usage_key);
 
+    stream_request(terminal, app, &request)?;
     Ok(CommandExec::Continue)
 }
 
-fn execute_internal_command(
+/// Runs a request with the terminal handed over, between a header and its exit code.
+fn run_reported_inherit(app: &mut AppState, request: &RunRequest) -> i32 {
+    println!();
+    println!("fzc: {}", request.display_name);
+    if let Some(dir) = &request.working_dir {
+        println!("working directory: {}", dir.display());
+    }
+    if let Some(environment) = &request.environment {
+        println!("environment: {environment}");
+    }
+    println!("$ {}", request.command_line);
+    println!();
+
+    app.emit_started(request);
+    let started = Instant::now();
+    let run_result = run_inherit_with_retries(request);
+    let code = match &run_result {
+        Ok((code, _)) => {
+            println!("exit code: {code}");
+            *code
+        }
+        Err(err) => {
+            println!("execution failed: {err:#}");
+            127
+        }
+    };
+    app.record_usage(&request.usage_key);
+    let timed_out = matches!(run_result, Ok((_, true)));
+    let exit_code = run_result.ok().map(|(code, _)| code);
+    app.emit_finished(request, exit_code, started.elapsed());
+    app.record_history(request, exit_code, timed_out);
+    run_hook_inherit(&app.guard, request, exit_code, started.elapsed());
+    code
+}
+
+/// Streams staged or chained runs into the session one after another, each followed by the runs
+/// it chains with `then`; the first failure stops the rest. `label` tags each run, e.g. "queue".
+fn execute_queue(
     terminal: &mut TuiTerminal,
     app: &mut AppState,
-    request: InternalRunRequest,
+    requests: Vec<RunRequest>,
+    label: &str,
 ) -> Result<()> {
     app.mode = Mode::Search;
-    app.query.clear();
-    app.query_cursor = 0;
-    app.refresh_filtered();
-
-    let label = match &request.command {
-        InternalCommand::Reload => "/reload",
-        InternalCommand::Init { .. } => "/init",
-        InternalCommand::Unknown(_) => "internal",
-    };
-    app.start_loading(label);
-    terminal.draw(|frame| draw_ui(frame, app))?;
-
-    let runtime = app.runtime.clone();
-    let command = request.command;
-    let (tx, rx) = mpsc::channel::<InternalTaskResult>();
-    let _worker = thread::spawn(move || {
-        let result = run_internal_task(&runtime, command);
-        let _ = tx.send(result);
-    });
-
-    loop {
-        match rx.recv_timeout(Duration::from_millis(25)) {
-            Ok(result) => {
-                match result {
-                    InternalTaskResult::Reloaded(payload) => {
-                        let count = payload.commands.len();
-                        app.apply_reload_payload(payload);
-                        app.push_info(format!("Reloaded {count} commands"));
-                    }
-                    InternalTaskResult::Inited { path, payload } => {
-                        let count = payload.commands.len();
-                        app.apply_reload_payload(payload);
-                        app.push_info(format!("Wrote example config: {}", path.display()));
-                        app.push_info(format!("Reloaded {count} commands"));
-                    }
-                    InternalTaskResult::Error(err) => app.push_error(err),
-                }
-                break;
-            }
-            Err(mpsc::RecvTimeoutError::Timeout) => {
-                app.tick_loading();
-                terminal.draw(|frame| draw_ui(frame, app))?;
+    for pipeline in requests
+        .iter()
+        .filter_map(|request| request.pipeline.as_ref())
+    {
+        app.record_usage(&pipeline.usage_key);
+    }
+    let requests: Vec<RunRequest> = requests
+        .into_iter()
+        .flat_map(|mut request| {
+            let then = std::mem::take(&mut request.then);
+            std::iter::once(request).chain(then)
+        })
+        .collect();
+    let total = requests.len();
+    let names: Vec<&str> = requests
+        .iter()
+        .map(|request| request.display_name.as_str())
+        .collect();
+    app.push_info(format!("{label}: {}", names.join(" → ")));
+    for (position, request) in requests.into_iter().enumerate() {
+        app.push_info(format!(
+            "[{label} {}/{total}] {}",
+            position + 1,
+            request.display_name
+        ));
+        let continue_on_error = request.continue_on_error;
+        let exit_code = match app.with_secrets(request) {
+            Ok(request) => stream_request(terminal, app, &request)?,
+            Err(err) => {
+                app.push_error(format!("{err:#}"));
+                None
             }
-            Err(mpsc::RecvTimeoutError::Disconnected) => {
-                app.push_error("internal command worker disconnected unexpectedly");
-                break;
+        };
+        if exit_code != Some(0) && continue_on_error && position + 1 < total {
+            app.push_info(app.runtime.locale.strings().continuing);
+        } else if exit_code != Some(0) {
+            let skipped = total - position - 1;
+            if skipped > 0 {
+                app.push_error(format!(
+                    "{label} stopped: {skipped} remaining command(s) not run"
+                ));
             }
+            break;
         }
     }
-
-    app.stop_loading();
     Ok(())
 }
 
-fn run_internal_task(runtime: &RuntimeContext, command: InternalCommand) -> InternalTaskResult {
+/// Runs a request inside the TUI with output streamed to the session; returns its exit code,
+/// or `None` if it could not start.
+fn stream_request(
+    terminal: &mut TuiTerminal,
+    app: &mut AppState,
+    request: &RunRequest,
+) -> Result<Option<i32>> {
+    let locale = app.runtime.locale;
+    let strings = locale.strings();
+    app.push_command(request.command_line.clone());
+    if let Some(dir) = &request.working_dir {
+        app.push_info(i18n::fill(
+            strings.working_directory,
+            &[("dir", &dir.display().to_string())],
+        ));
+    }
+    if let Some(environment) = &request.environment {
+        app.push_info(i18n::fill(strings.environment, &[("name", environment)]));
+    }
+    app.start_loading(&request.display_name);
+    terminal.draw(|frame| draw_ui(frame, app))?;
+
+    match prepare_working_dir(request) {
+        Ok(true) => app.push_info(app.runtime.locale.strings().created_working_dir),
+        Ok(false) => {}
+        Err(err) => {
+            app.push_error(format!("{err:#}"));
+            return Ok(None);
+        }
+    }
+    app.emit_started(request);
+    let started = Instant::now();
+    let attempts = request.retry.retries.saturating_add(1);
+    let mut attempt = 1;
+    let mut timed_out;
+    let exit_code = loop {
+        if attempts > 1 {
+            app.push_info(i18n::fill(
+                strings.attempt,
+                &[
+                    ("attempt", &attempt.to_string()),
+                    ("attempts", &attempts.to_string()),
+                ],
+            ));
+        }
+        let run_result = run_shell_command_streaming(terminal, app, request);
+        timed_out = matches!(&run_result, Ok(result) if result.timed_out);
+        let exit_code = match run_result {
+            Ok(result) if result.timed_out => {
+                let limit = request.timeout.unwrap_or_default().as_secs();
+                app.push_error(i18n::fill(
+                    strings.timed_out,
+                    &[("limit", &locale.format_age(limit))],
+                ));
+                Some(result.exit_code)
+            }
+            Ok(result) if result.interrupted => {
+                app.push_info(strings.interrupted);
+                break Some(result.exit_code);
+            }
+            Ok(result) => {
+                app.push_info(i18n::fill(
+                    strings.exit_code,
+                    &[("code", &result.exit_code.to_string())],
+                ));
+                Some(result.exit_code)
+            }
+            Err(err) => {
+                app.push_error(i18n::fill(
+                    strings.execution_failed,
+                    &[("error", &format!("{err:#}"))],
+                ));
+                break None;
+            }
+        };
+        if exit_code == Some(0) || attempt == attempts {
+            break exit_code;
+        }
+        attempt += 1;
+        if !request.retry.delay.is_zero() {
+            app.push_info(i18n::fill(
+                strings.retrying,
+                &[("delay", &locale.format_age(request.retry.delay.as_secs()))],
+            ));
+            if !wait_for_retry(terminal, app, request.retry.delay)? {
+                app.push_info(strings.retry_canceled);
+                break exit_code;
+            }
+        }
+    };
+    app.stop_loading();
+    app.emit_finished(request, exit_code, started.elapsed());
+    app.record_usage(&request.usage_key);
+    app.record_history(request, exit_code, timed_out);
+    if exit_code == Some(0) {
+        app.queue_provider_refresh(&request.command_line);
+    }
+    match guarded_hook(&app.guard, request, exit_code, started.elapsed()) {
+        Some(Ok(hook)) => app.start_hook(hook, request),
+        Some(Err(skipped)) => app.push_error(skipped),
+        None => {}
+    }
+
+    Ok(exit_code)
+}
+
+/// Creates a missing working directory for commands with `create_working_dir`; returns whether
+/// it had to.
+fn prepare_working_dir(request: &RunRequest) -> Result<bool> {
+    match &request.working_dir {
+        Some(dir) if request.create_working_dir && !dir.is_dir() => {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create working directory {}", dir.display()))?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Follows a background job in the session until it exits; Escape sends it back to the
+/// background and Ctrl+C stops it.
+fn foreground_job(terminal: &mut TuiTerminal, app: &mut AppState, id: usize) -> Result<()> {
+    app.mode = Mode::Search;
+    let Some(job) = app.jobs.iter().find(|job| job.id == id) else {
+        return Ok(());
+    };
+    let label = job.request.display_name.clone();
+    let command_line = job.request.command_line.clone();
+    let backlog: Vec<(ChatLineKind, String)> = job
+        .lines
+        .iter()
+        .map(|line| (line.kind, line.text.clone()))
+        .collect();
+    let running = job.is_running();
+
+    app.push_command(command_line);
+    app.push_info(i18n::fill(
+        app.runtime.locale.strings().job_foreground,
+        &[("id", &id.to_string())],
+    ));
+    for (kind, text) in backlog {
+        app.push_line(kind, text);
+    }
+    if !running {
+        app.push_info(i18n::fill(
+            app.runtime.locale.strings().job_finished,
+            &[("id", &id.to_string())],
+        ));
+        return Ok(());
+    }
+
+    app.followed_job = Some(id);
+    app.start_loading(&label);
+    loop {
+        app.poll_jobs();
+        app.tick_loading();
+        terminal.draw(|frame| draw_ui(frame, app))?;
+        if !app.jobs.iter().any(|job| job.id == id && job.is_running()) {
+            break;
+        }
+        if event::poll(Duration::from_millis(25))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            match key.code {
+                KeyCode::Esc => {
+                    app.push_info(i18n::fill(
+                        app.runtime.locale.strings().job_backgrounded,
+                        &[("id", &id.to_string())],
+                    ));
+                    break;
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.kill_job(id);
+                }
+                _ => {}
+            }
+        }
+    }
+    app.followed_job = None;
+    app.stop_loading();
+    Ok(())
+}
+
+/// Sleeps out a retry delay with the UI still drawn; returns false if Escape cancels it.
+fn wait_for_retry(terminal: &mut TuiTerminal, app: &mut AppState, delay: Duration) -> Result<bool> {
+    // Without a representable deadline, only Escape ends the wait.
+    let deadline = Instant::now().checked_add(delay);
+    loop {
+        terminal.draw(|frame| draw_ui(frame, app))?;
+        let remaining = deadline.map_or(Duration::MAX, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        });
+        if remaining.is_zero() {
+            return Ok(true);
+        }
+        if event::poll(remaining.min(Duration::from_millis(100)))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && key.code == KeyCode::Esc
+        {
+            return Ok(false);
+        }
+    }
+}
+
+/// Runs a request on the restored terminal, re-running non-zero exits per its retry policy;
+/// returns the last exit code and whether that run timed out.
+fn run_inherit_with_retries(request: &RunRequest) -> Result<(i32, bool)> {
+    if prepare_working_dir(request)? {
+        eprintln!("fzc: created working directory");
+    }
+    let attempts = request.retry.retries.saturating_add(1);
+    let mut attempt = 1;
+    loop {
+        let (code, timed_out) = run_shell_command_inherit(
+            &request.command_line,
+            request.working_dir.as_deref(),
+            &request.env,
+            request.color,
+            request.timeout,
+        )?;
+        if timed_out {
+            eprintln!(
+                "fzc: timed out after {}",
+                history::format_age(request.timeout.unwrap_or_default().as_secs())
+            );
+        }
+        if code == 0 || attempt == attempts {
+            return Ok((code, timed_out));
+        }
+        attempt += 1;
+        eprintln!(
+            "fzc: exit code {code}; attempt {attempt}/{attempts} in {}",
+            history::format_age(request.retry.delay.as_secs())
+        );
+        thread::sleep(request.retry.delay);
+    }
+}
+
+/// The `on_success` or `on_failure` hook for a finished run, rendered; a command that failed
+/// to start counts as a failure with an empty `{{exit_code}}`. `{{name}}` is shell-escaped, so
+/// it stands as one word and cannot inject commands.
+fn hook_command_line(
+    request: &RunRequest,
+    exit_code: Option<i32>,
+    elapsed: Duration,
+) -> Option<String> {
+    let template = if exit_code == Some(0) {
+        request.hooks.on_success.as_ref()
+    } else {
+        request.hooks.on_failure.as_ref()
+    }?;
+    let values = HashMap::from([
+        (
+            "exit_code".to_string(),
+            exit_code.map(|code| code.to_string()).unwrap_or_default(),
+        ),
+        (
+            "duration".to_string(),
+            format!("{:.2}", elapsed.as_secs_f64()),
+        ),
+        (
+            "name".to_string(),
+            provider::shell_escape_arg(&request.display_name),
+        ),
+    ]);
+    Some(render_template(template, &values))
+}
+
+/// The hook to run for a finished run, or why `[safety]` keeps it from running: hooks are not
+/// confirmed, so anything but an allowed line is skipped.
+fn guarded_hook(
+    guard: &CommandGuard,
+    request: &RunRequest,
+    exit_code: Option<i32>,
+    elapsed: Duration,
+) -> Option<Result<String, String>> {
+    let hook = hook_command_line(request, exit_code, elapsed)?;
+    Some(match guard.check(&hook, None, None) {
+        GuardVerdict::Allow => Ok(hook),
+        GuardVerdict::Warn(reason) => Err(format!("hook skipped: it {reason}")),
+        GuardVerdict::Deny(_) | GuardVerdict::Confirm(_) => {
+            Err("hook skipped: blocked by [safety] patterns".to_string())
+        }
+    })
+}
+
+/// Runs the finished command's hook on the restored terminal; a failing hook is reported
+/// but does not change fzc's exit code.
+fn run_hook_inherit(
+    guard: &CommandGuard,
+    request: &RunRequest,
+    exit_code: Option<i32>,
+    elapsed: Duration,
+) {
+    let hook = match guarded_hook(guard, request, exit_code, elapsed) {
+        Some(Ok(hook)) => hook,
+        Some(Err(skipped)) => {
+            eprintln!("fzc: {skipped}");
+            return;
+        }
+        None => return,
+    };
+    if let Err(err) = run_shell_command_inherit(
+        &hook,
+        request.working_dir.as_deref(),
+        &request.env,
+        request.color,
+        None,
+    ) {
+        eprintln!("fzc: hook failed: {err:#}");
+    }
+}
+
+fn execute_internal_command(
+    terminal: &mut TuiTerminal,
+    app: &mut AppState,
+    request: InternalRunRequest,
+) -> Result<()> {
+    app.mode = Mode::Search;
+    app.query.clear();
+    app.query_cursor = 0;
+    app.refresh_filtered();
+
+    let label = match &request.command {
+        InternalCommand::Reload => "/reload",
+        InternalCommand::Init { .. } => "/init",
+        InternalCommand::Aliases => "/aliases",
+        InternalCommand::SwitchProject(_) => "/projects",
+        InternalCommand::Unknown(_) => "internal",
+    };
+    app.start_loading(label);
+    terminal.draw(|frame| draw_ui(frame, app))?;
+
+    let runtime = app.runtime.clone();
+    let command = request.command;
+    let (tx, rx) = mpsc::channel::<InternalTaskResult>();
+    let _worker = thread::spawn(move || {
+        let result = run_internal_task(&runtime, command);
+        let _ = tx.send(result);
+    });
+
+    loop {
+        match rx.recv_timeout(Duration::from_millis(25)) {
+            Ok(result) => {
+                match result {
+                    InternalTaskResult::Reloaded(payload) => {
+                        let count = payload.commands.len();
+                        app.apply_reload_payload(payload);
+                        app.push_info(reloaded_message(app.runtime.locale, count));
+                    }
+                    InternalTaskResult::Inited { path, payload } => {
+                        let count = payload.commands.len();
+                        app.apply_reload_payload(payload);
+                        app.push_info(i18n::fill(
+                            app.runtime.locale.strings().wrote_config,
+                            &[("path", &path.display().to_string())],
+                        ));
+                        app.push_info(reloaded_message(app.runtime.locale, count));
+                    }
+                    InternalTaskResult::Switched { cwd, payload } => {
+                        let count = payload.commands.len();
+                        match std::env::set_current_dir(&cwd) {
+                            Ok(()) => {
+                                if app.runtime.persist_usage {
+                                    let _ = record_recent_project(&cwd);
+                                }
+                                app.runtime.cwd = cwd;
+                                app.apply_reload_payload(payload);
+                                app.push_info(i18n::fill(
+                                    app.runtime.locale.strings().switched_to,
+                                    &[("dir", &app.runtime.cwd.display().to_string())],
+                                ));
+                                app.push_info(reloaded_message(app.runtime.locale, count));
+                            }
+                            Err(err) => app.push_error(format!(
+                                "failed to switch to {}: {err}",
+                                cwd.display()
+                            )),
+                        }
+                    }
+                    InternalTaskResult::Report(lines) => {
+                        for line in lines {
+                            app.push_info(line);
+                        }
+                    }
+                    InternalTaskResult::Error(err) => app.push_error(err),
+                }
+                break;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                app.tick_loading();
+                terminal.draw(|frame| draw_ui(frame, app))?;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                app.push_error(app.runtime.locale.strings().worker_disconnected);
+                break;
+            }
+        }
+    }
+
+    app.stop_loading();
+    Ok(())
+}
+
+fn run_internal_task(runtime: &RuntimeContext, command: InternalCommand) -> InternalTaskResult {
     match command {
         InternalCommand::Reload => match load_catalog_payload(runtime) {
             Ok(payload) => InternalTaskResult::Reloaded(payload),
@@ -273,40 +1335,136 @@ fn run_internal_task(runtime: &RuntimeContext, command: InternalCommand) -> Inte
             },
             Err(err) => InternalTaskResult::Error(format!("init failed: {err:#}")),
         },
+        InternalCommand::Aliases => {
+            match config::load(&runtime.cwd, runtime.explicit_config_path.as_deref()).and_then(
+                |loaded| {
+                    let providers = loaded.config.providers;
+                    let external: Vec<String> =
+                        providers.external.iter().map(|e| e.name.clone()).collect();
+                    Ok((providers.alias_map()?, external))
+                },
+            ) {
+                Ok((aliases, external)) => {
+                    let mut names = provider::PROVIDER_NAMES.to_vec();
+                    names.extend(external.iter().map(String::as_str));
+                    InternalTaskResult::Report(provider_alias_report(&aliases, &names))
+                }
+                Err(err) => InternalTaskResult::Error(format!("aliases failed: {err:#}")),
+            }
+        }
+        InternalCommand::SwitchProject(cwd) => {
+            if !cwd.is_dir() {
+                return InternalTaskResult::Error(format!(
+                    "project directory no longer exists: {}",
+                    cwd.display()
+                ));
+            }
+            let runtime = RuntimeContext {
+                cwd: cwd.clone(),
+                ..runtime.clone()
+            };
+            match load_catalog_payload(&runtime) {
+                Ok(payload) => InternalTaskResult::Switched { cwd, payload },
+                Err(err) => InternalTaskResult::Error(format!("reload failed: {err:#}")),
+            }
+        }
         InternalCommand::Unknown(name) => InternalTaskResult::Error(format!(
-            "Unknown internal command '/{name}'. Available: /reload, /init"
+            "Unknown internal command '/{name}'. Available: {}",
+            internal_command_list(INTERNAL_COMMANDS)
         )),
     }
 }
 
+/// One "search <provider>…" entry per lazy provider, naming the filter that loads it.
+fn lazy_placeholders(
+    lazy: &[String],
+    provider_aliases: &HashMap<String, String>,
+) -> Vec<CommandEntry> {
+    lazy.iter()
+        .map(|name| {
+            let filter = provider_aliases
+                .iter()
+                .find(|(_, provider)| *provider == name)
+                .map_or(name.as_str(), |(alias, _)| alias.as_str());
+            provider::lazy_placeholder(name, filter)
+        })
+        .collect()
+}
+
+fn reloaded_message(locale: Locale, count: usize) -> String {
+    i18n::fill(
+        locale.strings().reloaded,
+        &[("count", &locale.format_count(count))],
+    )
+}
+
 fn load_catalog_payload(runtime: &RuntimeContext) -> Result<ReloadPayload> {
     let loaded = config::load(&runtime.cwd, runtime.explicit_config_path.as_deref())?;
     let provider_aliases = loaded.config.providers.alias_map()?;
+    let guard = CommandGuard::from_config(&loaded.config.safety)?;
 
     let mut catalog = CommandCatalog::empty();
     if loaded.config.providers.config.enabled {
         catalog.extend(CommandCatalog::from_config(&loaded, &runtime.cwd)?.into_vec());
     }
-    catalog.extend(provider::load_provider_commands(
-        &loaded.config.providers,
-        &runtime.cwd,
-    )?);
+    let providers = &loaded.config.providers;
+    let lazy = provider::lazy_provider_names(providers);
+    for name in provider::enabled_provider_names(providers) {
+        if !lazy.contains(&name) {
+            catalog.extend(provider::load_provider(providers, &runtime.cwd, &name)?);
+        }
+    }
+    catalog.extend(lazy_placeholders(&lazy, &provider_aliases));
 
     let mut commands = catalog.into_vec();
-    commands.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    commands.sort_by_key(|command| command.name.to_lowercase());
 
     Ok(ReloadPayload {
         commands,
         config_path: loaded.path,
+        global_config_path: loaded.global,
         provider_aliases,
         ranking: RankingSettings {
             usage_enabled: loaded.config.ranking.usage_enabled,
             usage_weight: loaded.config.ranking.usage_weight,
+            usage_scale: loaded.config.ranking.usage_scale,
+            usage_cap: loaded.config.ranking.usage_cap,
+            decay_half_life_days: loaded.config.ranking.decay_half_life_days,
         },
+        output: OutputSettings {
+            max_line_bytes: loaded.config.output.max_line_bytes,
+            pretty_json: loaded.config.output.pretty_json,
+            color: loaded.config.output.color,
+            pty: loaded.config.output.pty,
+            timeout: loaded
+                .config
+                .output
+                .timeout
+                .as_deref()
+                .and_then(|raw| parse_timeout(raw).ok()),
+            target: loaded.config.output.target,
+            terminal: loaded.config.output.terminal.clone(),
+        },
+        guard,
+        hooks: CommandHooks::from_config(
+            loaded.config.hooks.on_success.as_deref(),
+            loaded.config.hooks.on_failure.as_deref(),
+        ),
+        providers: loaded.config.providers,
     })
 }
 
-fn run_shell_command_inherit(command: &str, working_dir: Option<&Path>) -> Result<i32> {
+/// Runs a command on fzc's terminal and returns its exit code and whether it timed out. Past its
+/// `timeout` it is sent SIGTERM and killed FORCE_KILL_AFTER later, and exits with 124. Without a
+/// terminal on stdin it runs in its own process group so that reaches everything it started;
+/// with one it stays in fzc's foreground group and only its shell is signalled.
+fn run_shell_command_inherit(
+    command: &str,
+    working_dir: Option<&Path>,
+    env: &[(String, String)],
+    color: ColorMode,
+    timeout: Option<Duration>,
+) -> Result<(i32, bool)> {
     #[cfg(target_os = "windows")]
     let mut process = {
         let mut cmd = Command::new("cmd");
@@ -324,24 +1482,60 @@ fn run_shell_command_inherit(command: &str, working_dir: Option<&Path>) -> Resul
     if let Some(dir) = working_dir {
         process.current_dir(dir);
     }
-    apply_color_env(&mut process);
+    apply_color_env(&mut process, color);
+    process.envs(env.iter().map(|(key, value)| (key, value)));
+    let grouped = timeout.is_some() && !io::stdin().is_terminal();
+    if grouped {
+        own_process_group(&mut process);
+    }
 
-    let status = process
+    let mut child = process
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
-        .status()
+        .spawn()
         .with_context(|| format!("failed to start shell command: {command}"))?;
+    let Some(deadline) = timeout.and_then(|timeout| Instant::now().checked_add(timeout)) else {
+        return Ok((child.wait()?.code().unwrap_or_default(), false));
+    };
 
-    Ok(status.code().unwrap_or_default())
+    let stop = |child: &mut Child, stop: Stop| {
+        if grouped {
+            stop_process_group(child, stop);
+        } else {
+            stop_child(child, stop);
+        }
+    };
+    let mut timed_out_at: Option<Instant> = None;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if timed_out_at.is_some() {
+                if grouped {
+                    stop_process_group(&mut child, Stop::Kill);
+                }
+                // Like coreutils `timeout`.
+                return Ok((124, true));
+            }
+            return Ok((status.code().unwrap_or_default(), false));
+        }
+        match timed_out_at {
+            None if Instant::now() >= deadline => {
+                stop(&mut child, Stop::Terminate);
+                timed_out_at = Some(Instant::now());
+            }
+            Some(at) if at.elapsed() >= FORCE_KILL_AFTER => stop(&mut child, Stop::Kill),
+            _ => {}
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
 }
 
 fn run_shell_command_streaming(
     terminal: &mut TuiTerminal,
     app: &mut AppState,
-    command: &str,
-    working_dir: Option<&Path>,
+    request: &RunRequest,
 ) -> Result<StreamRunResult> {
+    let command = request.command_line.as_str();
     #[cfg(target_os = "windows")]
     let mut process = {
         let mut cmd = Command::new("cmd");
@@ -356,72 +1550,134 @@ fn run_shell_command_streaming(
         cmd
     };
 
-    if let Some(dir) = working_dir {
+    if let Some(dir) = &request.working_dir {
         process.current_dir(dir);
     }
-    apply_color_env(&mut process);
-
-    process.stdin(Stdio::null());
-    process.stdout(Stdio::piped());
-    process.stderr(Stdio::piped());
-
-    let mut child = process
-        .spawn()
-        .with_context(|| format!("failed to start shell command: {command}"))?;
-
-    let stdout = child
-        .stdout
-        .take()
-        .context("failed to capture stdout from command process")?;
-    let stderr = child
-        .stderr
-        .take()
-        .context("failed to capture stderr from command process")?;
+    apply_color_env(&mut process, request.color);
+    process.env(SESSION_ENV, std::process::id().to_string());
+    process.envs(request.env.iter().map(|(key, value)| (key, value)));
 
     let (tx, rx) = mpsc::channel::<StreamChunk>();
-    let _stdout_reader = spawn_stream_reader(stdout, ChatLineKind::Stdout, tx.clone());
-    let _stderr_reader = spawn_stream_reader(stderr, ChatLineKind::Stderr, tx.clone());
+    let (mut child, mut pty_input) = if request.pty {
+        let size = terminal.size()?;
+        let (rows, cols) = session_size(Rect::new(0, 0, size.width, size.height), app);
+        spawn_in_pty(process, rows, cols, tx.clone())
+            .with_context(|| format!("failed to start shell command: {command}"))?
+    } else {
+        // A pty command already leads its own session and process group.
+        own_process_group(&mut process);
+        process.stdin(Stdio::null());
+        process.stdout(Stdio::piped());
+        process.stderr(Stdio::piped());
+
+        let mut child = process
+            .spawn()
+            .with_context(|| format!("failed to start shell command: {command}"))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .context("failed to capture stdout from command process")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("failed to capture stderr from command process")?;
+        let _stdout_reader = spawn_stream_reader(stdout, ChatLineKind::Stdout, tx.clone());
+        let _stderr_reader = spawn_stream_reader(stderr, ChatLineKind::Stderr, tx.clone());
+        (child, None)
+    };
     drop(tx);
 
+    app.output_filter = None;
+    app.filter_input = None;
+    app.live_line = false;
+
+    // Escape interrupts the command's process group first so tools can clean up; another Escape
+    // once FORCE_KILL_AFTER has passed kills it. Running past the timeout works the same way,
+    // starting with SIGTERM.
+    let started = Instant::now();
+    let mut interrupted_at: Option<Instant> = None;
+    let mut timed_out_at: Option<Instant> = None;
+    let mut killed = false;
     loop {
-        if should_interrupt_running_command()? {
-            let _ = child.kill();
-            let _ = child.wait();
-
-            while let Ok(chunk) = rx.recv_timeout(Duration::from_millis(10)) {
-                app.push_line(chunk.kind, chunk.text);
+        if poll_running_command_key(app, pty_input.as_mut())? {
+            let strings = app.runtime.locale.strings();
+            match interrupted_at {
+                None => {
+                    stop_process_group(&mut child, Stop::Interrupt);
+                    interrupted_at = Some(Instant::now());
+                    app.push_info(i18n::fill(
+                        strings.interrupt_sent,
+                        &[("seconds", &FORCE_KILL_AFTER.as_secs().to_string())],
+                    ));
+                }
+                Some(at) if at.elapsed() >= FORCE_KILL_AFTER => {
+                    stop_process_group(&mut child, Stop::Kill);
+                    killed = true;
+                    app.push_info(strings.force_killed);
+                }
+                Some(_) => {}
             }
-            app.tick_loading();
             terminal.draw(|frame| draw_ui(frame, app))?;
-
-            return Ok(StreamRunResult {
-                exit_code: 130,
-                interrupted: true,
-            });
+        }
+        if let Some(limit) = request.timeout {
+            match timed_out_at {
+                None if interrupted_at.is_none() && started.elapsed() >= limit => {
+                    stop_process_group(&mut child, Stop::Terminate);
+                    timed_out_at = Some(Instant::now());
+                }
+                Some(at) if !killed && at.elapsed() >= FORCE_KILL_AFTER => {
+                    stop_process_group(&mut child, Stop::Kill);
+                    killed = true;
+                }
+                _ => {}
+            }
         }
 
         while let Ok(chunk) = rx.try_recv() {
-            app.push_line(chunk.kind, chunk.text);
+            app.push_output(chunk);
             app.tick_loading();
             terminal.draw(|frame| draw_ui(frame, app))?;
         }
 
         if let Some(status) = child.try_wait()? {
+            if let Some(stopped_at) = interrupted_at.or(timed_out_at)
+                && !killed
+            {
+                // The shell is gone; what it started still gets the rest of FORCE_KILL_AFTER to
+                // clean up before whatever is left goes with it.
+                while stopped_at.elapsed() < FORCE_KILL_AFTER && process_group_alive(&child) {
+                    for chunk in rx.try_iter() {
+                        app.push_output(chunk);
+                    }
+                    app.tick_loading();
+                    terminal.draw(|frame| draw_ui(frame, app))?;
+                    thread::sleep(Duration::from_millis(20));
+                }
+                stop_process_group(&mut child, Stop::Kill);
+            }
             while let Ok(chunk) = rx.recv_timeout(Duration::from_millis(10)) {
-                app.push_line(chunk.kind, chunk.text);
+                app.push_output(chunk);
             }
             app.tick_loading();
             terminal.draw(|frame| draw_ui(frame, app))?;
 
             return Ok(StreamRunResult {
-                exit_code: status.code().unwrap_or_default(),
-                interrupted: false,
+                exit_code: match status.code() {
+                    // Like coreutils `timeout`.
+                    _ if timed_out_at.is_some() => 124,
+                    Some(code) => code,
+                    None if interrupted_at.is_some() => 130,
+                    None => 0,
+                },
+                interrupted: interrupted_at.is_some(),
+                timed_out: timed_out_at.is_some(),
             });
         }
 
         match rx.recv_timeout(Duration::from_millis(20)) {
             Ok(chunk) => {
-                app.push_line(chunk.kind, chunk.text);
+                app.push_output(chunk);
                 app.tick_loading();
                 terminal.draw(|frame| draw_ui(frame, app))?;
             }
@@ -434,29 +1690,697 @@ fn run_shell_command_streaming(
     }
 }
 
-fn should_interrupt_running_command() -> Result<bool> {
-    if !event::poll(Duration::from_millis(0))? {
-        return Ok(false);
+fn mask_secret_values(values: &[String], text: String) -> String {
+    if !values.iter().any(|value| text.contains(value.as_str())) {
+        return text;
     }
+    values
+        .iter()
+        .fold(text, |text, value| text.replace(value.as_str(), MASK))
+}
 
-    let Event::Key(key) = event::read()? else {
-        return Ok(false);
+/// Starts a background job with its output piped to the returned receiver, in its own process
+/// group so stopping it reaches everything it started.
+fn spawn_background_job(request: &RunRequest) -> Result<(Child, mpsc::Receiver<StreamChunk>)> {
+    #[cfg(target_os = "windows")]
+    let mut process = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(&request.command_line);
+        cmd
     };
-    if key.kind != KeyEventKind::Press {
-        return Ok(false);
-    }
 
-    Ok(matches!(key.code, KeyCode::Esc))
-}
+    #[cfg(not(target_os = "windows"))]
+    let mut process = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(&request.command_line);
+        cmd
+    };
 
-fn apply_color_env(process: &mut Command) {
-    process
-        .env("CLICOLOR_FORCE", "1")
-        .env("FORCE_COLOR", "1")
-        .env(
-            "TERM",
-            std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string()),
+    own_process_group(&mut process);
+    if let Some(dir) = &request.working_dir {
+        process.current_dir(dir);
+    }
+    apply_color_env(&mut process, request.color);
+    process.env(SESSION_ENV, std::process::id().to_string());
+    process.envs(request.env.iter().map(|(key, value)| (key, value)));
+
+    let mut child = process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start shell command: {}", request.command_line))?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to capture stdout from command process")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("failed to capture stderr from command process")?;
+
+    let (tx, rx) = mpsc::channel::<StreamChunk>();
+    let _stdout_reader = spawn_stream_reader(stdout, ChatLineKind::Stdout, tx.clone());
+    let _stderr_reader = spawn_stream_reader(stderr, ChatLineKind::Stderr, tx);
+    Ok((child, rx))
+}
+
+/// The process that opens `request` in a new tmux window or pane (which must be started from
+/// inside tmux), or through the `[output] terminal` launcher. Secret values never go on a command
+/// line, where `ps` would show them: they are read from a private file the run deletes first.
+fn target_process(
+    request: &RunRequest,
+    cwd: &Path,
+    terminal: Option<&str>,
+    in_tmux: bool,
+) -> Result<Command> {
+    let dir = request.working_dir.as_deref().unwrap_or(cwd);
+    let secret_keys: Vec<String> = request
+        .secrets
+        .iter()
+        .map(|name| secrets::env_var(name))
+        .collect();
+    let (secret_env, plain_env): (Vec<_>, Vec<_>) = request
+        .env
+        .iter()
+        .partition(|(key, _)| secret_keys.contains(key));
+    let mut command_line = request.command_line.clone();
+    if !secret_env.is_empty() {
+        let file = write_secret_env_file(&secret_env)?;
+        command_line = format!(
+            "sh -c '. \"$0\"; rm -f -- \"$0\"; exec sh -c \"$1\"' {} {}",
+            provider::shell_escape_arg(&file.display().to_string()),
+            provider::shell_escape_arg(&command_line)
         );
+    }
+    let mut process = match request.target {
+        ExecTarget::Session => bail!("'{}' runs in the session", request.display_name),
+        ExecTarget::TmuxWindow | ExecTarget::TmuxPane => {
+            if !in_tmux {
+                bail!("fzc is not running inside tmux ($TMUX is not set)");
+            }
+            let mut cmd = Command::new("tmux");
+            if request.target == ExecTarget::TmuxWindow {
+                cmd.args(["new-window", "-n", &request.display_name]);
+            } else {
+                cmd.arg("split-window");
+            }
+            cmd.arg("-c").arg(dir);
+            for (key, value) in &plain_env {
+                cmd.arg("-e").arg(format!("{key}={value}"));
+            }
+            cmd.arg(&command_line);
+            cmd
+        }
+        ExecTarget::Terminal => {
+            let template =
+                terminal.context("target = \"terminal\" needs an `[output] terminal` launcher")?;
+            if !plain_env.is_empty() {
+                let assignments: Vec<String> = plain_env
+                    .iter()
+                    .map(|(key, value)| provider::shell_escape_arg(&format!("{key}={value}")))
+                    .collect();
+                command_line = format!(
+                    "env {} sh -c {}",
+                    assignments.join(" "),
+                    provider::shell_escape_arg(&command_line)
+                );
+            }
+            let launcher = template
+                .replace(
+                    "{{cwd}}",
+                    &provider::shell_escape_arg(&dir.display().to_string()),
+                )
+                .replace("{{command}}", &provider::shell_escape_arg(&command_line));
+
+            #[cfg(target_os = "windows")]
+            let mut cmd = {
+                let mut cmd = Command::new("cmd");
+                cmd.arg("/C").arg(launcher);
+                cmd
+            };
+
+            #[cfg(not(target_os = "windows"))]
+            let mut cmd = {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(launcher);
+                cmd
+            };
+            cmd.current_dir(dir);
+            cmd
+        }
+    };
+    own_process_group(&mut process);
+    process.stdin(Stdio::null()).stdout(Stdio::null());
+    Ok(process)
+}
+
+/// Writes `export KEY='value'` lines to a new file only the current user can read.
+fn write_secret_env_file(env: &[&(String, String)]) -> Result<PathBuf> {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    let path = std::env::temp_dir().join(format!("fzc-env-{}-{nonce}", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    for (key, value) in env {
+        writeln!(file, "export {key}={}", provider::shell_escape_arg(value))?;
+    }
+    Ok(path)
+}
+
+/// How a command in its own process group is asked to stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    /// SIGINT, like Ctrl+C in a terminal.
+    Interrupt,
+    /// SIGTERM.
+    Terminate,
+    /// SIGKILL; no cleanup runs.
+    Kill,
+}
+
+/// Two Escapes closer together than this only interrupt, so a double tap cannot skip cleanup.
+const FORCE_KILL_AFTER: Duration = Duration::from_secs(2);
+
+#[cfg(windows)]
+const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+#[cfg(windows)]
+const CTRL_BREAK_EVENT: u32 = 1;
+
+#[cfg(windows)]
+unsafe extern "system" {
+    fn GenerateConsoleCtrlEvent(ctrl_event: u32, process_group_id: u32) -> i32;
+}
+
+/// Starts `process` as the leader of a new process group, which [`stop_process_group`] signals.
+fn own_process_group(process: &mut Command) {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(process, 0);
+    #[cfg(windows)]
+    std::os::windows::process::CommandExt::creation_flags(process, CREATE_NEW_PROCESS_GROUP);
+}
+
+/// Signals the process group `child` leads, which still reaches its members after `child` itself
+/// has exited. On Windows, interrupting and terminating both send Ctrl+Break and killing ends the
+/// process tree.
+fn stop_process_group(child: &mut Child, stop: Stop) {
+    #[cfg(unix)]
+    {
+        let signal = match stop {
+            Stop::Interrupt => libc::SIGINT,
+            Stop::Terminate => libc::SIGTERM,
+            Stop::Kill => libc::SIGKILL,
+        };
+        // SAFETY: kill(2) on the process group the child was started in.
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), signal);
+        }
+    }
+    #[cfg(windows)]
+    {
+        if stop == Stop::Kill {
+            let killed_tree = Command::new("taskkill")
+                .args(["/T", "/F", "/PID", &child.id().to_string()])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            if !killed_tree {
+                let _ = child.kill();
+            }
+        } else {
+            // SAFETY: the child leads a process group created with CREATE_NEW_PROCESS_GROUP.
+            unsafe {
+                GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, child.id());
+            }
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stop;
+        let _ = child.kill();
+    }
+}
+
+/// Whether anything is left in the process group `child` led, which outlives `child` itself.
+fn process_group_alive(child: &Child) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill(2) with signal 0 only checks whether the group exists.
+        unsafe { libc::kill(-(child.id() as libc::pid_t), 0) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = child;
+        false
+    }
+}
+
+/// Signals `child` alone, for commands that share fzc's process group.
+fn stop_child(child: &mut Child, stop: Stop) {
+    #[cfg(unix)]
+    {
+        let signal = match stop {
+            Stop::Interrupt => libc::SIGINT,
+            Stop::Terminate => libc::SIGTERM,
+            Stop::Kill => libc::SIGKILL,
+        };
+        // SAFETY: kill(2) on the child's own pid.
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, signal);
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = stop;
+        let _ = child.kill();
+    }
+}
+
+/// Runs a scheduled request and its chained runs one after another, stopping at the first
+/// failure unless the run may fail; returns the last exit code and the output of all of them.
+fn run_scheduled(runs: &[RunRequest]) -> Result<(i32, Vec<StreamChunk>)> {
+    let mut code = 0;
+    let mut output = Vec::new();
+    for run in runs {
+        prepare_working_dir(run)?;
+        let (run_code, chunks) = run_shell_command_captured(
+            &run.command_line,
+            run.working_dir.as_deref(),
+            &run.env,
+            run.color,
+            run.timeout,
+        )?;
+        output.extend(chunks);
+        code = run_code;
+        if code != 0 && !run.continue_on_error {
+            break;
+        }
+    }
+    Ok((code, output))
+}
+
+/// Runs a command to completion without a terminal, for scheduled background jobs. With a
+/// `timeout`, its process group is killed once it runs longer and the run fails.
+fn run_shell_command_captured(
+    command: &str,
+    working_dir: Option<&Path>,
+    env: &[(String, String)],
+    color: ColorMode,
+    timeout: Option<Duration>,
+) -> Result<(i32, Vec<StreamChunk>)> {
+    #[cfg(target_os = "windows")]
+    let mut process = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    let mut process = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+
+    own_process_group(&mut process);
+    if let Some(dir) = working_dir {
+        process.current_dir(dir);
+    }
+    apply_color_env(&mut process, color);
+    process.envs(env.iter().map(|(key, value)| (key, value)));
+    process.env(SESSION_ENV, std::process::id().to_string());
+
+    let mut child = process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start shell command: {command}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to capture stdout from command process")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("failed to capture stderr from command process")?;
+    let (tx, rx) = mpsc::channel::<StreamChunk>();
+    let readers = [
+        spawn_stream_reader(stdout, ChatLineKind::Stdout, tx.clone()),
+        spawn_stream_reader(stderr, ChatLineKind::Stderr, tx),
+    ];
+
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            stop_process_group(&mut child, Stop::Kill);
+            let _ = child.wait();
+            bail!(
+                "timed out after {}s",
+                timeout.unwrap_or_default().as_secs_f32()
+            );
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    for reader in readers {
+        let _ = reader.join();
+    }
+    Ok((status.code().unwrap_or_default(), rx.try_iter().collect()))
+}
+
+/// The shell line that expands an `eval = true` param value as inside double quotes: `$(...)`,
+/// `$VAR`, and `$((...))` are substituted without word splitting. The assignment keeps the exit
+/// status of a failed command substitution.
+fn eval_command_line(expression: &str) -> String {
+    format!(
+        "fzc_value=\"{}\" && printf '%s' \"$fzc_value\"",
+        double_quoted_body(expression)
+    )
+}
+
+/// Escapes what would end or break a double-quoted string around `expression`: a `"` outside of
+/// `$(...)` and backquotes (inside them quotes are parsed afresh) and a trailing lone `\`.
+fn double_quoted_body(expression: &str) -> String {
+    let mut body = String::with_capacity(expression.len());
+    // Open parens of `$(...)` / `$((...))`, and the quote open inside them.
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut backquoted = false;
+    let mut chars = expression.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' && quote != Some('\'') {
+            body.push(ch);
+            match chars.next() {
+                Some(next) => body.push(next),
+                None if depth == 0 && !backquoted => body.push('\\'),
+                None => {}
+            }
+            continue;
+        }
+        if depth > 0 {
+            match (quote, ch) {
+                (Some(open), _) if ch == open => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(ch),
+                (None, '(') => depth += 1,
+                (None, ')') => depth -= 1,
+                _ => {}
+            }
+        } else if ch == '`' {
+            backquoted = !backquoted;
+        } else if ch == '$' && !backquoted && chars.peek() == Some(&'(') {
+            body.push(ch);
+            body.push('(');
+            chars.next();
+            depth = 1;
+            continue;
+        } else if ch == '"' && !backquoted {
+            body.push('\\');
+        }
+        body.push(ch);
+    }
+    body
+}
+
+/// Whether a rendered line still holds a `{{...}}` placeholder.
+fn has_placeholders(line: &str) -> bool {
+    line.contains("{{") && line.contains("}}")
+}
+
+/// Puts `text` on the system clipboard through the terminal (OSC 52), which also works over ssh;
+/// terminals that do not support it ignore the sequence.
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))
+        .and_then(|()| stdout.flush())
+        .context("failed to write to the terminal")
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |acc, (index, byte)| {
+            acc | u32::from(*byte) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// A param's fixed `value`, else its default, else empty (or the flag token when enabled).
+fn param_fallback_value(param: &ParamSpec) -> String {
+    match param.kind {
+        ParamType::Value | ParamType::Choice(_) | ParamType::Path(_) => param
+            .value_value
+            .clone()
+            .or_else(|| param.default_value.clone())
+            .unwrap_or_default(),
+        ParamType::Flag if param.value_flag.or(param.default_flag).unwrap_or(false) => {
+            param.flag_token()
+        }
+        ParamType::Flag => String::new(),
+    }
+}
+
+/// Param values for a command run on behalf of another: the other's values of the same name,
+/// else the param's fallback. Fails with the name of a required param that has neither, since
+/// nobody is prompted for it.
+fn inherited_values(
+    command: &CommandEntry,
+    values: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    let inherited: HashMap<String, String> = command
+        .params
+        .iter()
+        .map(|param| {
+            let value = values
+                .get(&param.name)
+                .cloned()
+                .unwrap_or_else(|| param_fallback_value(param));
+            (param.name.clone(), value)
+        })
+        .collect();
+    let missing = command.params.iter().find(|param| {
+        param.required
+            && !matches!(param.kind, ParamType::Flag)
+            && param.value_value.is_none()
+            && param.default_value.is_none()
+            && !values.contains_key(&param.name)
+            && param_enabled(param, &inherited)
+    });
+    match missing {
+        Some(param) => Err(format!(
+            "required param '{}' has no value or default",
+            param.name
+        )),
+        None => Ok(inherited),
+    }
+}
+
+/// Inlines `{{command:Name}}` (or `{{command:provider::name}}`) with the referenced command's
+/// template, recursively. `chain` holds the names being expanded so cycles are rejected, and
+/// every referenced command is collected so its params can fall back to their defaults.
+fn expand_command_refs<'a>(
+    commands: &'a [CommandEntry],
+    template: &str,
+    chain: &mut Vec<String>,
+    referenced: &mut Vec<&'a CommandEntry>,
+) -> Result<String, String> {
+    const PREFIX: &str = "{{command:";
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(PREFIX) {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + PREFIX.len()..start + len].trim();
+        let target = commands
+            .iter()
+            .find(|command| command.name == name || command_usage_key(command) == name)
+            .ok_or_else(|| format!("'{}' references unknown command '{name}'", chain[0]))?;
+        if !target.steps.is_empty() {
+            return Err(format!(
+                "'{}' references pipeline '{name}', which has no template of its own",
+                chain[0]
+            ));
+        }
+        if chain.contains(&target.name) {
+            return Err(format!(
+                "'{}' has a command reference cycle: {} -> {}",
+                chain[0],
+                chain.join(" -> "),
+                target.name
+            ));
+        }
+        chain.push(target.name.clone());
+        let expanded = expand_command_refs(commands, &target.template, chain, referenced)?;
+        chain.pop();
+        referenced.push(target);
+
+        output.push_str(&rest[..start]);
+        output.push_str(&expanded);
+        rest = &rest[start + len + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Handles a pending key while a command streams; returns true when it should be interrupted.
+/// With a pty, keys and pastes go to the command, except Escape and the output filter, and the
+/// pty follows the session pane when the screen is resized.
+fn poll_running_command_key(app: &mut AppState, pty_input: Option<&mut fs::File>) -> Result<bool> {
+    if !event::poll(Duration::from_millis(0))? {
+        return Ok(false);
+    }
+
+    let key = match event::read()? {
+        Event::Key(key) if key.kind == KeyEventKind::Press => key,
+        #[cfg(unix)]
+        Event::Paste(text) => {
+            if let Some(input) = pty_input {
+                let _ = input.write_all(text.as_bytes());
+            }
+            return Ok(false);
+        }
+        #[cfg(unix)]
+        Event::Resize(width, height) => {
+            if let Some(input) = pty_input {
+                let (rows, cols) = session_size(Rect::new(0, 0, width, height), app);
+                crate::pty::resize(input, rows, cols);
+            }
+            return Ok(false);
+        }
+        _ => return Ok(false),
+    };
+    #[cfg(unix)]
+    if let Some(input) = pty_input
+        && app.filter_input.is_none()
+        && key.code != KeyCode::Esc
+    {
+        if let Some(bytes) = crate::pty::key_bytes(key) {
+            let _ = input.write_all(&bytes);
+        }
+        return Ok(false);
+    }
+    #[cfg(not(unix))]
+    let _ = pty_input;
+
+    Ok(app.on_running_key(key))
+}
+
+/// Rows and columns inside the session pane on a screen of `area`, given to pty commands as
+/// their terminal size.
+fn session_size(area: Rect, app: &AppState) -> (u16, u16) {
+    let side_pane = app.details_index().is_some() || app.wanted_preview().is_some();
+    let session = screen_layout(app, area, side_pane).session;
+    (
+        session.height.saturating_sub(2),
+        session.width.saturating_sub(2),
+    )
+}
+
+/// Starts `process` on a pseudo-terminal; returns the child and the pty's input side, while a
+/// reader thread turns its output into (possibly partial) lines.
+#[cfg(unix)]
+fn spawn_in_pty(
+    process: Command,
+    rows: u16,
+    cols: u16,
+    tx: mpsc::Sender<StreamChunk>,
+) -> Result<(Child, Option<fs::File>)> {
+    let (child, mut master) = crate::pty::spawn(process, rows, cols)?;
+    let input = master.try_clone()?;
+    thread::spawn(move || {
+        let mut lines = crate::pty::LineAssembler::default();
+        let mut buffer = [0u8; 4096];
+        let send = |text: String, partial: bool| {
+            tx.send(StreamChunk {
+                kind: ChatLineKind::Stdout,
+                text,
+                partial,
+            })
+            .is_ok()
+        };
+        // Reads fail with EIO once every process on the terminal has exited.
+        while let Ok(read) = io::Read::read(&mut master, &mut buffer) {
+            if read == 0 {
+                break;
+            }
+            for line in lines.feed(&buffer[..read]) {
+                let sent = match line {
+                    crate::pty::PtyLine::Done(text) => send(text, false),
+                    crate::pty::PtyLine::Partial(text) => send(text, true),
+                };
+                if !sent {
+                    return;
+                }
+            }
+        }
+        if let Some(text) = lines.finish() {
+            send(text, false);
+        }
+    });
+    Ok((child, Some(input)))
+}
+
+#[cfg(not(unix))]
+fn spawn_in_pty(
+    _process: Command,
+    _rows: u16,
+    _cols: u16,
+    _tx: mpsc::Sender<StreamChunk>,
+) -> Result<(Child, Option<fs::File>)> {
+    bail!("pty = true is only supported on Unix")
+}
+
+/// Set for commands streamed into the session pane so a nested `fzc` can refuse to start.
+pub const SESSION_ENV: &str = "FZC_SESSION";
+
+fn apply_color_env(process: &mut Command, color: ColorMode) {
+    for (key, value) in color_env(color) {
+        match value {
+            Some(value) => process.env(key, value),
+            None => process.env_remove(key),
+        };
+    }
+    process.env(
+        "TERM",
+        std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string()),
+    );
+}
+
+/// Variables to set (`Some`) or remove (`None`) for a color mode.
+fn color_env(color: ColorMode) -> Vec<(&'static str, Option<&'static str>)> {
+    match color {
+        ColorMode::Auto => Vec::new(),
+        ColorMode::Force => vec![("CLICOLOR_FORCE", Some("1")), ("FORCE_COLOR", Some("1"))],
+        ColorMode::Never => vec![
+            ("NO_COLOR", Some("1")),
+            ("CLICOLOR_FORCE", None),
+            ("FORCE_COLOR", None),
+        ],
+    }
 }
 
 fn spawn_stream_reader<R: io::Read + Send + 'static>(
@@ -473,7 +2397,12 @@ fn spawn_stream_reader<R: io::Read + Send + 'static>(
                 Ok(0) => break,
                 Ok(_) => {
                     let text = line.trim_end_matches(['\n', '\r']).to_string();
-                    if tx.send(StreamChunk { kind, text }).is_err() {
+                    let chunk = StreamChunk {
+                        kind,
+                        text,
+                        partial: false,
+                    };
+                    if tx.send(chunk).is_err() {
                         break;
                     }
                 }
@@ -484,108 +2413,522 @@ fn spawn_stream_reader<R: io::Read + Send + 'static>(
 }
 
 fn draw_ui(frame: &mut Frame, app: &AppState) {
-    let bottom_height = if app.show_help { 14 } else { 1 };
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Min(8),
-            Constraint::Length(8),
-            Constraint::Length(1),
-            Constraint::Length(bottom_height),
-        ])
-        .split(frame.area());
-
-    draw_chat_panel(frame, app, chunks[0]);
-    draw_commands_panel(frame, app, chunks[1]);
-    draw_search_bar(frame, app, chunks[2]);
-    if app.show_help {
-        draw_help_panel(frame, app, chunks[3]);
-    } else {
-        draw_hint_bar(frame, app, chunks[3]);
+    if matches!(app.mode, Mode::Selection) {
+        draw_selection_view(frame, app);
+        return;
     }
+    let details = app.details_index();
+    let preview = app.wanted_preview();
+    let layout = screen_layout(app, frame.area(), details.is_some() || preview.is_some());
+
+    draw_chat_panel(frame, app, layout.session);
+    draw_job_panes(frame, app, &layout.jobs);
+    draw_commands_panel(frame, app, layout.commands);
+    match (details, preview, layout.side) {
+        (Some(index), _, Some(side)) => draw_details_panel(frame, app, &app.commands[index], side),
+        (None, Some(Ok(preview)), Some(side)) => draw_preview_panel(frame, app, &preview, side),
+        (None, Some(Err(err)), Some(side)) => draw_preview_error(frame, app, &err, side),
+        _ => {}
+    }
+    draw_search_bar(frame, app, layout.search);
+    draw_hint_bar(frame, app, layout.bottom);
 
     if matches!(app.mode, Mode::Search) && !app.is_loading {
-        let x = chunks[2].x.saturating_add(8 + app.query_cursor as u16);
-        let y = chunks[2].y;
+        let x = layout.search.x.saturating_add(8 + app.query_cursor as u16);
+        let y = layout.search.y;
         frame.set_cursor_position((x, y));
     }
 
     match &app.mode {
         Mode::Prompt(prompt) => draw_prompt_popup(frame, app, prompt),
         Mode::InternalPrompt(prompt) => draw_internal_prompt_popup(frame, app, prompt),
-        Mode::Search => {}
+        Mode::EnvironmentPicker(picker) => draw_environment_picker_popup(frame, app, picker),
+        Mode::ProjectPicker(picker) => draw_project_picker_popup(frame, app, picker),
+        Mode::HistoryPicker(picker) => {
+            draw_history_picker_popup(frame, picker, &app.runtime.theme, app.runtime.locale)
+        }
+        Mode::ConfirmRun(confirm) => {
+            draw_confirm_run_popup(frame, confirm, &app.runtime.theme, app.runtime.locale)
+        }
+        Mode::InitDiff(diff) => {
+            draw_init_diff_popup(frame, diff, &app.runtime.theme, app.runtime.locale)
+        }
+        Mode::Palette(palette) => {
+            draw_palette_popup(frame, palette, &app.runtime.theme, app.runtime.locale)
+        }
+        Mode::Pager(pager) => {
+            draw_pager_popup(frame, pager, &app.runtime.theme, app.runtime.locale)
+        }
+        Mode::QueueReview(review) => draw_queue_review_popup(frame, app, review),
+        Mode::DryRun(dry_run) => {
+            draw_dry_run_popup(frame, dry_run, &app.runtime.theme, app.runtime.locale)
+        }
+        Mode::Jobs(state) => draw_jobs_popup(frame, app, state),
+        Mode::Caches(state) => draw_caches_popup(frame, app, state),
+        Mode::Help(help) => draw_help_popup(frame, app, help),
+        Mode::Search | Mode::Selection | Mode::WarnRun(_) => {}
+    }
+
+    if app.runtime.color_support != ColorSupport::TrueColor {
+        for cell in frame.buffer_mut().content.iter_mut() {
+            if app.runtime.color_support == ColorSupport::Monochrome
+                && matches!(cell.bg, Color::Rgb(..))
+            {
+                cell.modifier |= Modifier::REVERSED;
+            }
+            cell.fg = downgrade_color(cell.fg, app.runtime.color_support);
+            cell.bg = downgrade_color(cell.bg, app.runtime.color_support);
+        }
     }
 }
 
-fn draw_chat_panel(frame: &mut Frame, app: &AppState, area: Rect) {
-    let max_lines = area.height.saturating_sub(2) as usize;
-    let visible = max_lines.max(1);
-    let max_offset = app.chat.len().saturating_sub(visible);
-    let offset = app.session_scroll.min(max_offset);
-    let start = app
-        .chat
-        .len()
-        .saturating_sub(visible.saturating_add(offset));
+/// Where the main panes sit; shared by drawing and mouse hit-testing.
+struct ScreenLayout {
+    session: Rect,
+    /// One per `[ui] job_panes` pane, stacked beside the session.
+    jobs: Vec<Rect>,
+    commands: Rect,
+    /// Details or preview pane beside the command list.
+    side: Option<Rect>,
+    search: Rect,
+    bottom: Rect,
+}
 
-    let items: Vec<ListItem<'_>> = app.chat.iter().skip(start).map(render_chat_line).collect();
+fn screen_layout(app: &AppState, area: Rect, side_pane: bool) -> ScreenLayout {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(SESSION_MIN_HEIGHT),
+            Constraint::Length(commands_panel_height(app, area.height)),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(area);
 
-    let border_color = if app.active_pane == ActivePane::Session {
-        Color::Rgb(88, 150, 201)
+    let (session, jobs) = if app.job_panes.is_empty() {
+        (chunks[0], Vec::new())
     } else {
-        Color::Rgb(70, 84, 96)
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(chunks[0]);
+        let count = app.job_panes.len() as u32;
+        let jobs = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints((0..count).map(|_| Constraint::Ratio(1, count)))
+            .split(columns[1]);
+        (columns[0], jobs.to_vec())
     };
-    let list = List::new(items).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title(if app.active_pane == ActivePane::Session {
-                "Session [active]"
-            } else {
-                "Session"
-            })
-            .border_type(BorderType::Rounded)
-            .border_style(Style::default().fg(border_color)),
-    );
+    let (commands, side) = if side_pane {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+            .split(chunks[1]);
+        (columns[0], Some(columns[1]))
+    } else {
+        (chunks[1], None)
+    };
+    ScreenLayout {
+        session,
+        jobs,
+        commands,
+        side,
+        search: chunks[2],
+        bottom: chunks[3],
+    }
+}
 
-    frame.render_widget(list, area);
+/// Tall enough for every matching command while the session has room to spare, shrinking back to
+/// `COMMANDS_MIN_HEIGHT` as output fills the session.
+fn commands_panel_height(app: &AppState, height: u16) -> u16 {
+    // The search and hint bars take a line each.
+    let available = height.saturating_sub(2);
+    let session_lines = u16::try_from(app.chat.len()).unwrap_or(u16::MAX);
+    let session = session_lines.saturating_add(2).max(SESSION_MIN_HEIGHT);
+    let rows = u16::try_from(app.filtered.len())
+        .unwrap_or(u16::MAX)
+        .saturating_add(2);
+    rows.min(available.saturating_sub(session))
+        .max(COMMANDS_MIN_HEIGHT)
 }
 
-fn render_chat_line(entry: &ChatLine) -> ListItem<'static> {
-    match entry.kind {
-        ChatLineKind::Info => {
-            let style = Style::default().fg(Color::Gray);
-            ListItem::new(Line::from(vec![
-                Span::styled("• ".to_string(), style),
-                Span::styled(entry.text.clone(), style),
-            ]))
+fn downgrade_color(color: Color, support: ColorSupport) -> Color {
+    match (support, color) {
+        (ColorSupport::TrueColor, _) => color,
+        (ColorSupport::Monochrome, _) => Color::Reset,
+        (ColorSupport::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_ansi256(r, g, b)),
+        (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => nearest_ansi16(r, g, b),
+        (ColorSupport::Ansi16, Color::Indexed(index)) => {
+            let (r, g, b) = ansi256_to_rgb(index);
+            nearest_ansi16(r, g, b)
         }
-        ChatLineKind::Command => {
+        _ => color,
+    }
+}
+
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            249..=255 => 231,
+            _ => 232 + ((r as u16 - 8) * 24 / 241) as u8,
+        };
+    }
+    let level = |value: u8| ((value as u16 * 5 + 127) / 255) as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn ansi256_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI16[index as usize].1,
+        16..=231 => {
+            let index = index - 16;
+            let step = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            (step(index / 36), step(index / 6 % 6), step(index % 6))
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let dr = r as i32 - cr as i32;
+        let dg = g as i32 - cg as i32;
+        let db = b as i32 - cb as i32;
+        dr * dr + dg * dg + db * db
+    };
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// One sub-pane per scheduled or background job with `[ui] job_panes`, stacked beside the main
+/// Session stream.
+fn draw_job_panes(frame: &mut Frame, app: &AppState, areas: &[Rect]) {
+    let theme = &app.runtime.theme;
+    let strings = app.runtime.locale.strings();
+    for (index, (pane, area)) in app.job_panes.iter().zip(areas).enumerate() {
+        let focused = app.active_pane == ActivePane::Session && app.focused_job == Some(index);
+        let (status, status_color) = match (&pane.source, pane.last_exit) {
+            (PaneSource::Job(id), _) => (
+                i18n::fill(strings.job_running, &[("id", &id.to_string())]),
+                theme.warning,
+            ),
+            (_, Some(0)) => (strings.status_ok.to_string(), theme.success),
+            (_, Some(code)) => (
+                i18n::fill(strings.status_exit, &[("code", &code.to_string())]),
+                theme.error,
+            ),
+            (_, None) => (strings.status_failed.to_string(), theme.error),
+        };
+        let mut title = vec![
+            Span::raw(format!("{} ", pane.name)),
+            Span::styled(status, Style::default().fg(status_color)),
+        ];
+        if focused {
+            title.push(Span::raw(" [active]"));
+        }
+        let border_color = if focused {
+            theme.border_active
+        } else {
+            theme.border
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(Line::from(title))
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(border_color));
+        let visible = block.inner(*area).height as usize;
+        let end = pane.lines.len().saturating_sub(pane.scroll);
+        let start = end.saturating_sub(visible);
+        let items: Vec<ListItem<'_>> = pane.lines[start..end]
+            .iter()
+            .map(|line| ListItem::new(Line::from(render_chat_spans(line, theme))))
+            .collect();
+        frame.render_widget(List::new(items).block(block), *area);
+    }
+}
+
+fn draw_chat_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = &app.runtime.theme;
+    let session_active = app.active_pane == ActivePane::Session && app.focused_job.is_none();
+    let border_color = if session_active {
+        theme.border_active
+    } else {
+        theme.border
+    };
+    let mut title = if session_active {
+        "Session [active]".to_string()
+    } else {
+        "Session".to_string()
+    };
+    if let Some(input) = &app.filter_input {
+        title.push_str(&format!(" filter: {input}_"));
+    } else if let Some(filter) = &app.output_filter {
+        title.push_str(&format!(" filter: {}", filter.pattern));
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(border_color));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let rows = app.session_rows();
+    let max_lines = inner.height as usize;
+    let visible = max_lines.max(1);
+    let chat_start = |visible: usize| {
+        let max_offset = rows.len().saturating_sub(visible);
+        let offset = app.session_scroll.min(max_offset);
+        rows.len().saturating_sub(visible.saturating_add(offset))
+    };
+
+    // Reserve the top row for the command whose output is scrolled into view.
+    let mut start = chat_start(visible);
+    let mut sticky = None;
+    if visible > 1 {
+        let reserved_start = chat_start(visible - 1);
+        if let Some(index) = sticky_command_index(&app.chat, &rows, reserved_start) {
+            start = reserved_start;
+            sticky = Some(index);
+        }
+    }
+
+    let mut list_area = inner;
+    if let Some(index) = sticky {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+        let header = Paragraph::new(Line::from(vec![
+            Span::styled(
+                "$ ".to_string(),
+                Style::default()
+                    .fg(theme.command)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                app.chat[index].text.clone(),
+                Style::default()
+                    .fg(theme.command)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]))
+        .style(Style::default().bg(theme.header_bg));
+        frame.render_widget(header, areas[0]);
+        list_area = areas[1];
+    }
+
+    let folded = !app.expand_repeats;
+    let items: Vec<ListItem<'_>> = rows
+        .iter()
+        .skip(start)
+        .map(|row| {
+            let entry = &app.chat[row.line];
+            match (row.json_line, &entry.json) {
+                (Some(part), Some(json)) => render_json_line(&json[part]),
+                _ => render_chat_line(entry, folded, theme),
+            }
+        })
+        .collect();
+    frame.render_widget(List::new(items), list_area);
+}
+
+/// Session text alone, without borders or other panes, so the terminal's own mouse selection
+/// copies just the output.
+fn draw_selection_view(frame: &mut Frame, app: &AppState) {
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(frame.area());
+
+    let rows = app.session_rows();
+    let visible = (areas[0].height as usize).max(1);
+    let offset = app.session_scroll.min(rows.len().saturating_sub(visible));
+    let start = rows.len().saturating_sub(visible.saturating_add(offset));
+    let lines: Vec<Line<'_>> = rows
+        .iter()
+        .skip(start)
+        .take(visible)
+        .map(|row| Line::from(selection_text(&app.chat[row.line], row.json_line)))
+        .collect();
+    frame.render_widget(Paragraph::new(lines), areas[0]);
+    frame.render_widget(
+        Paragraph::new(app.runtime.locale.strings().selection_hint)
+            .style(Style::default().fg(Color::DarkGray)),
+        areas[1],
+    );
+}
+
+/// Plain text of one session row, untruncated and without the pane's decorations.
+fn selection_text(entry: &ChatLine, json_line: Option<usize>) -> String {
+    if let (Some(part), Some(json)) = (json_line, &entry.json) {
+        return json[part].clone();
+    }
+    let text = entry.full_text.as_deref().unwrap_or(&entry.text);
+    match entry.kind {
+        ChatLineKind::Command => format!("$ {text}"),
+        _ => text.to_string(),
+    }
+}
+
+/// `text` without its ANSI color sequences.
+fn plain_text(text: &str) -> String {
+    parse_ansi_spans(text, Style::default(), Color::Reset)
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect()
+}
+
+/// The `$ command` line owning the first visible row, when that line itself is scrolled away.
+fn sticky_command_index(chat: &[ChatLine], rows: &[SessionRow], start: usize) -> Option<usize> {
+    let first = rows.get(start)?.line;
+    if start == 0 || matches!(chat[first].kind, ChatLineKind::Command) {
+        return None;
+    }
+    chat[..first]
+        .iter()
+        .rposition(|line| matches!(line.kind, ChatLineKind::Command))
+}
+
+fn render_chat_line(entry: &ChatLine, folded: bool, theme: &Theme) -> ListItem<'static> {
+    let mut spans = render_chat_spans(entry, theme);
+    if folded && entry.repeat > 1 {
+        spans.push(Span::styled(
+            format!(" … (×{})", entry.repeat),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    ListItem::new(Line::from(spans))
+}
+
+fn render_chat_spans(entry: &ChatLine, theme: &Theme) -> Vec<Span<'static>> {
+    match entry.kind {
+        ChatLineKind::Info => {
+            let style = Style::default().fg(theme.info);
+            vec![
+                Span::styled("• ".to_string(), style),
+                Span::styled(entry.text.clone(), style),
+            ]
+        }
+        ChatLineKind::Banner => {
             let style = Style::default()
-                .fg(Color::Cyan)
+                .fg(theme.banner_fg)
+                .bg(theme.banner_bg)
                 .add_modifier(Modifier::BOLD);
-            ListItem::new(Line::from(vec![
+            vec![Span::styled(format!(" ▲ {} ", entry.text), style)]
+        }
+        ChatLineKind::Command => {
+            let style = Style::default()
+                .fg(theme.command)
+                .add_modifier(Modifier::BOLD);
+            vec![
                 Span::styled("$ ".to_string(), style),
                 Span::styled(entry.text.clone(), style),
-            ]))
+            ]
         }
         ChatLineKind::Stdout => {
             let prefix_style = Style::default().fg(Color::DarkGray);
-            let default_style = Style::default().fg(Color::White);
+            let default_style = Style::default().fg(theme.stdout);
             let mut spans = vec![Span::styled("  ".to_string(), prefix_style)];
-            spans.extend(parse_ansi_spans(&entry.text, default_style, Color::White));
-            ListItem::new(Line::from(spans))
+            spans.extend(parse_ansi_spans(&entry.text, default_style, theme.stdout));
+            spans
         }
         ChatLineKind::Stderr => {
             let prefix_style = Style::default().fg(Color::DarkGray);
-            let default_style = Style::default().fg(Color::LightRed);
+            let default_style = Style::default().fg(theme.stderr);
             let mut spans = vec![Span::styled("! ".to_string(), prefix_style)];
-            spans.extend(parse_ansi_spans(
-                &entry.text,
-                default_style,
-                Color::LightRed,
-            ));
-            ListItem::new(Line::from(spans))
+            spans.extend(parse_ansi_spans(&entry.text, default_style, theme.stderr));
+            spans
+        }
+    }
+}
+
+fn render_json_line(text: &str) -> ListItem<'static> {
+    let mut spans = vec![Span::styled(
+        "  ".to_string(),
+        Style::default().fg(Color::DarkGray),
+    )];
+    let indent_len = text.len() - text.trim_start().len();
+    spans.push(Span::raw(text[..indent_len].to_string()));
+
+    let mut rest = &text[indent_len..];
+    while !rest.is_empty() {
+        let (token, style) = if rest.starts_with('"') {
+            let end = json_string_end(rest);
+            let is_key = rest[end..].trim_start().starts_with(':');
+            let color = if is_key {
+                Color::LightBlue
+            } else {
+                Color::LightGreen
+            };
+            (&rest[..end], Style::default().fg(color))
+        } else {
+            let end = rest
+                .find(['"', ',', ':', '{', '}', '[', ']'])
+                .map(|index| index.max(1))
+                .unwrap_or(rest.len());
+            let token = &rest[..end];
+            let color = match token.trim() {
+                "true" | "false" | "null" => Color::LightMagenta,
+                value if value.parse::<f64>().is_ok() => Color::LightYellow,
+                _ => Color::Gray,
+            };
+            (token, Style::default().fg(color))
+        };
+        spans.push(Span::styled(token.to_string(), style));
+        rest = &rest[token.len()..];
+    }
+
+    ListItem::new(Line::from(spans))
+}
+
+fn json_string_end(text: &str) -> usize {
+    let mut escaped = false;
+    for (index, ch) in text.char_indices().skip(1) {
+        match ch {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return index + 1,
+            _ => escaped = false,
         }
     }
+    text.len()
+}
+
+/// Pretty-printed lines for output that is a single JSON object or array.
+fn pretty_json_lines(text: &str) -> Option<Vec<String>> {
+    let trimmed = text.trim();
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        return None;
+    }
+    let value = serde_json::from_str::<serde_json::Value>(trimmed).ok()?;
+    let pretty = serde_json::to_string_pretty(&value).ok()?;
+    Some(pretty.lines().map(ToString::to_string).collect())
 }
 
 fn parse_ansi_spans(text: &str, default_style: Style, default_fg: Color) -> Vec<Span<'static>> {
@@ -602,7 +2945,7 @@ fn parse_ansi_spans(text: &str, default_style: Style, default_fg: Color) -> Vec<
             }
 
             let mut seq = String::new();
-            while let Some(next) = chars.next() {
+            for next in chars.by_ref() {
                 if next == 'm' {
                     apply_sgr_sequence(&seq, &mut style, default_style, default_fg);
                     break;
@@ -713,32 +3056,198 @@ fn map_ansi_color(code: u16) -> Color {
     }
 }
 
-fn draw_commands_panel(frame: &mut Frame, app: &AppState, area: Rect) {
-    let total = if app.is_internal_query() {
-        app.internal_commands.len()
-    } else {
-        app.commands.len()
+fn draw_preview_panel(frame: &mut Frame, app: &AppState, preview: &PreviewRequest, area: Rect) {
+    let theme = &app.runtime.theme;
+    let status = |text: &str| {
+        vec![Line::from(Span::styled(
+            text.to_string(),
+            Style::default().fg(Color::DarkGray),
+        ))]
     };
-    let title = if app.active_pane == ActivePane::Commands {
-        format!("Commands ({}/{total}) [active]", app.filtered.len())
-    } else {
-        format!("Commands ({}/{total})", app.filtered.len())
+    let lines = match app.preview_cache.get(&preview.command_line) {
+        Some(output) if output.lines.is_empty() => status("(no output)"),
+        Some(output) => output
+            .lines
+            .iter()
+            .map(|line| Line::from(line.clone()))
+            .collect(),
+        None if !matches!(
+            app.guard.check(&preview.command_line, None, None),
+            GuardVerdict::Allow
+        ) =>
+        {
+            status("preview blocked by [safety] patterns")
+        }
+        None => status("running preview…"),
+    };
+
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(i18n::fill(
+                app.runtime.locale.strings().preview_of,
+                &[("command", &preview.command_line)],
+            ))
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(theme.border)),
+    );
+    frame.render_widget(panel, area);
+}
+
+fn draw_preview_error(frame: &mut Frame, app: &AppState, error: &str, area: Rect) {
+    let panel = Paragraph::new(Line::from(Span::styled(
+        error.to_string(),
+        Style::default().fg(Color::DarkGray),
+    )))
+    .wrap(Wrap { trim: false })
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.runtime.locale.strings().preview_title)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(app.runtime.theme.border)),
+    );
+    frame.render_widget(panel, area);
+}
+
+fn draw_details_panel(frame: &mut Frame, app: &AppState, command: &CommandEntry, area: Rect) {
+    let theme = &app.runtime.theme;
+    let panel = Paragraph::new(command_detail_lines(app, command))
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(i18n::fill(
+                    app.runtime.locale.strings().details_of,
+                    &[("name", &command.name)],
+                ))
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(theme.border)),
+        );
+    frame.render_widget(panel, area);
+}
+
+/// Everything the one-line list row truncates: template, params, source, and usage.
+fn command_detail_lines(app: &AppState, command: &CommandEntry) -> Vec<Line<'static>> {
+    let dim = Style::default().fg(Color::DarkGray);
+    let field = |label: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{label:<10}"), dim),
+            Span::raw(value),
+        ])
+    };
+    let mut lines = vec![Line::from(vec![
+        Span::styled("$ ", dim),
+        Span::styled(
+            command.template.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+    ])];
+    if let Some(description) = &command.description {
+        lines.push(Line::from(Span::styled(description.clone(), dim)));
+    }
+    for param in &command.params {
+        let kind = match &param.kind {
+            ParamType::Value => "value",
+            ParamType::Flag => "flag",
+            ParamType::Choice(_) => "choice",
+            ParamType::Path(_) => "path",
+        };
+        let fixed = match &param.kind {
+            ParamType::Flag => param
+                .value_flag
+                .or(param.default_flag)
+                .map(|enabled| enabled.to_string()),
+            _ => param
+                .value_value
+                .clone()
+                .or_else(|| param.default_value.clone()),
+        };
+        let mut text = format!("{} ({kind})", param.name);
+        if let Some(fixed) = fixed {
+            text.push_str(&format!(" = {fixed}"));
+        }
+        if let ParamType::Choice(options) = &param.kind {
+            text.push_str(&format!(" [{}]", options.join("/")));
+        }
+        lines.push(field("param", text));
+    }
+    let mut provider = command_provider_name(command).to_string();
+    if let Some(badge) = &command.badge {
+        provider.push_str(&format!(" ({badge})"));
+    }
+    lines.push(field("provider", provider));
+    lines.push(field(
+        "directory",
+        command
+            .working_dir
+            .as_ref()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_else(|| "(current)".to_string()),
+    ));
+    if let Some(host) = &command.host {
+        lines.push(field("host", host.clone()));
+    }
+    if !command.environments.is_empty() {
+        let names: Vec<&str> = command
+            .environments
+            .iter()
+            .map(|environment| environment.name.as_str())
+            .collect();
+        lines.push(field("envs", names.join(", ")));
+    }
+    let locale = app.runtime.locale;
+    let usage = match app.usage.get(&command_usage_key(command)) {
+        Some(record) if record.count > 0 => i18n::fill(
+            locale.strings().usage,
+            &[
+                ("count", &locale.format_count(record.count as usize)),
+                (
+                    "age",
+                    &locale.format_age(history::now().saturating_sub(record.last_used)),
+                ),
+            ],
+        ),
+        _ => locale.strings().never_run.to_string(),
     };
+    lines.push(field("usage", usage));
+    lines
+}
+
+fn draw_commands_panel(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = &app.runtime.theme;
+    let total = app.list_len();
+    let mut title = format!("Commands ({}/{total})", app.filtered.len());
+    if !app.loading_providers.is_empty() {
+        title.push_str(&format!(" loading {}…", app.loading_providers.join(", ")));
+    }
+    if app.staging || !app.queue.is_empty() {
+        title.push_str(&format!(" [queue: {}]", app.queue.len()));
+    }
+    if app.active_pane == ActivePane::Commands {
+        title.push_str(" [active]");
+    }
     let border_color = if app.active_pane == ActivePane::Commands {
-        Color::Rgb(88, 150, 201)
+        theme.border_active
     } else {
-        Color::Rgb(70, 84, 96)
+        theme.border
     };
 
     if app.filtered.is_empty() {
-        let empty = Paragraph::new("No matching commands")
-            .alignment(Alignment::Center)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(title)
-                    .border_style(Style::default().fg(Color::DarkGray)),
-            );
+        let empty = Paragraph::new(vec![
+            Line::from(app.runtime.locale.strings().no_matching_commands),
+            Line::from(Span::styled(
+                app.empty_result_hint(),
+                Style::default().fg(Color::DarkGray),
+            )),
+        ])
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(Color::DarkGray)),
+        );
         frame.render_widget(empty, area);
         return;
     }
@@ -750,22 +3259,50 @@ fn draw_commands_panel(frame: &mut Frame, app: &AppState, area: Rect) {
             SearchItem::Command(index) => {
                 let command = &app.commands[*index];
                 let provider_name = command_provider_name(command);
-                let provider_badge = app
+                let mut provider_badge = app
                     .provider_alias_by_name
                     .get(provider_name)
                     .cloned()
                     .unwrap_or_else(|| provider_name.to_string());
+                if let Some(badge) = &command.badge {
+                    provider_badge.push(' ');
+                    provider_badge.push_str(badge);
+                }
                 let display_name = display_command_name(command, provider_name);
+                let locale = app.runtime.locale;
 
-                let mut spans = vec![
+                let mut spans = Vec::new();
+                if app.is_pinned(command) {
+                    spans.push(Span::styled("★ ", Style::default().fg(Color::Yellow)));
+                }
+                spans.extend([
                     Span::styled(
                         format!("[{provider_badge}] "),
                         Style::default()
-                            .fg(Color::LightCyan)
+                            .fg(theme.badge_color(provider_name))
                             .add_modifier(Modifier::BOLD),
                     ),
-                    Span::styled(display_name, Style::default().fg(Color::White)),
-                ];
+                    Span::styled(display_name, Style::default().fg(theme.text)),
+                ]);
+                if let Some(age) = app.stale_provider_age(provider_name) {
+                    spans.push(Span::styled(
+                        format!(
+                            " ({})",
+                            i18n::fill(
+                                locale.strings().provider_age,
+                                &[("age", &locale.format_age(age))]
+                            )
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+
+                if let Some(host) = &command.host {
+                    spans.push(Span::styled(
+                        format!(" @{host}"),
+                        Style::default().fg(Color::LightMagenta),
+                    ));
+                }
 
                 if let Some(description) = &command.description {
                     spans.push(Span::styled(
@@ -785,7 +3322,7 @@ fn draw_commands_panel(frame: &mut Frame, app: &AppState, area: Rect) {
                             .fg(Color::LightGreen)
                             .add_modifier(Modifier::BOLD),
                     ),
-                    Span::styled(internal.name.to_string(), Style::default().fg(Color::White)),
+                    Span::styled(internal.name.to_string(), Style::default().fg(theme.text)),
                     Span::styled(
                         format!(" | {}", internal.description),
                         Style::default().fg(Color::DarkGray),
@@ -799,18 +3336,32 @@ fn draw_commands_panel(frame: &mut Frame, app: &AppState, area: Rect) {
     let mut list_state = ListState::default();
     list_state.select(Some(app.selected));
 
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(border_color));
+    let rows = area.height.saturating_sub(2) as usize;
+    if app.filtered.len() > rows {
+        let locale = app.runtime.locale;
+        let first = app.command_list_offset(rows);
+        let range = i18n::fill(
+            locale.strings().page_range,
+            &[
+                ("first", &locale.format_count(first + 1)),
+                ("last", &locale.format_count(first + rows)),
+                ("count", &locale.format_count(app.filtered.len())),
+            ],
+        );
+        block = block.title_bottom(Line::from(format!(" {range} ")).right_aligned());
+    }
+
     let list = List::new(items)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(title)
-                .border_type(BorderType::Rounded)
-                .border_style(Style::default().fg(border_color)),
-        )
+        .block(block)
         .highlight_style(
             Style::default()
-                .bg(Color::Rgb(42, 88, 116))
-                .fg(Color::White)
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
                 .add_modifier(Modifier::BOLD),
         )
         .highlight_symbol("▸ ");
@@ -819,6 +3370,7 @@ fn draw_commands_panel(frame: &mut Frame, app: &AppState, area: Rect) {
 }
 
 fn draw_search_bar(frame: &mut Frame, app: &AppState, area: Rect) {
+    let theme = &app.runtime.theme;
     let search_text = if app.is_loading {
         let label = app.loading_label.as_deref().unwrap_or("command");
         format!(
@@ -835,19 +3387,39 @@ fn draw_search_bar(frame: &mut Frame, app: &AppState, area: Rect) {
     frame.render_widget(
         Paragraph::new(Line::from(Span::styled(
             search_text,
-            Style::default().fg(Color::White),
+            Style::default().fg(theme.text),
         ))),
         area,
     );
 }
 
 fn draw_hint_bar(frame: &mut Frame, app: &AppState, area: Rect) {
-    let text = if app.show_help {
-        "  Press ? or Esc to close help"
+    let locale = app.runtime.locale;
+    let strings = locale.strings();
+    let running = app.jobs.iter().filter(|job| job.is_running()).count();
+    let text = if app.filter_input.is_some() {
+        format!("  {}", strings.hint_filter)
+    } else if app.followed_job.is_some() {
+        format!("  {}", strings.hint_foreground)
     } else if app.is_loading {
-        "  Esc to interrupt"
+        format!("  {}", strings.hint_running)
+    } else if running > 0 {
+        let jobs = i18n::fill(
+            strings.hint_jobs_running,
+            &[("count", &locale.format_count(running))],
+        );
+        format!("  {} · {jobs}", strings.hint_help)
+    } else if let Some((provider, age)) = app.current_command_index().and_then(|index| {
+        let provider = command_provider_name(&app.commands[index]);
+        Some((provider, app.stale_provider_age(provider)?))
+    }) {
+        let age = i18n::fill(strings.provider_age, &[("age", &locale.format_age(age))]);
+        format!(
+            "  {} · {provider} {age} · {}",
+            strings.hint_help, strings.hint_refresh
+        )
     } else {
-        "  ? for help"
+        format!("  {}", strings.hint_help)
     };
     let hint = Paragraph::new(text)
         .style(Style::default().fg(Color::DarkGray))
@@ -855,102 +3427,221 @@ fn draw_hint_bar(frame: &mut Frame, app: &AppState, area: Rect) {
     frame.render_widget(hint, area);
 }
 
-fn draw_help_panel(frame: &mut Frame, _app: &AppState, area: Rect) {
-    let rows = vec![
-        Line::from("  Enter          Run selected command"),
-        Line::from("  Option+Enter   Run selected command and exit"),
-        Line::from("  Tab            Toggle command/session focus"),
-        Line::from("  Up/Down        Scroll active pane"),
-        Line::from("  PgUp/PgDn      Scroll active pane faster"),
-        Line::from("  Left/Right     Move cursor in search input"),
-        Line::from("  Home/End       Jump cursor in search input"),
-        Line::from("  Backspace/Del  Edit search input"),
-        Line::from("  :provider text Filter by provider"),
-        Line::from("  /              Internal commands"),
-        Line::from("  ?              Toggle this help"),
-        Line::from("  Esc            Clear search / quit / interrupt running command"),
-    ];
-    let content = Paragraph::new(rows).alignment(Alignment::Left).block(
+fn draw_help_popup(frame: &mut Frame, app: &AppState, help: &HelpState) {
+    let theme = &app.runtime.theme;
+    let strings = app.runtime.locale.strings();
+    let area = frame.area();
+    frame.render_widget(Clear, area);
+    frame.render_widget(
         Block::default()
-            .borders(Borders::NONE)
-            .style(Style::default().fg(Color::DarkGray)),
+            .borders(Borders::ALL)
+            .title(strings.help_title)
+            .title_bottom(strings.help_hint)
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
     );
-    frame.render_widget(content, area);
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
+    frame.render_widget(Paragraph::new(format!("> {}", help.query)), body[0]);
+    frame.set_cursor_position((
+        body[0]
+            .x
+            .saturating_add(2 + help.query.chars().count() as u16),
+        body[0].y,
+    ));
+
+    let groups = app.help_groups(&help.query);
+    if groups.is_empty() {
+        frame.render_widget(
+            Paragraph::new(strings.help_no_matches).style(Style::default().fg(Color::DarkGray)),
+            body[1],
+        );
+        return;
+    }
+    let mut lines = Vec::new();
+    for group in &groups {
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::from(Span::styled(
+            group.title.as_str(),
+            Style::default().fg(theme.info).add_modifier(Modifier::BOLD),
+        )));
+        for (keys, action) in &group.rows {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {keys:<16}"), Style::default().fg(theme.command)),
+                Span::raw(*action),
+            ]));
+        }
+    }
+    let scroll = help.scroll.min(lines.len().saturating_sub(1)) as u16;
+    frame.render_widget(Paragraph::new(lines).scroll((scroll, 0)), body[1]);
 }
 
 fn draw_prompt_popup(frame: &mut Frame, app: &AppState, prompt: &PromptState) {
-    let area = centered_rect(70, 30, frame.area());
+    let theme = &app.runtime.theme;
+    let strings = app.runtime.locale.strings();
+    let command = &app.commands[prompt.command_index];
+    let param_idx = prompt.pending_params[prompt.current_param];
+    let param = &command.params[param_idx];
+    let listing = app.prompt_listing(prompt);
+
+    let height = if listing.is_some() { 60 } else { 35 };
+    let area = centered_rect(70, height, frame.area());
     frame.render_widget(Clear, area);
     frame.render_widget(
         Block::default()
             .borders(Borders::ALL)
-            .title("Parameter")
-            .style(Style::default().bg(Color::Black)),
+            .title(strings.param_title)
+            .style(Style::default().bg(theme.popup_bg)),
         area,
     );
 
-    let command = &app.commands[prompt.command_index];
-    let param_idx = prompt.pending_params[prompt.current_param];
-    let param = &command.params[param_idx];
-
+    let description: Vec<Line<'_>> = param
+        .description
+        .as_deref()
+        .map(|text| text.lines().map(Line::from).collect())
+        .unwrap_or_default();
     let body = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(1),
+            Constraint::Length(description.len() as u16),
             Constraint::Length(1),
             Constraint::Length(1),
             Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(2),
         ])
         .margin(1)
         .split(area);
+    let [
+        heading_area,
+        description_area,
+        helper_area,
+        command_area,
+        input_area,
+        listing_area,
+        rendered_area,
+    ] = [
+        body[0], body[1], body[2], body[3], body[4], body[5], body[6],
+    ];
 
+    let group = param
+        .group
+        .as_deref()
+        .map(|group| format!("{group} › "))
+        .unwrap_or_default();
     let heading = format!(
-        "{} ({}/{})",
+        "{group}{} ({}/{})",
         param.prompt,
         prompt.current_param + 1,
         prompt.pending_params.len()
     );
-    frame.render_widget(Paragraph::new(heading), body[0]);
+    frame.render_widget(Paragraph::new(heading), heading_area);
+    frame.render_widget(
+        Paragraph::new(description).style(Style::default().fg(Color::Gray)),
+        description_area,
+    );
 
     let helper_text = match param.kind {
-        ParamType::Value => param
-            .placeholder
-            .as_deref()
-            .or(param.default_value.as_deref())
-            .map(|value| format!("placeholder: {value}"))
-            .unwrap_or_default(),
+        ParamType::Value => match (&param.default_value, &param.placeholder) {
+            (Some(_), _) => strings.param_default_hint.to_string(),
+            (None, Some(_)) => strings.param_placeholder_hint.to_string(),
+            (None, None) => String::new(),
+        },
         ParamType::Flag => {
             let default = if param.default_flag.unwrap_or(false) {
-                "yes"
+                strings.yes
             } else {
-                "no"
+                strings.no
             };
-            format!("answer: y/n (Enter = {default})")
+            i18n::fill(strings.param_flag_hint, &[("default", default)])
         }
+        ParamType::Choice(_) => strings.param_choice_hint.to_string(),
+        ParamType::Path(_) => strings.param_path_hint.to_string(),
     };
-    frame.render_widget(Paragraph::new(helper_text), body[1]);
+    frame.render_widget(
+        Paragraph::new(helper_text).style(Style::default().fg(Color::DarkGray)),
+        helper_area,
+    );
 
     frame.render_widget(
-        Paragraph::new(format!("command: {}", command.name)),
-        body[2],
+        Paragraph::new(i18n::fill(
+            strings.param_command,
+            &[("name", &command.name)],
+        )),
+        command_area,
     );
 
-    let input_line = format!("> {}", prompt.input);
-    frame.render_widget(Paragraph::new(input_line), body[3]);
+    let mut input_spans = vec![Span::raw(format!("> {}", prompt.input))];
+    if prompt.input.is_empty()
+        && let Some(ghost) = prompt_ghost_text(param)
+    {
+        input_spans.push(Span::styled(
+            ghost.to_string(),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if param.eval && !prompt.input.is_empty() {
+        let evaluated = app
+            .preview_cache
+            .get(&eval_command_line(&prompt.input))
+            .map(|output| output.lines.join(" "))
+            .unwrap_or_else(|| "…".to_string());
+        input_spans.push(Span::styled(
+            format!("  = {evaluated}"),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(input_spans)), input_area);
+
+    if let Some(rows) = &listing {
+        let items: Vec<ListItem<'_>> = rows.iter().map(|row| ListItem::new(row.as_str())).collect();
+        let mut list_state = ListState::default();
+        list_state.select((!rows.is_empty()).then_some(prompt.choice_selected));
+        let list = List::new(items)
+            .highlight_style(
+                Style::default()
+                    .bg(theme.highlight_bg)
+                    .fg(theme.highlight_fg)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("▸ ");
+        frame.render_stateful_widget(list, listing_area, &mut list_state);
+    }
 
-    let x = body[3].x.saturating_add(2 + prompt.input.len() as u16);
-    let y = body[3].y;
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("$ ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                app.prompt_command_line(prompt),
+                Style::default().fg(theme.command),
+            ),
+        ]))
+        .wrap(Wrap { trim: false }),
+        rendered_area,
+    );
+
+    let x = input_area
+        .x
+        .saturating_add(2 + prompt.input.chars().count() as u16);
+    let y = input_area.y;
     frame.set_cursor_position((x, y));
 }
 
 fn draw_internal_prompt_popup(frame: &mut Frame, app: &AppState, prompt: &InternalPromptState) {
+    let theme = &app.runtime.theme;
     let area = centered_rect(70, 30, frame.area());
     frame.render_widget(Clear, area);
     frame.render_widget(
         Block::default()
             .borders(Borders::ALL)
-            .title("Internal Parameter")
-            .style(Style::default().bg(Color::Black)),
+            .title(app.runtime.locale.strings().internal_param_title)
+            .style(Style::default().bg(theme.popup_bg)),
         area,
     );
 
@@ -983,1712 +3674,9667 @@ fn draw_internal_prompt_popup(frame: &mut Frame, app: &AppState, prompt: &Intern
     frame.set_cursor_position((x, y));
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, rect: Rect) -> Rect {
-    let vertical = Layout::default()
+fn draw_project_picker_popup(frame: &mut Frame, app: &AppState, picker: &ProjectPickerState) {
+    let theme = &app.runtime.theme;
+    let area = centered_rect(70, 40, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.runtime.locale.strings().projects_title)
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
+
+    let body = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
-        ])
-        .split(rect);
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
 
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(vertical[1])[1]
-}
+    frame.render_widget(
+        Paragraph::new(app.runtime.locale.strings().projects_hint)
+            .style(Style::default().fg(Color::DarkGray)),
+        body[0],
+    );
 
-enum UiAction {
-    None,
-    Quit,
-    Run(RunRequest),
-    RunInternal(InternalRunRequest),
+    let current = project_root(&app.runtime.cwd);
+    let items: Vec<ListItem<'_>> = picker
+        .projects
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let marker = if *path == current { "  (current)" } else { "" };
+            ListItem::new(format!("{} {}{marker}", index + 1, path.display()))
+        })
+        .collect();
+    let mut list_state = ListState::default();
+    list_state.select(Some(picker.selected));
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▸ ");
+    frame.render_stateful_widget(list, body[1], &mut list_state);
 }
 
-enum LoopExit {
-    NeedsRestore,
-    AlreadyRestored,
-}
+fn draw_history_picker_popup(
+    frame: &mut Frame,
+    picker: &HistoryPickerState,
+    theme: &Theme,
+    locale: Locale,
+) {
+    let area = centered_rect(80, 60, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(locale.strings().history_title)
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
 
-enum CommandExec {
-    Continue,
-    ExitAlreadyRestored,
-}
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
 
-struct StreamRunResult {
-    exit_code: i32,
-    interrupted: bool,
-}
+    frame.render_widget(
+        Paragraph::new(locale.strings().history_hint).style(Style::default().fg(Color::DarkGray)),
+        body[0],
+    );
 
-struct RunRequest {
-    display_name: String,
-    command_line: String,
-    working_dir: Option<PathBuf>,
-    usage_key: String,
-    return_to_tui: bool,
+    let now = history::now();
+    let items: Vec<ListItem<'_>> = picker
+        .entries
+        .iter()
+        .map(|entry| {
+            let age = locale.format_age(now.saturating_sub(entry.timestamp));
+            let exit = match entry.exit_code {
+                _ if entry.timed_out => "t/o".to_string(),
+                Some(code) => code.to_string(),
+                None => "-".to_string(),
+            };
+            let exit_style = match entry.exit_code {
+                Some(0) => Style::default().fg(theme.success),
+                _ => Style::default().fg(theme.error),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{age:>4} "), Style::default().fg(Color::DarkGray)),
+                Span::styled(format!("{exit:>4}  "), exit_style),
+                Span::raw(format!("{}  ", entry.name)),
+                Span::styled(
+                    format!("$ {}", entry.command_line),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    let mut list_state = ListState::default();
+    list_state.select(Some(picker.selected));
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▸ ");
+    frame.render_stateful_widget(list, body[1], &mut list_state);
 }
 
-struct InternalRunRequest {
-    command: InternalCommand,
-}
+fn draw_jobs_popup(frame: &mut Frame, app: &AppState, state: &JobsState) {
+    let theme = &app.runtime.theme;
+    let strings = app.runtime.locale.strings();
+    let area = centered_rect(80, 50, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(strings.jobs_title)
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
 
-enum Mode {
-    Search,
-    Prompt(PromptState),
-    InternalPrompt(InternalPromptState),
-}
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
 
-struct PromptState {
-    command_index: usize,
-    pending_params: Vec<usize>,
-    current_param: usize,
-    input: String,
-    values: HashMap<String, String>,
-    return_to_tui: bool,
-}
+    frame.render_widget(
+        Paragraph::new(strings.jobs_hint).style(Style::default().fg(Color::DarkGray)),
+        body[0],
+    );
 
-struct InternalPromptState {
-    command_index: usize,
-    input: String,
+    let items: Vec<ListItem<'_>> = app
+        .jobs
+        .iter()
+        .map(|job| {
+            let exit = |code: i32| i18n::fill(strings.status_exit, &[("code", &code.to_string())]);
+            let (status, status_color) = match job.status {
+                JobStatus::Running => (strings.status_running.to_string(), Color::Cyan),
+                JobStatus::Stopping => (strings.status_stopping.to_string(), theme.warning),
+                JobStatus::Exited(0) => (exit(0), theme.success),
+                JobStatus::Exited(code) => (exit(code), theme.error),
+                JobStatus::Killed => (strings.status_killed.to_string(), theme.error),
+            };
+            let status_style = Style::default().fg(status_color);
+            let elapsed = job
+                .finished
+                .unwrap_or_else(Instant::now)
+                .duration_since(job.started);
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:>3} ", job.id),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(format!("{status:<9}"), status_style),
+                Span::styled(
+                    format!("{:>4}  ", app.runtime.locale.format_age(elapsed.as_secs())),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(format!("{}  ", job.request.display_name)),
+                Span::styled(
+                    format!("$ {}", job.request.command_line),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    let mut list_state = ListState::default();
+    list_state.select(Some(state.selected));
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▸ ");
+    frame.render_stateful_widget(list, body[1], &mut list_state);
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum ActivePane {
-    Commands,
-    Session,
-}
+fn draw_caches_popup(frame: &mut Frame, app: &AppState, state: &CachesState) {
+    let theme = &app.runtime.theme;
+    let locale = app.runtime.locale;
+    let area = centered_rect(80, 50, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.runtime.locale.strings().cache_title)
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
 
-#[derive(Clone, Copy)]
-enum ChatLineKind {
-    Info,
-    Command,
-    Stdout,
-    Stderr,
-}
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
 
-struct ChatLine {
-    kind: ChatLineKind,
-    text: String,
-}
+    frame.render_widget(
+        Paragraph::new(locale.strings().cache_hint).style(Style::default().fg(Color::DarkGray)),
+        body[0],
+    );
 
-struct StreamChunk {
-    kind: ChatLineKind,
-    text: String,
+    let now = history::now();
+    let items: Vec<ListItem<'_>> = state
+        .items
+        .iter()
+        .map(|item| {
+            let (kind, project, age, size) = match item {
+                CacheItem::Previews(count) => (
+                    "preview",
+                    format!("{} output(s), in memory", locale.format_count(*count)),
+                    format!("<{}", locale.format_age(PREVIEW_TTL.as_secs())),
+                    String::new(),
+                ),
+                CacheItem::File(entry) => (
+                    entry.kind,
+                    entry.project.clone(),
+                    locale.format_age(now.saturating_sub(entry.written)),
+                    format_size(entry.size),
+                ),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{kind:<8}"), Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{project:<32}")),
+                Span::styled(
+                    format!("{age:>6}  {size:>9}"),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    let mut list_state = ListState::default();
+    list_state.select(Some(state.selected));
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▸ ");
+    frame.render_stateful_widget(list, body[1], &mut list_state);
 }
 
-#[derive(Clone)]
-enum InternalCommand {
-    Reload,
-    Init { force: bool },
-    Unknown(String),
+/// A file size in the largest binary unit under 1024, like `512 B` or `3.4 KiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
 }
 
-#[derive(Clone, Copy)]
-enum InternalCommandKind {
-    Reload,
-    Init,
-}
+fn draw_environment_picker_popup(
+    frame: &mut Frame,
+    app: &AppState,
+    picker: &EnvironmentPickerState,
+) {
+    let theme = &app.runtime.theme;
+    let area = centered_rect(70, 30, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(app.runtime.locale.strings().environment_title)
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
 
-struct InternalCommandDef {
-    name: &'static str,
-    description: &'static str,
-    kind: InternalCommandKind,
-    default_force: bool,
-}
+    let command = &app.commands[picker.command_index];
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(1),
+        ])
+        .margin(1)
+        .split(area);
 
-#[derive(Clone, Copy)]
-enum SearchItem {
-    Command(usize),
-    Internal(usize),
-}
+    frame.render_widget(
+        Paragraph::new(format!("command: {}", command.name)),
+        body[0],
+    );
+    frame.render_widget(
+        Paragraph::new(app.runtime.locale.strings().environment_hint)
+            .style(Style::default().fg(Color::DarkGray)),
+        body[1],
+    );
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-struct UsageStore {
-    #[serde(default)]
-    counts: HashMap<String, u64>,
+    let items: Vec<ListItem<'_>> = command
+        .environments
+        .iter()
+        .enumerate()
+        .map(|(index, environment)| ListItem::new(format!("{} {}", index + 1, environment.name)))
+        .collect();
+    let mut list_state = ListState::default();
+    list_state.select(Some(picker.selected));
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▸ ");
+    frame.render_stateful_widget(list, body[2], &mut list_state);
 }
 
-struct AppState {
-    commands: Vec<CommandEntry>,
-    filtered: Vec<SearchItem>,
-    internal_commands: Vec<InternalCommandDef>,
-    selected: usize,
-    query: String,
-    query_cursor: usize,
-    matcher: SkimMatcherV2,
-    mode: Mode,
-    chat: Vec<ChatLine>,
-    config_path: Option<PathBuf>,
-    provider_aliases: HashMap<String, String>,
-    provider_alias_by_name: HashMap<String, String>,
-    provider_names_without_alias: HashSet<String>,
-    ranking: RankingSettings,
-    usage_counts: HashMap<String, u64>,
-    usage_path: Option<PathBuf>,
-    is_loading: bool,
-    loading_label: Option<String>,
-    spinner_index: usize,
-    show_help: bool,
-    runtime: RuntimeContext,
-    active_pane: ActivePane,
-    session_scroll: usize,
+fn draw_dry_run_popup(frame: &mut Frame, dry_run: &DryRunState, theme: &Theme, locale: Locale) {
+    let area = centered_rect(80, 50, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(i18n::fill(
+                locale.strings().dry_run_of,
+                &[("name", &dry_run.request.display_name)],
+            ))
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
+    frame.render_widget(
+        Paragraph::new(locale.strings().dry_run_hint).style(Style::default().fg(Color::DarkGray)),
+        body[0],
+    );
+
+    let request = &dry_run.request;
+    let label =
+        |text: &str| Span::styled(format!("{text:<12}"), Style::default().fg(Color::DarkGray));
+    let mut lines = vec![
+        Line::default(),
+        Line::from(vec![
+            Span::styled("$ ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                request.command_line.clone(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::default(),
+        Line::from(vec![
+            label("directory"),
+            Span::raw(
+                request
+                    .working_dir
+                    .as_ref()
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_else(|| "(current)".to_string()),
+            ),
+        ]),
+    ];
+    if let Some(environment) = &request.environment {
+        lines.push(Line::from(vec![
+            label("environment"),
+            Span::raw(environment.clone()),
+        ]));
+    }
+    for (index, (key, value)) in request.env.iter().enumerate() {
+        let prefix = if index == 0 { "env" } else { "" };
+        lines.push(Line::from(vec![
+            label(prefix),
+            Span::raw(format!("{key}={value}")),
+        ]));
+    }
+    if !request.then.is_empty() {
+        let names: Vec<&str> = request
+            .then
+            .iter()
+            .map(|run| run.display_name.as_str())
+            .collect();
+        lines.push(Line::from(vec![
+            label("then"),
+            Span::raw(names.join(" → ")),
+        ]));
+    }
+    if dry_run.unevaluated.is_some() {
+        lines.push(Line::from(vec![
+            label("eval"),
+            Span::raw("params are shown unexpanded; they are run when the command is"),
+        ]));
+    }
+    let (safety, color) = match &dry_run.verdict {
+        GuardVerdict::Allow => ("allowed".to_string(), theme.success),
+        GuardVerdict::Warn(reason) => (format!("warns: {reason}"), theme.warning),
+        GuardVerdict::Confirm(pattern) => (format!("asks to confirm ('{pattern}')"), theme.warning),
+        GuardVerdict::Deny(pattern) => (format!("refused ('{pattern}')"), theme.error),
+    };
+    lines.push(Line::from(vec![
+        label("safety"),
+        Span::styled(safety, Style::default().fg(color)),
+    ]));
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), body[1]);
 }
 
-impl AppState {
-    fn new(
-        mut commands: Vec<CommandEntry>,
+fn draw_queue_review_popup(frame: &mut Frame, app: &AppState, review: &QueueReviewState) {
+    let theme = &app.runtime.theme;
+    let area = centered_rect(80, 60, frame.area());
+    frame.render_widget(Clear, area);
+    let border = if review.confirming {
+        Color::LightYellow
+    } else {
+        Color::Reset
+    };
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(i18n::fill(
+                app.runtime.locale.strings().queue_of,
+                &[("count", &app.queue.len().to_string())],
+            ))
+            .border_style(Style::default().fg(border))
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
+
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
+
+    let help = if review.confirming {
+        Paragraph::new(i18n::fill(
+            app.runtime.locale.strings().queue_confirm,
+            &[("count", &app.queue.len().to_string())],
+        ))
+        .style(Style::default().fg(Color::LightYellow))
+    } else {
+        Paragraph::new(app.runtime.locale.strings().queue_hint)
+            .style(Style::default().fg(Color::DarkGray))
+    };
+    frame.render_widget(help, body[0]);
+
+    let items: Vec<ListItem<'_>> = app
+        .queue
+        .iter()
+        .enumerate()
+        .map(|(index, request)| {
+            let mut spans = vec![
+                Span::styled(
+                    format!("{:>2}. ", index + 1),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(format!("{}  ", request.display_name)),
+            ];
+            if let Some(environment) = &request.environment {
+                spans.push(Span::styled(
+                    format!("[{environment}] "),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            spans.push(Span::styled(
+                format!("$ {}", request.command_line),
+                Style::default().fg(Color::DarkGray),
+            ));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let mut list_state = ListState::default();
+    list_state.select((!app.queue.is_empty()).then_some(review.selected));
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▸ ");
+    frame.render_stateful_widget(list, body[1], &mut list_state);
+}
+
+fn warning_banner(name: &str, reason: &str) -> String {
+    format!("WARNING: '{name}' {reason}")
+}
+
+fn draw_confirm_run_popup(
+    frame: &mut Frame,
+    confirm: &ConfirmRunState,
+    theme: &Theme,
+    locale: Locale,
+) {
+    let area = centered_rect(70, 30, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(locale.strings().confirm_title)
+            .border_style(Style::default().fg(Color::LightYellow))
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
+
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(u16::from(confirm.warning.is_some())),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .margin(1)
+        .split(area);
+
+    if let Some(banner) = &confirm.warning {
+        frame.render_widget(
+            Paragraph::new(format!(" ▲ {banner} ")).style(
+                Style::default()
+                    .fg(theme.banner_fg)
+                    .bg(theme.banner_bg)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            body[0],
+        );
+    }
+    frame.render_widget(
+        Paragraph::new(format!("matches safety pattern '{}'", confirm.pattern))
+            .style(Style::default().fg(Color::LightYellow)),
+        body[1],
+    );
+    frame.render_widget(
+        Paragraph::new(format!("$ {}", confirm.request.command_line)),
+        body[2],
+    );
+    frame.render_widget(Paragraph::new(locale.strings().confirm_hint), body[3]);
+    frame.render_widget(Paragraph::new(format!("> {}", confirm.input)), body[4]);
+
+    let x = body[4].x.saturating_add(2 + confirm.input.len() as u16);
+    let y = body[4].y;
+    frame.set_cursor_position((x, y));
+}
+
+fn draw_init_diff_popup(frame: &mut Frame, diff: &InitDiffState, theme: &Theme, locale: Locale) {
+    let area = centered_rect(90, 80, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(i18n::fill(
+                locale.strings().overwrite_path,
+                &[("path", &diff.path.display().to_string())],
+            ))
+            .border_style(Style::default().fg(Color::LightYellow))
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
+
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .margin(1)
+        .split(area);
+
+    let lines: Vec<Line<'_>> = diff
+        .lines
+        .iter()
+        .skip(diff.scroll)
+        .take(body[0].height as usize)
+        .map(|line| match line {
+            config::DiffLine::Same(text) => Line::from(Span::styled(
+                format!("  {text}"),
+                Style::default().fg(Color::DarkGray),
+            )),
+            config::DiffLine::Removed(text) => Line::from(Span::styled(
+                format!("- {text}"),
+                Style::default().fg(Color::LightRed),
+            )),
+            config::DiffLine::Added(text) => Line::from(Span::styled(
+                format!("+ {text}"),
+                Style::default().fg(Color::LightGreen),
+            )),
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), body[0]);
+    frame.render_widget(Paragraph::new(locale.strings().init_diff_hint), body[1]);
+}
+
+fn draw_palette_popup(frame: &mut Frame, palette: &PaletteState, theme: &Theme, locale: Locale) {
+    let area = centered_rect(60, 50, frame.area());
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(locale.strings().palette_title)
+            .style(Style::default().bg(theme.popup_bg)),
+        area,
+    );
+
+    let body = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .margin(1)
+        .split(area);
+
+    frame.render_widget(Paragraph::new(format!("> {}", palette.query)), body[0]);
+
+    let items: Vec<ListItem<'_>> = palette
+        .filtered
+        .iter()
+        .map(|index| {
+            let (name, description, _) = PALETTE_ACTIONS[*index];
+            ListItem::new(Line::from(vec![
+                Span::styled(name.to_string(), Style::default().fg(theme.text)),
+                Span::styled(
+                    format!(" | {description}"),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    let mut list_state = ListState::default();
+    list_state.select((!palette.filtered.is_empty()).then_some(palette.selected));
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(theme.highlight_bg)
+                .fg(theme.highlight_fg)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▸ ");
+    frame.render_stateful_widget(list, body[1], &mut list_state);
+
+    let x = body[0]
+        .x
+        .saturating_add(2 + palette.query.chars().count() as u16);
+    frame.set_cursor_position((x, body[0].y));
+}
+
+fn draw_pager_popup(frame: &mut Frame, pager: &PagerState, theme: &Theme, locale: Locale) {
+    let area = centered_rect(90, 80, frame.area());
+    frame.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(pager.title.as_str())
+        .title_bottom(locale.strings().pager_hint)
+        .style(Style::default().bg(theme.popup_bg));
+    let content = Paragraph::new(pager.text.as_str())
+        .wrap(Wrap { trim: false })
+        .scroll((pager.scroll, 0))
+        .block(block);
+    frame.render_widget(content, area);
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, rect: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(rect);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+enum UiAction {
+    None,
+    Quit,
+    Run(Box<RunRequest>),
+    /// Staged runs, executed in order until one fails.
+    RunQueue(Vec<RunRequest>),
+    RunInternal(InternalRunRequest),
+    /// Follow a background job's output in the session until it exits or is sent back.
+    ForegroundJob(usize),
+}
+
+enum LoopExit {
+    NeedsRestore,
+    AlreadyRestored(i32),
+}
+
+enum CommandExec {
+    Continue,
+    ExitAlreadyRestored(i32),
+}
+
+struct StreamRunResult {
+    exit_code: i32,
+    interrupted: bool,
+    timed_out: bool,
+}
+
+#[derive(Clone)]
+struct RunRequest {
+    display_name: String,
+    command_line: String,
+    working_dir: Option<PathBuf>,
+    usage_key: String,
+    return_to_tui: bool,
+    env: Vec<(String, String)>,
+    environment: Option<String>,
+    /// The `host` the command line runs on over ssh.
+    host: Option<String>,
+    color: ColorMode,
+    /// Param values the command line was rendered from, kept for history.
+    params: BTreeMap<String, String>,
+    hooks: CommandHooks,
+    retry: RetryPolicy,
+    timeout: Option<Duration>,
+    create_working_dir: bool,
+    /// Run in a pseudo-terminal when streamed into the session.
+    pty: bool,
+    /// Start as a background job instead of streaming into the session.
+    background: bool,
+    /// Where the run goes when started from the TUI.
+    target: ExecTarget,
+    /// `{{secret:name}}` names; `command_line` refers to their variables, which
+    /// [`AppState::with_secrets`] fills in right before the run.
+    secrets: Vec<String>,
+    /// Runs chained with `then`, flattened in order; each starts only after the one before it
+    /// exits 0.
+    then: Vec<RunRequest>,
+    /// A pipeline step whose failure does not stop the runs after it.
+    continue_on_error: bool,
+    /// The `[[pipelines]]` entry this run and its `then` runs are the steps of.
+    pipeline: Option<PipelineRun>,
+}
+
+#[derive(Clone)]
+struct PipelineRun {
+    name: String,
+    usage_key: String,
+}
+
+struct InternalRunRequest {
+    command: InternalCommand,
+}
+
+enum Mode {
+    Search,
+    Prompt(PromptState),
+    InternalPrompt(InternalPromptState),
+    EnvironmentPicker(EnvironmentPickerState),
+    ProjectPicker(ProjectPickerState),
+    HistoryPicker(HistoryPickerState),
+    ConfirmRun(ConfirmRunState),
+    /// A run waiting for Enter after its warning banner.
+    WarnRun(Box<RunRequest>),
+    InitDiff(InitDiffState),
+    Palette(PaletteState),
+    Pager(PagerState),
+    QueueReview(QueueReviewState),
+    /// The resolved run shown without executing it (Ctrl+R).
+    DryRun(DryRunState),
+    /// Full-screen plain session text for copying with the terminal's mouse selection.
+    Selection,
+    /// `/jobs`: background jobs with their status.
+    Jobs(JobsState),
+    /// `/cache`: cached previews and provider results, each removable.
+    Caches(CachesState),
+    /// `?`: keybindings and internal commands, filtered as you type.
+    Help(HelpState),
+}
+
+/// Secrets shorter than this are not masked in output.
+const MIN_MASKED_SECRET: usize = 4;
+/// Oldest lines of a job pane are dropped beyond this.
+const MAX_JOB_PANE_LINES: usize = 500;
+/// Two clicks on the same command within this window run it.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// Session lines moved per scroll wheel notch.
+const MOUSE_SCROLL_LINES: isize = 3;
+/// The commands panel never gets shorter than this (borders included).
+const COMMANDS_MIN_HEIGHT: u16 = 8;
+/// Height the session keeps while the commands panel grows into the rest.
+const SESSION_MIN_HEIGHT: u16 = 8;
+
+/// What a job pane follows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PaneSource {
+    /// A scheduled command, by usage key.
+    Schedule(String),
+    /// A background job, by id; its pane closes when the job ends.
+    Job(usize),
+}
+
+/// Output of one scheduled or background job, kept apart from the main Session stream.
+struct JobPane {
+    source: PaneSource,
+    name: String,
+    lines: Vec<ChatLine>,
+    /// Rows scrolled up from the bottom.
+    scroll: usize,
+    last_exit: Option<i32>,
+}
+
+impl JobPane {
+    /// Appends lines already masked and cut down for display, keeping the newest
+    /// [`MAX_JOB_PANE_LINES`].
+    fn extend(&mut self, lines: impl IntoIterator<Item = ChatLine>) {
+        self.lines.extend(lines);
+        let overflow = self.lines.len().saturating_sub(MAX_JOB_PANE_LINES);
+        self.lines.drain(..overflow);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JobStatus {
+    Running,
+    /// Sent SIGTERM; killing it again sends SIGKILL.
+    Stopping,
+    Exited(i32),
+    /// Ended by a signal.
+    Killed,
+}
+
+/// A command started with Ctrl+B (or `background = true`) that keeps running while the TUI is
+/// used; listed by `/jobs`.
+struct BackgroundJob {
+    id: usize,
+    request: RunRequest,
+    child: Child,
+    output: mpsc::Receiver<StreamChunk>,
+    /// The newest [`MAX_JOB_PANE_LINES`] lines of output.
+    lines: Vec<ChatLine>,
+    started: Instant,
+    /// Set once the job ends, for the `/jobs` duration.
+    finished: Option<Instant>,
+    /// When the job was sent SIGTERM for running past its `timeout`.
+    timed_out_at: Option<Instant>,
+    status: JobStatus,
+}
+
+impl BackgroundJob {
+    fn is_running(&self) -> bool {
+        matches!(self.status, JobStatus::Running | JobStatus::Stopping)
+    }
+}
+
+impl Drop for BackgroundJob {
+    /// Jobs do not outlive fzc.
+    fn drop(&mut self) {
+        if self.is_running() {
+            stop_process_group(&mut self.child, Stop::Kill);
+            let _ = self.child.wait();
+        }
+    }
+}
+
+struct JobsState {
+    selected: usize,
+}
+
+/// One row of `/cache`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CacheItem {
+    /// The in-memory preview outputs, by count.
+    Previews(usize),
+    File(provider::CacheEntry),
+}
+
+struct CachesState {
+    items: Vec<CacheItem>,
+    selected: usize,
+}
+
+#[derive(Default)]
+struct HelpState {
+    query: String,
+    scroll: usize,
+}
+
+/// One titled section of the help overlay.
+#[derive(Debug)]
+struct HelpGroup {
+    title: String,
+    rows: Vec<(String, &'static str)>,
+}
+
+/// A command with a `schedule`, re-run in the background while the TUI is idle.
+struct ScheduledJob {
+    /// The command's usage key, unique even when providers share a command name.
+    key: String,
+    name: String,
+    /// Resolved when the schedule is set up, with the runs it chains.
+    request: RunRequest,
+    interval: Duration,
+    next_run: Instant,
+    running: bool,
+}
+
+/// How long an `eval = true` param may take to expand before the run is refused.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a preview's output is reused before it is run again.
+const PREVIEW_TTL: Duration = Duration::from_secs(5);
+const PREVIEW_MAX_LINES: usize = 200;
+/// A preview runs once the selection has rested on it this long, so scrolling past commands
+/// does not start one for each.
+const PREVIEW_DEBOUNCE: Duration = Duration::from_millis(250);
+/// Preview commands still running after this are killed.
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(10);
+/// Provider commands loaded longer ago than this show their age (F5 reloads them).
+const STALE_PROVIDER_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// A rendered preview command plus where to run it.
+#[derive(Clone, PartialEq, Eq)]
+struct PreviewRequest {
+    command_line: String,
+    working_dir: Option<PathBuf>,
+    /// `{{secret:name}}` names, fetched right before the preview runs.
+    secrets: Vec<String>,
+}
+
+struct PreviewOutput {
+    finished: Instant,
+    lines: Vec<String>,
+}
+
+struct ProviderLoad {
+    name: String,
+    result: Result<Vec<CommandEntry>, String>,
+}
+
+struct ScheduledOutcome {
+    key: String,
+    name: String,
+    result: Result<(i32, Vec<StreamChunk>), String>,
+}
+
+/// A finished `on_success`/`on_failure` hook: its output and exit code, or why it failed to start.
+struct HookOutcome {
+    result: Result<(i32, Vec<StreamChunk>), String>,
+}
+
+/// Display-only filter over output lines; patterns that are not valid regexes match as substrings.
+struct OutputFilter {
+    pattern: String,
+    regex: Option<Regex>,
+}
+
+impl OutputFilter {
+    fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            regex: Regex::new(pattern).ok(),
+        }
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(text),
+            None => text.contains(&self.pattern),
+        }
+    }
+}
+
+struct PagerState {
+    title: String,
+    text: String,
+    scroll: u16,
+}
+
+/// A directory's entries as `(name, is_dir)`, sorted by name.
+struct DirListing {
+    dir: PathBuf,
+    entries: Vec<(String, bool)>,
+}
+
+struct PromptState {
+    command_index: usize,
+    pending_params: Vec<usize>,
+    current_param: usize,
+    input: String,
+    values: HashMap<String, String>,
+    environment: Option<usize>,
+    return_to_tui: bool,
+    /// Values from an earlier run that pre-fill the input, keyed by param name.
+    previous: HashMap<String, String>,
+    /// Highlighted row among the filtered options of a choice param.
+    choice_selected: usize,
+}
+
+struct InitDiffState {
+    path: PathBuf,
+    lines: Vec<config::DiffLine>,
+    scroll: usize,
+}
+
+struct DryRunState {
+    request: RunRequest,
+    verdict: GuardVerdict,
+    /// Set while `eval = true` params are shown unexpanded; running resolves the command again.
+    unevaluated: Option<(usize, HashMap<String, String>, Option<usize>)>,
+}
+
+struct ConfirmRunState {
+    request: RunRequest,
+    pattern: String,
+    /// Banner for a chained run that `[safety]` also warns about.
+    warning: Option<String>,
+    input: String,
+}
+
+struct EnvironmentPickerState {
+    command_index: usize,
+    selected: usize,
+    return_to_tui: bool,
+    previous: HashMap<String, String>,
+    /// Prompt for every param, as when editing a history entry's values.
+    edit_all: bool,
+}
+
+struct ProjectPickerState {
+    projects: Vec<PathBuf>,
+    selected: usize,
+}
+
+struct QueueReviewState {
+    selected: usize,
+    /// Enter was pressed once; the next Enter runs the whole queue.
+    confirming: bool,
+}
+
+struct HistoryPickerState {
+    /// Newest first.
+    entries: Vec<HistoryEntry>,
+    selected: usize,
+}
+
+struct InternalPromptState {
+    command_index: usize,
+    input: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ActivePane {
+    Commands,
+    Session,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ChatLineKind {
+    Info,
+    /// A warning shown before a risky run.
+    Banner,
+    Command,
+    Stdout,
+    Stderr,
+}
+
+struct ChatLine {
+    kind: ChatLineKind,
+    text: String,
+    /// How many identical consecutive output lines this entry stands for.
+    repeat: usize,
+    /// Original text when `text` was cut down to `max_line_bytes`.
+    full_text: Option<String>,
+    /// Pretty-printed form when the line is a JSON document.
+    json: Option<Vec<String>>,
+}
+
+/// One rendered row of the session pane.
+#[derive(Clone, Copy)]
+struct SessionRow {
+    line: usize,
+    json_line: Option<usize>,
+}
+
+struct StreamChunk {
+    kind: ChatLineKind,
+    text: String,
+    /// An unfinished pty line (e.g. a progress bar) that later chunks redraw.
+    partial: bool,
+}
+
+#[derive(Clone)]
+enum InternalCommand {
+    Reload,
+    Init { force: bool },
+    Aliases,
+    SwitchProject(PathBuf),
+    Unknown(String),
+}
+
+#[derive(Clone, Copy)]
+enum InternalCommandKind {
+    Reload,
+    Init,
+    Aliases,
+    Projects,
+    History,
+    Cache,
+    Doctor,
+    Jobs,
+    Env,
+}
+
+/// `/env`: session environment variables.
+#[derive(Debug, PartialEq, Eq)]
+enum EnvCommand {
+    List,
+    Set(String, String),
+    Unset(String),
+}
+
+/// What a Ctrl shortcut in the command list does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum KeyAction {
+    DryRun,
+    Background,
+    ToggleStaging,
+    ReviewQueue,
+    SelectNext,
+    SelectPrevious,
+    FocusJobPane,
+    SelectionMode,
+    Palette,
+    TogglePin,
+    ToggleDetails,
+    Quit,
+}
+
+/// The command list's Ctrl shortcuts; key handling and the help overlay both read this table.
+const CTRL_KEYMAP: [(char, KeyAction); 12] = [
+    ('r', KeyAction::DryRun),
+    ('b', KeyAction::Background),
+    ('t', KeyAction::ToggleStaging),
+    ('q', KeyAction::ReviewQueue),
+    ('j', KeyAction::SelectNext),
+    ('k', KeyAction::SelectPrevious),
+    ('o', KeyAction::FocusJobPane),
+    ('s', KeyAction::SelectionMode),
+    ('p', KeyAction::Palette),
+    ('f', KeyAction::TogglePin),
+    ('d', KeyAction::ToggleDetails),
+    ('c', KeyAction::Quit),
+];
+
+/// The keys of a help row: fixed text, or whatever Ctrl shortcuts [`CTRL_KEYMAP`] binds to
+/// these actions.
+enum HelpKeys {
+    Fixed(&'static str),
+    Ctrl(&'static [KeyAction]),
+}
+
+/// The help overlay's key rows as a section index into `Strings::help_sections` and the keys;
+/// row `n` is labeled by `Strings::help_labels[n]`.
+const HELP_KEYS: [(usize, HelpKeys); i18n::HELP_ROWS] = [
+    (0, HelpKeys::Fixed("Enter")),
+    (0, HelpKeys::Fixed("Option+Enter")),
+    (0, HelpKeys::Ctrl(&[KeyAction::DryRun])),
+    (0, HelpKeys::Ctrl(&[KeyAction::Background])),
+    (0, HelpKeys::Ctrl(&[KeyAction::ToggleStaging])),
+    (0, HelpKeys::Ctrl(&[KeyAction::ReviewQueue])),
+    (0, HelpKeys::Fixed("Esc")),
+    (0, HelpKeys::Fixed("/")),
+    (1, HelpKeys::Fixed("Left/Right")),
+    (1, HelpKeys::Fixed("Home/End")),
+    (1, HelpKeys::Fixed("Backspace/Del")),
+    (1, HelpKeys::Fixed(":provider text")),
+    (1, HelpKeys::Fixed("/")),
+    (1, HelpKeys::Fixed("Esc")),
+    (2, HelpKeys::Fixed("Tab")),
+    (2, HelpKeys::Fixed("Up/Down")),
+    (
+        2,
+        HelpKeys::Ctrl(&[KeyAction::SelectNext, KeyAction::SelectPrevious]),
+    ),
+    (2, HelpKeys::Fixed("PgUp/PgDn")),
+    (2, HelpKeys::Ctrl(&[KeyAction::FocusJobPane])),
+    (2, HelpKeys::Ctrl(&[KeyAction::SelectionMode])),
+    (3, HelpKeys::Ctrl(&[KeyAction::Palette])),
+    (3, HelpKeys::Ctrl(&[KeyAction::TogglePin])),
+    (3, HelpKeys::Ctrl(&[KeyAction::ToggleDetails])),
+    (3, HelpKeys::Fixed("F5")),
+    (4, HelpKeys::Fixed("?")),
+    (4, HelpKeys::Ctrl(&[KeyAction::Quit])),
+];
+
+/// The action bound to Ctrl plus `ch` in the command list.
+fn ctrl_key_action(ch: char) -> Option<KeyAction> {
+    CTRL_KEYMAP
+        .iter()
+        .find(|(key, _)| *key == ch)
+        .map(|(_, action)| *action)
+}
+
+/// The Ctrl shortcuts bound to `actions`, like `Ctrl+J/Ctrl+K`.
+fn ctrl_keys_text(actions: &[KeyAction]) -> String {
+    actions
+        .iter()
+        .flat_map(|action| CTRL_KEYMAP.iter().filter(move |(_, bound)| bound == action))
+        .map(|(key, _)| format!("Ctrl+{}", key.to_ascii_uppercase()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PaletteAction {
+    ShowHelp,
+    ToggleFocus,
+    ToggleUsageRanking,
+    ToggleRepeatFolding,
+    TogglePrettyJson,
+    ViewTruncatedLine,
+    SelectText,
+    ClearOutputFilter,
+    ClearSession,
+    ShowConfigPath,
+    ToggleDetails,
+    ToggleStaging,
+    ReviewQueue,
+    Reload,
+    Quit,
+}
+
+const PALETTE_ACTIONS: [(&str, &str, PaletteAction); 15] = [
+    (
+        "Help",
+        "Search keybindings and internal commands",
+        PaletteAction::ShowHelp,
+    ),
+    (
+        "Switch focus",
+        "Toggle between commands and session pane",
+        PaletteAction::ToggleFocus,
+    ),
+    (
+        "Switch sort mode",
+        "Toggle usage-weighted vs alphabetical ordering",
+        PaletteAction::ToggleUsageRanking,
+    ),
+    (
+        "Toggle repeated line folding",
+        "Expand or collapse runs of identical output lines",
+        PaletteAction::ToggleRepeatFolding,
+    ),
+    (
+        "Toggle JSON pretty-print",
+        "Pretty-print and highlight JSON output lines",
+        PaletteAction::TogglePrettyJson,
+    ),
+    (
+        "View truncated line",
+        "Open the nearest truncated output line in the pager",
+        PaletteAction::ViewTruncatedLine,
+    ),
+    (
+        "Select session text",
+        "Show session output alone for copying with the mouse (Ctrl+S)",
+        PaletteAction::SelectText,
+    ),
+    (
+        "Clear output filter",
+        "Show all session output lines again",
+        PaletteAction::ClearOutputFilter,
+    ),
+    (
+        "Clear session",
+        "Remove all session output",
+        PaletteAction::ClearSession,
+    ),
+    (
+        "Show config path",
+        "Print the loaded config file path",
+        PaletteAction::ShowConfigPath,
+    ),
+    (
+        "Toggle details pane",
+        "Show the selected command's template, params, and usage (Ctrl+D)",
+        PaletteAction::ToggleDetails,
+    ),
+    (
+        "Toggle staging",
+        "Queue selected commands instead of running them (Ctrl+T)",
+        PaletteAction::ToggleStaging,
+    ),
+    (
+        "Review queue",
+        "Reorder, remove, and run staged commands (Ctrl+Q)",
+        PaletteAction::ReviewQueue,
+    ),
+    (
+        "Reload",
+        "Reload config and providers",
+        PaletteAction::Reload,
+    ),
+    ("Quit", "Exit fzc", PaletteAction::Quit),
+];
+
+struct PaletteState {
+    query: String,
+    filtered: Vec<usize>,
+    selected: usize,
+}
+
+#[derive(Clone, Copy)]
+struct InternalCommandDef {
+    name: &'static str,
+    description: &'static str,
+    kind: InternalCommandKind,
+    default_force: bool,
+}
+
+const INTERNAL_COMMANDS: &[InternalCommandDef] = &[
+    InternalCommandDef {
+        name: "/aliases",
+        description: "List provider aliases and filter conflicts",
+        kind: InternalCommandKind::Aliases,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/cache",
+        description: "List cached previews and provider results to clear them",
+        kind: InternalCommandKind::Cache,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/doctor",
+        description: "Check the catalog for problems such as missing working directories",
+        kind: InternalCommandKind::Doctor,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/env",
+        description: "List, set, or unset session variables: /env set KEY=value, /env unset KEY",
+        kind: InternalCommandKind::Env,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/history",
+        description: "Browse and re-run executed commands",
+        kind: InternalCommandKind::History,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/init",
+        description: "Create default config file",
+        kind: InternalCommandKind::Init,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/jobs",
+        description: "Show background jobs: view output, foreground, or stop them",
+        kind: InternalCommandKind::Jobs,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/projects",
+        description: "Jump to a recently used project",
+        kind: InternalCommandKind::Projects,
+        default_force: false,
+    },
+    InternalCommandDef {
+        name: "/reload",
+        description: "Reload config and providers",
+        kind: InternalCommandKind::Reload,
+        default_force: false,
+    },
+];
+
+/// "/aliases, /cache, …" for messages about an unknown internal command.
+fn internal_command_list(commands: &[InternalCommandDef]) -> String {
+    commands
+        .iter()
+        .map(|command| command.name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Clone, Copy)]
+enum SearchItem {
+    Command(usize),
+    Internal(usize),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RecentProjectsStore {
+    #[serde(default)]
+    roots: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageStore {
+    #[serde(default)]
+    counts: HashMap<String, u64>,
+    #[serde(default)]
+    frecency: HashMap<String, FrecencyRecord>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct FrecencyRecord {
+    score: f64,
+    /// Unix seconds.
+    last_used: u64,
+}
+
+/// Run count plus a score that halves every `decay_half_life_days` without use.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct UsageRecord {
+    count: u64,
+    score: f64,
+    last_used: u64,
+}
+
+impl UsageRecord {
+    fn frecency(&self, now: u64, half_life_days: f64) -> f64 {
+        if half_life_days <= 0.0 {
+            return self.count as f64;
+        }
+        self.decayed_score(now, half_life_days)
+    }
+
+    fn decayed_score(&self, now: u64, half_life_days: f64) -> f64 {
+        if half_life_days <= 0.0 {
+            return self.score;
+        }
+        let elapsed_days = now.saturating_sub(self.last_used) as f64 / 86_400.0;
+        self.score * 0.5_f64.powf(elapsed_days / half_life_days)
+    }
+
+    fn record_use(&mut self, now: u64, half_life_days: f64) {
+        self.score = self.decayed_score(now, half_life_days) + 1.0;
+        self.count = self.count.saturating_add(1);
+        self.last_used = self.last_used.max(now);
+    }
+}
+
+struct AppState {
+    commands: Vec<CommandEntry>,
+    filtered: Vec<SearchItem>,
+    internal_commands: Vec<InternalCommandDef>,
+    selected: usize,
+    query: String,
+    query_cursor: usize,
+    matcher: SkimMatcherV2,
+    mode: Mode,
+    chat: Vec<ChatLine>,
+    /// The last chat line is a pty line still being redrawn and gets replaced by the next one.
+    live_line: bool,
+    config_path: Option<PathBuf>,
+    provider_aliases: HashMap<String, String>,
+    provider_alias_by_name: HashMap<String, String>,
+    provider_names_without_alias: HashSet<String>,
+    ranking: RankingSettings,
+    output: OutputSettings,
+    guard: CommandGuard,
+    usage: HashMap<String, UsageRecord>,
+    usage_path: Option<PathBuf>,
+    history_path: Option<PathBuf>,
+    /// Usage keys of pinned commands; they sort above everything else.
+    pinned: BTreeSet<String>,
+    pins_path: Option<PathBuf>,
+    events: Option<EventSocket>,
+    /// Right-hand pane with the selected command's template, params, and usage (Ctrl+D).
+    show_details: bool,
+    /// Set by Ctrl+R: the next resolved run is shown in the dry-run popup instead of executed.
+    dry_run: bool,
+    /// Set by Ctrl+B: the next resolved run starts as a background job.
+    background_run: bool,
+    /// While set, run requests are queued instead of executed.
+    staging: bool,
+    queue: Vec<RunRequest>,
+    /// Earlier queue states, newest last, restored by `u` on the review screen.
+    queue_undo: Vec<Vec<RunRequest>>,
+    is_loading: bool,
+    loading_label: Option<String>,
+    spinner_index: usize,
+    runtime: RuntimeContext,
+    active_pane: ActivePane,
+    session_scroll: usize,
+    expand_repeats: bool,
+    output_filter: Option<OutputFilter>,
+    filter_input: Option<String>,
+    schedules: Vec<ScheduledJob>,
+    /// Per-job scrollback for scheduled and background jobs, up to `runtime.job_panes`, in the
+    /// order they were opened.
+    job_panes: Vec<JobPane>,
+    /// The job pane that scrolls while the Session pane is active (Ctrl+O); `None` is the main stream.
+    focused_job: Option<usize>,
+    /// Background jobs in start order, finished ones included until fzc exits.
+    jobs: Vec<BackgroundJob>,
+    /// `/env set`: variables added to every command run in this session, over the catalog's own.
+    session_env: BTreeMap<String, String>,
+    /// Secret values fetched this session, masked wherever output is shown.
+    secret_values: Vec<String>,
+    next_job_id: usize,
+    /// The job whose new output is also streamed into the session (foreground).
+    followed_job: Option<usize>,
+    /// Last click on the command list, for double-click detection.
+    last_click: Option<(Instant, usize)>,
+    schedule_tx: mpsc::Sender<ScheduledOutcome>,
+    schedule_rx: mpsc::Receiver<ScheduledOutcome>,
+    hook_tx: mpsc::Sender<HookOutcome>,
+    hook_rx: mpsc::Receiver<HookOutcome>,
+    /// Hooks started in the background that have not reported back yet.
+    hooks_running: usize,
+    loading_providers: Vec<String>,
+    /// A provider in the current load round failed, so the catalog is not snapshotted.
+    provider_load_failed: bool,
+    provider_rx: Option<mpsc::Receiver<ProviderLoad>>,
+    /// Providers whose refresh triggers matched a finished run, reloaded once none is loading.
+    pending_refresh: Vec<String>,
+    /// Unix seconds of each provider's last successful load.
+    provider_loaded_at: HashMap<String, u64>,
+    preview_cache: HashMap<String, PreviewOutput>,
+    /// The preview command being run, and since when.
+    preview_running: Option<(String, Instant)>,
+    /// The preview command waiting out [`PREVIEW_DEBOUNCE`], and since when it is wanted.
+    preview_wanted: Option<(String, Instant)>,
+    /// The directory a path param is completing in, read once rather than on every draw.
+    path_listing: Option<DirListing>,
+    preview_tx: mpsc::Sender<(String, Vec<String>)>,
+    preview_rx: mpsc::Receiver<(String, Vec<String>)>,
+    last_activity: Instant,
+    search_index: Vec<SearchIndexEntry>,
+    score_buffer: Vec<(usize, i64, i64)>,
+}
+
+impl AppState {
+    fn new(
+        mut commands: Vec<CommandEntry>,
         config_path: Option<PathBuf>,
         provider_aliases: HashMap<String, String>,
         ranking: RankingSettings,
+        output: OutputSettings,
+        guard: CommandGuard,
         runtime: RuntimeContext,
     ) -> Self {
-        commands.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        commands.sort_by_key(|command| command.name.to_lowercase());
         let count = commands.len();
         let provider_alias_by_name = provider_aliases
             .iter()
-            .map(|(alias, provider)| (provider.clone(), alias.clone()))
-            .collect();
-        let provider_names_without_alias =
-            provider_names_without_alias(&commands, &provider_alias_by_name);
-        let (usage_counts, usage_path) = load_usage_store();
-        let mut app = Self {
+            .map(|(alias, provider)| (provider.clone(), alias.clone()))
+            .collect();
+        let provider_names_without_alias =
+            provider_names_without_alias(&commands, &provider_alias_by_name);
+        let (usage, usage_path) = if runtime.persist_usage {
+            load_usage_store()
+        } else {
+            (HashMap::new(), None)
+        };
+        let history_path = if runtime.persist_usage {
+            history::history_path()
+        } else {
+            None
+        };
+        let pins_path = if runtime.persist_usage {
+            favorites::favorites_path()
+        } else {
+            None
+        };
+        let pinned = pins_path
+            .as_deref()
+            .map(favorites::load)
+            .unwrap_or_default();
+        let (schedule_tx, schedule_rx) = mpsc::channel();
+        let (hook_tx, hook_rx) = mpsc::channel();
+        let (preview_tx, preview_rx) = mpsc::channel();
+        let mut app = Self {
+            commands,
+            filtered: Vec::new(),
+            internal_commands: INTERNAL_COMMANDS.to_vec(),
+            selected: 0,
+            query: String::new(),
+            query_cursor: 0,
+            matcher: SkimMatcherV2::default(),
+            mode: Mode::Search,
+            chat: Vec::new(),
+            live_line: false,
+            config_path,
+            provider_aliases,
+            provider_alias_by_name,
+            provider_names_without_alias,
+            ranking,
+            output,
+            guard,
+            usage,
+            usage_path,
+            history_path,
+            pinned,
+            pins_path,
+            events: None,
+            show_details: false,
+            dry_run: false,
+            background_run: false,
+            staging: false,
+            queue: Vec::new(),
+            queue_undo: Vec::new(),
+            is_loading: false,
+            loading_label: None,
+            spinner_index: 0,
+            runtime,
+            active_pane: ActivePane::Commands,
+            session_scroll: 0,
+            expand_repeats: false,
+            output_filter: None,
+            filter_input: None,
+            schedules: Vec::new(),
+            schedule_tx,
+            schedule_rx,
+            hook_tx,
+            hook_rx,
+            hooks_running: 0,
+            job_panes: Vec::new(),
+            focused_job: None,
+            jobs: Vec::new(),
+            session_env: BTreeMap::new(),
+            secret_values: Vec::new(),
+            next_job_id: 1,
+            followed_job: None,
+            last_click: None,
+            loading_providers: Vec::new(),
+            provider_load_failed: false,
+            provider_rx: None,
+            pending_refresh: Vec::new(),
+            provider_loaded_at: HashMap::new(),
+            preview_cache: HashMap::new(),
+            preview_running: None,
+            preview_wanted: None,
+            path_listing: None,
+            preview_tx,
+            preview_rx,
+            last_activity: Instant::now(),
+            search_index: Vec::new(),
+            score_buffer: Vec::new(),
+        };
+
+        app.rebuild_search_index();
+        app.refresh_filtered();
+        app.push_info(i18n::fill(
+            app.runtime.locale.strings().loaded,
+            &[("count", &app.runtime.locale.format_count(count))],
+        ));
+        app.push_info(app.config_line());
+        for hint in app.runtime.provider_hints.clone() {
+            app.push_info(hint);
+        }
+        if let Some(path) = app.runtime.events_socket.clone() {
+            match EventSocket::bind(&path) {
+                Ok(socket) => {
+                    app.push_info(i18n::fill(
+                        app.runtime.locale.strings().events_socket,
+                        &[("path", &socket.path().display().to_string())],
+                    ));
+                    app.events = Some(socket);
+                }
+                Err(err) => app.push_error(format!("{err:#}")),
+            }
+        }
+        app.rebuild_schedules(Instant::now());
+        app.note_catalog_problems();
+
+        app
+    }
+
+    fn on_key(&mut self, key: KeyEvent) -> UiAction {
+        let action = match self.mode {
+            Mode::Search => self.on_search_key(key),
+            Mode::Prompt(_) => self.on_prompt_key(key),
+            Mode::InternalPrompt(_) => self.on_internal_prompt_key(key),
+            Mode::EnvironmentPicker(_) => self.on_environment_picker_key(key),
+            Mode::ProjectPicker(_) => self.on_project_picker_key(key),
+            Mode::HistoryPicker(_) => self.on_history_picker_key(key),
+            Mode::ConfirmRun(_) => self.on_confirm_run_key(key),
+            Mode::WarnRun(_) => self.on_warn_run_key(key),
+            Mode::InitDiff(_) => self.on_init_diff_key(key),
+            Mode::Palette(_) => self.on_palette_key(key),
+            Mode::Pager(_) => self.on_pager_key(key),
+            Mode::QueueReview(_) => self.on_queue_review_key(key),
+            Mode::DryRun(_) => self.on_dry_run_key(key),
+            Mode::Selection => self.on_selection_key(key),
+            Mode::Jobs(_) => self.on_jobs_key(key),
+            Mode::Caches(_) => self.on_caches_key(key),
+            Mode::Help(_) => self.on_help_key(key),
+        };
+        let action = self.finish_action(action);
+        self.refresh_path_listing();
+        action
+    }
+
+    /// Resets a canceled dry run and diverts runs into the queue while staging.
+    fn finish_action(&mut self, action: UiAction) -> UiAction {
+        if matches!(self.mode, Mode::Search) {
+            // Prompts for a dry run or background job were canceled.
+            self.dry_run = false;
+            self.background_run = false;
+        }
+        match action {
+            UiAction::Run(request) if self.staging => {
+                self.stage_request(*request);
+                UiAction::None
+            }
+            action => action,
+        }
+    }
+
+    /// Clicks select a command or focus a pane, a double-click runs the command, and the wheel
+    /// scrolls the active pane. Popups and help ignore the mouse.
+    /// `[ui] mouse`, except in selection mode, which hands the mouse back to the terminal so its
+    /// own click-and-drag selection works.
+    fn wants_mouse_capture(&self) -> bool {
+        self.runtime.mouse && !matches!(self.mode, Mode::Selection)
+    }
+
+    fn on_mouse(&mut self, mouse: MouseEvent, area: Rect) -> UiAction {
+        let step = match mouse.kind {
+            MouseEventKind::ScrollUp => 1,
+            MouseEventKind::ScrollDown => -1,
+            MouseEventKind::Down(MouseButton::Left) => 0,
+            _ => return UiAction::None,
+        };
+        if !matches!(self.mode, Mode::Search) {
+            return UiAction::None;
+        }
+        if step != 0 {
+            match self.active_pane {
+                ActivePane::Commands => self.move_selection(-step),
+                ActivePane::Session => self.scroll_session(step * MOUSE_SCROLL_LINES),
+            }
+            return UiAction::None;
+        }
+
+        let side_pane = self.details_index().is_some() || self.wanted_preview().is_some();
+        let layout = screen_layout(self, area, side_pane);
+        let position = Position::new(mouse.column, mouse.row);
+        if layout.session.contains(position) {
+            self.active_pane = ActivePane::Session;
+            self.focused_job = None;
+            return UiAction::None;
+        }
+        if let Some(index) = layout.jobs.iter().position(|rect| rect.contains(position)) {
+            self.active_pane = ActivePane::Session;
+            self.focused_job = Some(index);
+            return UiAction::None;
+        }
+        if !layout.commands.contains(position) {
+            return UiAction::None;
+        }
+        self.active_pane = ActivePane::Commands;
+        let Some(row) = self.command_row_at(layout.commands, mouse.row) else {
+            return UiAction::None;
+        };
+        let now = Instant::now();
+        let double = self.last_click.is_some_and(|(clicked, last_row)| {
+            last_row == row && now.duration_since(clicked) <= DOUBLE_CLICK
+        });
+        self.selected = row;
+        self.last_click = (!double).then_some((now, row));
+        if !double {
+            return UiAction::None;
+        }
+        let action = if self.is_internal_query() {
+            self.prepare_selected_internal_command()
+        } else {
+            self.prepare_selected_command(true)
+        };
+        self.finish_action(action)
+    }
+
+    /// Commands, or internal commands while the query starts with `/`, before filtering.
+    fn list_len(&self) -> usize {
+        if self.is_internal_query() {
+            self.internal_commands.len()
+        } else {
+            self.commands.len()
+        }
+    }
+
+    /// First filtered row shown when the command list has `rows` lines. The list is drawn with
+    /// a fresh state each frame, so it scrolls just enough to keep the selection on the last
+    /// visible row.
+    fn command_list_offset(&self, rows: usize) -> usize {
+        self.selected.saturating_sub(rows.saturating_sub(1))
+    }
+
+    /// The filtered row drawn at screen row `y` of the command list occupying `area`.
+    fn command_row_at(&self, area: Rect, y: u16) -> Option<usize> {
+        let top = area.y + 1;
+        let height = area.height.saturating_sub(2) as usize;
+        let line = y.checked_sub(top)? as usize;
+        if line >= height {
+            return None;
+        }
+        let row = self.command_list_offset(height) + line;
+        (row < self.filtered.len()).then_some(row)
+    }
+
+    fn on_selection_key(&mut self, key: KeyEvent) -> UiAction {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.mode = Mode::Search,
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.mode = Mode::Search
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return UiAction::Quit;
+            }
+            KeyCode::Up => self.scroll_session(1),
+            KeyCode::Down => self.scroll_session(-1),
+            KeyCode::PageUp => self.scroll_session(10),
+            KeyCode::PageDown => self.scroll_session(-10),
+            _ => {}
+        }
+        UiAction::None
+    }
+
+    /// Inserts pasted text into whichever input is active, as one edit.
+    fn on_paste(&mut self, text: &str) {
+        let text = sanitize_paste(text);
+        if text.is_empty() {
+            return;
+        }
+
+        match &mut self.mode {
+            Mode::Search => {
+                self.active_pane = ActivePane::Commands;
+                for ch in text.chars() {
+                    insert_char_at(&mut self.query, self.query_cursor, ch);
+                    self.query_cursor += 1;
+                }
+                self.refresh_filtered();
+            }
+            Mode::Prompt(prompt) => prompt.input.push_str(&text),
+            Mode::InternalPrompt(prompt) => prompt.input.push_str(&text),
+            Mode::ConfirmRun(confirm) => confirm.input.push_str(&text),
+            Mode::Help(help) => {
+                help.query.push_str(&text);
+                help.scroll = 0;
+            }
+            Mode::Palette(_) => {
+                if let Mode::Palette(mut palette) = std::mem::replace(&mut self.mode, Mode::Search)
+                {
+                    palette.query.push_str(&text);
+                    self.filter_palette(&mut palette);
+                    self.mode = Mode::Palette(palette);
+                }
+            }
+            _ => {}
+        }
+        self.refresh_path_listing();
+    }
+
+    fn on_key_action(&mut self, action: KeyAction) -> UiAction {
+        match action {
+            KeyAction::DryRun | KeyAction::Background if self.is_internal_query() => {
+                return UiAction::None;
+            }
+            KeyAction::DryRun => {
+                self.dry_run = true;
+                return self.prepare_selected_command(true);
+            }
+            KeyAction::Background => {
+                self.background_run = true;
+                return self.prepare_selected_command(true);
+            }
+            KeyAction::Quit => return UiAction::Quit,
+            KeyAction::ToggleStaging => self.toggle_staging(),
+            KeyAction::ReviewQueue => self.open_queue_review(),
+            KeyAction::SelectNext | KeyAction::SelectPrevious => {
+                let down = action == KeyAction::SelectNext;
+                if self.active_pane == ActivePane::Session {
+                    self.scroll_session(if down { -1 } else { 1 });
+                } else {
+                    self.move_selection(if down { 1 } else { -1 });
+                }
+            }
+            KeyAction::FocusJobPane => self.cycle_job_focus(),
+            KeyAction::SelectionMode => self.mode = Mode::Selection,
+            KeyAction::Palette => self.open_palette(),
+            KeyAction::TogglePin => self.toggle_pin(),
+            KeyAction::ToggleDetails => self.show_details = !self.show_details,
+        }
+        UiAction::None
+    }
+
+    fn on_search_key(&mut self, key: KeyEvent) -> UiAction {
+        if matches!(key.code, KeyCode::Char('?')) {
+            self.mode = Mode::Help(HelpState::default());
+            return UiAction::None;
+        }
+
+        if matches!(key.code, KeyCode::Tab) {
+            self.active_pane = match self.active_pane {
+                ActivePane::Commands => ActivePane::Session,
+                ActivePane::Session => ActivePane::Commands,
+            };
+            return UiAction::None;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL)
+            && let KeyCode::Char(ch) = key.code
+            && let Some(action) = ctrl_key_action(ch)
+        {
+            return self.on_key_action(action);
+        }
+
+        match key.code {
+            KeyCode::Esc => {
+                if self.query.is_empty() {
+                    UiAction::Quit
+                } else {
+                    self.query.clear();
+                    self.query_cursor = 0;
+                    self.refresh_filtered();
+                    self.active_pane = ActivePane::Commands;
+                    UiAction::None
+                }
+            }
+            KeyCode::Enter => {
+                if self.active_pane == ActivePane::Session {
+                    return UiAction::None;
+                }
+                if self.is_internal_query() {
+                    return self.prepare_selected_internal_command();
+                }
+                let return_to_tui = !key.modifiers.contains(KeyModifiers::ALT);
+                self.prepare_selected_command(return_to_tui)
+            }
+            KeyCode::Left => {
+                if self.query_cursor > 0 {
+                    self.query_cursor -= 1;
+                }
+                self.active_pane = ActivePane::Commands;
+                UiAction::None
+            }
+            KeyCode::Right => {
+                let len = self.query.chars().count();
+                if self.query_cursor < len {
+                    self.query_cursor += 1;
+                }
+                self.active_pane = ActivePane::Commands;
+                UiAction::None
+            }
+            KeyCode::Home => {
+                self.query_cursor = 0;
+                self.active_pane = ActivePane::Commands;
+                UiAction::None
+            }
+            KeyCode::End => {
+                self.query_cursor = self.query.chars().count();
+                self.active_pane = ActivePane::Commands;
+                UiAction::None
+            }
+            KeyCode::Up => {
+                if self.active_pane == ActivePane::Session {
+                    self.scroll_session(1);
+                } else {
+                    self.move_selection(-1);
+                }
+                UiAction::None
+            }
+            KeyCode::Down => {
+                if self.active_pane == ActivePane::Session {
+                    self.scroll_session(-1);
+                } else {
+                    self.move_selection(1);
+                }
+                UiAction::None
+            }
+            KeyCode::PageUp => {
+                if self.active_pane == ActivePane::Session {
+                    self.scroll_session(10);
+                } else {
+                    self.move_selection_by(-10);
+                }
+                UiAction::None
+            }
+            KeyCode::PageDown => {
+                if self.active_pane == ActivePane::Session {
+                    self.scroll_session(-10);
+                } else {
+                    self.move_selection_by(10);
+                }
+                UiAction::None
+            }
+            KeyCode::F(5) => {
+                self.refresh_selected_provider();
+                UiAction::None
+            }
+            KeyCode::Backspace => {
+                if self.query_cursor > 0 && remove_char_at(&mut self.query, self.query_cursor - 1) {
+                    self.query_cursor -= 1;
+                    self.refresh_filtered();
+                }
+                self.active_pane = ActivePane::Commands;
+                UiAction::None
+            }
+            KeyCode::Delete => {
+                if remove_char_at(&mut self.query, self.query_cursor) {
+                    self.refresh_filtered();
+                }
+                self.active_pane = ActivePane::Commands;
+                UiAction::None
+            }
+            KeyCode::Char(ch)
+                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
+            {
+                self.active_pane = ActivePane::Commands;
+                insert_char_at(&mut self.query, self.query_cursor, ch);
+                self.query_cursor += 1;
+                self.refresh_filtered();
+                UiAction::None
+            }
+            _ => UiAction::None,
+        }
+    }
+
+    fn on_prompt_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut prompt_state = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::Prompt(prompt) => prompt,
+            _ => return UiAction::None,
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.push_info(self.runtime.locale.strings().param_canceled);
+                self.mode = Mode::Search;
+                UiAction::None
+            }
+            KeyCode::Backspace => {
+                prompt_state.input.pop();
+                prompt_state.choice_selected = 0;
+                self.mode = Mode::Prompt(prompt_state);
+                UiAction::None
+            }
+            KeyCode::Up | KeyCode::Down => {
+                let count = self
+                    .prompt_listing(&prompt_state)
+                    .map_or(0, |rows| rows.len());
+                if count > 0 {
+                    prompt_state.choice_selected = if key.code == KeyCode::Up {
+                        (prompt_state.choice_selected + count - 1) % count
+                    } else {
+                        (prompt_state.choice_selected + 1) % count
+                    };
+                }
+                self.mode = Mode::Prompt(prompt_state);
+                UiAction::None
+            }
+            KeyCode::Tab => {
+                let param_index = prompt_state.pending_params[prompt_state.current_param];
+                let param = &self.commands[prompt_state.command_index].params[param_index];
+                if prompt_state.input.is_empty()
+                    && let Some(ghost) = prompt_ghost_text(param)
+                {
+                    prompt_state.input = ghost.to_string();
+                } else if matches!(param.kind, ParamType::Path(_))
+                    && let Some(rows) = self.prompt_listing(&prompt_state)
+                {
+                    // A unique match or longer shared prefix completes; otherwise take the
+                    // highlighted row.
+                    let prefix = common_prefix(&rows);
+                    let completed = match rows.as_slice() {
+                        [] => None,
+                        [only] => Some(only.clone()),
+                        _ if prefix.len() > prompt_state.input.len() => Some(prefix),
+                        _ => rows.get(prompt_state.choice_selected).cloned(),
+                    };
+                    if let Some(completed) = completed {
+                        prompt_state.input = completed;
+                        prompt_state.choice_selected = 0;
+                    }
+                }
+                self.mode = Mode::Prompt(prompt_state);
+                UiAction::None
+            }
+            KeyCode::Char(ch)
+                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
+            {
+                let param_index = prompt_state.pending_params[prompt_state.current_param];
+                let param = self.commands[prompt_state.command_index].params[param_index].clone();
+                if matches!(param.kind, ParamType::Flag) {
+                    let typed = ch.to_string();
+                    if let Some(flag_value) =
+                        parse_flag_input(&typed, param.default_flag.unwrap_or(false))
+                    {
+                        let token = if flag_value {
+                            param.flag_token()
+                        } else {
+                            String::new()
+                        };
+                        prompt_state.values.insert(param.name.clone(), token);
+                        prompt_state.current_param += 1;
+                        prompt_state.input.clear();
+                        self.skip_disabled_params(&mut prompt_state);
+
+                        if prompt_state.current_param >= prompt_state.pending_params.len() {
+                            let index = prompt_state.command_index;
+                            let values = prompt_state.values;
+                            let environment = prompt_state.environment;
+                            let return_to_tui = prompt_state.return_to_tui;
+                            self.mode = Mode::Search;
+                            return self.build_run_request(
+                                index,
+                                values,
+                                environment,
+                                return_to_tui,
+                            );
+                        }
+
+                        self.mode = Mode::Prompt(prompt_state);
+                        return UiAction::None;
+                    }
+                }
+
+                prompt_state.input.push(ch);
+                prompt_state.choice_selected = 0;
+                self.mode = Mode::Prompt(prompt_state);
+                UiAction::None
+            }
+            KeyCode::Enter => {
+                let param_index = prompt_state.pending_params[prompt_state.current_param];
+                let param = self.commands[prompt_state.command_index].params[param_index].clone();
+                let input = prompt_state.input.trim().to_string();
+
+                match param.kind {
+                    ParamType::Value | ParamType::Path(_) => {
+                        let value = if input.is_empty() {
+                            if let Some(default) = &param.default_value {
+                                default.clone()
+                            } else if param.required {
+                                self.push_info(i18n::fill(
+                                    self.runtime.locale.strings().param_required,
+                                    &[("name", &param.name)],
+                                ));
+                                self.mode = Mode::Prompt(prompt_state);
+                                return UiAction::None;
+                            } else {
+                                String::new()
+                            }
+                        } else {
+                            input
+                        };
+
+                        if !value.is_empty() {
+                            prompt_state.values.insert(param.name.clone(), value);
+                        }
+                    }
+                    ParamType::Flag => {
+                        let default = param.default_flag.unwrap_or(false);
+                        let Some(flag_value) = parse_flag_input(&input, default) else {
+                            self.push_info(self.runtime.locale.strings().answer_yes_no);
+                            self.mode = Mode::Prompt(prompt_state);
+                            return UiAction::None;
+                        };
+                        let token = if flag_value {
+                            param.flag_token()
+                        } else {
+                            String::new()
+                        };
+                        prompt_state.values.insert(param.name.clone(), token);
+                    }
+                    ParamType::Choice(options) => {
+                        let matches = filter_choices(&self.matcher, &options, &input);
+                        let Some(&choice) = matches.get(prompt_state.choice_selected) else {
+                            self.push_info(i18n::fill(
+                                self.runtime.locale.strings().no_option_matches,
+                                &[("input", &input)],
+                            ));
+                            self.mode = Mode::Prompt(prompt_state);
+                            return UiAction::None;
+                        };
+                        prompt_state
+                            .values
+                            .insert(param.name.clone(), options[choice].clone());
+                    }
+                }
+
+                prompt_state.current_param += 1;
+                prompt_state.input.clear();
+                self.skip_disabled_params(&mut prompt_state);
+
+                if prompt_state.current_param >= prompt_state.pending_params.len() {
+                    let index = prompt_state.command_index;
+                    let values = prompt_state.values;
+                    let environment = prompt_state.environment;
+                    let return_to_tui = prompt_state.return_to_tui;
+                    self.mode = Mode::Search;
+                    self.build_run_request(index, values, environment, return_to_tui)
+                } else {
+                    self.mode = Mode::Prompt(prompt_state);
+                    UiAction::None
+                }
+            }
+            _ => {
+                self.mode = Mode::Prompt(prompt_state);
+                UiAction::None
+            }
+        }
+    }
+
+    fn prepare_selected_command(&mut self, return_to_tui: bool) -> UiAction {
+        let Some(command_index) = self.current_command_index() else {
+            self.push_info(self.runtime.locale.strings().no_command_selected);
+            return UiAction::None;
+        };
+        if let CommandSource::Lazy(provider) = &self.commands[command_index].source {
+            // Running the placeholder types its filter, which loads the provider.
+            let filter = self
+                .provider_alias_by_name
+                .get(provider)
+                .cloned()
+                .unwrap_or_else(|| provider.clone());
+            self.dry_run = false;
+            self.background_run = false;
+            self.query = format!(":{filter} ");
+            self.query_cursor = self.query.chars().count();
+            self.refresh_filtered();
+            return UiAction::None;
+        }
+
+        if !self.commands[command_index].environments.is_empty() {
+            self.mode = Mode::EnvironmentPicker(EnvironmentPickerState {
+                command_index,
+                selected: 0,
+                return_to_tui,
+                previous: HashMap::new(),
+                edit_all: false,
+            });
+            return UiAction::None;
+        }
+
+        self.prepare_command(command_index, None, return_to_tui, HashMap::new())
+    }
+
+    fn prepare_command(
+        &mut self,
+        command_index: usize,
+        environment: Option<usize>,
+        return_to_tui: bool,
+        previous: HashMap<String, String>,
+    ) -> UiAction {
+        self.prepare_command_with(command_index, environment, return_to_tui, previous, false)
+    }
+
+    /// Params that are not prompted for take their `previous` value, then their default. With
+    /// `edit_all`, those are prompted for too, so any value of an earlier run can be changed.
+    fn prepare_command_with(
+        &mut self,
+        command_index: usize,
+        environment: Option<usize>,
+        return_to_tui: bool,
+        previous: HashMap<String, String>,
+        edit_all: bool,
+    ) -> UiAction {
+        let command = &self.commands[command_index];
+        let mut values = environment
+            .and_then(|index| command.environments.get(index))
+            .map(|environment| environment.vars.clone())
+            .unwrap_or_default();
+        let mut pending_params = Vec::new();
+
+        for (idx, param) in command.params.iter().enumerate() {
+            if values.contains_key(&param.name) {
+                continue;
+            }
+            match param.kind {
+                ParamType::Value | ParamType::Choice(_) | ParamType::Path(_) => {
+                    if let Some(value) = &param.value_value {
+                        values.insert(param.name.clone(), value.clone());
+                        continue;
+                    }
+
+                    if edit_all || param.requires_input() {
+                        pending_params.push(idx);
+                        continue;
+                    }
+
+                    let recorded = previous.get(&param.name).filter(|value| match &param.kind {
+                        ParamType::Choice(options) => options.contains(value),
+                        _ => true,
+                    });
+                    if let Some(value) = recorded.or(param.default_value.as_ref()) {
+                        values.insert(param.name.clone(), value.clone());
+                    }
+                }
+                ParamType::Flag => {
+                    if let Some(value) = param.value_flag {
+                        let token = if value {
+                            param.flag_token()
+                        } else {
+                            String::new()
+                        };
+                        values.insert(param.name.clone(), token);
+                        continue;
+                    }
+
+                    if edit_all || param.requires_input() {
+                        pending_params.push(idx);
+                        continue;
+                    }
+
+                    let token = if param.default_flag.unwrap_or(false) {
+                        param.flag_token()
+                    } else {
+                        String::new()
+                    };
+                    values.insert(param.name.clone(), token);
+                }
+            }
+        }
+
+        let mut prompt = PromptState {
+            command_index,
+            pending_params,
+            current_param: 0,
+            input: String::new(),
+            values,
+            environment,
+            return_to_tui,
+            previous,
+            choice_selected: 0,
+        };
+        self.skip_disabled_params(&mut prompt);
+        if prompt.current_param >= prompt.pending_params.len() {
+            return self.build_run_request(
+                command_index,
+                prompt.values,
+                environment,
+                return_to_tui,
+            );
+        }
+
+        self.mode = Mode::Prompt(prompt);
+        UiAction::None
+    }
+
+    /// The command line as far as the prompts have filled it: answered params, the input being
+    /// typed (or the highlighted choice), and `{{name}}` left for params still to come.
+    fn prompt_command_line(&self, prompt: &PromptState) -> String {
+        let command = &self.commands[prompt.command_index];
+        let mut values = prompt.values.clone();
+        if let Some(param) = prompt
+            .pending_params
+            .get(prompt.current_param)
+            .and_then(|index| command.params.get(*index))
+        {
+            let current = match &param.kind {
+                ParamType::Value | ParamType::Path(_) => {
+                    (!prompt.input.is_empty()).then(|| prompt.input.clone())
+                }
+                ParamType::Choice(options) => filter_choices(&self.matcher, options, &prompt.input)
+                    .get(prompt.choice_selected)
+                    .map(|&choice| options[choice].clone()),
+                ParamType::Flag => None,
+            };
+            if let Some(current) = current {
+                values.insert(param.name.clone(), current);
+            }
+        }
+        for param in &command.params {
+            if !param_enabled(param, &values) {
+                values.entry(param.name.clone()).or_default();
+            }
+        }
+        self.render_command_line(command, &command.template, &mut values)
+            .map_or_else(|_| command.template.clone(), |(line, _)| line)
+    }
+
+    /// Rows listed under the input: filtered options of a choice param, or completions of a
+    /// path param relative to the command's working dir.
+    fn prompt_listing(&self, prompt: &PromptState) -> Option<Vec<String>> {
+        let command = &self.commands[prompt.command_index];
+        let param = &command.params[prompt.pending_params[prompt.current_param]];
+        match &param.kind {
+            ParamType::Choice(options) => Some(
+                filter_choices(&self.matcher, options, &prompt.input)
+                    .into_iter()
+                    .map(|index| options[index].clone())
+                    .collect(),
+            ),
+            ParamType::Path(glob) => {
+                let (dir, dir_part, name_prefix) = self.path_prompt_dir(prompt);
+                let rows = match &self.path_listing {
+                    Some(listing) if listing.dir == dir => {
+                        path_completions(&listing.entries, dir_part, name_prefix, glob.as_deref())
+                    }
+                    _ => path_completions(
+                        &read_dir_listing(&dir),
+                        dir_part,
+                        name_prefix,
+                        glob.as_deref(),
+                    ),
+                };
+                Some(rows)
+            }
+            ParamType::Value | ParamType::Flag => None,
+        }
+    }
+
+    /// The directory a path prompt's input points into, then the input split into its directory
+    /// part and the file name typed so far.
+    fn path_prompt_dir<'a>(&self, prompt: &'a PromptState) -> (PathBuf, &'a str, &'a str) {
+        let command = &self.commands[prompt.command_index];
+        let base = command.working_dir.as_deref().unwrap_or(&self.runtime.cwd);
+        let (dir_part, name_prefix) = match prompt.input.rfind('/') {
+            Some(split) => prompt.input.split_at(split + 1),
+            None => ("", prompt.input.as_str()),
+        };
+        let dir = if dir_part.is_empty() {
+            base.to_path_buf()
+        } else {
+            base.join(dir_part)
+        };
+        (dir, dir_part, name_prefix)
+    }
+
+    /// Re-reads the listing for the active path prompt once its input moves to another
+    /// directory; drops it when no path prompt is open.
+    fn refresh_path_listing(&mut self) {
+        let Mode::Prompt(prompt) = &self.mode else {
+            self.path_listing = None;
+            return;
+        };
+        let command = &self.commands[prompt.command_index];
+        let Some(&param_index) = prompt.pending_params.get(prompt.current_param) else {
+            return;
+        };
+        if !matches!(command.params[param_index].kind, ParamType::Path(_)) {
+            self.path_listing = None;
+            return;
+        }
+        let (dir, _, _) = self.path_prompt_dir(prompt);
+        if self
+            .path_listing
+            .as_ref()
+            .is_none_or(|listing| listing.dir != dir)
+        {
+            let entries = read_dir_listing(&dir);
+            self.path_listing = Some(DirListing { dir, entries });
+        }
+    }
+
+    /// Moves past pending params whose controlling flag (`when`) is off, then pre-fills the
+    /// next one from `previous`; choice params highlight the previous value or the default.
+    fn skip_disabled_params(&self, prompt: &mut PromptState) {
+        let params = &self.commands[prompt.command_index].params;
+        while let Some(&index) = prompt.pending_params.get(prompt.current_param) {
+            if param_enabled(&params[index], &prompt.values) {
+                break;
+            }
+            prompt.current_param += 1;
+        }
+        prompt.choice_selected = 0;
+        let Some(param) = prompt
+            .pending_params
+            .get(prompt.current_param)
+            .map(|&index| &params[index])
+        else {
+            return;
+        };
+        let previous = prompt.previous.get(&param.name);
+        match &param.kind {
+            ParamType::Choice(options) => {
+                let wanted = previous.or(param.default_value.as_ref());
+                prompt.choice_selected = options
+                    .iter()
+                    .position(|option| Some(option) == wanted)
+                    .unwrap_or_default();
+            }
+            ParamType::Value | ParamType::Path(_) => {
+                if let Some(value) = previous {
+                    prompt.input = value.clone();
+                }
+            }
+            ParamType::Flag => {
+                if let Some(value) = previous {
+                    prompt.input = if value.is_empty() { "n" } else { "y" }.to_string();
+                }
+            }
+        }
+    }
+
+    fn on_environment_picker_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut picker = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::EnvironmentPicker(picker) => picker,
+            _ => return UiAction::None,
+        };
+        let count = self.commands[picker.command_index].environments.len();
+
+        match key.code {
+            KeyCode::Esc => {
+                self.push_info(self.runtime.locale.strings().environment_canceled);
+                UiAction::None
+            }
+            KeyCode::Up => {
+                picker.selected = (picker.selected + count - 1) % count;
+                self.mode = Mode::EnvironmentPicker(picker);
+                UiAction::None
+            }
+            KeyCode::Down => {
+                picker.selected = (picker.selected + 1) % count;
+                self.mode = Mode::EnvironmentPicker(picker);
+                UiAction::None
+            }
+            KeyCode::Enter => self.prepare_command_with(
+                picker.command_index,
+                Some(picker.selected),
+                picker.return_to_tui,
+                picker.previous,
+                picker.edit_all,
+            ),
+            KeyCode::Char(ch) if ch.is_ascii_digit() && ch != '0' => {
+                let index = ch as usize - '1' as usize;
+                if index >= count {
+                    self.mode = Mode::EnvironmentPicker(picker);
+                    return UiAction::None;
+                }
+                self.prepare_command_with(
+                    picker.command_index,
+                    Some(index),
+                    picker.return_to_tui,
+                    picker.previous,
+                    picker.edit_all,
+                )
+            }
+            _ => {
+                self.mode = Mode::EnvironmentPicker(picker);
+                UiAction::None
+            }
+        }
+    }
+
+    fn open_project_picker(&mut self) -> UiAction {
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+
+        if !self.runtime.persist_usage {
+            self.push_info(self.runtime.locale.strings().projects_disabled);
+            return UiAction::None;
+        }
+        let projects = load_recent_projects();
+        if projects.is_empty() {
+            self.push_info(self.runtime.locale.strings().no_projects);
+            return UiAction::None;
+        }
+        self.mode = Mode::ProjectPicker(ProjectPickerState {
+            projects,
+            selected: 0,
+        });
+        UiAction::None
+    }
+
+    fn on_project_picker_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut picker = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::ProjectPicker(picker) => picker,
+            _ => return UiAction::None,
+        };
+        let count = picker.projects.len();
+
+        let chosen = match key.code {
+            KeyCode::Esc => return UiAction::None,
+            KeyCode::Up => {
+                picker.selected = (picker.selected + count - 1) % count;
+                None
+            }
+            KeyCode::Down => {
+                picker.selected = (picker.selected + 1) % count;
+                None
+            }
+            KeyCode::Enter => Some(picker.selected),
+            KeyCode::Char(ch) if ch.is_ascii_digit() && ch != '0' => {
+                Some(ch as usize - '1' as usize).filter(|index| *index < count)
+            }
+            _ => None,
+        };
+
+        match chosen {
+            Some(index) => UiAction::RunInternal(InternalRunRequest {
+                command: InternalCommand::SwitchProject(picker.projects.swap_remove(index)),
+            }),
+            None => {
+                self.mode = Mode::ProjectPicker(picker);
+                UiAction::None
+            }
+        }
+    }
+
+    fn open_history_picker(&mut self) -> UiAction {
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+
+        let Some(path) = &self.history_path else {
+            self.push_info(self.runtime.locale.strings().history_disabled);
+            return UiAction::None;
+        };
+        let mut entries = history::load(path);
+        if entries.is_empty() {
+            self.push_info(self.runtime.locale.strings().no_history);
+            return UiAction::None;
+        }
+        entries.reverse();
+        self.mode = Mode::HistoryPicker(HistoryPickerState {
+            entries,
+            selected: 0,
+        });
+        UiAction::None
+    }
+
+    fn on_history_picker_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut picker = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::HistoryPicker(picker) => picker,
+            _ => return UiAction::None,
+        };
+        let count = picker.entries.len();
+
+        match key.code {
+            KeyCode::Esc => UiAction::None,
+            KeyCode::Up => {
+                picker.selected = (picker.selected + count - 1) % count;
+                self.mode = Mode::HistoryPicker(picker);
+                UiAction::None
+            }
+            KeyCode::Down => {
+                picker.selected = (picker.selected + 1) % count;
+                self.mode = Mode::HistoryPicker(picker);
+                UiAction::None
+            }
+            KeyCode::Enter => {
+                let entry = picker.entries.swap_remove(picker.selected);
+                self.rerun_history_entry(entry, false)
+            }
+            KeyCode::Char('e') => {
+                let entry = picker.entries.swap_remove(picker.selected);
+                self.rerun_history_entry(entry, true)
+            }
+            _ => {
+                self.mode = Mode::HistoryPicker(picker);
+                UiAction::None
+            }
+        }
+    }
+
+    /// Starts the recorded command again, with its earlier values pre-filled in the prompt;
+    /// `edit` prompts for every param instead of only those without a default. Values masked by
+    /// redaction are asked for again.
+    fn rerun_history_entry(&mut self, entry: HistoryEntry, edit: bool) -> UiAction {
+        let Some(command_index) = self
+            .commands
+            .iter()
+            .position(|command| command_usage_key(command) == entry.command)
+        else {
+            self.push_info(i18n::fill(
+                self.runtime.locale.strings().not_in_catalog,
+                &[("name", &entry.name)],
+            ));
+            return UiAction::None;
+        };
+        let previous: HashMap<String, String> = entry
+            .params
+            .into_iter()
+            .filter(|(_, value)| !value.contains(MASK))
+            .collect();
+        let environments = &self.commands[command_index].environments;
+        let environment = entry
+            .environment
+            .and_then(|name| environments.iter().position(|env| env.name == name));
+        if environment.is_none() && !environments.is_empty() {
+            self.mode = Mode::EnvironmentPicker(EnvironmentPickerState {
+                command_index,
+                selected: 0,
+                return_to_tui: true,
+                previous,
+                edit_all: edit,
+            });
+            return UiAction::None;
+        }
+        self.prepare_command_with(command_index, environment, true, previous, edit)
+    }
+
+    fn build_run_request(
+        &mut self,
+        index: usize,
+        values: HashMap<String, String>,
+        environment: Option<usize>,
+        return_to_tui: bool,
+    ) -> UiAction {
+        let unevaluated = (self.dry_run && self.uses_eval_params(index))
+            .then(|| (index, values.clone(), environment));
+        let mut request = match self.resolve_run_request(index, values, environment, return_to_tui)
+        {
+            Ok(request) => request,
+            Err(message) => {
+                self.push_info(message);
+                return UiAction::None;
+            }
+        };
+        request.background |= std::mem::take(&mut self.background_run);
+        if std::mem::take(&mut self.dry_run) {
+            let (verdict, _, _) = self.check_chain(&request);
+            self.mode = Mode::DryRun(DryRunState {
+                request,
+                verdict,
+                unevaluated,
+            });
+            return UiAction::None;
+        }
+        self.guarded_run(request)
+    }
+
+    /// Whether running the command expands an `eval = true` param, also through `then`.
+    fn uses_eval_params(&self, index: usize) -> bool {
+        let command = &self.commands[index];
+        command.params.iter().any(|param| param.eval)
+            || command
+                .then
+                .iter()
+                .chain(command.steps.iter().map(|step| &step.command))
+                .filter_map(|name| find_command_by_name(&self.commands, name).ok())
+                .any(|target| self.commands[target].params.iter().any(|param| param.eval))
+    }
+
+    /// Runs `request` unless a `[safety]` pattern refuses it or asks for confirmation first.
+    fn guarded_run(&mut self, request: RunRequest) -> UiAction {
+        let (verdict, name, warning) = self.check_chain(&request);
+        match verdict {
+            GuardVerdict::Allow => {}
+            GuardVerdict::Warn(reason) => {
+                let banner = i18n::fill(
+                    self.runtime.locale.strings().warn_run,
+                    &[("banner", &warning_banner(name, &reason))],
+                );
+                self.push_line(ChatLineKind::Banner, banner);
+                self.mode = Mode::WarnRun(Box::new(request));
+                return UiAction::None;
+            }
+            GuardVerdict::Deny(pattern) => {
+                self.push_error(format!(
+                    "Refusing to run '{name}': matches safety pattern '{pattern}'"
+                ));
+                return UiAction::None;
+            }
+            GuardVerdict::Confirm(pattern) => {
+                self.mode = Mode::ConfirmRun(ConfirmRunState {
+                    request,
+                    pattern,
+                    warning,
+                    input: String::new(),
+                });
+                return UiAction::None;
+            }
+        }
+
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+
+        UiAction::Run(Box::new(request))
+    }
+
+    /// Renders `template` for `command` the way its runs are: `{{command:...}}` references
+    /// inlined (their params falling back to their defaults), `{{secret:...}}` as environment
+    /// variable references, context values such as `{{git_branch}}` added to `values`, and values
+    /// quoted where the command asks for it. Returns the line, which may still hold placeholders
+    /// without a value, and the secret names it uses.
+    fn render_command_line(
+        &self,
+        command: &CommandEntry,
+        template: &str,
+        values: &mut HashMap<String, String>,
+    ) -> Result<(String, Vec<String>), String> {
+        let context_dir = command.working_dir.as_deref().unwrap_or(&self.runtime.cwd);
+        let mut referenced = Vec::new();
+        let template = expand_command_refs(
+            &self.commands,
+            template,
+            &mut vec![command.name.clone()],
+            &mut referenced,
+        )?;
+        for param in referenced.iter().flat_map(|target| &target.params) {
+            values
+                .entry(param.name.clone())
+                .or_insert_with(|| param_fallback_value(param));
+        }
+        let secrets = secrets::names(&template);
+        let template = secrets::substitute(&template);
+        insert_context_values(&template, context_dir, values);
+        let mut line_values = values.clone();
+        for (name, value) in &mut line_values {
+            let param = command.params.iter().find(|param| &param.name == name);
+            let quoted = match param {
+                Some(param) if matches!(param.kind, ParamType::Flag) => false,
+                Some(param) if matches!(param.kind, ParamType::Path(_)) => {
+                    *value = quote_path_value(value);
+                    continue;
+                }
+                Some(param) if param.eval && !self.dry_run => true,
+                _ => command.quote_params,
+            };
+            if quoted {
+                *value = provider::shell_escape_arg(value);
+            }
+        }
+        Ok((render_template(&template, &line_values), secrets))
+    }
+
+    /// Renders a command with its param values; fails while placeholders remain unresolved.
+    fn resolve_single_request(
+        &self,
+        index: usize,
+        mut values: HashMap<String, String>,
+        environment: Option<usize>,
+        return_to_tui: bool,
+    ) -> Result<RunRequest, String> {
+        let command = &self.commands[index];
+        let disabled: Vec<String> = command
+            .params
+            .iter()
+            .filter(|param| !param_enabled(param, &values))
+            .map(|param| param.name.clone())
+            .collect();
+        for name in disabled {
+            values.insert(name, String::new());
+        }
+        let params = command
+            .params
+            .iter()
+            .filter_map(|param| {
+                let value = values.get(&param.name)?;
+                Some((param.name.clone(), value.clone()))
+            })
+            .collect();
+        let context_dir = command.working_dir.as_deref().unwrap_or(&self.runtime.cwd);
+        // A dry run shows the expressions; they are only run when the command is.
+        for param in command
+            .params
+            .iter()
+            .filter(|param| param.eval && !self.dry_run)
+        {
+            if let Some(value) = values
+                .get_mut(&param.name)
+                .filter(|value| !value.is_empty())
+            {
+                let expanded = self.eval_param(value, context_dir).map_err(|err| {
+                    format!(
+                        "Param '{}' of '{}' failed to evaluate: {err:#}",
+                        param.name, command.name
+                    )
+                })?;
+                *value = expanded;
+            }
+        }
+        let (rendered, secrets) =
+            self.render_command_line(command, &command.template, &mut values)?;
+        if !secrets.is_empty() && command.host.is_some() {
+            return Err(format!(
+                "Command '{}' runs on a host; {{{{secret:...}}}} only works for local commands",
+                command.name
+            ));
+        }
+        if has_placeholders(&rendered) {
+            return Err(format!(
+                "Command '{}' still has unresolved placeholders",
+                command.name
+            ));
+        }
+
+        if let Some(dir) = &command.working_dir
+            && !command.create_working_dir
+            && !dir.is_dir()
+        {
+            return Err(format!(
+                "Working directory {} of '{}' does not exist (set create_working_dir = true to create it)",
+                dir.display(),
+                command.name
+            ));
+        }
+
+        let display_name = command.name.clone();
+        let working_dir = command.working_dir.clone();
+        let usage_key = command_usage_key(command);
+        let environment = environment.and_then(|index| command.environments.get(index));
+        let mut env: Vec<(String, String)> = command
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), render_template(value, &values)))
+            .collect();
+        if let Some(environment) = environment {
+            env.retain(|(key, _)| !environment.env.iter().any(|(name, _)| name == key));
+            env.extend(environment.env.iter().cloned());
+        }
+        env.retain(|(key, _)| !self.session_env.contains_key(key));
+        env.extend(self.session_env_pairs());
+        let environment = environment.map(|environment| environment.name.clone());
+        let (command_line, env) = match &command.host {
+            Some(host) => (remote_command_line(host, &rendered, &env), Vec::new()),
+            None => (rendered, env),
+        };
+
+        Ok(RunRequest {
+            display_name,
+            command_line,
+            working_dir,
+            usage_key,
+            return_to_tui: return_to_tui && !command.inherit,
+            env,
+            environment,
+            host: command.host.clone(),
+            color: command.color.unwrap_or(self.output.color),
+            params,
+            hooks: command.hooks.or(&self.runtime.hooks),
+            retry: command.retry,
+            timeout: command.timeout.or(self.output.timeout),
+            create_working_dir: command.create_working_dir,
+            // Windows has no pty support here; commands there keep using pipes.
+            pty: cfg!(unix) && command.pty.unwrap_or(self.output.pty),
+            background: command.background,
+            target: command.target.unwrap_or(self.output.target),
+            secrets,
+            then: Vec::new(),
+            continue_on_error: false,
+            pipeline: None,
+        })
+    }
+
+    /// Resolves a command together with the commands it chains with `then`. Chained commands
+    /// are not prompted for: their params take this command's values of the same name, else
+    /// their defaults, and they use the environment of the same name if they have one.
+    fn resolve_run_request(
+        &self,
+        index: usize,
+        values: HashMap<String, String>,
+        environment: Option<usize>,
+        return_to_tui: bool,
+    ) -> Result<RunRequest, String> {
+        let command = &self.commands[index];
+        if !command.steps.is_empty() {
+            return self.resolve_pipeline(index, &values, return_to_tui);
+        }
+        if command.then.is_empty() {
+            return self.resolve_single_request(index, values, environment, return_to_tui);
+        }
+        let environment_name = environment
+            .and_then(|index| command.environments.get(index))
+            .map(|environment| environment.name.clone());
+        let mut then = Vec::new();
+        self.resolve_then(
+            index,
+            &values,
+            environment_name.as_deref(),
+            return_to_tui,
+            &mut vec![command.name.clone()],
+            &mut then,
+        )?;
+        let mut request = self.resolve_single_request(index, values, environment, return_to_tui)?;
+        request.then = then;
+        Ok(request)
+    }
+
+    /// Resolves every step of a pipeline (each followed by its `then` commands) into one request
+    /// whose `then` holds the rest. Steps take `values` like chained commands do.
+    fn resolve_pipeline(
+        &self,
+        index: usize,
+        values: &HashMap<String, String>,
+        return_to_tui: bool,
+    ) -> Result<RunRequest, String> {
+        let pipeline = &self.commands[index];
+        let mut runs = Vec::new();
+        for step in &pipeline.steps {
+            let target_index = find_command_by_name(&self.commands, &step.command)
+                .map_err(|err| format!("pipeline '{}': {err:#}", pipeline.name))?;
+            let target = &self.commands[target_index];
+            if !target.steps.is_empty() {
+                return Err(format!(
+                    "pipeline '{}' cannot run pipeline '{}' as a step",
+                    pipeline.name, target.name
+                ));
+            }
+            let mut run = self.resolve_single_request(
+                target_index,
+                inherited_values(target, values).map_err(|err| {
+                    format!("pipeline '{}' step '{}': {err}", pipeline.name, target.name)
+                })?,
+                None,
+                return_to_tui,
+            )?;
+            run.continue_on_error = step.continue_on_error;
+            runs.push(run);
+            self.resolve_then(
+                target_index,
+                values,
+                None,
+                return_to_tui,
+                &mut vec![pipeline.name.clone(), target.name.clone()],
+                &mut runs,
+            )?;
+        }
+        let mut request = runs.remove(0);
+        request.then = runs;
+        request.pipeline = Some(PipelineRun {
+            name: pipeline.name.clone(),
+            usage_key: command_usage_key(pipeline),
+        });
+        Ok(request)
+    }
+
+    fn resolve_then(
+        &self,
+        index: usize,
+        values: &HashMap<String, String>,
+        environment: Option<&str>,
+        return_to_tui: bool,
+        chain: &mut Vec<String>,
+        then: &mut Vec<RunRequest>,
+    ) -> Result<(), String> {
+        for name in &self.commands[index].then {
+            let target_index = find_command_by_name(&self.commands, name)
+                .map_err(|err| format!("'{}' chains to {err:#}", chain[0]))?;
+            let target = &self.commands[target_index];
+            if !target.steps.is_empty() {
+                return Err(format!(
+                    "'{}' chains to pipeline '{}'; list its steps instead",
+                    chain[0], target.name
+                ));
+            }
+            if chain.contains(&target.name) {
+                return Err(format!(
+                    "'{}' has a `then` cycle: {} -> {}",
+                    chain[0],
+                    chain.join(" -> "),
+                    target.name
+                ));
+            }
+            let target_values = inherited_values(target, values)
+                .map_err(|err| format!("'{}' chains to '{}': {err}", chain[0], target.name))?;
+            let target_environment = environment.and_then(|name| {
+                target
+                    .environments
+                    .iter()
+                    .position(|environment| environment.name.eq_ignore_ascii_case(name))
+            });
+            then.push(self.resolve_single_request(
+                target_index,
+                target_values,
+                target_environment,
+                return_to_tui,
+            )?);
+            chain.push(target.name.clone());
+            self.resolve_then(
+                target_index,
+                values,
+                environment,
+                return_to_tui,
+                chain,
+                then,
+            )?;
+            chain.pop();
+        }
+        Ok(())
+    }
+
+    /// The first `[safety]` verdict other than allow over a request and the runs chained after
+    /// it (refusals first, then confirms, then warnings), with the name of the command it is for.
+    /// A confirm also carries the warning banner of any run that would have warned.
+    fn check_chain<'a>(&self, request: &'a RunRequest) -> (GuardVerdict, &'a str, Option<String>) {
+        let mut confirm = None;
+        let mut warn = None;
+        for run in std::iter::once(request).chain(&request.then) {
+            let (environment, host) = (run.environment.as_deref(), run.host.as_deref());
+            let name = run.display_name.as_str();
+            match self.guard.check(&run.command_line, environment, host) {
+                GuardVerdict::Deny(pattern) => return (GuardVerdict::Deny(pattern), name, None),
+                GuardVerdict::Confirm(pattern) => {
+                    confirm.get_or_insert((GuardVerdict::Confirm(pattern), name));
+                    if warn.is_none() {
+                        warn = self
+                            .guard
+                            .warning(&run.command_line, environment, host)
+                            .map(|reason| (reason, name));
+                    }
+                }
+                GuardVerdict::Warn(reason) => {
+                    warn.get_or_insert((reason, name));
+                }
+                GuardVerdict::Allow => {}
+            }
+        }
+        match (confirm, warn) {
+            (Some((verdict, name)), warn) => (
+                verdict,
+                name,
+                warn.map(|(reason, name)| warning_banner(name, &reason)),
+            ),
+            (None, Some((reason, name))) => (GuardVerdict::Warn(reason), name, None),
+            (None, None) => (GuardVerdict::Allow, request.display_name.as_str(), None),
+        }
+    }
+
+    fn on_dry_run_key(&mut self, key: KeyEvent) -> UiAction {
+        let dry_run = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::DryRun(dry_run) => dry_run,
+            _ => return UiAction::None,
+        };
+        match key.code {
+            KeyCode::Enter => {
+                let Some((index, values, environment)) = dry_run.unevaluated else {
+                    return self.guarded_run(dry_run.request);
+                };
+                self.background_run = dry_run.request.background;
+                return self.build_run_request(
+                    index,
+                    values,
+                    environment,
+                    dry_run.request.return_to_tui,
+                );
+            }
+            KeyCode::Char('c') => match copy_to_clipboard(&dry_run.request.command_line) {
+                Ok(()) => self.push_info(i18n::fill(
+                    self.runtime.locale.strings().copied,
+                    &[("text", &dry_run.request.command_line)],
+                )),
+                Err(err) => self.push_error(i18n::fill(
+                    self.runtime.locale.strings().copy_failed,
+                    &[("error", &format!("{err:#}"))],
+                )),
+            },
+            KeyCode::Esc | KeyCode::Char('q') => {}
+            _ => self.mode = Mode::DryRun(dry_run),
+        }
+        UiAction::None
+    }
+
+    /// Enter runs a command held back by a warning banner; any other key cancels it.
+    fn on_warn_run_key(&mut self, key: KeyEvent) -> UiAction {
+        let request = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::WarnRun(request) => request,
+            _ => return UiAction::None,
+        };
+        if key.code != KeyCode::Enter {
+            self.push_info(self.runtime.locale.strings().run_canceled);
+            return UiAction::None;
+        }
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+        UiAction::Run(request)
+    }
+
+    fn on_confirm_run_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut confirm = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::ConfirmRun(confirm) => confirm,
+            _ => return UiAction::None,
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.push_info(self.runtime.locale.strings().run_canceled);
+                UiAction::None
+            }
+            KeyCode::Backspace => {
+                confirm.input.pop();
+                self.mode = Mode::ConfirmRun(confirm);
+                UiAction::None
+            }
+            KeyCode::Char(ch)
+                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
+            {
+                confirm.input.push(ch);
+                self.mode = Mode::ConfirmRun(confirm);
+                UiAction::None
+            }
+            KeyCode::Enter => {
+                if !confirm.input.trim().eq_ignore_ascii_case("yes") {
+                    self.push_info(self.runtime.locale.strings().type_yes);
+                    self.mode = Mode::ConfirmRun(confirm);
+                    return UiAction::None;
+                }
+
+                self.query.clear();
+                self.query_cursor = 0;
+                self.refresh_filtered();
+                UiAction::Run(Box::new(confirm.request))
+            }
+            _ => {
+                self.mode = Mode::ConfirmRun(confirm);
+                UiAction::None
+            }
+        }
+    }
+
+    fn on_internal_prompt_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut prompt_state = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::InternalPrompt(prompt) => prompt,
+            _ => return UiAction::None,
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.push_info(self.runtime.locale.strings().internal_canceled);
+                self.mode = Mode::Search;
+                UiAction::None
+            }
+            KeyCode::Backspace => {
+                prompt_state.input.pop();
+                self.mode = Mode::InternalPrompt(prompt_state);
+                UiAction::None
+            }
+            KeyCode::Char(ch)
+                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
+            {
+                let typed = ch.to_string();
+                let command = &self.internal_commands[prompt_state.command_index];
+                let default = command.default_force;
+                if let Some(force) = parse_flag_input(&typed, default) {
+                    self.mode = Mode::Search;
+                    self.request_init(force)
+                } else {
+                    prompt_state.input.push(ch);
+                    self.mode = Mode::InternalPrompt(prompt_state);
+                    UiAction::None
+                }
+            }
+            KeyCode::Enter => {
+                let command = &self.internal_commands[prompt_state.command_index];
+                let default = command.default_force;
+                let Some(force) = parse_flag_input(prompt_state.input.trim(), default) else {
+                    self.push_info(self.runtime.locale.strings().answer_yes_no);
+                    self.mode = Mode::InternalPrompt(prompt_state);
+                    return UiAction::None;
+                };
+
+                self.mode = Mode::Search;
+                self.request_init(force)
+            }
+            _ => {
+                self.mode = Mode::InternalPrompt(prompt_state);
+                UiAction::None
+            }
+        }
+    }
+
+    fn request_init(&mut self, force: bool) -> UiAction {
+        let run = UiAction::RunInternal(InternalRunRequest {
+            command: InternalCommand::Init { force },
+        });
+        if !force {
+            return run;
+        }
+
+        let diff = config::global_config_path()
+            .and_then(|path| Ok((config::example_config_overwrite_diff(&path)?, path)));
+        match diff {
+            Ok((Some(lines), path)) => {
+                self.mode = Mode::InitDiff(InitDiffState {
+                    path,
+                    lines,
+                    scroll: 0,
+                });
+                UiAction::None
+            }
+            Ok((None, _)) => run,
+            Err(err) => {
+                self.push_error(i18n::fill(
+                    self.runtime.locale.strings().init_failed,
+                    &[("error", &format!("{err:#}"))],
+                ));
+                UiAction::None
+            }
+        }
+    }
+
+    fn on_init_diff_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut diff = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::InitDiff(diff) => diff,
+            _ => return UiAction::None,
+        };
+
+        let max_scroll = diff.lines.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => UiAction::RunInternal(InternalRunRequest {
+                command: InternalCommand::Init { force: true },
+            }),
+            KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {
+                self.push_info(self.runtime.locale.strings().init_canceled);
+                UiAction::None
+            }
+            KeyCode::Up => {
+                diff.scroll = diff.scroll.saturating_sub(1);
+                self.mode = Mode::InitDiff(diff);
+                UiAction::None
+            }
+            KeyCode::Down => {
+                diff.scroll = (diff.scroll + 1).min(max_scroll);
+                self.mode = Mode::InitDiff(diff);
+                UiAction::None
+            }
+            KeyCode::PageUp => {
+                diff.scroll = diff.scroll.saturating_sub(10);
+                self.mode = Mode::InitDiff(diff);
+                UiAction::None
+            }
+            KeyCode::PageDown => {
+                diff.scroll = (diff.scroll + 10).min(max_scroll);
+                self.mode = Mode::InitDiff(diff);
+                UiAction::None
+            }
+            _ => {
+                self.mode = Mode::InitDiff(diff);
+                UiAction::None
+            }
+        }
+    }
+
+    fn open_palette(&mut self) {
+        let mut palette = PaletteState {
+            query: String::new(),
+            filtered: Vec::new(),
+            selected: 0,
+        };
+        self.filter_palette(&mut palette);
+        self.mode = Mode::Palette(palette);
+    }
+
+    fn filter_palette(&self, palette: &mut PaletteState) {
+        let query = palette.query.trim().to_lowercase();
+        let mut scored: Vec<(usize, i64)> = PALETTE_ACTIONS
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (name, description, _))| {
+                if query.is_empty() {
+                    return Some((index, 0));
+                }
+                let haystack = format!("{name} {description}").to_lowercase();
+                self.matcher
+                    .fuzzy_match(&haystack, &query)
+                    .map(|score| (index, score))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        palette.filtered = scored.into_iter().map(|(index, _)| index).collect();
+        palette.selected = 0;
+    }
+
+    fn on_palette_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut palette = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::Palette(palette) => palette,
+            _ => return UiAction::None,
+        };
+
+        match key.code {
+            KeyCode::Esc => UiAction::None,
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => UiAction::None,
+            KeyCode::Enter => {
+                let Some(index) = palette.filtered.get(palette.selected).copied() else {
+                    self.mode = Mode::Palette(palette);
+                    return UiAction::None;
+                };
+                self.run_palette_action(PALETTE_ACTIONS[index].2)
+            }
+            KeyCode::Up => {
+                if !palette.filtered.is_empty() {
+                    let len = palette.filtered.len();
+                    palette.selected = (palette.selected + len - 1) % len;
+                }
+                self.mode = Mode::Palette(palette);
+                UiAction::None
+            }
+            KeyCode::Down => {
+                if !palette.filtered.is_empty() {
+                    palette.selected = (palette.selected + 1) % palette.filtered.len();
+                }
+                self.mode = Mode::Palette(palette);
+                UiAction::None
+            }
+            KeyCode::Backspace => {
+                palette.query.pop();
+                self.filter_palette(&mut palette);
+                self.mode = Mode::Palette(palette);
+                UiAction::None
+            }
+            KeyCode::Char(ch)
+                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
+            {
+                palette.query.push(ch);
+                self.filter_palette(&mut palette);
+                self.mode = Mode::Palette(palette);
+                UiAction::None
+            }
+            _ => {
+                self.mode = Mode::Palette(palette);
+                UiAction::None
+            }
+        }
+    }
+
+    fn run_palette_action(&mut self, action: PaletteAction) -> UiAction {
+        match action {
+            PaletteAction::ShowHelp => {
+                self.mode = Mode::Help(HelpState::default());
+                UiAction::None
+            }
+            PaletteAction::ToggleFocus => {
+                self.active_pane = match self.active_pane {
+                    ActivePane::Commands => ActivePane::Session,
+                    ActivePane::Session => ActivePane::Commands,
+                };
+                UiAction::None
+            }
+            PaletteAction::ToggleUsageRanking => {
+                self.ranking.usage_enabled = !self.ranking.usage_enabled;
+                self.refresh_filtered();
+                let strings = self.runtime.locale.strings();
+                self.push_info(if self.ranking.usage_enabled {
+                    strings.sort_usage
+                } else {
+                    strings.sort_alphabetical
+                });
+                UiAction::None
+            }
+            PaletteAction::ToggleRepeatFolding => {
+                self.expand_repeats = !self.expand_repeats;
+                self.session_scroll = 0;
+                UiAction::None
+            }
+            PaletteAction::TogglePrettyJson => {
+                self.output.pretty_json = !self.output.pretty_json;
+                self.session_scroll = 0;
+                UiAction::None
+            }
+            PaletteAction::ViewTruncatedLine => {
+                self.open_truncated_line_pager();
+                UiAction::None
+            }
+            PaletteAction::ClearOutputFilter => {
+                self.output_filter = None;
+                self.session_scroll = 0;
+                UiAction::None
+            }
+            PaletteAction::SelectText => {
+                self.mode = Mode::Selection;
+                UiAction::None
+            }
+            PaletteAction::ClearSession => {
+                self.chat.clear();
+                self.session_scroll = 0;
+                UiAction::None
+            }
+            PaletteAction::ShowConfigPath => {
+                self.push_info(self.config_line());
+                UiAction::None
+            }
+            PaletteAction::ToggleDetails => {
+                self.show_details = !self.show_details;
+                UiAction::None
+            }
+            PaletteAction::ToggleStaging => {
+                self.toggle_staging();
+                UiAction::None
+            }
+            PaletteAction::ReviewQueue => {
+                self.open_queue_review();
+                UiAction::None
+            }
+            PaletteAction::Reload => UiAction::RunInternal(InternalRunRequest {
+                command: InternalCommand::Reload,
+            }),
+            PaletteAction::Quit => UiAction::Quit,
+        }
+    }
+
+    fn prepare_selected_internal_command(&mut self) -> UiAction {
+        let trimmed = self.query.trim();
+        if let Some(command) = parse_env_command(trimmed) {
+            match command {
+                Ok(command) => self.run_env_command(command),
+                Err(err) => self.push_error(err),
+            }
+            return UiAction::None;
+        }
+        if let Some(parsed) = parse_internal_command(trimmed) {
+            match parsed {
+                InternalCommand::Reload => {
+                    return UiAction::RunInternal(InternalRunRequest {
+                        command: InternalCommand::Reload,
+                    });
+                }
+                InternalCommand::Init { force } => {
+                    if query_has_force_flag(trimmed) {
+                        return self.request_init(force);
+                    }
+                }
+                InternalCommand::Aliases => {
+                    return UiAction::RunInternal(InternalRunRequest {
+                        command: InternalCommand::Aliases,
+                    });
+                }
+                InternalCommand::SwitchProject(_) => {}
+                InternalCommand::Unknown(name) => {
+                    let is_prefix = self
+                        .internal_commands
+                        .iter()
+                        .any(|command| command.name[1..].starts_with(&name));
+                    if !is_prefix && let Some(suggestion) = self.suggest_internal_command(&name) {
+                        self.query = suggestion.to_string();
+                        self.query_cursor = self.query.chars().count();
+                        self.refresh_filtered();
+                        self.push_info(i18n::fill(
+                            self.runtime.locale.strings().did_you_mean,
+                            &[("name", &name), ("suggestion", suggestion)],
+                        ));
+                        return UiAction::None;
+                    }
+                }
+            }
+        }
+
+        let Some(index) = self.current_internal_index() else {
+            self.push_info(format!(
+                "Unknown internal command. Available: {}",
+                internal_command_list(&self.internal_commands)
+            ));
+            return UiAction::None;
+        };
+
+        let command = &self.internal_commands[index];
+        match command.kind {
+            InternalCommandKind::Reload => UiAction::RunInternal(InternalRunRequest {
+                command: InternalCommand::Reload,
+            }),
+            InternalCommandKind::Aliases => UiAction::RunInternal(InternalRunRequest {
+                command: InternalCommand::Aliases,
+            }),
+            InternalCommandKind::Projects => self.open_project_picker(),
+            InternalCommandKind::History => self.open_history_picker(),
+            InternalCommandKind::Cache => self.open_caches(),
+            InternalCommandKind::Doctor => {
+                self.run_doctor();
+                UiAction::None
+            }
+            InternalCommandKind::Jobs => self.open_jobs(),
+            InternalCommandKind::Env => {
+                self.run_env_command(EnvCommand::List);
+                UiAction::None
+            }
+            InternalCommandKind::Init => {
+                self.mode = Mode::InternalPrompt(InternalPromptState {
+                    command_index: index,
+                    input: String::new(),
+                });
+                UiAction::None
+            }
+        }
+    }
+
+    fn suggest_internal_command(&self, name: &str) -> Option<&'static str> {
+        if name.is_empty() {
+            return None;
+        }
+
+        self.internal_commands
+            .iter()
+            .map(|command| {
+                let candidate = &command.name[1..];
+                let fuzzy = self
+                    .matcher
+                    .fuzzy_match(candidate, name)
+                    .unwrap_or_default();
+                (edit_distance(name, candidate), -fuzzy, command.name)
+            })
+            .filter(|(distance, _, candidate)| *distance <= 2.max(candidate.len() / 3))
+            .min()
+            .map(|(_, _, candidate)| candidate)
+    }
+
+    /// `Config: <path>`, naming the global config too when the project config was merged over it.
+    fn config_line(&self) -> String {
+        let strings = self.runtime.locale.strings();
+        match (&self.config_path, &self.runtime.global_config_path) {
+            (Some(path), Some(global)) => i18n::fill(
+                strings.config_merged,
+                &[
+                    ("path", &path.display().to_string()),
+                    ("global", &global.display().to_string()),
+                ],
+            ),
+            (Some(path), None) => i18n::fill(
+                strings.config_path,
+                &[("path", &path.display().to_string())],
+            ),
+            (None, _) => strings.config_none.to_string(),
+        }
+    }
+
+    fn apply_reload_payload(&mut self, payload: ReloadPayload) {
+        self.commands = payload.commands;
+        self.config_path = payload.config_path;
+        self.runtime.global_config_path = payload.global_config_path;
+        self.provider_aliases = payload.provider_aliases;
+        self.provider_alias_by_name = self
+            .provider_aliases
+            .iter()
+            .map(|(alias, provider)| (provider.clone(), alias.clone()))
+            .collect();
+        self.provider_names_without_alias =
+            provider_names_without_alias(&self.commands, &self.provider_alias_by_name);
+        self.ranking = payload.ranking;
+        self.output = payload.output;
+        self.guard = payload.guard;
+        self.runtime.providers = payload.providers;
+        self.runtime.hooks = payload.hooks;
+        self.rebuild_search_index();
+        self.refresh_filtered();
+        if self.selected >= self.filtered.len() {
+            self.selected = 0;
+        }
+        self.loading_providers.clear();
+        self.provider_rx = None;
+        self.pending_refresh.clear();
+        let now = history::now();
+        let enabled = provider::enabled_provider_names(&self.runtime.providers);
+        self.provider_loaded_at
+            .retain(|name, _| enabled.contains(name));
+        for name in enabled {
+            if self.runtime.providers.lazy.contains(&name) {
+                self.provider_loaded_at.remove(&name);
+            } else {
+                let loaded_at = provider::provider_cached_at(&name, &self.runtime.cwd);
+                self.provider_loaded_at
+                    .insert(name, loaded_at.unwrap_or(now));
+            }
+        }
+        self.rebuild_schedules(Instant::now());
+        self.note_catalog_problems();
+        self.record_catalog_snapshot();
+    }
+
+    /// Starts one loader thread per enabled provider; results arrive via `poll_provider_loads`.
+    /// Lazy providers get their placeholder instead and load once their filter is typed.
+    fn start_provider_loads(&mut self) {
+        let lazy = provider::lazy_provider_names(&self.runtime.providers);
+        let names: Vec<String> = provider::enabled_provider_names(&self.runtime.providers)
+            .into_iter()
+            .filter(|name| !lazy.contains(name))
+            .collect();
+        if !lazy.is_empty() {
+            self.commands
+                .extend(lazy_placeholders(&lazy, &self.provider_aliases));
+            self.commands
+                .sort_by_key(|command| command.name.to_lowercase());
+            self.provider_names_without_alias =
+                provider_names_without_alias(&self.commands, &self.provider_alias_by_name);
+            self.rebuild_search_index();
+            self.refresh_filtered();
+        }
+        if names.is_empty() {
+            self.record_catalog_snapshot();
+        }
+        self.spawn_provider_loads(names);
+    }
+
+    /// Whether `provider` is lazy and its commands have not been loaded yet.
+    fn is_unexpanded(&self, provider: &str) -> bool {
+        self.runtime
+            .providers
+            .lazy
+            .iter()
+            .any(|name| name == provider)
+            && !self.provider_loaded_at.contains_key(provider)
+    }
+
+    /// Lazy providers' commands show only under their own filter, and their placeholder only
+    /// elsewhere or while they load.
+    fn hidden_by_lazy(&self, command: &CommandEntry, provider_filter: Option<&str>) -> bool {
+        let provider = command_provider_name(command);
+        if !self
+            .runtime
+            .providers
+            .lazy
+            .iter()
+            .any(|name| name == provider)
+        {
+            return false;
+        }
+        let filtered_to_it =
+            provider_filter.is_some_and(|filter| filter.eq_ignore_ascii_case(provider));
+        match command.source {
+            CommandSource::Lazy(_) => filtered_to_it && !self.is_unexpanded(provider),
+            _ => !filtered_to_it,
+        }
+    }
+
+    /// The lazy provider `provider_filter` names, when it still has to be loaded.
+    fn lazy_provider_to_expand(&self, provider_filter: Option<&str>) -> Option<String> {
+        let filter = provider_filter?;
+        let name = self
+            .runtime
+            .providers
+            .lazy
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(filter))?;
+        (self.is_unexpanded(name)
+            && !self.loading_providers.contains(name)
+            && !self.pending_refresh.contains(name))
+        .then(|| name.clone())
+    }
+
+    /// Saves the complete catalog for `fzc catalog diff` when it changed since the last snapshot.
+    fn record_catalog_snapshot(&self) {
+        if !self.runtime.persist_usage {
+            return;
+        }
+        if let Some(path) = snapshots::snapshots_path(&self.runtime.cwd) {
+            let _ = snapshots::record(
+                &path,
+                snapshots::CatalogSnapshot::new(
+                    &self.commands,
+                    &self.runtime.providers.lazy,
+                    history::now(),
+                ),
+            );
+        }
+    }
+
+    /// Loads `names` on background threads; their results replace earlier commands of the same
+    /// provider.
+    fn spawn_provider_loads(&mut self, names: Vec<String>) {
+        if names.is_empty() {
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        for name in &names {
+            let name = name.clone();
+            let providers = self.runtime.providers.clone();
+            let cwd = self.runtime.cwd.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let result = provider::load_provider(&providers, &cwd, &name)
+                    .map_err(|err| format!("{err:#}"));
+                let _ = tx.send(ProviderLoad { name, result });
+            });
+        }
+        self.loading_providers = names;
+        self.provider_load_failed = false;
+        self.provider_rx = Some(rx);
+    }
+
+    /// How long to wait for input: short while active or waiting on background work.
+    fn poll_timeout(&self, now: Instant) -> Duration {
+        let busy = self.provider_rx.is_some()
+            || self.preview_running.is_some()
+            || self.preview_wanted.is_some()
+            || self.hooks_running > 0
+            || self.schedules.iter().any(|job| job.running)
+            || self.jobs.iter().any(BackgroundJob::is_running);
+        if busy || now.duration_since(self.last_activity) < IDLE_AFTER {
+            self.runtime.poll.interval
+        } else {
+            self.runtime.poll.idle_interval
+        }
+    }
+
+    /// Merges finished provider loads into the catalog; returns whether anything changed.
+    fn poll_provider_loads(&mut self) -> bool {
+        let Some(rx) = &self.provider_rx else {
+            if self.pending_refresh.is_empty() {
+                return false;
+            }
+            let names = std::mem::take(&mut self.pending_refresh);
+            self.push_info(i18n::fill(
+                self.runtime.locale.strings().refreshing,
+                &[("names", &names.join(", "))],
+            ));
+            self.spawn_provider_loads(names);
+            return true;
+        };
+        let finished: Vec<ProviderLoad> = rx.try_iter().collect();
+        if finished.is_empty() {
+            return false;
+        }
+
+        let selected_key = self
+            .current_command_index()
+            .map(|index| command_usage_key(&self.commands[index]));
+        let mut scheduled = false;
+        for load in finished {
+            self.loading_providers.retain(|name| *name != load.name);
+            match load.result {
+                Ok(commands) => {
+                    let locale = self.runtime.locale;
+                    self.push_info(i18n::fill(
+                        locale.strings().loaded_provider,
+                        &[
+                            ("count", &locale.format_count(commands.len())),
+                            ("provider", &load.name),
+                        ],
+                    ));
+                    scheduled |= commands.iter().any(|command| command.schedule.is_some());
+                    // Results read from a provider's cache are as old as the cache.
+                    let loaded_at = provider::provider_cached_at(&load.name, &self.runtime.cwd);
+                    self.provider_loaded_at
+                        .insert(load.name.clone(), loaded_at.unwrap_or_else(history::now));
+                    self.commands.retain(|command| {
+                        command_provider_name(command) != load.name
+                            || matches!(command.source, CommandSource::Lazy(_))
+                    });
+                    self.commands.extend(commands);
+                }
+                Err(err) => {
+                    self.provider_load_failed = true;
+                    self.push_error(i18n::fill(
+                        self.runtime.locale.strings().provider_failed,
+                        &[("provider", &load.name), ("error", &err)],
+                    ));
+                }
+            }
+        }
+        let complete = self.loading_providers.is_empty();
+        if complete {
+            self.provider_rx = None;
+        }
+
+        self.commands
+            .sort_by_key(|command| command.name.to_lowercase());
+        self.provider_names_without_alias =
+            provider_names_without_alias(&self.commands, &self.provider_alias_by_name);
+        self.rebuild_search_index();
+        self.refresh_filtered();
+        // Keep the highlighted command under the cursor as new entries arrive.
+        if let Some(key) = selected_key {
+            self.selected = self
+                .filtered
+                .iter()
+                .position(|item| {
+                    matches!(item, SearchItem::Command(index)
+                        if command_usage_key(&self.commands[*index]) == key)
+                })
+                .unwrap_or(0);
+        }
+        if scheduled {
+            self.rebuild_schedules(Instant::now());
+        }
+        if complete && !self.provider_load_failed {
+            self.record_catalog_snapshot();
+        }
+        true
+    }
+
+    /// Expands an `eval = true` param value in `dir`; expressions the safety settings would
+    /// refuse, ask about or warn about are not run.
+    fn eval_param(&self, expression: &str, dir: &Path) -> Result<String> {
+        let command_line = eval_command_line(expression);
+        match self.guard.check(&command_line, None, None) {
+            GuardVerdict::Allow => {}
+            GuardVerdict::Warn(reason) => bail!("expression {reason}"),
+            GuardVerdict::Deny(pattern) | GuardVerdict::Confirm(pattern) => {
+                bail!("matches safety pattern '{pattern}'")
+            }
+        }
+        let (code, chunks) = run_shell_command_captured(
+            &command_line,
+            Some(dir),
+            &self.session_env_pairs(),
+            ColorMode::Never,
+            Some(EVAL_TIMEOUT),
+        )?;
+        let (stdout, stderr): (Vec<_>, Vec<_>) = chunks
+            .into_iter()
+            .partition(|chunk| chunk.kind == ChatLineKind::Stdout);
+        let text = |chunks: Vec<StreamChunk>| {
+            chunks
+                .into_iter()
+                .map(|chunk| chunk.text)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        if code != 0 {
+            let stderr = text(stderr);
+            if stderr.is_empty() {
+                bail!("exited with {code}");
+            }
+            bail!("{stderr}");
+        }
+        Ok(text(stdout))
+    }
+
+    /// While an `eval = true` param is being typed: the expansion of its input, run and cached
+    /// like a preview.
+    fn wanted_eval_preview(&self) -> Option<PreviewRequest> {
+        let Mode::Prompt(prompt) = &self.mode else {
+            return None;
+        };
+        let command = &self.commands[prompt.command_index];
+        let param = command
+            .params
+            .get(*prompt.pending_params.get(prompt.current_param)?)?;
+        (param.eval && !prompt.input.is_empty()).then(|| PreviewRequest {
+            command_line: eval_command_line(&prompt.input),
+            working_dir: Some(
+                command
+                    .working_dir
+                    .clone()
+                    .unwrap_or_else(|| self.runtime.cwd.clone()),
+            ),
+            secrets: Vec::new(),
+        })
+    }
+
+    /// Preview for the selected command, or the one being prompted for, with the params known so
+    /// far and the others' defaults; fails while a required param has neither.
+    fn wanted_preview(&self) -> Option<Result<PreviewRequest, String>> {
+        let (index, values) = match &self.mode {
+            Mode::Search => (self.current_command_index()?, HashMap::new()),
+            Mode::EnvironmentPicker(picker) => (picker.command_index, HashMap::new()),
+            Mode::Prompt(prompt) => {
+                let mut values = prompt.values.clone();
+                let command = &self.commands[prompt.command_index];
+                if let Some(param) = prompt
+                    .pending_params
+                    .get(prompt.current_param)
+                    .and_then(|index| command.params.get(*index))
+                    && matches!(param.kind, ParamType::Value | ParamType::Path(_))
+                    && !prompt.input.is_empty()
+                {
+                    values.insert(param.name.clone(), prompt.input.clone());
+                }
+                (prompt.command_index, values)
+            }
+            _ => return None,
+        };
+        let command = &self.commands[index];
+        let template = command.preview.as_deref()?;
+        let request = inherited_values(command, &values).and_then(|mut values| {
+            let (command_line, secrets) =
+                self.render_command_line(command, template, &mut values)?;
+            if has_placeholders(&command_line) {
+                return Err("the preview still has unresolved placeholders".to_string());
+            }
+            Ok(PreviewRequest {
+                command_line,
+                working_dir: command.working_dir.clone(),
+                secrets,
+            })
+        });
+        Some(request)
+    }
+
+    /// Stores finished previews and starts the wanted one unless it is cached or one is running;
+    /// returns whether a preview finished.
+    fn poll_preview(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        while let Ok((command_line, lines)) = self.preview_rx.try_recv() {
+            changed = true;
+            if self
+                .preview_running
+                .as_ref()
+                .is_some_and(|(running, _)| *running == command_line)
+            {
+                self.preview_running = None;
+            }
+            let lines = lines
+                .into_iter()
+                .map(|line| self.mask_secrets(line))
+                .collect();
+            self.preview_cache.insert(
+                command_line,
+                PreviewOutput {
+                    finished: now,
+                    lines,
+                },
+            );
+        }
+        // The runner kills the command at PREVIEW_TIMEOUT, but a process it left behind can keep
+        // its output open; stop waiting for it then.
+        if let Some((command_line, started)) = &self.preview_running
+            && now.duration_since(*started) >= PREVIEW_TIMEOUT + FORCE_KILL_AFTER
+        {
+            let line = format!(
+                "preview did not finish within {}s",
+                PREVIEW_TIMEOUT.as_secs()
+            );
+            self.preview_cache.insert(
+                command_line.clone(),
+                PreviewOutput {
+                    finished: now,
+                    lines: vec![line],
+                },
+            );
+            self.preview_running = None;
+            changed = true;
+        }
+        self.preview_cache
+            .retain(|_, output| now.duration_since(output.finished) < PREVIEW_TTL);
+
+        let Some(request) = self
+            .wanted_eval_preview()
+            .filter(|request| !self.preview_cache.contains_key(&request.command_line))
+            .or_else(|| self.wanted_preview().and_then(Result::ok))
+        else {
+            return changed;
+        };
+        if self.preview_running.is_some()
+            || self.preview_cache.contains_key(&request.command_line)
+            || !matches!(
+                self.guard.check(&request.command_line, None, None),
+                GuardVerdict::Allow
+            )
+        {
+            return changed;
+        }
+        match &self.preview_wanted {
+            Some((wanted, since)) if *wanted == request.command_line => {
+                if now.duration_since(*since) < PREVIEW_DEBOUNCE {
+                    return changed;
+                }
+            }
+            _ => {
+                self.preview_wanted = Some((request.command_line.clone(), now));
+                return changed;
+            }
+        }
+        self.preview_wanted = None;
+
+        let mut env = self.session_env_pairs();
+        for name in &request.secrets {
+            match secrets::fetch(&self.runtime.secrets, name) {
+                Ok(value) => env.push((secrets::env_var(name), value)),
+                Err(err) => {
+                    let error = format!("secret '{name}': {err:#}");
+                    self.preview_cache.insert(
+                        request.command_line,
+                        PreviewOutput {
+                            finished: now,
+                            lines: vec![error],
+                        },
+                    );
+                    return true;
+                }
+            }
+        }
+        self.preview_running = Some((request.command_line.clone(), now));
+        let tx = self.preview_tx.clone();
+        thread::spawn(move || {
+            let lines = match run_shell_command_captured(
+                &request.command_line,
+                request.working_dir.as_deref(),
+                &env,
+                ColorMode::Never,
+                Some(PREVIEW_TIMEOUT),
+            ) {
+                Ok((_, chunks)) => chunks
+                    .into_iter()
+                    .take(PREVIEW_MAX_LINES)
+                    .map(|chunk| chunk.text)
+                    .collect(),
+                Err(err) => vec![format!("{err:#}")],
+            };
+            let _ = tx.send((request.command_line, lines));
+        });
+        changed
+    }
+
+    /// Collects scheduled commands; ones that need input or trip the safety guard are skipped.
+    fn rebuild_schedules(&mut self, now: Instant) {
+        let mut schedules = Vec::new();
+        let mut skipped = Vec::new();
+        for (index, command) in self.commands.iter().enumerate() {
+            let Some(interval) = command.schedule else {
+                continue;
+            };
+            // Nobody is prompted for a scheduled run, so it renders like a chained command.
+            let request = inherited_values(command, &HashMap::new())
+                .and_then(|values| self.resolve_run_request(index, values, None, false));
+            let request = match request {
+                Ok(request) => request,
+                Err(err) => {
+                    skipped.push(format!("schedule for '{}' skipped: {err}", command.name));
+                    continue;
+                }
+            };
+            match self.check_chain(&request).0 {
+                GuardVerdict::Allow => {}
+                GuardVerdict::Warn(reason) => {
+                    skipped.push(format!(
+                        "schedule for '{}' skipped: it {reason}",
+                        command.name
+                    ));
+                    continue;
+                }
+                GuardVerdict::Deny(_) | GuardVerdict::Confirm(_) => {
+                    skipped.push(format!(
+                        "schedule for '{}' skipped: blocked by [safety] patterns",
+                        command.name
+                    ));
+                    continue;
+                }
+            }
+            schedules.push(ScheduledJob {
+                key: command_usage_key(command),
+                name: command.name.clone(),
+                request,
+                interval,
+                next_run: now + interval,
+                running: false,
+            });
+        }
+
+        if !schedules.is_empty() {
+            self.push_info(i18n::fill(
+                self.runtime.locale.strings().scheduled,
+                &[("count", &schedules.len().to_string())],
+            ));
+        }
+        for message in skipped {
+            self.push_error(message);
+        }
+        self.close_job_panes(|source| match source {
+            PaneSource::Schedule(key) => schedules.iter().any(|job| &job.key == key),
+            PaneSource::Job(_) => true,
+        });
+        self.schedules = schedules;
+    }
+
+    /// Posts finished background runs and starts the ones that are due; returns whether output
+    /// was posted.
+    fn poll_schedules(&mut self, now: Instant) -> bool {
+        let mut changed = false;
+        while let Ok(outcome) = self.schedule_rx.try_recv() {
+            changed = true;
+            if let Some(job) = self.schedules.iter_mut().find(|job| job.key == outcome.key) {
+                job.running = false;
+            }
+            let source = PaneSource::Schedule(outcome.key.clone());
+            if let Some(slot) = self.job_pane_for(source, &outcome.name) {
+                // One finished run: its output, then its exit code (or why it failed to start).
+                let (lines, last_exit) = match outcome.result {
+                    Ok((code, chunks)) => {
+                        let mut lines: Vec<ChatLine> = chunks
+                            .into_iter()
+                            .map(|chunk| self.pane_line(chunk.kind, chunk.text))
+                            .collect();
+                        lines
+                            .push(self.pane_line(ChatLineKind::Info, format!("exit code: {code}")));
+                        (lines, Some(code))
+                    }
+                    Err(err) => (vec![self.pane_line(ChatLineKind::Stderr, err)], None),
+                };
+                let pane = &mut self.job_panes[slot];
+                pane.extend(lines);
+                pane.last_exit = last_exit;
+                continue;
+            }
+            match outcome.result {
+                Ok((code, chunks)) => {
+                    self.push_info(i18n::fill(
+                        self.runtime.locale.strings().schedule_exit,
+                        &[("name", &outcome.name), ("code", &code.to_string())],
+                    ));
+                    for chunk in chunks {
+                        self.push_line(chunk.kind, chunk.text);
+                    }
+                }
+                Err(err) => self.push_error(i18n::fill(
+                    self.runtime.locale.strings().schedule_failed,
+                    &[("name", &outcome.name), ("error", &err)],
+                )),
+            }
+        }
+
+        let due: Vec<usize> = (0..self.schedules.len())
+            .filter(|&slot| {
+                let job = &self.schedules[slot];
+                !job.running && now >= job.next_run
+            })
+            .collect();
+        for slot in due {
+            let job = &mut self.schedules[slot];
+            job.next_run = now + job.interval;
+            let key = job.key.clone();
+            let name = job.name.clone();
+            let runs = match self.scheduled_runs(self.schedules[slot].request.clone()) {
+                Ok(runs) => runs,
+                Err(err) => {
+                    self.push_error(i18n::fill(
+                        self.runtime.locale.strings().schedule_failed,
+                        &[("name", &name), ("error", &format!("{err:#}"))],
+                    ));
+                    continue;
+                }
+            };
+            self.schedules[slot].running = true;
+            let tx = self.schedule_tx.clone();
+            thread::spawn(move || {
+                let result = run_scheduled(&runs).map_err(|err| format!("{err:#}"));
+                let _ = tx.send(ScheduledOutcome { key, name, result });
+            });
+        }
+        changed
+    }
+
+    /// Starts a finished run's hook in the background, so a slow hook does not hold up the UI.
+    fn start_hook(&mut self, hook: String, request: &RunRequest) {
+        self.push_command(hook.clone());
+        self.hooks_running += 1;
+        let working_dir = request.working_dir.clone();
+        let env = request.env.clone();
+        let color = request.color;
+        let tx = self.hook_tx.clone();
+        thread::spawn(move || {
+            let result =
+                run_shell_command_captured(&hook, working_dir.as_deref(), &env, color, None)
+                    .map_err(|err| format!("{err:#}"));
+            let _ = tx.send(HookOutcome { result });
+        });
+    }
+
+    /// Posts the output of finished hooks; returns whether anything was posted.
+    fn poll_hooks(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(outcome) = self.hook_rx.try_recv() {
+            changed = true;
+            self.hooks_running = self.hooks_running.saturating_sub(1);
+            match outcome.result {
+                Ok((code, chunks)) => {
+                    for chunk in chunks {
+                        self.push_line(chunk.kind, chunk.text);
+                    }
+                    if code != 0 {
+                        self.push_error(i18n::fill(
+                            self.runtime.locale.strings().hook_exit_code,
+                            &[("code", &code.to_string())],
+                        ));
+                    }
+                }
+                Err(err) => self.push_error(i18n::fill(
+                    self.runtime.locale.strings().hook_failed,
+                    &[("error", &err)],
+                )),
+            }
+        }
+        changed
+    }
+
+    /// A scheduled request and the runs it chains, in order, with their secrets fetched and the
+    /// current session env applied.
+    fn scheduled_runs(&mut self, mut request: RunRequest) -> Result<Vec<RunRequest>> {
+        let then = std::mem::take(&mut request.then);
+        let session_env = self.session_env_pairs();
+        std::iter::once(request)
+            .chain(then)
+            .map(|mut run| {
+                if run.host.is_none() {
+                    run.env
+                        .retain(|(key, _)| !self.session_env.contains_key(key));
+                    run.env.extend(session_env.iter().cloned());
+                }
+                self.with_secrets(run)
+            })
+            .collect()
+    }
+
+    /// The index of the job's own pane, opened on first use while fewer than `[ui] job_panes`
+    /// exist.
+    fn job_pane_for(&mut self, source: PaneSource, name: &str) -> Option<usize> {
+        if let Some(index) = self.job_panes.iter().position(|pane| pane.source == source) {
+            return Some(index);
+        }
+        if self.job_panes.len() >= self.runtime.job_panes {
+            return None;
+        }
+        self.job_panes.push(JobPane {
+            source,
+            name: name.to_string(),
+            lines: Vec::new(),
+            scroll: 0,
+            last_exit: None,
+        });
+        Some(self.job_panes.len() - 1)
+    }
+
+    /// Closes the panes `keep` rejects, moving focus back to the session when its pane closes.
+    fn close_job_panes(&mut self, keep: impl Fn(&PaneSource) -> bool) {
+        let mut index = 0;
+        while index < self.job_panes.len() {
+            if keep(&self.job_panes[index].source) {
+                index += 1;
+                continue;
+            }
+            self.job_panes.remove(index);
+            self.focused_job = match self.focused_job {
+                Some(focused) if focused == index => None,
+                Some(focused) if focused > index => Some(focused - 1),
+                focused => focused,
+            };
+        }
+    }
+
+    /// An output line for a job pane, masked and cut down like the session's.
+    fn pane_line(&self, kind: ChatLineKind, text: String) -> ChatLine {
+        let text = self.mask_secrets(text);
+        let (text, full_text) = if matches!(kind, ChatLineKind::Stdout | ChatLineKind::Stderr) {
+            truncate_output_line(text, self.output.max_line_bytes)
+        } else {
+            (text, None)
+        };
+        ChatLine {
+            kind,
+            text,
+            repeat: 1,
+            full_text,
+            json: None,
+        }
+    }
+
+    /// Moves Session scrolling to the next job pane, then back to the main stream.
+    fn cycle_job_focus(&mut self) {
+        self.active_pane = ActivePane::Session;
+        self.focused_job = match self.focused_job {
+            None if !self.job_panes.is_empty() => Some(0),
+            Some(index) if index + 1 < self.job_panes.len() => Some(index + 1),
+            _ => None,
+        };
+    }
+
+    fn start_background_job(&mut self, request: RunRequest) {
+        let spawned = prepare_working_dir(&request).and_then(|_| spawn_background_job(&request));
+        let (child, output) = match spawned {
+            Ok(spawned) => spawned,
+            Err(err) => {
+                self.push_error(i18n::fill(
+                    self.runtime.locale.strings().job_start_failed,
+                    &[
+                        ("name", &request.display_name),
+                        ("error", &format!("{err:#}")),
+                    ],
+                ));
+                return;
+            }
+        };
+        let id = self.next_job_id;
+        self.next_job_id += 1;
+        self.push_command(request.command_line.clone());
+        self.push_info(i18n::fill(
+            self.runtime.locale.strings().job_started,
+            &[("id", &id.to_string()), ("name", &request.display_name)],
+        ));
+        self.emit_started(&request);
+        self.record_usage(&request.usage_key);
+        self.job_pane_for(PaneSource::Job(id), &request.display_name);
+        self.jobs.push(BackgroundJob {
+            id,
+            request,
+            child,
+            output,
+            lines: Vec::new(),
+            started: Instant::now(),
+            finished: None,
+            timed_out_at: None,
+            status: JobStatus::Running,
+        });
+    }
+
+    /// Opens `request` in its tmux window, pane, or terminal tab and returns to the list right
+    /// away; the run's output and exit code stay there.
+    fn launch_in_target(&mut self, request: RunRequest) {
+        let launched = prepare_working_dir(&request).and_then(|_| {
+            let mut process = target_process(
+                &request,
+                &self.runtime.cwd,
+                self.output.terminal.as_deref(),
+                std::env::var_os("TMUX").is_some(),
+            )?;
+            if request.target == ExecTarget::Terminal {
+                // Some launchers stay in the foreground until their window is closed, so they
+                // are reaped off the UI thread.
+                let mut launcher = process.stderr(Stdio::null()).spawn()?;
+                thread::spawn(move || launcher.wait());
+                return Ok(());
+            }
+            let output = process.stderr(Stdio::piped()).output()?;
+            if !output.status.success() {
+                bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+            }
+            Ok(())
+        });
+        if let Err(err) = launched {
+            self.push_error(i18n::fill(
+                self.runtime.locale.strings().start_failed,
+                &[
+                    ("name", &request.display_name),
+                    ("error", &format!("{err:#}")),
+                ],
+            ));
+            return;
+        }
+        let place = match request.target {
+            ExecTarget::TmuxWindow => "a new tmux window",
+            ExecTarget::TmuxPane => "a new tmux pane",
+            _ => "a new terminal",
+        };
+        self.push_command(request.command_line.clone());
+        self.push_info(format!("{} started in {place}", request.display_name));
+        let skipped = if request.hooks.on_success.is_some() || request.hooks.on_failure.is_some() {
+            "its hooks and history entry are"
+        } else {
+            "its history entry is"
+        };
+        self.push_info(format!(
+            "fzc does not see how runs in {place} end, so {skipped} skipped"
+        ));
+        self.emit_started(&request);
+        self.record_usage(&request.usage_key);
+    }
+
+    /// Collects background job output and reports jobs that ended; returns whether anything
+    /// changed.
+    fn poll_jobs(&mut self) -> bool {
+        let mut changed = false;
+        let mut streamed = Vec::new();
+        let mut paned = Vec::new();
+        let mut ended = Vec::new();
+        for (index, job) in self.jobs.iter_mut().enumerate() {
+            for mut chunk in job.output.try_iter() {
+                changed = true;
+                chunk.text = self
+                    .runtime
+                    .redactor
+                    .redact(mask_secret_values(&self.secret_values, chunk.text));
+                if self.followed_job == Some(job.id) {
+                    streamed.push(StreamChunk {
+                        kind: chunk.kind,
+                        text: chunk.text.clone(),
+                        partial: false,
+                    });
+                }
+                paned.push((job.id, chunk.kind, chunk.text.clone()));
+                job.lines.push(ChatLine {
+                    kind: chunk.kind,
+                    text: chunk.text,
+                    repeat: 1,
+                    full_text: None,
+                    json: None,
+                });
+            }
+            let overflow = job.lines.len().saturating_sub(MAX_JOB_PANE_LINES);
+            job.lines.drain(..overflow);
+
+            if job.is_running()
+                && let Ok(Some(status)) = job.child.try_wait()
+            {
+                job.status = match status.code() {
+                    // Like coreutils `timeout`.
+                    _ if job.timed_out_at.is_some() => JobStatus::Exited(124),
+                    Some(code) => JobStatus::Exited(code),
+                    None => JobStatus::Killed,
+                };
+                job.finished = Some(Instant::now());
+                ended.push(index);
+            } else if let Some(limit) = job.request.timeout {
+                // Past its timeout a job is stopped like a session command.
+                match job.timed_out_at {
+                    None if job.status == JobStatus::Running && job.started.elapsed() >= limit => {
+                        stop_process_group(&mut job.child, Stop::Terminate);
+                        job.status = JobStatus::Stopping;
+                        job.timed_out_at = Some(Instant::now());
+                    }
+                    Some(at) if at.elapsed() >= FORCE_KILL_AFTER => {
+                        stop_process_group(&mut job.child, Stop::Kill);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for chunk in streamed {
+            self.push_output(chunk);
+        }
+        for (id, kind, text) in paned {
+            let max_line_bytes = self.output.max_line_bytes;
+            if let Some(pane) = self
+                .job_panes
+                .iter_mut()
+                .find(|pane| pane.source == PaneSource::Job(id))
+            {
+                let (text, full_text) = truncate_output_line(text, max_line_bytes);
+                pane.extend([ChatLine {
+                    kind,
+                    text,
+                    repeat: 1,
+                    full_text,
+                    json: None,
+                }]);
+            }
+        }
+        for index in ended {
+            changed = true;
+            let id = self.jobs[index].id;
+            self.close_job_panes(|source| *source != PaneSource::Job(id));
+            let job = &self.jobs[index];
+            let elapsed = job.started.elapsed();
+            let timed_out = job.timed_out_at.is_some();
+            let (message, exit_code) = match job.status {
+                JobStatus::Exited(code) if timed_out => (
+                    format!(
+                        "timed out after {}",
+                        self.runtime
+                            .locale
+                            .format_age(job.request.timeout.unwrap_or_default().as_secs())
+                    ),
+                    Some(code),
+                ),
+                JobStatus::Exited(code) => (format!("exit code: {code}"), Some(code)),
+                _ => ("stopped".to_string(), None),
+            };
+            let message = format!("[job {}] {} {message}", job.id, job.request.display_name);
+            let request = job.request.clone();
+            if exit_code == Some(0) {
+                self.push_info(message);
+            } else {
+                self.push_error(message);
+            }
+            self.emit_finished(&request, exit_code, elapsed);
+            self.record_history(&request, exit_code, timed_out);
+        }
+        changed
+    }
+
+    /// Asks a running job to stop; a job already asked is killed.
+    fn kill_job(&mut self, id: usize) {
+        let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) else {
+            return;
+        };
+        match job.status {
+            JobStatus::Running => {
+                stop_process_group(&mut job.child, Stop::Terminate);
+                job.status = JobStatus::Stopping;
+            }
+            JobStatus::Stopping => stop_process_group(&mut job.child, Stop::Kill),
+            JobStatus::Exited(_) | JobStatus::Killed => {}
+        }
+    }
+
+    fn open_jobs(&mut self) -> UiAction {
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+
+        if self.jobs.is_empty() {
+            self.push_info(self.runtime.locale.strings().no_jobs);
+            return UiAction::None;
+        }
+        self.mode = Mode::Jobs(JobsState {
+            selected: self.jobs.len() - 1,
+        });
+        UiAction::None
+    }
+
+    fn on_jobs_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut state = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::Jobs(state) => state,
+            _ => return UiAction::None,
+        };
+        let count = self.jobs.len();
+        let Some(job) = self.jobs.get(state.selected) else {
+            return UiAction::None;
+        };
+        let id = job.id;
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return UiAction::None,
+            KeyCode::Up => state.selected = (state.selected + count - 1) % count,
+            KeyCode::Down => state.selected = (state.selected + 1) % count,
+            KeyCode::Char('f') => return UiAction::ForegroundJob(id),
+            KeyCode::Char('x') | KeyCode::Delete => self.kill_job(id),
+            KeyCode::Enter | KeyCode::Char('o') => {
+                let text = job
+                    .lines
+                    .iter()
+                    .map(|line| plain_text(&line.text))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.mode = Mode::Pager(PagerState {
+                    title: format!("Job {id}: {}", job.request.display_name),
+                    text,
+                    scroll: 0,
+                });
+                return UiAction::None;
+            }
+            _ => {}
+        }
+        self.mode = Mode::Jobs(state);
+        UiAction::None
+    }
+
+    /// What `/cache` lists: the preview outputs, then every cache file, newest first.
+    fn cache_items(&self) -> Vec<CacheItem> {
+        let previews = (!self.preview_cache.is_empty())
+            .then_some(CacheItem::Previews(self.preview_cache.len()));
+        previews
+            .into_iter()
+            .chain(provider::cache_entries().into_iter().map(CacheItem::File))
+            .collect()
+    }
+
+    /// Provider results load on start, `/reload`, and F5; only the slow Gradle task discovery,
+    /// completion names, and the short-lived preview output are cached.
+    fn open_caches(&mut self) -> UiAction {
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+
+        let items = self.cache_items();
+        if items.is_empty() {
+            self.push_info(self.runtime.locale.strings().nothing_cached);
+            return UiAction::None;
+        }
+        self.mode = Mode::Caches(CachesState { items, selected: 0 });
+        UiAction::None
+    }
+
+    fn on_caches_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut state = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::Caches(state) => state,
+            _ => return UiAction::None,
+        };
+        let count = state.items.len();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return UiAction::None,
+            KeyCode::Up => state.selected = (state.selected + count - 1) % count,
+            KeyCode::Down => state.selected = (state.selected + 1) % count,
+            KeyCode::Char('a') => {
+                let items = std::mem::take(&mut state.items);
+                self.clear_cache_items(&items);
+                return UiAction::None;
+            }
+            KeyCode::Char('x') | KeyCode::Delete | KeyCode::Enter => {
+                let item = state.items[state.selected].clone();
+                self.clear_cache_items(&[item]);
+                state.items = self.cache_items();
+                if state.items.is_empty() {
+                    return UiAction::None;
+                }
+                state.selected = state.selected.min(state.items.len() - 1);
+            }
+            _ => {}
+        }
+        self.mode = Mode::Caches(state);
+        UiAction::None
+    }
+
+    /// Drops cached previews and deletes cache files; shared by clearing one `/cache` row and all
+    /// of them.
+    fn clear_cache_items(&mut self, items: &[CacheItem]) {
+        let mut files = 0;
+        for item in items {
+            match item {
+                CacheItem::Previews(_) => {
+                    let previews = self.preview_cache.len();
+                    self.preview_cache.clear();
+                    self.push_info(i18n::fill(
+                        self.runtime.locale.strings().previews_cleared,
+                        &[
+                            ("count", &previews.to_string()),
+                            ("seconds", &PREVIEW_TTL.as_secs().to_string()),
+                        ],
+                    ));
+                }
+                CacheItem::File(entry) => {
+                    if provider::remove_cache_entry(entry) {
+                        files += 1;
+                    } else {
+                        self.push_error(i18n::fill(
+                            self.runtime.locale.strings().cache_remove_failed,
+                            &[("path", &entry.path.display().to_string())],
+                        ));
+                    }
+                }
+            }
+        }
+        if files > 0 {
+            self.push_info(i18n::fill(
+                self.runtime.locale.strings().cache_files_removed,
+                &[("count", &files.to_string())],
+            ));
+        }
+    }
+
+    /// Points at `/doctor` when the catalog has problems, without listing them all at startup.
+    fn note_catalog_problems(&mut self) {
+        let count = catalog_problems(&self.commands).len();
+        if count > 0 {
+            self.push_error(format!(
+                "{count} catalog problem(s), e.g. a missing working_dir — run /doctor for details"
+            ));
+        }
+    }
+
+    fn run_env_command(&mut self, command: EnvCommand) {
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+
+        match command {
+            EnvCommand::List if self.session_env.is_empty() => {
+                self.push_info(self.runtime.locale.strings().no_session_vars);
+            }
+            EnvCommand::List => {
+                self.push_info(self.runtime.locale.strings().session_vars);
+                let lines: Vec<String> = self
+                    .session_env
+                    .iter()
+                    .map(|(key, value)| format!("  {key}={value}"))
+                    .collect();
+                for line in lines {
+                    self.push_info(line);
+                }
+            }
+            EnvCommand::Set(key, value) => {
+                self.push_info(i18n::fill(
+                    self.runtime.locale.strings().session_var_set,
+                    &[("key", &key), ("value", &value)],
+                ));
+                self.session_env.insert(key, value);
+                // Previews ran with the old variables.
+                self.preview_cache.clear();
+            }
+            EnvCommand::Unset(key) => {
+                if self.session_env.remove(&key).is_some() {
+                    self.push_info(i18n::fill(
+                        self.runtime.locale.strings().session_var_unset,
+                        &[("key", &key)],
+                    ));
+                    self.preview_cache.clear();
+                } else {
+                    self.push_info(i18n::fill(
+                        self.runtime.locale.strings().no_session_var,
+                        &[("key", &key)],
+                    ));
+                }
+            }
+        }
+    }
+
+    fn session_env_pairs(&self) -> Vec<(String, String)> {
+        self.session_env
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    fn run_doctor(&mut self) {
+        self.query.clear();
+        self.query_cursor = 0;
+        self.refresh_filtered();
+
+        let problems = catalog_problems(&self.commands);
+        if problems.is_empty() {
+            self.push_info(i18n::fill(
+                self.runtime.locale.strings().doctor_ok,
+                &[("count", &self.commands.len().to_string())],
+            ));
+            return;
+        }
+        self.push_info(i18n::fill(
+            self.runtime.locale.strings().doctor_problems,
+            &[("count", &problems.len().to_string())],
+        ));
+        for problem in problems {
+            self.push_error(problem);
+        }
+    }
+
+    fn emit(&self, event: RunEvent<'_>) {
+        if let Some(events) = &self.events {
+            events.emit(&event);
+        }
+    }
+
+    fn emit_started(&self, request: &RunRequest) {
+        self.emit(RunEvent::Started {
+            name: &request.display_name,
+            command_line: &request.command_line,
+            working_dir: request.working_dir.as_deref(),
+        });
+    }
+
+    fn emit_finished(&self, request: &RunRequest, exit_code: Option<i32>, duration: Duration) {
+        self.emit(RunEvent::Finished {
+            name: &request.display_name,
+            exit_code,
+            duration,
+        });
+    }
+
+    /// A line the running command printed: shown in the session and sent to event clients.
+    fn push_output(&mut self, mut chunk: StreamChunk) {
+        chunk.text = self.mask_secrets(chunk.text);
+        if std::mem::take(&mut self.live_line) {
+            self.chat.pop();
+        }
+        if chunk.partial {
+            let (text, full_text) = truncate_output_line(chunk.text, self.output.max_line_bytes);
+            self.chat.push(ChatLine {
+                kind: chunk.kind,
+                text,
+                repeat: 1,
+                full_text,
+                json: None,
+            });
+            self.live_line = true;
+            if self.active_pane == ActivePane::Commands {
+                self.session_scroll = 0;
+            }
+            return;
+        }
+        self.emit(RunEvent::Output {
+            stderr: chunk.kind == ChatLineKind::Stderr,
+            line: &chunk.text,
+        });
+        self.push_line(chunk.kind, chunk.text);
+    }
+
+    fn push_info<S: Into<String>>(&mut self, text: S) {
+        self.push_line(ChatLineKind::Info, text.into());
+    }
+
+    fn push_command<S: Into<String>>(&mut self, text: S) {
+        self.push_line(ChatLineKind::Command, text.into());
+    }
+
+    fn push_error<S: Into<String>>(&mut self, text: S) {
+        self.push_line(ChatLineKind::Stderr, text.into());
+    }
+
+    fn push_line(&mut self, kind: ChatLineKind, text: String) {
+        let text = self.mask_secrets(text);
+        self.live_line = false;
+        let is_output = matches!(kind, ChatLineKind::Stdout | ChatLineKind::Stderr);
+        if let Some(last) = self.chat.last_mut()
+            && is_output
+            && last.kind == kind
+            && last.full_text.as_deref().unwrap_or(&last.text) == text
+        {
+            last.repeat = last.repeat.saturating_add(1);
+            if self.active_pane == ActivePane::Commands {
+                self.session_scroll = 0;
+            }
+            return;
+        }
+
+        let json = if is_output {
+            pretty_json_lines(&text)
+        } else {
+            None
+        };
+        let (text, full_text) = if is_output {
+            truncate_output_line(text, self.output.max_line_bytes)
+        } else {
+            (text, None)
+        };
+        self.chat.push(ChatLine {
+            kind,
+            text,
+            repeat: 1,
+            full_text,
+            json,
+        });
+        if self.active_pane == ActivePane::Commands {
+            self.session_scroll = 0;
+        }
+        if self.chat.len() > MAX_CHAT_LINES {
+            let overflow = self.chat.len() - MAX_CHAT_LINES;
+            self.chat.drain(0..overflow);
+            self.session_scroll = self.session_scroll.saturating_sub(overflow);
+        }
+    }
+
+    fn start_loading(&mut self, label: &str) {
+        self.is_loading = true;
+        self.loading_label = Some(label.to_string());
+        self.spinner_index = 0;
+    }
+
+    fn stop_loading(&mut self) {
+        self.is_loading = false;
+        self.loading_label = None;
+    }
+
+    fn tick_loading(&mut self) {
+        if self.is_loading {
+            self.spinner_index = (self.spinner_index + 1) % SPINNER_FRAMES.len();
+        }
+    }
+
+    fn spinner_frame(&self) -> &'static str {
+        SPINNER_FRAMES[self.spinner_index % SPINNER_FRAMES.len()]
+    }
+
+    /// Fetches the request's secrets into its environment, right before it runs.
+    fn with_secrets(&mut self, mut request: RunRequest) -> Result<RunRequest> {
+        for name in &request.secrets {
+            let value = secrets::fetch(&self.runtime.secrets, name)
+                .with_context(|| format!("secret '{name}' for '{}'", request.display_name))?;
+            // Short values would mask ordinary output.
+            if value.len() >= MIN_MASKED_SECRET && !self.secret_values.contains(&value) {
+                self.secret_values.push(value.clone());
+            }
+            request.env.push((secrets::env_var(name), value));
+        }
+        Ok(request)
+    }
+
+    /// Masks fetched secret values and `[privacy] redact` matches.
+    fn mask_secrets(&self, text: String) -> String {
+        self.runtime
+            .redactor
+            .redact(mask_secret_values(&self.secret_values, text))
+    }
+
+    fn record_usage(&mut self, key: &str) {
+        let now = history::now();
+        let half_life_days = self.ranking.decay_half_life_days;
+        let Some(path) = self.usage_path.clone() else {
+            self.usage
+                .entry(key.to_string())
+                .or_default()
+                .record_use(now, half_life_days);
+            return;
+        };
+        // Other fzc instances write the store too, so this use is added to what is on disk now,
+        // under a lock, rather than writing back the counts loaded at startup.
+        let _lock = UsageLock::acquire(&path);
+        let mut usage = if path.exists() {
+            read_usage_store(&path, now)
+        } else {
+            std::mem::take(&mut self.usage)
+        };
+        usage
+            .entry(key.to_string())
+            .or_default()
+            .record_use(now, half_life_days);
+        let _ = persist_usage_store(&usage, Some(&path));
+        self.usage = usage;
+    }
+
+    /// Checked per entry on every filter and draw, so it compares against the (few) pinned keys
+    /// instead of building the command's usage key.
+    fn is_pinned(&self, command: &CommandEntry) -> bool {
+        self.pinned.iter().any(|key| is_usage_key_of(key, command))
+    }
+
+    /// Pins or unpins the selected command and keeps it selected at its new position.
+    fn toggle_pin(&mut self) {
+        let Some(SearchItem::Command(index)) = self.filtered.get(self.selected).copied() else {
+            return;
+        };
+        let command = &self.commands[index];
+        let key = command_usage_key(command);
+        let message = if self.pinned.remove(&key) {
+            format!("Unpinned {}", command.name)
+        } else {
+            let message = format!("Pinned {}", command.name);
+            self.pinned.insert(key);
+            message
+        };
+        if let Some(path) = &self.pins_path
+            && let Err(err) = favorites::save(path, &self.pinned)
+        {
+            self.push_error(format!("{err:#}"));
+        }
+        self.push_info(message);
+        self.refresh_filtered();
+        if let Some(position) = self
+            .filtered
+            .iter()
+            .position(|item| matches!(item, SearchItem::Command(found) if *found == index))
+        {
+            self.selected = position;
+        }
+    }
+
+    /// Seconds since `provider` last loaded, once that is past `STALE_PROVIDER_AFTER`.
+    fn stale_provider_age(&self, provider: &str) -> Option<u64> {
+        let loaded_at = self.provider_loaded_at.get(provider)?;
+        let age = history::now().saturating_sub(*loaded_at);
+        (age >= STALE_PROVIDER_AFTER.as_secs()).then_some(age)
+    }
+
+    /// F5: reloads the provider of the selected command, bypassing its cache.
+    fn refresh_selected_provider(&mut self) {
+        let Some(index) = self.current_command_index() else {
+            return;
+        };
+        let name = command_provider_name(&self.commands[index]).to_string();
+        if self.is_unexpanded(&name) {
+            self.prepare_selected_command(true);
+        } else if !self.provider_loaded_at.contains_key(&name) {
+            self.push_info(format!(
+                "{name} commands are not provider results; /reload re-reads them"
+            ));
+        } else if !self.pending_refresh.contains(&name) && !self.loading_providers.contains(&name) {
+            provider::invalidate_provider_cache(&name, &self.runtime.cwd);
+            self.pending_refresh.push(name);
+        }
+    }
+
+    /// Queues a reload of every provider whose refresh triggers match a successful run.
+    fn queue_provider_refresh(&mut self, command_line: &str) {
+        for name in provider::providers_to_refresh(&self.runtime.providers, command_line) {
+            if !self.pending_refresh.contains(&name) {
+                self.pending_refresh.push(name);
+            }
+        }
+    }
+
+    fn toggle_staging(&mut self) {
+        self.staging = !self.staging;
+        if self.staging {
+            self.push_info(self.runtime.locale.strings().staging_on);
+        } else {
+            self.push_info(i18n::fill(
+                self.runtime.locale.strings().staging_off,
+                &[("count", &self.queue.len().to_string())],
+            ));
+        }
+    }
+
+    fn stage_request(&mut self, request: RunRequest) {
+        self.push_info(i18n::fill(
+            self.runtime.locale.strings().queued,
+            &[
+                ("name", &request.display_name),
+                ("count", &(self.queue.len() + 1).to_string()),
+            ],
+        ));
+        self.edit_queue(|queue| queue.push(request));
+    }
+
+    /// Applies `edit` after saving the current queue for undo.
+    fn edit_queue(&mut self, edit: impl FnOnce(&mut Vec<RunRequest>)) {
+        self.queue_undo.push(self.queue.clone());
+        edit(&mut self.queue);
+    }
+
+    fn open_queue_review(&mut self) {
+        if self.queue.is_empty() && self.queue_undo.is_empty() {
+            self.push_info(self.runtime.locale.strings().queue_empty);
+            return;
+        }
+        self.mode = Mode::QueueReview(QueueReviewState {
+            selected: 0,
+            confirming: false,
+        });
+    }
+
+    fn on_queue_review_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut review = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::QueueReview(review) => review,
+            _ => return UiAction::None,
+        };
+        if review.confirming {
+            if key.code != KeyCode::Enter || self.queue.is_empty() {
+                review.confirming = false;
+                self.mode = Mode::QueueReview(review);
+                return UiAction::None;
+            }
+            self.staging = false;
+            self.queue_undo.clear();
+            return UiAction::RunQueue(std::mem::take(&mut self.queue));
+        }
+
+        let count = self.queue.len();
+        let selected = review.selected;
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return UiAction::None,
+            KeyCode::Enter if count > 0 => review.confirming = true,
+            KeyCode::Up if shift && selected > 0 => {
+                self.edit_queue(|queue| queue.swap(selected, selected - 1));
+                review.selected -= 1;
+            }
+            KeyCode::Char('K') if selected > 0 => {
+                self.edit_queue(|queue| queue.swap(selected, selected - 1));
+                review.selected -= 1;
+            }
+            KeyCode::Down if shift && selected + 1 < count => {
+                self.edit_queue(|queue| queue.swap(selected, selected + 1));
+                review.selected += 1;
+            }
+            KeyCode::Char('J') if selected + 1 < count => {
+                self.edit_queue(|queue| queue.swap(selected, selected + 1));
+                review.selected += 1;
+            }
+            KeyCode::Up if count > 0 => review.selected = (selected + count - 1) % count,
+            KeyCode::Down if count > 0 => review.selected = (selected + 1) % count,
+            KeyCode::Delete | KeyCode::Backspace | KeyCode::Char('d') if count > 0 => {
+                self.edit_queue(|queue| {
+                    queue.remove(selected);
+                });
+            }
+            KeyCode::Char('u') => {
+                if let Some(previous) = self.queue_undo.pop() {
+                    self.queue = previous;
+                }
+            }
+            _ => {}
+        }
+        review.selected = review.selected.min(self.queue.len().saturating_sub(1));
+        self.mode = Mode::QueueReview(review);
+        UiAction::None
+    }
+
+    fn record_history(&self, request: &RunRequest, exit_code: Option<i32>, timed_out: bool) {
+        let Some(path) = &self.history_path else {
+            return;
+        };
+        let entry = HistoryEntry {
+            command: request.usage_key.clone(),
+            name: request.display_name.clone(),
+            command_line: self.mask_secrets(request.command_line.clone()),
+            timestamp: history::now(),
+            cwd: request
+                .working_dir
+                .clone()
+                .unwrap_or_else(|| self.runtime.cwd.clone()),
+            exit_code,
+            environment: request.environment.clone(),
+            params: request
+                .params
+                .iter()
+                .map(|(name, value)| {
+                    // Masked as `name=value` so key-based patterns like `password=` see the name.
+                    let masked = self.mask_secrets(format!("{name}={value}"));
+                    let value = masked
+                        .strip_prefix(&format!("{name}="))
+                        .unwrap_or(MASK)
+                        .to_string();
+                    (name.clone(), value)
+                })
+                .collect(),
+            timed_out,
+        };
+        let _ = history::append(path, entry);
+    }
+
+    fn usage_boost_for_command(&self, command: &CommandEntry) -> i64 {
+        if !self.ranking.usage_enabled {
+            return 0;
+        }
+
+        let usage = self
+            .usage
+            .get(&command_usage_key(command))
+            .map(|record| record.frecency(history::now(), self.ranking.decay_half_life_days))
+            .unwrap_or_default();
+        usage_boost(usage, &self.ranking)
+    }
+
+    fn is_internal_query(&self) -> bool {
+        self.query.trim_start().starts_with('/')
+    }
+
+    fn current_internal_index(&self) -> Option<usize> {
+        match self.filtered.get(self.selected).copied() {
+            Some(SearchItem::Internal(index)) => Some(index),
+            _ => None,
+        }
+    }
+
+    fn refresh_filtered(&mut self) {
+        if self.is_internal_query() {
+            let trimmed = self.query.trim_start();
+            let internal_query = trimmed.trim_start_matches('/').trim();
+            let normalized = internal_query.to_lowercase();
+            let mut scored = Vec::new();
+
+            for (index, command) in self.internal_commands.iter().enumerate() {
+                let haystack = format!("{} {}", command.name, command.description).to_lowercase();
+                let fuzzy = if normalized.is_empty() {
+                    1
+                } else {
+                    self.matcher
+                        .fuzzy_match(&haystack, &normalized)
+                        .unwrap_or_default()
+                };
+
+                if normalized.is_empty() || fuzzy > 0 || haystack.contains(&normalized) {
+                    let contains_bonus = if !normalized.is_empty() && haystack.contains(&normalized)
+                    {
+                        10_000
+                    } else {
+                        0
+                    };
+                    scored.push((
+                        index,
+                        fuzzy.saturating_mul(10) + contains_bonus,
+                        command.name,
+                    ));
+                }
+            }
+
+            scored.sort_by(|a, b| match b.1.cmp(&a.1) {
+                Ordering::Equal => a.2.cmp(b.2),
+                other => other,
+            });
+            self.filtered = scored
+                .into_iter()
+                .map(|(index, _, _)| SearchItem::Internal(index))
+                .collect();
+            self.selected = 0;
+            return;
+        }
+
+        let (provider_filter, query, unknown_alias) = parse_query_provider_filter(
+            self.query.as_str(),
+            &self.provider_aliases,
+            &self.provider_names_without_alias,
+        );
+        if unknown_alias {
+            self.filtered.clear();
+            self.selected = 0;
+            return;
+        }
+        if let Some(name) = self.lazy_provider_to_expand(provider_filter) {
+            self.pending_refresh.push(name);
+        }
+
+        if query.is_empty() {
+            let mut ordered: Vec<(usize, i64, &str)> = self
+                .commands
+                .iter()
+                .zip(&self.search_index)
+                .enumerate()
+                .filter(|(_, (command, _))| {
+                    provider_filter.is_none_or(|provider| {
+                        command_provider_name(command).eq_ignore_ascii_case(provider)
+                    }) && !self.hidden_by_lazy(command, provider_filter)
+                })
+                .map(|(index, (command, entry))| {
+                    (
+                        index,
+                        self.usage_boost_for_command(command),
+                        entry.name_lower.as_str(),
+                    )
+                })
+                .collect();
+            ordered.sort_by(|a, b| match b.1.cmp(&a.1) {
+                Ordering::Equal => a.2.cmp(b.2),
+                other => other,
+            });
+            if !self.pinned.is_empty() {
+                ordered.sort_by_cached_key(|entry| !self.is_pinned(&self.commands[entry.0]));
+            }
+            self.filtered = ordered
+                .into_iter()
+                .map(|entry| SearchItem::Command(entry.0))
+                .collect();
+            self.selected = 0;
+            return;
+        }
+
+        let query_terms = tokenize_for_match(query);
+        let normalized_query = query.to_lowercase();
+        let mut scored = std::mem::take(&mut self.score_buffer);
+        scored.clear();
+
+        for (index, (command, entry)) in self.commands.iter().zip(&self.search_index).enumerate() {
+            if provider_filter.is_some_and(|provider| {
+                !command_provider_name(command).eq_ignore_ascii_case(provider)
+            }) || self.hidden_by_lazy(command, provider_filter)
+            {
+                continue;
+            }
+
+            if let Some(score) =
+                score_command_match(&self.matcher, &normalized_query, &query_terms, entry)
+            {
+                let usage_bonus = self.usage_boost_for_command(command);
+                scored.push((index, score.total.saturating_add(usage_bonus), score.fuzzy));
+            }
+        }
+
+        let index = &self.search_index;
+        scored.sort_by(|a, b| match b.1.cmp(&a.1) {
+            Ordering::Equal => match b.2.cmp(&a.2) {
+                Ordering::Equal => index[a.0].name_lower.cmp(&index[b.0].name_lower),
+                other => other,
+            },
+            other => other,
+        });
+        if !self.pinned.is_empty() {
+            scored.sort_by_cached_key(|entry| !self.is_pinned(&self.commands[entry.0]));
+        }
+
+        self.filtered.clear();
+        self.filtered
+            .extend(scored.iter().map(|entry| SearchItem::Command(entry.0)));
+        self.score_buffer = scored;
+        self.selected = 0;
+    }
+
+    /// Rebuilds the lowercase search index; call whenever `commands` changes.
+    fn rebuild_search_index(&mut self) {
+        self.search_index = self.commands.iter().map(SearchIndexEntry::new).collect();
+    }
+
+    fn move_selection(&mut self, direction: isize) {
+        if self.filtered.is_empty() {
+            self.selected = 0;
+            return;
+        }
+
+        let len = self.filtered.len() as isize;
+        let next = (self.selected as isize + direction).rem_euclid(len);
+        self.selected = next as usize;
+    }
+
+    fn move_selection_by(&mut self, step: isize) {
+        self.move_selection(step);
+    }
+
+    /// Session rows in display order; folded repeats and pretty JSON expand to several rows.
+    fn session_rows(&self) -> Vec<SessionRow> {
+        let mut rows = Vec::with_capacity(self.chat.len());
+        for (index, line) in self.chat.iter().enumerate() {
+            if !self.output_line_visible(line) {
+                continue;
+            }
+            let count = if self.expand_repeats { line.repeat } else { 1 };
+            for _ in 0..count {
+                match &line.json {
+                    Some(json) if self.output.pretty_json => {
+                        rows.extend((0..json.len()).map(|part| SessionRow {
+                            line: index,
+                            json_line: Some(part),
+                        }));
+                    }
+                    _ => rows.push(SessionRow {
+                        line: index,
+                        json_line: None,
+                    }),
+                }
+            }
+        }
+        rows
+    }
+
+    fn output_line_visible(&self, line: &ChatLine) -> bool {
+        let Some(filter) = &self.output_filter else {
+            return true;
+        };
+        if !matches!(line.kind, ChatLineKind::Stdout | ChatLineKind::Stderr) {
+            return true;
+        }
+        filter.matches(line.full_text.as_deref().unwrap_or(&line.text))
+    }
+
+    /// Key handling while a command streams; the filter applies live as it is typed.
+    fn on_running_key(&mut self, key: KeyEvent) -> bool {
+        let Some(input) = self.filter_input.as_mut() else {
+            if key.code == KeyCode::Char('/') {
+                let current = self
+                    .output_filter
+                    .as_ref()
+                    .map(|filter| filter.pattern.clone())
+                    .unwrap_or_default();
+                self.filter_input = Some(current);
+                return false;
+            }
+            return key.code == KeyCode::Esc;
+        };
+
+        match key.code {
+            KeyCode::Esc => {
+                self.filter_input = None;
+                self.output_filter = None;
+                return false;
+            }
+            KeyCode::Enter => {
+                self.filter_input = None;
+                return false;
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.push(ch);
+            }
+            _ => return false,
+        }
+        self.output_filter = (!input.is_empty()).then(|| OutputFilter::new(input));
+        self.session_scroll = 0;
+        false
+    }
+
+    fn open_truncated_line_pager(&mut self) {
+        let rows = self.session_rows();
+        let bottom = rows.len().saturating_sub(1 + self.session_scroll);
+        let found = rows
+            .iter()
+            .take(bottom + 1)
+            .rev()
+            .chain(rows.iter().skip(bottom + 1))
+            .find_map(|row| self.chat[row.line].full_text.clone());
+
+        match found {
+            Some(text) => {
+                self.mode = Mode::Pager(PagerState {
+                    title: i18n::fill(
+                        self.runtime.locale.strings().full_line,
+                        &[("bytes", &text.len().to_string())],
+                    ),
+                    text,
+                    scroll: 0,
+                });
+            }
+            None => self.push_info(self.runtime.locale.strings().no_truncated_lines),
+        }
+    }
+
+    /// Help sections matching `query`: the key bindings of the UI language, then the internal
+    /// commands and palette actions as registered, so the overlay lists whatever exists. A match
+    /// on a section title keeps the whole section.
+    fn help_groups(&self, query: &str) -> Vec<HelpGroup> {
+        let strings = self.runtime.locale.strings();
+        let mut groups: Vec<HelpGroup> = strings
+            .help_sections
+            .iter()
+            .map(|title| HelpGroup {
+                title: title.to_string(),
+                rows: Vec::new(),
+            })
+            .collect();
+        for ((section, keys), label) in HELP_KEYS.iter().zip(strings.help_labels) {
+            let keys = match keys {
+                HelpKeys::Fixed(keys) => keys.to_string(),
+                HelpKeys::Ctrl(actions) => ctrl_keys_text(actions),
+            };
+            groups[*section].rows.push((keys, label));
+        }
+        groups.push(HelpGroup {
+            title: strings.help_internal_commands.to_string(),
+            rows: self
+                .internal_commands
+                .iter()
+                .map(|command| (command.name.to_string(), command.description))
+                .collect(),
+        });
+        groups.push(HelpGroup {
+            title: format!(
+                "{} ({})",
+                strings.help_palette,
+                ctrl_keys_text(&[KeyAction::Palette])
+            ),
+            rows: PALETTE_ACTIONS
+                .iter()
+                .map(|(title, description, _)| (title.to_string(), *description))
+                .collect(),
+        });
+
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return groups;
+        }
+        let matches = |text: &str| text.to_lowercase().contains(&query);
+        groups
+            .into_iter()
+            .filter_map(|mut group| {
+                if !matches(&group.title) {
+                    group
+                        .rows
+                        .retain(|(keys, action)| matches(keys) || matches(action));
+                }
+                (!group.rows.is_empty()).then_some(group)
+            })
+            .collect()
+    }
+
+    fn on_help_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut help = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::Help(help) => help,
+            _ => return UiAction::None,
+        };
+
+        match key.code {
+            KeyCode::Esc if help.query.is_empty() => return UiAction::None,
+            KeyCode::Esc => help.query.clear(),
+            KeyCode::Char('?') if help.query.is_empty() => return UiAction::None,
+            KeyCode::Enter => return UiAction::None,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return UiAction::None;
+            }
+            KeyCode::Char(ch) => {
+                help.query.push(ch);
+                help.scroll = 0;
+            }
+            KeyCode::Backspace => {
+                help.query.pop();
+                help.scroll = 0;
+            }
+            KeyCode::Up => help.scroll = help.scroll.saturating_sub(1),
+            KeyCode::Down => help.scroll = help.scroll.saturating_add(1),
+            KeyCode::PageUp => help.scroll = help.scroll.saturating_sub(10),
+            KeyCode::PageDown => help.scroll = help.scroll.saturating_add(10),
+            KeyCode::Home => help.scroll = 0,
+            _ => {}
+        }
+        // A title and a blank line per group, so scrolling stops at the last row.
+        let lines: usize = self
+            .help_groups(&help.query)
+            .iter()
+            .map(|group| group.rows.len() + 2)
+            .sum();
+        help.scroll = help.scroll.min(lines.saturating_sub(2));
+        self.mode = Mode::Help(help);
+        UiAction::None
+    }
+
+    fn on_pager_key(&mut self, key: KeyEvent) -> UiAction {
+        let mut pager = match std::mem::replace(&mut self.mode, Mode::Search) {
+            Mode::Pager(pager) => pager,
+            _ => return UiAction::None,
+        };
+
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return UiAction::None,
+            KeyCode::Up => pager.scroll = pager.scroll.saturating_sub(1),
+            KeyCode::Down => pager.scroll = pager.scroll.saturating_add(1),
+            KeyCode::PageUp => pager.scroll = pager.scroll.saturating_sub(10),
+            KeyCode::PageDown => pager.scroll = pager.scroll.saturating_add(10),
+            KeyCode::Home => pager.scroll = 0,
+            _ => {}
+        }
+        self.mode = Mode::Pager(pager);
+        UiAction::None
+    }
+
+    fn scroll_session(&mut self, delta: isize) {
+        if let Some(pane) = self
+            .focused_job
+            .and_then(|index| self.job_panes.get_mut(index))
+        {
+            pane.scroll = pane
+                .scroll
+                .saturating_add_signed(delta)
+                .min(pane.lines.len().saturating_sub(1));
+            return;
+        }
+        if delta > 0 {
+            self.session_scroll = self
+                .session_scroll
+                .saturating_add(delta as usize)
+                .min(self.session_rows().len().saturating_sub(1));
+        } else if delta < 0 {
+            self.session_scroll = self.session_scroll.saturating_sub((-delta) as usize);
+        }
+    }
+
+    fn empty_result_hint(&self) -> String {
+        if self.is_internal_query() {
+            let names: Vec<&str> = self
+                .internal_commands
+                .iter()
+                .map(|command| command.name)
+                .collect();
+            return format!("available internal commands: {}", names.join(", "));
+        }
+        if self.commands.is_empty() {
+            return "catalog has 0 commands — run /init or enable a provider".to_string();
+        }
+
+        let (provider_filter, query, unknown_alias) = parse_query_provider_filter(
+            self.query.as_str(),
+            &self.provider_aliases,
+            &self.provider_names_without_alias,
+        );
+        if unknown_alias {
+            let typed = query_provider_token(&self.query);
+            return match self.closest_provider_filter(&typed) {
+                Some((alias, provider)) if alias == provider => {
+                    format!("no provider ':{typed}' — did you mean ':{alias}'?")
+                }
+                Some((alias, provider)) => {
+                    format!("no provider ':{typed}' — did you mean ':{alias}' ({provider})?")
+                }
+                None => format!("no provider ':{typed}' — see /aliases"),
+            };
+        }
+
+        match provider_filter {
+            Some(provider) => format!("no {provider} commands match '{}'", query.trim()),
+            None => format!("no commands match '{}'", query.trim()),
+        }
+    }
+
+    fn closest_provider_filter(&self, typed: &str) -> Option<(String, String)> {
+        let candidates = self
+            .provider_aliases
+            .iter()
+            .map(|(alias, provider)| (alias.clone(), provider.clone()))
+            .chain(
+                self.provider_names_without_alias
+                    .iter()
+                    .map(|name| (name.clone(), name.clone())),
+            );
+
+        candidates
+            .map(|(alias, provider)| (edit_distance(typed, &alias), alias, provider))
+            .filter(|(distance, alias, _)| *distance <= 2.max(alias.chars().count() / 2))
+            .min()
+            .map(|(_, alias, provider)| (alias, provider))
+    }
+
+    fn current_command_index(&self) -> Option<usize> {
+        match self.filtered.get(self.selected).copied() {
+            Some(SearchItem::Command(index)) => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The command shown in the details pane, while it is open.
+    fn details_index(&self) -> Option<usize> {
+        self.show_details
+            .then(|| self.current_command_index())
+            .flatten()
+    }
+}
+
+fn provider_names_without_alias(
+    commands: &[CommandEntry],
+    provider_alias_by_name: &HashMap<String, String>,
+) -> HashSet<String> {
+    commands
+        .iter()
+        .map(command_provider_name)
+        .filter(|provider| !provider_alias_by_name.contains_key(*provider))
+        .map(|provider| provider.to_ascii_lowercase())
+        .collect()
+}
+
+pub fn command_provider_name(command: &CommandEntry) -> &str {
+    match &command.source {
+        CommandSource::Config => "config",
+        CommandSource::Provider(name) | CommandSource::Lazy(name) => name,
+    }
+}
+
+fn display_command_name(command: &CommandEntry, provider_name: &str) -> String {
+    let prefix = format!("{provider_name} ");
+    if command.name.to_ascii_lowercase().starts_with(&prefix) {
+        return command.name[prefix.len()..].to_string();
+    }
+    command.name.clone()
+}
+
+fn parse_query_provider_filter<'a>(
+    query: &'a str,
+    provider_aliases: &'a HashMap<String, String>,
+    provider_names_without_alias: &'a HashSet<String>,
+) -> (Option<&'a str>, &'a str, bool) {
+    let trimmed = query.trim_start();
+    if !trimmed.starts_with(':') {
+        return (None, query, false);
+    }
+
+    let after = &trimmed[1..];
+    let alias_end = after.find(char::is_whitespace).unwrap_or(after.len());
+    let alias = after[..alias_end].trim().to_ascii_lowercase();
+    let remaining = after[alias_end..].trim_start();
+
+    if alias.is_empty() {
+        return (None, query, false);
+    }
+
+    match provider_aliases.get(&alias) {
+        Some(provider) => (Some(provider.as_str()), remaining, false),
+        None => match provider_names_without_alias.get(alias.as_str()) {
+            Some(provider_name) => (Some(provider_name.as_str()), remaining, false),
+            None => (None, remaining, true),
+        },
+    }
+}
+
+fn query_provider_token(query: &str) -> String {
+    let after = query.trim_start().trim_start_matches(':');
+    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+    after[..end].to_ascii_lowercase()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Cuts `text` to at most `max_bytes` (on a char boundary), returning the original when cut.
+fn truncate_output_line(text: String, max_bytes: usize) -> (String, Option<String>) {
+    if max_bytes == 0 || text.len() <= max_bytes {
+        return (text, None);
+    }
+
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let shown = format!("{}… [+{} bytes]", &text[..end], text.len() - end);
+    (shown, Some(text))
+}
+
+/// `/env`, `/env list`, `/env set KEY=value`, or `/env unset KEY`; `None` for other queries.
+fn parse_env_command(query: &str) -> Option<Result<EnvCommand, String>> {
+    let rest = query.trim().strip_prefix("/env")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (action, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let args = args.trim();
+    let valid_key = |key: &str| {
+        !key.is_empty()
+            && !key.starts_with(|ch: char| ch.is_ascii_digit())
+            && key
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+    };
+    Some(match action {
+        "" | "list" => Ok(EnvCommand::List),
+        "set" => match args.split_once('=') {
+            Some((key, value)) if valid_key(key) => {
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .or_else(|| {
+                        value
+                            .strip_prefix('\'')
+                            .and_then(|value| value.strip_suffix('\''))
+                    })
+                    .unwrap_or(value);
+                Ok(EnvCommand::Set(key.to_string(), value.to_string()))
+            }
+            _ => Err("usage: /env set KEY=value (KEY: letters, digits, and _)".to_string()),
+        },
+        "unset" if valid_key(args) => Ok(EnvCommand::Unset(args.to_string())),
+        "unset" => Err("usage: /env unset KEY".to_string()),
+        other => Err(format!(
+            "unknown /env action '{other}' (list, set KEY=value, unset KEY)"
+        )),
+    })
+}
+
+fn parse_internal_command(query: &str) -> Option<InternalCommand> {
+    let trimmed = query.trim();
+    if !trimmed.starts_with('/') {
+        return None;
+    }
+
+    let mut parts = trimmed[1..].split_whitespace();
+    let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+    if name.is_empty() {
+        return Some(InternalCommand::Unknown(String::new()));
+    }
+
+    match name.as_str() {
+        "reload" => Some(InternalCommand::Reload),
+        "aliases" => Some(InternalCommand::Aliases),
+        "init" => {
+            let force = parts.any(|part| part == "--force" || part == "-f");
+            Some(InternalCommand::Init { force })
+        }
+        _ => Some(InternalCommand::Unknown(name)),
+    }
+}
+
+fn provider_alias_report(
+    provider_aliases: &HashMap<String, String>,
+    provider_names: &[&str],
+) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let mut aliases: Vec<(&String, &String)> = provider_aliases.iter().collect();
+    aliases.sort();
+    if aliases.is_empty() {
+        lines.push("No provider aliases configured".to_string());
+    } else {
+        lines.push("Provider aliases:".to_string());
+        for (alias, provider) in &aliases {
+            lines.push(format!("  :{alias} -> {provider}"));
+        }
+    }
+
+    let aliased: HashSet<&str> = provider_aliases.values().map(String::as_str).collect();
+    let by_name: Vec<String> = provider_names
+        .iter()
+        .filter(|name| !aliased.contains(*name))
+        .map(|name| format!(":{name}"))
+        .collect();
+    if !by_name.is_empty() {
+        lines.push(format!("Usable by provider name: {}", by_name.join(", ")));
+    }
+
+    for (alias, provider) in &aliases {
+        if let Some(name) = provider_names
+            .iter()
+            .find(|name| **name != provider.as_str() && name.eq_ignore_ascii_case(alias))
+        {
+            lines.push(format!(
+                "Conflict: alias ':{alias}' points to '{provider}' and shadows provider name '{name}'"
+            ));
+        }
+    }
+    for provider in provider_names {
+        if let Some((alias, _)) = aliases
+            .iter()
+            .find(|(_, target)| target.as_str() == *provider)
+            && !alias.eq_ignore_ascii_case(provider)
+        {
+            lines.push(format!(
+                "Note: ':{provider}' finds nothing because '{provider}' has alias ':{alias}'"
+            ));
+        }
+    }
+
+    lines
+}
+
+/// Dimmed text shown in an empty value prompt: the default, else the placeholder.
+fn prompt_ghost_text(param: &ParamSpec) -> Option<&str> {
+    match param.kind {
+        ParamType::Value | ParamType::Path(_) => param
+            .default_value
+            .as_deref()
+            .or(param.placeholder.as_deref()),
+        ParamType::Flag | ParamType::Choice(_) => None,
+    }
+}
+
+/// A directory's entries as `(name, is_dir)`, sorted; empty when it cannot be read.
+fn read_dir_listing(dir: &Path) -> Vec<(String, bool)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut listing: Vec<(String, bool)> = entries
+        .flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            (name, entry.path().is_dir())
+        })
+        .collect();
+    listing.sort();
+    listing
+}
+
+/// Entries of the directory typed so far whose names start with the typed file name, as full
+/// inputs; directories end in `/` and files must match `glob` when one is set.
+fn path_completions(
+    entries: &[(String, bool)],
+    dir_part: &str,
+    name_prefix: &str,
+    glob: Option<&str>,
+) -> Vec<String> {
+    let matcher = glob
+        .and_then(|glob| globset::Glob::new(glob).ok())
+        .map(|glob| glob.compile_matcher());
+    entries
+        .iter()
+        .filter_map(|(name, is_dir)| {
+            if !name.starts_with(name_prefix) || (name.starts_with('.') && name_prefix.is_empty()) {
+                return None;
+            }
+            if *is_dir {
+                return Some(format!("{dir_part}{name}/"));
+            }
+            matcher
+                .as_ref()
+                .is_none_or(|matcher| matcher.is_match(name))
+                .then(|| format!("{dir_part}{name}"))
+        })
+        .collect()
+}
+
+/// A path param's value for the command line: shell-quoted so names with spaces or quotes stay
+/// one argument, with a leading `~/` left bare for the shell to expand.
+fn quote_path_value(value: &str) -> String {
+    match value.strip_prefix("~/") {
+        Some(rest) if !rest.is_empty() => format!("~/{}", provider::shell_escape_arg(rest)),
+        _ if value.is_empty() || value == "~" || value == "~/" => value.to_string(),
+        _ => provider::shell_escape_arg(value),
+    }
+}
+
+fn common_prefix(rows: &[String]) -> String {
+    let Some(first) = rows.first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for row in &rows[1..] {
+        len = first
+            .char_indices()
+            .zip(row.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((index, ch), _)| index + ch.len_utf8())
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+/// Indices of `options` matching the typed filter, best fuzzy match first.
+fn filter_choices(matcher: &SkimMatcherV2, options: &[String], query: &str) -> Vec<usize> {
+    let query = query.trim();
+    if query.is_empty() {
+        return (0..options.len()).collect();
+    }
+    let mut scored: Vec<(usize, i64)> = options
+        .iter()
+        .enumerate()
+        .filter_map(|(index, option)| Some((index, matcher.fuzzy_match(option, query)?)))
+        .collect();
+    scored.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+    scored.into_iter().map(|(index, _)| index).collect()
+}
+
+/// A param is disabled when its `when` flag has been answered "no" (rendered as empty).
+/// Problems that would otherwise only surface when a command runs: a `working_dir` that is
+/// missing and not created on demand.
+pub fn catalog_problems(commands: &[CommandEntry]) -> Vec<String> {
+    commands
+        .iter()
+        .filter_map(|command| {
+            let dir = command.working_dir.as_ref()?;
+            (!command.create_working_dir && !dir.is_dir()).then(|| {
+                format!(
+                    "'{}': working_dir {} does not exist (create it or set create_working_dir = true)",
+                    command.name,
+                    dir.display()
+                )
+            })
+        })
+        .collect()
+}
+
+fn param_enabled(param: &ParamSpec, values: &HashMap<String, String>) -> bool {
+    param
+        .when
+        .as_ref()
+        .and_then(|flag| values.get(flag))
+        .is_none_or(|token| !token.is_empty())
+}
+
+fn parse_flag_input(input: &str, default: bool) -> Option<bool> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Some(default);
+    }
+
+    match trimmed.to_ascii_lowercase().as_str() {
+        "y" | "yes" | "true" | "1" | "on" => Some(true),
+        "n" | "no" | "false" | "0" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn query_has_force_flag(query: &str) -> bool {
+    query
+        .split_whitespace()
+        .any(|part| part == "--force" || part == "-f")
+}
+
+pub fn command_usage_key(command: &CommandEntry) -> String {
+    format!("{}::{}", command_provider_name(command), command.name)
+}
+
+/// Whether `key` equals [`command_usage_key`] of `command`.
+fn is_usage_key_of(key: &str, command: &CommandEntry) -> bool {
+    key.strip_prefix(command_provider_name(command))
+        .and_then(|rest| rest.strip_prefix("::"))
+        == Some(command.name.as_str())
+}
+
+fn load_usage_store() -> (HashMap<String, UsageRecord>, Option<PathBuf>) {
+    let Some(path) = usage_store_path() else {
+        return (HashMap::new(), None);
+    };
+    if !path.exists() {
+        return (HashMap::new(), Some(path));
+    }
+
+    (read_usage_store(&path, history::now()), Some(path))
+}
+
+/// Usage records from the store; counts written before frecency existed start decaying at `now`.
+fn read_usage_store(path: &Path, now: u64) -> HashMap<String, UsageRecord> {
+    let store = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str::<UsageStore>(&content).ok())
+        .unwrap_or_default();
+    store
+        .counts
+        .into_iter()
+        .map(|(key, count)| {
+            let frecency = store.frecency.get(&key).copied();
+            let record = UsageRecord {
+                count,
+                score: frecency.map_or(count as f64, |frecency| frecency.score),
+                last_used: frecency.map_or(now, |frecency| frecency.last_used),
+            };
+            (key, record)
+        })
+        .collect()
+}
+
+fn persist_usage_store(usage: &HashMap<String, UsageRecord>, path: Option<&Path>) -> Result<()> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create usage directory {}", parent.display()))?;
+    }
+
+    let payload = UsageStore {
+        counts: usage
+            .iter()
+            .map(|(key, record)| (key.clone(), record.count))
+            .collect(),
+        frecency: usage
+            .iter()
+            .map(|(key, record)| {
+                let frecency = FrecencyRecord {
+                    score: record.score,
+                    last_used: record.last_used,
+                };
+                (key.clone(), frecency)
+            })
+            .collect(),
+    };
+    let serialized = toml::to_string(&payload).context("failed to serialize usage store")?;
+    // Renamed into place so a reader never sees a half-written store.
+    let partial = path.with_extension(format!("toml.{}.tmp", std::process::id()));
+    fs::write(&partial, serialized)
+        .with_context(|| format!("failed to write usage store {}", partial.display()))?;
+    fs::rename(&partial, path)
+        .with_context(|| format!("failed to write usage store {}", path.display()))?;
+    Ok(())
+}
+
+/// A `usage.toml.lock` file held while one instance reads, updates, and writes the usage store.
+/// Without it (after waiting for a stale one, or when it cannot be created) the update goes
+/// ahead unlocked.
+struct UsageLock(Option<PathBuf>);
+
+impl UsageLock {
+    const WAIT: Duration = Duration::from_secs(1);
+
+    fn acquire(store: &Path) -> Self {
+        let path = store.with_extension("toml.lock");
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let started = Instant::now();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Self(Some(path)),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if started.elapsed() >= Self::WAIT {
+                        // Left behind by an instance that died while holding it.
+                        let _ = fs::remove_file(&path);
+                        return Self(None);
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(_) => return Self(None),
+            }
+        }
+    }
+}
+
+impl Drop for UsageLock {
+    fn drop(&mut self) {
+        if let Some(path) = &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn usage_store_path() -> Option<PathBuf> {
+    let config_root = dirs::config_dir()?;
+    Some(config_root.join("fzc").join("usage.toml"))
+}
+
+const MAX_RECENT_PROJECTS: usize = 20;
+const PROJECT_MARKERS: &[&str] = &[
+    ".git",
+    "fzc.toml",
+    ".fzc.toml",
+    "justfile",
+    "composer.json",
+    "package.json",
+];
+
+/// Nearest ancestor of `cwd` that looks like a project root, or `cwd` itself.
+fn project_root(cwd: &Path) -> PathBuf {
+    cwd.ancestors()
+        .find(|dir| {
+            PROJECT_MARKERS
+                .iter()
+                .any(|marker| dir.join(marker).exists())
+        })
+        .unwrap_or(cwd)
+        .to_path_buf()
+}
+
+fn remember_project(roots: &mut Vec<PathBuf>, root: &Path) {
+    roots.retain(|existing| existing != root);
+    roots.insert(0, root.to_path_buf());
+    roots.truncate(MAX_RECENT_PROJECTS);
+}
+
+fn load_recent_projects() -> Vec<PathBuf> {
+    recent_projects_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str::<RecentProjectsStore>(&content).ok())
+        .map(|store| store.roots)
+        .unwrap_or_default()
+}
+
+fn record_recent_project(root: &Path) -> Result<()> {
+    let Some(path) = recent_projects_path() else {
+        return Ok(());
+    };
+
+    let mut roots = load_recent_projects();
+    remember_project(&mut roots, root);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create projects directory {}", parent.display()))?;
+    }
+    let serialized = toml::to_string(&RecentProjectsStore { roots })
+        .context("failed to serialize recent projects")?;
+    fs::write(&path, serialized)
+        .with_context(|| format!("failed to write recent projects {}", path.display()))?;
+    Ok(())
+}
+
+fn recent_projects_path() -> Option<PathBuf> {
+    let config_root = dirs::config_dir()?;
+    Some(config_root.join("fzc").join("projects.toml"))
+}
+
+/// Ranking boost for a (possibly decayed) usage score.
+fn usage_boost(usage: f64, ranking: &RankingSettings) -> i64 {
+    let weight = ranking.usage_weight.max(0) as f64;
+    let boost = match ranking.usage_scale {
+        UsageScale::Linear => (usage * weight) as i64,
+        UsageScale::Log => (usage.ln_1p() / std::f64::consts::LN_2 * weight) as i64,
+    };
+    match ranking.usage_cap {
+        Some(cap) => boost.min(cap.max(0)),
+        None => boost,
+    }
+}
+
+/// Pasted text as a single input line: line breaks and tabs become spaces, other
+/// control characters are dropped.
+fn sanitize_paste(text: &str) -> String {
+    let flattened: String = text
+        .chars()
+        .filter_map(|ch| match ch {
+            '\n' | '\r' | '\t' => Some(' '),
+            ch if ch.is_control() => None,
+            ch => Some(ch),
+        })
+        .collect();
+    flattened.trim().to_string()
+}
+
+fn insert_char_at(value: &mut String, char_index: usize, ch: char) {
+    let byte_index = byte_index_for_char(value, char_index);
+    value.insert(byte_index, ch);
+}
+
+fn remove_char_at(value: &mut String, char_index: usize) -> bool {
+    let start = byte_index_for_char(value, char_index);
+    if start >= value.len() {
+        return false;
+    }
+    let end = byte_index_for_char(value, char_index + 1);
+    value.replace_range(start..end, "");
+    true
+}
+
+fn byte_index_for_char(value: &str, char_index: usize) -> usize {
+    if char_index == 0 {
+        return 0;
+    }
+    value
+        .char_indices()
+        .nth(char_index)
+        .map(|(index, _)| index)
+        .unwrap_or(value.len())
+}
+
+#[derive(Debug)]
+struct MatchScore {
+    total: i64,
+    fuzzy: i64,
+}
+
+/// Lowercased search text for one command, built once per catalog change instead of per keystroke.
+struct SearchIndexEntry {
+    /// Name, description, and template.
+    haystack: String,
+    name_lower: String,
+    name_terms: Vec<String>,
+    haystack_terms: Vec<String>,
+}
+
+impl SearchIndexEntry {
+    fn new(command: &CommandEntry) -> Self {
+        let name_lower = command.name.to_lowercase();
+        let mut haystack = name_lower.clone();
+        if let Some(desc) = &command.description {
+            haystack.push(' ');
+            haystack.push_str(&desc.to_lowercase());
+        }
+        haystack.push(' ');
+        haystack.push_str(&command.template.to_lowercase());
+
+        Self {
+            name_terms: tokenize_for_match(&name_lower),
+            haystack_terms: tokenize_for_match(&haystack),
+            haystack,
+            name_lower,
+        }
+    }
+}
+
+fn score_command_match(
+    matcher: &SkimMatcherV2,
+    normalized_query: &str,
+    query_terms: &[String],
+    entry: &SearchIndexEntry,
+) -> Option<MatchScore> {
+    let fuzzy = matcher
+        .fuzzy_match(&entry.haystack, normalized_query)
+        .unwrap_or_default();
+    if query_terms.is_empty() {
+        return Some(MatchScore {
+            total: fuzzy * 10,
+            fuzzy,
+        });
+    }
+
+    let name_terms = &entry.name_terms;
+    let haystack_terms = &entry.haystack_terms;
+
+    let mut exact_name_hits = 0i64;
+    let mut partial_name_hits = 0i64;
+    let mut coverage_hits = 0i64;
+    let mut description_hits = 0i64;
+
+    for term in query_terms {
+        let mut matched_name = false;
+        for token in name_terms {
+            match token_match_quality(token, term) {
+                2 => {
+                    exact_name_hits += 1;
+                    matched_name = true;
+                    break;
+                }
+                1 => {
+                    partial_name_hits += 1;
+                    matched_name = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        if matched_name {
+            coverage_hits += 1;
+            continue;
+        }
+
+        if haystack_terms
+            .iter()
+            .any(|token| token_match_quality(token, term) > 0)
+        {
+            coverage_hits += 1;
+            description_hits += 1;
+        }
+    }
+
+    if fuzzy == 0 && coverage_hits == 0 {
+        return None;
+    }
+
+    let all_terms_in_name = query_terms.iter().all(|term| {
+        name_terms
+            .iter()
+            .any(|token| token_match_quality(token, term) > 0)
+    });
+    let ordered_in_name = terms_in_order(name_terms, query_terms);
+    let contiguous_in_name = terms_contiguous(name_terms, query_terms);
+    let query_phrase = query_terms.join(" ");
+    let normalized_name = name_terms.join(" ");
+    let phrase_match = !query_phrase.is_empty() && normalized_name.contains(&query_phrase);
+
+    let total = fuzzy * 10
+        + exact_name_hits * 12_000
+        + partial_name_hits * 6_000
+        + coverage_hits * 10_000
+        + description_hits * 2_500
+        + if all_terms_in_name { 35_000 } else { 0 }
+        + if ordered_in_name { 10_000 } else { 0 }
+        + if contiguous_in_name { 10_000 } else { 0 }
+        + if phrase_match { 15_000 } else { 0 };
+
+    Some(MatchScore { total, fuzzy })
+}
+
+fn tokenize_for_match(raw: &str) -> Vec<String> {
+    raw.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+fn token_match_quality(token: &str, term: &str) -> i64 {
+    if token == term {
+        return 2;
+    }
+    if token.starts_with(term) || token.contains(term) {
+        return 1;
+    }
+    0
+}
+
+fn terms_in_order(name_terms: &[String], query_terms: &[String]) -> bool {
+    if query_terms.is_empty() {
+        return false;
+    }
+
+    let mut cursor = 0usize;
+    for query in query_terms {
+        let mut found = false;
+        while cursor < name_terms.len() {
+            if token_match_quality(&name_terms[cursor], query) > 0 {
+                found = true;
+                cursor += 1;
+                break;
+            }
+            cursor += 1;
+        }
+
+        if !found {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn terms_contiguous(name_terms: &[String], query_terms: &[String]) -> bool {
+    if query_terms.is_empty() || query_terms.len() > name_terms.len() {
+        return false;
+    }
+
+    for start in 0..=(name_terms.len() - query_terms.len()) {
+        let mut all_match = true;
+        for offset in 0..query_terms.len() {
+            if token_match_quality(&name_terms[start + offset], &query_terms[offset]) == 0 {
+                all_match = false;
+                break;
+            }
+        }
+        if all_match {
+            return true;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::model::CommandSource;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn mock_command(name: &str) -> CommandEntry {
+        CommandEntry {
+            description: Some("artisan command".to_string()),
+            ..CommandEntry::new(
+                name,
+                format!("php artisan {name}"),
+                CommandSource::Provider("artisan".to_string()),
+            )
+        }
+    }
+
+    /// A required value-style param that prompts in the TUI; override fields with `..`.
+    fn test_param(name: &str, kind: ParamType) -> ParamSpec {
+        ParamSpec {
+            name: name.to_string(),
+            kind,
+            prompt: name.to_string(),
+            placeholder: None,
+            description: None,
+            default_value: None,
+            value_value: None,
+            default_flag: None,
+            value_flag: None,
+            required: true,
+            prompt_in_tui: true,
+            group: None,
+            when: None,
+            eval: false,
+        }
+    }
+
+    fn app_with(commands: Vec<CommandEntry>) -> AppState {
+        AppState::new(
             commands,
-            filtered: Vec::new(),
-            internal_commands: vec![
-                InternalCommandDef {
-                    name: "/init",
-                    description: "Create default config file",
-                    kind: InternalCommandKind::Init,
-                    default_force: false,
-                },
-                InternalCommandDef {
-                    name: "/reload",
-                    description: "Reload config and providers",
-                    kind: InternalCommandKind::Reload,
-                    default_force: false,
-                },
-            ],
-            selected: 0,
-            query: String::new(),
-            query_cursor: 0,
-            matcher: SkimMatcherV2::default(),
-            mode: Mode::Search,
-            chat: Vec::new(),
-            config_path,
-            provider_aliases,
-            provider_alias_by_name,
-            provider_names_without_alias,
-            ranking,
-            usage_counts,
-            usage_path,
-            is_loading: false,
-            loading_label: None,
-            spinner_index: 0,
-            show_help: false,
-            runtime,
-            active_pane: ActivePane::Commands,
-            session_scroll: 0,
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            test_runtime(),
+        )
+    }
+
+    fn default_ranking() -> RankingSettings {
+        RankingSettings {
+            usage_enabled: true,
+            usage_weight: 8_000,
+            usage_scale: UsageScale::Linear,
+            usage_cap: None,
+            decay_half_life_days: 30.0,
+        }
+    }
+
+    fn default_output() -> OutputSettings {
+        OutputSettings {
+            max_line_bytes: 4_096,
+            pretty_json: false,
+            color: ColorMode::Force,
+            pty: false,
+            timeout: None,
+            target: ExecTarget::Session,
+            terminal: None,
+        }
+    }
+
+    fn test_runtime() -> RuntimeContext {
+        RuntimeContext {
+            cwd: std::env::temp_dir(),
+            explicit_config_path: None,
+            global_config_path: None,
+            provider_hints: Vec::new(),
+            persist_usage: false,
+            color_support: ColorSupport::TrueColor,
+            providers: ProvidersConfig::default(),
+            inline: false,
+            events_socket: None,
+            job_panes: 0,
+            mouse: false,
+            theme: Theme::default(),
+            redactor: Redactor::default(),
+            locale: Locale::default(),
+            secrets: SecretsConfig::default(),
+            hooks: CommandHooks::default(),
+            poll: PollSettings {
+                interval: Duration::from_millis(100),
+                idle_interval: Duration::from_secs(1),
+            },
+        }
+    }
+
+    fn top_name_for(query: &str, commands: Vec<CommandEntry>) -> String {
+        let mut app = app_with(commands);
+        app.query = query.to_string();
+        app.refresh_filtered();
+
+        let SearchItem::Command(index) = app.filtered[0] else {
+            panic!("expected command result");
         };
+        app.commands[index].name.clone()
+    }
+
+    #[test]
+    fn phrase_query_prefers_cache_clear() {
+        let top = top_name_for(
+            "cache clear",
+            vec![
+                mock_command("artisan cache:clear"),
+                mock_command("artisan clear-compiled"),
+                mock_command("artisan cache:table"),
+            ],
+        );
+        assert_eq!(top, "artisan cache:clear");
+    }
 
+    #[test]
+    fn search_matches_command_templates_via_index() {
+        let mut release = mock_command("release");
+        release.template = "./scripts/ship-it.sh --tag".to_string();
+        let mut app = app_with(vec![mock_command("migrate"), release]);
+        assert_eq!(app.search_index.len(), app.commands.len());
+        assert!(app.search_index[1].haystack.contains("ship-it.sh"));
+
+        app.query = "ship".to_string();
         app.refresh_filtered();
-        app.push_info(format!("Loaded {count} commands"));
-        if let Some(path) = &app.config_path {
-            app.push_info(format!("Config: {}", path.display()));
-        } else {
-            app.push_info("Config: none (providers only or defaults)");
-        }
+        assert_eq!(app.filtered.len(), 1);
+        let SearchItem::Command(index) = app.filtered[0] else {
+            panic!("expected command result");
+        };
+        assert_eq!(app.commands[index].name, "release");
+    }
 
-        app
+    #[test]
+    fn reversed_query_still_prefers_cache_clear() {
+        let top = top_name_for(
+            "clear cache",
+            vec![
+                mock_command("artisan cache:clear"),
+                mock_command("artisan clear-compiled"),
+                mock_command("artisan cache:table"),
+            ],
+        );
+        assert_eq!(top, "artisan cache:clear");
     }
 
-    fn on_key(&mut self, key: KeyEvent) -> UiAction {
-        if self.show_help {
-            match key.code {
-                KeyCode::Esc | KeyCode::Char('?') => {
-                    self.show_help = false;
-                    return UiAction::None;
-                }
-                _ => {
-                    // Auto-close help and continue handling the key normally.
-                    self.show_help = false;
-                }
-            }
-        }
+    #[test]
+    fn prompt_submit_switches_back_to_search_mode() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy --env={{env}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Environment".to_string(),
+            ..test_param("env", ParamType::Value)
+        }];
 
-        match self.mode {
-            Mode::Search => self.on_search_key(key),
-            Mode::Prompt(_) => self.on_prompt_key(key),
-            Mode::InternalPrompt(_) => self.on_internal_prompt_key(key),
+        let mut app = app_with(vec![command]);
+        let action = app.prepare_selected_command(true);
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::Prompt(_)));
+
+        if let Mode::Prompt(prompt) = &mut app.mode {
+            prompt.input = "production".to_string();
         }
+
+        let action = app.on_prompt_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, UiAction::Run(_)));
+        assert!(matches!(app.mode, Mode::Search));
     }
 
-    fn on_search_key(&mut self, key: KeyEvent) -> UiAction {
-        if matches!(key.code, KeyCode::Char('?')) {
-            self.show_help = true;
-            return UiAction::None;
-        }
+    #[test]
+    fn tab_accepts_prompt_ghost_text_for_editing() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy --branch={{branch}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Branch".to_string(),
+            placeholder: Some("feature/".to_string()),
+            description: Some("Branch to deploy.\nMust exist on origin.".to_string()),
+            ..test_param("branch", ParamType::Value)
+        }];
 
-        if matches!(key.code, KeyCode::Tab) {
-            self.active_pane = match self.active_pane {
-                ActivePane::Commands => ActivePane::Session,
-                ActivePane::Session => ActivePane::Commands,
-            };
-            return UiAction::None;
-        }
+        let mut app = app_with(vec![command]);
+        app.prepare_selected_command(true);
+        app.on_prompt_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        app.on_prompt_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
 
-        match key.code {
-            KeyCode::Esc => {
-                if self.query.is_empty() {
-                    UiAction::Quit
-                } else {
-                    self.query.clear();
-                    self.query_cursor = 0;
-                    self.refresh_filtered();
-                    self.active_pane = ActivePane::Commands;
-                    UiAction::None
-                }
-            }
-            KeyCode::Enter => {
-                if self.active_pane == ActivePane::Session {
-                    return UiAction::None;
-                }
-                if self.is_internal_query() {
-                    return self.prepare_selected_internal_command();
-                }
-                let return_to_tui = !key.modifiers.contains(KeyModifiers::ALT);
-                self.prepare_selected_command(return_to_tui)
-            }
-            KeyCode::Left => {
-                if self.query_cursor > 0 {
-                    self.query_cursor -= 1;
-                }
-                self.active_pane = ActivePane::Commands;
-                UiAction::None
-            }
-            KeyCode::Right => {
-                let len = self.query.chars().count();
-                if self.query_cursor < len {
-                    self.query_cursor += 1;
-                }
-                self.active_pane = ActivePane::Commands;
-                UiAction::None
-            }
-            KeyCode::Home => {
-                self.query_cursor = 0;
-                self.active_pane = ActivePane::Commands;
-                UiAction::None
-            }
-            KeyCode::End => {
-                self.query_cursor = self.query.chars().count();
-                self.active_pane = ActivePane::Commands;
-                UiAction::None
-            }
-            KeyCode::Up => {
-                if self.active_pane == ActivePane::Session {
-                    self.scroll_session(1);
-                } else {
-                    self.move_selection(-1);
-                }
-                UiAction::None
-            }
-            KeyCode::Down => {
-                if self.active_pane == ActivePane::Session {
-                    self.scroll_session(-1);
-                } else {
-                    self.move_selection(1);
-                }
-                UiAction::None
-            }
-            KeyCode::PageUp => {
-                if self.active_pane == ActivePane::Session {
-                    self.scroll_session(10);
-                } else {
-                    self.move_selection_by(-10);
-                }
-                UiAction::None
-            }
-            KeyCode::PageDown => {
-                if self.active_pane == ActivePane::Session {
-                    self.scroll_session(-10);
-                } else {
-                    self.move_selection_by(10);
-                }
-                UiAction::None
-            }
-            KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                if self.active_pane == ActivePane::Session {
-                    self.scroll_session(-1);
-                } else {
-                    self.move_selection(1);
-                }
-                UiAction::None
-            }
-            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                if self.active_pane == ActivePane::Session {
-                    self.scroll_session(1);
-                } else {
-                    self.move_selection(-1);
-                }
-                UiAction::None
-            }
-            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => UiAction::Quit,
-            KeyCode::Backspace => {
-                if self.query_cursor > 0 && remove_char_at(&mut self.query, self.query_cursor - 1) {
-                    self.query_cursor -= 1;
-                    self.refresh_filtered();
-                }
-                self.active_pane = ActivePane::Commands;
-                UiAction::None
-            }
-            KeyCode::Delete => {
-                if remove_char_at(&mut self.query, self.query_cursor) {
-                    self.refresh_filtered();
-                }
-                self.active_pane = ActivePane::Commands;
-                UiAction::None
-            }
-            KeyCode::Char(ch)
-                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
-            {
-                self.active_pane = ActivePane::Commands;
-                insert_char_at(&mut self.query, self.query_cursor, ch);
-                self.query_cursor += 1;
-                self.refresh_filtered();
-                UiAction::None
-            }
-            _ => UiAction::None,
-        }
+        let Mode::Prompt(prompt) = &app.mode else {
+            panic!("expected prompt");
+        };
+        assert_eq!(prompt.input, "feature/x");
     }
 
-    fn on_prompt_key(&mut self, key: KeyEvent) -> UiAction {
-        let mut prompt_state = match std::mem::replace(&mut self.mode, Mode::Search) {
-            Mode::Prompt(prompt) => prompt,
-            Mode::Search => return UiAction::None,
-            Mode::InternalPrompt(_) => return UiAction::None,
+    #[test]
+    fn params_controlled_by_an_off_flag_are_skipped() {
+        let param = |name: &str, kind, when: Option<&str>| ParamSpec {
+            group: Some("Database".to_string()),
+            when: when.map(ToString::to_string),
+            ..test_param(name, kind)
         };
+        let mut command = mock_command("deploy");
+        command.template = "deploy {{migrate}} --seed={{seeder}}".to_string();
+        command.params = vec![
+            param("migrate", ParamType::Flag, None),
+            param("seeder", ParamType::Value, Some("migrate")),
+        ];
 
-        match key.code {
-            KeyCode::Esc => {
-                self.push_info("Parameter entry canceled");
-                self.mode = Mode::Search;
-                UiAction::None
-            }
-            KeyCode::Backspace => {
-                prompt_state.input.pop();
-                self.mode = Mode::Prompt(prompt_state);
-                UiAction::None
-            }
-            KeyCode::Char(ch)
-                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
-            {
-                let param_index = prompt_state.pending_params[prompt_state.current_param];
-                let param = self.commands[prompt_state.command_index].params[param_index].clone();
-                if matches!(param.kind, ParamType::Flag) {
-                    let typed = ch.to_string();
-                    if let Some(flag_value) =
-                        parse_flag_input(&typed, param.default_flag.unwrap_or(false))
-                    {
-                        let token = if flag_value {
-                            param.flag_token()
-                        } else {
-                            String::new()
-                        };
-                        prompt_state.values.insert(param.name.clone(), token);
-                        prompt_state.current_param += 1;
-                        prompt_state.input.clear();
+        let mut app = app_with(vec![command]);
+        app.prepare_selected_command(true);
+        let action = app.on_prompt_key(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE));
 
-                        if prompt_state.current_param >= prompt_state.pending_params.len() {
-                            let index = prompt_state.command_index;
-                            let values = prompt_state.values;
-                            let return_to_tui = prompt_state.return_to_tui;
-                            self.mode = Mode::Search;
-                            return self.build_run_request(index, values, return_to_tui);
-                        }
+        let UiAction::Run(request) = action else {
+            panic!("expected the seeder prompt to be skipped");
+        };
+        assert_eq!(request.command_line, "deploy  --seed=");
+    }
 
-                        self.mode = Mode::Prompt(prompt_state);
-                        return UiAction::None;
-                    }
-                }
+    #[test]
+    fn paste_inserts_sanitized_text_at_search_cursor() {
+        let mut app = app_with(vec![mock_command("artisan migrate:fresh")]);
+        app.query = "ar".to_string();
+        app.query_cursor = 1;
 
-                prompt_state.input.push(ch);
-                self.mode = Mode::Prompt(prompt_state);
-                UiAction::None
-            }
-            KeyCode::Enter => {
-                let param_index = prompt_state.pending_params[prompt_state.current_param];
-                let param = self.commands[prompt_state.command_index].params[param_index].clone();
-                let input = prompt_state.input.trim().to_string();
+        app.on_paste("migrate\u{1b}\n");
+        assert_eq!(app.query, "amigrater");
+        assert_eq!(app.query_cursor, 8);
 
-                match param.kind {
-                    ParamType::Value => {
-                        let value = if input.is_empty() {
-                            if let Some(default) = &param.default_value {
-                                default.clone()
-                            } else if param.required {
-                                self.push_info(format!("'{}' is required", param.name));
-                                self.mode = Mode::Prompt(prompt_state);
-                                return UiAction::None;
-                            } else {
-                                String::new()
-                            }
-                        } else {
-                            input
-                        };
+        assert_eq!(sanitize_paste("a\tb\r\nc\u{7}"), "a b  c");
+    }
 
-                        if !value.is_empty() {
-                            prompt_state.values.insert(param.name.clone(), value);
-                        }
-                    }
-                    ParamType::Flag => {
-                        let default = param.default_flag.unwrap_or(false);
-                        let Some(flag_value) = parse_flag_input(&input, default) else {
-                            self.push_info("Please enter y or n");
-                            self.mode = Mode::Prompt(prompt_state);
-                            return UiAction::None;
-                        };
-                        let token = if flag_value {
-                            param.flag_token()
-                        } else {
-                            String::new()
-                        };
-                        prompt_state.values.insert(param.name.clone(), token);
-                    }
-                }
+    #[test]
+    fn command_color_mode_overrides_global_default() {
+        let mut quiet = mock_command("export report");
+        quiet.color = Some(ColorMode::Never);
+        let mut app = app_with(vec![mock_command("artisan test"), quiet]);
+
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected run request");
+        };
+        assert_eq!(request.color, ColorMode::Force);
+        let UiAction::Run(request) = app.build_run_request(1, HashMap::new(), None, true) else {
+            panic!("expected run request");
+        };
+        assert_eq!(request.color, ColorMode::Never);
+        assert!(color_env(ColorMode::Never).contains(&("CLICOLOR_FORCE", None)));
+        assert!(color_env(ColorMode::Auto).is_empty());
+    }
+
+    #[test]
+    fn pty_progress_lines_are_redrawn_in_place() {
+        let mut plain = mock_command("build");
+        plain.pty = Some(false);
+        let mut output = default_output();
+        output.pty = true;
+        let mut app = AppState::new(
+            vec![plain, mock_command("install")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            output,
+            CommandGuard::default(),
+            test_runtime(),
+        );
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected run request");
+        };
+        assert!(!request.pty);
+        let UiAction::Run(request) = app.build_run_request(1, HashMap::new(), None, true) else {
+            panic!("expected run request");
+        };
+        assert_eq!(request.pty, cfg!(unix));
 
-                prompt_state.current_param += 1;
-                prompt_state.input.clear();
+        let chunk = |text: &str, partial| StreamChunk {
+            kind: ChatLineKind::Stdout,
+            text: text.to_string(),
+            partial,
+        };
+        let before = app.chat.len();
+        app.push_output(chunk("installing 10%", true));
+        app.push_output(chunk("installing 60%", true));
+        app.push_output(chunk("installed 12 packages", false));
+        app.push_output(chunk("done", false));
+        let texts: Vec<&str> = app.chat[before..]
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect();
+        assert_eq!(texts, ["installed 12 packages", "done"]);
+    }
 
-                if prompt_state.current_param >= prompt_state.pending_params.len() {
-                    let index = prompt_state.command_index;
-                    let values = prompt_state.values;
-                    let return_to_tui = prompt_state.return_to_tui;
-                    self.mode = Mode::Search;
-                    self.build_run_request(index, values, return_to_tui)
-                } else {
-                    self.mode = Mode::Prompt(prompt_state);
-                    UiAction::None
-                }
-            }
-            _ => {
-                self.mode = Mode::Prompt(prompt_state);
-                UiAction::None
-            }
+    #[test]
+    fn job_panes_keep_scheduled_output_apart_up_to_the_limit() {
+        let mut runtime = test_runtime();
+        runtime.job_panes = 1;
+        let mut app = AppState::new(
+            vec![mock_command("migrate")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        let chunk = |text: &str| StreamChunk {
+            kind: ChatLineKind::Stdout,
+            text: text.to_string(),
+            partial: false,
+        };
+        for (name, result) in [
+            ("queue", Ok((0, vec![chunk("3 jobs")]))),
+            ("disk", Ok((0, vec![chunk("81%")]))),
+            ("queue", Ok((1, vec![chunk("redis down at hunter2-host")]))),
+        ] {
+            app.schedule_tx
+                .send(ScheduledOutcome {
+                    key: format!("test::{name}"),
+                    name: name.to_string(),
+                    result,
+                })
+                .unwrap();
         }
+        app.secret_values.push("hunter2".to_string());
+        app.poll_schedules(Instant::now());
+
+        assert_eq!(app.job_panes.len(), 1);
+        let pane = &app.job_panes[0];
+        let texts: Vec<&str> = pane.lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "3 jobs",
+                "exit code: 0",
+                &format!("redis down at {MASK}-host"),
+                "exit code: 1"
+            ]
+        );
+        assert_eq!(pane.last_exit, Some(1));
+        assert!(app.chat.iter().any(|line| line.text == "81%"));
+        assert!(!app.chat.iter().any(|line| line.text == "3 jobs"));
+
+        let ctrl_o = KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL);
+        app.on_key(ctrl_o);
+        assert_eq!(app.focused_job, Some(0));
+        app.on_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert_eq!(app.job_panes[0].scroll, 1);
+        app.on_key(ctrl_o);
+        assert_eq!(app.focused_job, None);
     }
 
-    fn prepare_selected_command(&mut self, return_to_tui: bool) -> UiAction {
-        let Some(command_index) = self.current_command_index() else {
-            self.push_info("No command selected");
-            return UiAction::None;
+    #[test]
+    fn job_panes_follow_background_jobs_until_they_end() {
+        let mut build = mock_command("build");
+        build.template = "echo 0123456789abcdef; sleep 0.3".to_string();
+        let mut runtime = test_runtime();
+        runtime.job_panes = 1;
+        let mut app = AppState::new(
+            vec![build],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            OutputSettings {
+                max_line_bytes: 8,
+                ..default_output()
+            },
+            CommandGuard::default(),
+            runtime,
+        );
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected run request");
         };
+        app.start_background_job(*request);
+        assert_eq!(app.job_panes.len(), 1);
+        assert_eq!(app.job_panes[0].source, PaneSource::Job(app.jobs[0].id));
+        app.on_key(KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL));
+        assert_eq!(app.focused_job, Some(0));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app
+            .job_panes
+            .first()
+            .is_some_and(|pane| pane.lines.is_empty())
+            && Instant::now() < deadline
+        {
+            app.poll_jobs();
+            thread::sleep(Duration::from_millis(10));
+        }
+        let line = &app.job_panes[0].lines[0];
+        assert_eq!(line.text, "01234567… [+8 bytes]");
+        assert_eq!(line.full_text.as_deref(), Some("0123456789abcdef"));
 
-        let command = &self.commands[command_index];
-        let mut values = HashMap::new();
-        let mut pending_params = Vec::new();
+        while app.jobs[0].is_running() && Instant::now() < deadline {
+            app.poll_jobs();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(app.job_panes.is_empty());
+        assert_eq!(app.focused_job, None);
+    }
 
-        for (idx, param) in command.params.iter().enumerate() {
-            match param.kind {
-                ParamType::Value => {
-                    if let Some(value) = &param.value_value {
-                        values.insert(param.name.clone(), value.clone());
-                        continue;
-                    }
+    #[test]
+    fn scheduled_commands_render_like_other_runs() {
+        let mut check = mock_command("check");
+        check.template = "echo {{command:greet}} in {{cwd}} {{secret:token}}".to_string();
+        check.schedule = Some(Duration::from_secs(60));
+        let mut greet = mock_command("greet");
+        greet.template = "hello {{who}}".to_string();
+        greet.params = vec![ParamSpec {
+            default_value: Some("world".to_string()),
+            ..test_param("who", ParamType::Value)
+        }];
+        let app = app_with(vec![check, greet]);
 
-                    if param.requires_input() {
-                        pending_params.push(idx);
-                        continue;
-                    }
+        assert_eq!(app.schedules.len(), 1);
+        let cwd = app.runtime.cwd.display().to_string();
+        assert_eq!(
+            app.schedules[0].request.command_line,
+            format!("echo hello world in {cwd} ${{FZC_SECRET_TOKEN}}")
+        );
+    }
 
-                    if let Some(default) = &param.default_value {
-                        values.insert(param.name.clone(), default.clone());
-                    }
-                }
-                ParamType::Flag => {
-                    if let Some(value) = param.value_flag {
-                        let token = if value {
-                            param.flag_token()
-                        } else {
-                            String::new()
-                        };
-                        values.insert(param.name.clone(), token);
-                        continue;
-                    }
+    #[test]
+    fn scheduled_commands_run_when_due_and_post_output() {
+        let mut tick = mock_command("queue size");
+        tick.template = "echo tick".to_string();
+        tick.schedule = Some(Duration::from_secs(60));
+        let mut needs_input = mock_command("deploy");
+        needs_input.template = "deploy {{env}}".to_string();
+        needs_input.schedule = Some(Duration::from_secs(60));
+        let mut app = app_with(vec![tick, needs_input]);
+        assert_eq!(app.schedules.len(), 1);
+        assert!(
+            app.chat
+                .iter()
+                .any(|line| line.text.contains("'deploy' skipped"))
+        );
 
-                    if param.requires_input() {
-                        pending_params.push(idx);
-                        continue;
-                    }
+        let started = Instant::now();
+        app.poll_schedules(started);
+        assert!(!app.schedules[0].running);
+        app.poll_schedules(started + Duration::from_secs(61));
+        assert!(app.schedules[0].running);
 
-                    let token = if param.default_flag.unwrap_or(false) {
-                        param.flag_token()
-                    } else {
-                        String::new()
-                    };
-                    values.insert(param.name.clone(), token);
-                }
-            }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.schedules[0].running && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_schedules(started + Duration::from_secs(62));
         }
+        let texts: Vec<&str> = app.chat.iter().map(|line| line.text.as_str()).collect();
+        assert!(
+            texts.contains(&"[schedule] queue size (exit code: 0)"),
+            "{texts:?}"
+        );
+        assert!(texts.contains(&"tick"), "{texts:?}");
+    }
 
-        if pending_params.is_empty() {
-            return self.build_run_request(command_index, values, return_to_tui);
+    #[test]
+    fn schedules_with_the_same_name_are_tracked_apart() {
+        let mut artisan = mock_command("status");
+        artisan.template = "echo artisan".to_string();
+        artisan.schedule = Some(Duration::from_secs(60));
+        let mut config = CommandEntry::new("status", "echo config", CommandSource::Config);
+        config.schedule = Some(Duration::from_secs(60));
+        let mut runtime = test_runtime();
+        runtime.job_panes = 2;
+        let mut app = AppState::new(
+            vec![artisan, config],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        assert_eq!(app.schedules.len(), 2);
+
+        let started = Instant::now() + Duration::from_secs(61);
+        app.poll_schedules(started);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.schedules.iter().any(|job| job.running) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_schedules(started);
         }
+        assert!(app.schedules.iter().all(|job| !job.running));
+        let mut panes: Vec<&str> = app
+            .job_panes
+            .iter()
+            .map(|pane| pane.lines[0].text.as_str())
+            .collect();
+        panes.sort_unstable();
+        assert_eq!(panes, ["artisan", "config"]);
+    }
 
-        self.mode = Mode::Prompt(PromptState {
-            command_index,
-            pending_params,
-            current_param: 0,
-            input: String::new(),
-            values,
-            return_to_tui,
-        });
-        UiAction::None
+    #[test]
+    fn scheduled_runs_get_their_env_and_chained_commands() {
+        let mut greet = mock_command("greet");
+        greet.template = "echo \"$GREETING\"".to_string();
+        greet.env = vec![("GREETING".to_string(), "hi there".to_string())];
+        greet.then = vec!["after".to_string()];
+        greet.schedule = Some(Duration::from_secs(60));
+        let mut after = mock_command("after");
+        after.template = "echo after".to_string();
+        let mut app = app_with(vec![greet, after]);
+        assert_eq!(app.schedules.len(), 1);
+
+        let started = Instant::now() + Duration::from_secs(61);
+        app.poll_schedules(started);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.schedules[0].running && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_schedules(started);
+        }
+        let texts: Vec<&str> = app.chat.iter().map(|line| line.text.as_str()).collect();
+        assert!(texts.contains(&"hi there"), "{texts:?}");
+        assert!(texts.contains(&"after"), "{texts:?}");
     }
 
-    fn build_run_request(
-        &mut self,
-        index: usize,
-        values: HashMap<String, String>,
-        return_to_tui: bool,
-    ) -> UiAction {
-        let command = &self.commands[index];
-        let rendered = render_template(&command.template, &values);
+    #[test]
+    fn scheduled_runs_create_their_working_dir() {
+        let dir = std::env::temp_dir().join(format!("fzc-schedule-dir-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut report = mock_command("report");
+        report.template = "pwd".to_string();
+        report.working_dir = Some(dir.join("out"));
+        report.create_working_dir = true;
+        report.schedule = Some(Duration::from_secs(60));
+        let mut app = app_with(vec![report]);
+        assert_eq!(app.schedules.len(), 1);
+
+        let started = Instant::now() + Duration::from_secs(61);
+        app.poll_schedules(started);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.schedules[0].running && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_schedules(started);
+        }
+        assert!(dir.join("out").is_dir());
+        assert!(
+            app.chat
+                .iter()
+                .any(|line| line.text == "[schedule] report (exit code: 0)")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-        if rendered.contains("{{") && rendered.contains("}}") {
-            self.push_info(format!(
-                "Command '{}' still has unresolved placeholders",
-                command.name
-            ));
-            return UiAction::None;
+    #[test]
+    fn commands_panel_grows_until_output_fills_the_session() {
+        let commands = (0..30)
+            .map(|index| match index {
+                0..10 => mock_command(&format!("deploy-{index:02}")),
+                _ => mock_command(&format!("task-{index:02}")),
+            })
+            .collect();
+        let mut app = app_with(commands);
+        app.chat.clear();
+        let area = Rect::new(0, 0, 100, 40);
+        assert_eq!(screen_layout(&app, area, false).commands.height, 30);
+        app.query = "deploy".to_string();
+        app.refresh_filtered();
+        assert_eq!(app.filtered.len(), 10);
+        assert_eq!(screen_layout(&app, area, false).commands.height, 12);
+        app.query.clear();
+        app.refresh_filtered();
+        for line in 0..100 {
+            app.push_info(format!("output {line}"));
         }
+        let commands = screen_layout(&app, area, false).commands;
+        assert_eq!(commands.height, COMMANDS_MIN_HEIGHT);
 
-        let display_name = command.name.clone();
-        let working_dir = command.working_dir.clone();
-        let usage_key = command_usage_key(command);
+        app.selected = 20;
+        assert_eq!(app.command_list_offset(commands.height as usize - 2), 15);
+        assert_eq!(app.command_row_at(commands, commands.y + 1), Some(15));
+    }
 
-        self.query.clear();
-        self.query_cursor = 0;
-        self.refresh_filtered();
+    #[test]
+    fn stale_provider_commands_show_their_age_and_refresh_on_f5() {
+        let mut migrate = mock_command("migrate");
+        migrate.source = CommandSource::Provider("artisan".to_string());
+        let mut app = app_with(vec![mock_command("deploy"), migrate]);
+        app.provider_loaded_at
+            .insert("artisan".to_string(), history::now() - 60);
+        assert_eq!(app.stale_provider_age("artisan"), None);
+        app.provider_loaded_at
+            .insert("artisan".to_string(), history::now() - 7_200);
+        assert!(
+            app.stale_provider_age("artisan")
+                .is_some_and(|age| age >= 7_200)
+        );
+        assert_eq!(app.stale_provider_age("config"), None);
 
-        UiAction::Run(RunRequest {
-            display_name,
-            command_line: rendered,
-            working_dir,
-            usage_key,
-            return_to_tui,
-        })
+        for name in ["deploy", "migrate"] {
+            app.selected = app
+                .filtered
+                .iter()
+                .position(|item| {
+                    matches!(item, SearchItem::Command(index) if app.commands[*index].name == name)
+                })
+                .unwrap();
+            app.on_key(KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE));
+        }
+        assert_eq!(app.pending_refresh, ["artisan"]);
     }
 
-    fn on_internal_prompt_key(&mut self, key: KeyEvent) -> UiAction {
-        let mut prompt_state = match std::mem::replace(&mut self.mode, Mode::Search) {
-            Mode::InternalPrompt(prompt) => prompt,
-            _ => return UiAction::None,
+    #[test]
+    fn refresh_triggers_reload_a_provider_in_place() {
+        let mut runtime = test_runtime();
+        runtime.providers.external = vec![config::ExternalProviderConfig {
+            name: "gen".to_string(),
+            command: r#"echo '[{"name": "gen one", "run": "true"}]'"#.to_string(),
+            alias: None,
+        }];
+        runtime.providers.refresh =
+            BTreeMap::from([("gen".to_string(), vec!["make gen*".to_string()])]);
+        let mut app = AppState::new(
+            vec![mock_command("migrate")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        let wait_for_loads = |app: &mut AppState| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while app.provider_rx.is_some() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+                app.poll_provider_loads();
+            }
         };
+        app.start_provider_loads();
+        wait_for_loads(&mut app);
+
+        app.queue_provider_refresh("make test");
+        assert!(app.pending_refresh.is_empty());
+        app.runtime.providers.external[0].command =
+            r#"echo '[{"name": "gen two", "run": "true"}]'"#.to_string();
+        app.queue_provider_refresh("make generate");
+        assert!(app.poll_provider_loads());
+        wait_for_loads(&mut app);
+
+        let names: Vec<&str> = app
+            .commands
+            .iter()
+            .map(|command| command.name.as_str())
+            .collect();
+        assert_eq!(names, ["gen two", "migrate"]);
+    }
 
-        match key.code {
-            KeyCode::Esc => {
-                self.push_info("Internal command canceled");
-                self.mode = Mode::Search;
-                UiAction::None
-            }
-            KeyCode::Backspace => {
-                prompt_state.input.pop();
-                self.mode = Mode::InternalPrompt(prompt_state);
-                UiAction::None
-            }
-            KeyCode::Char(ch)
-                if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT =>
-            {
-                let typed = ch.to_string();
-                let command = &self.internal_commands[prompt_state.command_index];
-                let default = command.default_force;
-                if let Some(force) = parse_flag_input(&typed, default) {
-                    self.mode = Mode::Search;
-                    UiAction::RunInternal(InternalRunRequest {
-                        command: InternalCommand::Init { force },
-                    })
-                } else {
-                    prompt_state.input.push(ch);
-                    self.mode = Mode::InternalPrompt(prompt_state);
-                    UiAction::None
-                }
-            }
-            KeyCode::Enter => {
-                let command = &self.internal_commands[prompt_state.command_index];
-                let default = command.default_force;
-                let Some(force) = parse_flag_input(prompt_state.input.trim(), default) else {
-                    self.push_info("Please enter y or n");
-                    self.mode = Mode::InternalPrompt(prompt_state);
-                    return UiAction::None;
-                };
+    #[test]
+    fn reload_replaces_the_provider_config() {
+        let dir = std::env::temp_dir().join(format!("fzc-reload-providers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("fzc.toml");
+        fs::write(
+            &config_path,
+            r#"
+[providers]
+lazy = ["fresh"]
+
+[[providers.external]]
+name = "fresh"
+command = "echo '[]'"
+"#,
+        )
+        .unwrap();
+        let mut runtime = test_runtime();
+        runtime.cwd = dir.clone();
+        runtime.explicit_config_path = Some(config_path);
+        runtime.providers.external = vec![config::ExternalProviderConfig {
+            name: "stale".to_string(),
+            command: "echo '[]'".to_string(),
+            alias: None,
+        }];
+        let mut app = AppState::new(
+            vec![mock_command("migrate")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        app.provider_loaded_at
+            .insert("stale".to_string(), history::now());
 
-                self.mode = Mode::Search;
-                UiAction::RunInternal(InternalRunRequest {
-                    command: InternalCommand::Init { force },
-                })
-            }
-            _ => {
-                self.mode = Mode::InternalPrompt(prompt_state);
-                UiAction::None
-            }
+        let payload = load_catalog_payload(&app.runtime).unwrap();
+        app.apply_reload_payload(payload);
+
+        let names: Vec<&str> = app
+            .runtime
+            .providers
+            .external
+            .iter()
+            .map(|external| external.name.as_str())
+            .collect();
+        assert_eq!(names, ["fresh"]);
+        assert!(app.is_unexpanded("fresh"));
+        assert!(!app.provider_loaded_at.contains_key("stale"));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn provider_results_stream_into_the_catalog() {
+        let mut runtime = test_runtime();
+        runtime.providers.external = vec![
+            config::ExternalProviderConfig {
+                name: "slow".to_string(),
+                command: r#"sleep 0.05; echo '[{"name": "aaa slow", "run": "true"}]'"#.to_string(),
+                alias: None,
+            },
+            config::ExternalProviderConfig {
+                name: "broken".to_string(),
+                command: "exit 1".to_string(),
+                alias: None,
+            },
+        ];
+        let mut app = AppState::new(
+            vec![mock_command("migrate")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        app.selected = 0;
+        app.start_provider_loads();
+        assert_eq!(app.loading_providers, vec!["slow", "broken"]);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.provider_rx.is_some() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_provider_loads();
+        }
+        assert!(app.loading_providers.is_empty());
+        assert_eq!(app.commands.len(), 2);
+        assert_eq!(app.filtered.len(), 2);
+        assert_eq!(
+            app.commands[app.current_command_index().unwrap()].name,
+            "migrate"
+        );
+        assert!(
+            app.chat
+                .iter()
+                .any(|line| line.text == "Loaded 1 slow commands")
+        );
+        assert!(
+            app.chat
+                .iter()
+                .any(|line| line.text.starts_with("provider 'broken' failed"))
+        );
+    }
+
+    #[test]
+    fn lazy_providers_load_when_their_filter_is_typed() {
+        let mut runtime = test_runtime();
+        runtime.providers.external = vec![config::ExternalProviderConfig {
+            name: "big".to_string(),
+            command: r#"echo '[{"name": "target one", "run": "true"}, {"name": "target two", "run": "true"}]'"#
+                .to_string(),
+            alias: Some("b".to_string()),
+        }];
+        runtime.providers.lazy = vec!["big".to_string()];
+        let mut app = AppState::new(
+            vec![mock_command("migrate")],
+            None,
+            HashMap::from([("b".to_string(), "big".to_string())]),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        app.start_provider_loads();
+        assert!(app.provider_rx.is_none());
+        let names = |app: &AppState| -> Vec<String> {
+            app.filtered
+                .iter()
+                .filter_map(|item| match item {
+                    SearchItem::Command(index) => Some(app.commands[*index].name.clone()),
+                    SearchItem::Internal(_) => None,
+                })
+                .collect()
+        };
+        assert_eq!(names(&app), ["migrate", "search big…"]);
+
+        app.selected = 1;
+        app.prepare_selected_command(true);
+        assert_eq!(app.query, ":b ");
+        assert_eq!(app.pending_refresh, ["big"]);
+        assert_eq!(names(&app), ["search big…"]);
+
+        app.poll_provider_loads();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.provider_rx.is_some() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_provider_loads();
         }
+        assert_eq!(names(&app), ["target one", "target two"]);
+
+        app.query.clear();
+        app.refresh_filtered();
+        assert_eq!(names(&app), ["migrate", "search big…"]);
     }
 
-    fn prepare_selected_internal_command(&mut self) -> UiAction {
-        let trimmed = self.query.trim();
-        if let Some(parsed) = parse_internal_command(trimmed) {
-            match parsed {
-                InternalCommand::Reload => {
-                    return UiAction::RunInternal(InternalRunRequest {
-                        command: InternalCommand::Reload,
-                    });
-                }
-                InternalCommand::Init { force } => {
-                    if query_has_force_flag(trimmed) {
-                        return UiAction::RunInternal(InternalRunRequest {
-                            command: InternalCommand::Init { force },
-                        });
-                    }
-                }
-                InternalCommand::Unknown(_) => {}
-            }
-        }
+    #[test]
+    fn preview_follows_prompt_input_and_is_cached() {
+        let mut command = mock_command("git log");
+        command.template = "git log {{branch}}".to_string();
+        command.preview = Some("echo log of {{branch}}".to_string());
+        command.params = vec![ParamSpec {
+            prompt: "Branch".to_string(),
+            default_value: Some("main".to_string()),
+            ..test_param("branch", ParamType::Value)
+        }];
+        let mut app = app_with(vec![command]);
+        assert_eq!(
+            app.wanted_preview().unwrap().unwrap().command_line,
+            "echo log of main"
+        );
 
-        let Some(index) = self.current_internal_index() else {
-            self.push_info("Unknown internal command. Available: /reload, /init");
-            return UiAction::None;
-        };
+        // Nothing runs until the selection has rested for PREVIEW_DEBOUNCE.
+        let started = Instant::now();
+        app.poll_preview(started);
+        app.poll_preview(started + PREVIEW_DEBOUNCE / 2);
+        assert!(app.preview_running.is_none());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        app.poll_preview(started + PREVIEW_DEBOUNCE);
+        assert!(app.preview_running.is_some());
+        while app.preview_running.is_some() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_preview(started + PREVIEW_DEBOUNCE);
+        }
+        assert_eq!(
+            app.preview_cache["echo log of main"].lines,
+            vec!["log of main"]
+        );
+        app.poll_preview(started + PREVIEW_DEBOUNCE);
+        assert!(app.preview_running.is_none());
+
+        // A runner that never reports back is given up on.
+        app.preview_running = Some(("sleep 60 &".to_string(), started));
+        app.poll_preview(started + PREVIEW_TIMEOUT + FORCE_KILL_AFTER);
+        assert!(app.preview_running.is_none());
+        assert_eq!(
+            app.preview_cache["sleep 60 &"].lines,
+            vec!["preview did not finish within 10s"]
+        );
 
-        let command = &self.internal_commands[index];
-        match command.kind {
-            InternalCommandKind::Reload => UiAction::RunInternal(InternalRunRequest {
-                command: InternalCommand::Reload,
-            }),
-            InternalCommandKind::Init => {
-                self.mode = Mode::InternalPrompt(InternalPromptState {
-                    command_index: index,
-                    input: String::new(),
-                });
-                UiAction::None
-            }
+        app.prepare_selected_command(true);
+        for ch in "dev".chars() {
+            app.on_prompt_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
         }
+        assert_eq!(
+            app.wanted_preview().unwrap().unwrap().command_line,
+            "echo log of dev"
+        );
     }
 
-    fn apply_reload_payload(&mut self, payload: ReloadPayload) {
-        self.commands = payload.commands;
-        self.config_path = payload.config_path;
-        self.provider_aliases = payload.provider_aliases;
-        self.provider_alias_by_name = self
-            .provider_aliases
+    #[test]
+    fn previews_render_like_runs_and_wait_for_required_params() {
+        let mut status = mock_command("status");
+        status.template = "deploy {{target}}".to_string();
+        status.preview = Some("{{command:base}} {{target}} in {{cwd}}".to_string());
+        status.params = vec![test_param("target", ParamType::Value)];
+        let mut base = mock_command("base");
+        base.template = "kubectl get {{kind}}".to_string();
+        base.params = vec![ParamSpec {
+            default_value: Some("pods".to_string()),
+            ..test_param("kind", ParamType::Value)
+        }];
+        let mut app = app_with(vec![status, base]);
+        app.selected = app
+            .filtered
             .iter()
-            .map(|(alias, provider)| (provider.clone(), alias.clone()))
-            .collect();
-        self.provider_names_without_alias =
-            provider_names_without_alias(&self.commands, &self.provider_alias_by_name);
-        self.ranking = payload.ranking;
-        self.refresh_filtered();
-        if self.selected >= self.filtered.len() {
-            self.selected = 0;
+            .position(|item| {
+                matches!(item, SearchItem::Command(index) if app.commands[*index].name == "status")
+            })
+            .unwrap();
+
+        let Some(Err(err)) = app.wanted_preview() else {
+            panic!("expected the preview to wait for 'target'");
+        };
+        assert_eq!(err, "required param 'target' has no value or default");
+
+        app.prepare_selected_command(true);
+        for ch in "web".chars() {
+            app.on_prompt_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
         }
+        let cwd = app.runtime.cwd.display().to_string();
+        assert_eq!(
+            app.wanted_preview().unwrap().unwrap().command_line,
+            format!("kubectl get pods web in {cwd}")
+        );
     }
 
-    fn push_info<S: Into<String>>(&mut self, text: S) {
-        self.push_line(ChatLineKind::Info, text.into());
-    }
+    #[cfg(unix)]
+    #[test]
+    fn eval_params_are_expanded_before_substitution() {
+        let mut command = mock_command("tag");
+        command.template = "git tag {{tag}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Tag".to_string(),
+            eval: true,
+            ..test_param("tag", ParamType::Value)
+        }];
+        let mut app = app_with(vec![command]);
+        let values = |tag: &str| HashMap::from([("tag".to_string(), tag.to_string())]);
 
-    fn push_command<S: Into<String>>(&mut self, text: S) {
-        self.push_line(ChatLineKind::Command, text.into());
+        let UiAction::Run(request) =
+            app.build_run_request(0, values("v$((1 + 2)) $(echo 'a b')"), None, true)
+        else {
+            panic!("expected run request");
+        };
+        assert_eq!(request.command_line, "git tag 'v3 a b'");
+        assert_eq!(request.params["tag"], "v$((1 + 2)) $(echo 'a b')");
+        assert!(
+            app.resolve_single_request(0, values("$(false)"), None, true)
+                .is_err()
+        );
+        let rendered = |app: &mut AppState, tag: &str| {
+            app.resolve_single_request(0, values(tag), None, true)
+                .unwrap()
+                .command_line
+        };
+        assert_eq!(
+            rendered(&mut app, "$(printf 'x; echo pwned')"),
+            "git tag 'x; echo pwned'"
+        );
+        assert_eq!(
+            rendered(&mut app, r#"say "$(echo "hi")" \"#),
+            r#"git tag 'say "hi" \'"#
+        );
+
+        let marker = std::env::temp_dir().join(format!("fzc-eval-dry-{}", std::process::id()));
+        let touch = format!("$(touch {})", marker.display());
+        app.dry_run = true;
+        assert!(matches!(
+            app.build_run_request(0, values(&touch), None, true),
+            UiAction::None
+        ));
+        let Mode::DryRun(dry_run) = &app.mode else {
+            panic!("expected the dry-run popup");
+        };
+        assert_eq!(dry_run.request.command_line, format!("git tag {touch}"));
+        assert!(!marker.exists());
+        app.mode = Mode::Search;
+
+        app.prepare_selected_command(true);
+        for ch in "$(pwd)".chars() {
+            app.on_prompt_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        assert_eq!(
+            app.wanted_eval_preview().unwrap().command_line,
+            eval_command_line("$(pwd)")
+        );
     }
 
-    fn push_error<S: Into<String>>(&mut self, text: S) {
-        self.push_line(ChatLineKind::Stderr, text.into());
+    #[cfg(unix)]
+    #[test]
+    fn http_param_values_cannot_break_out_of_their_argument() {
+        let raw = r#"
+[[commands]]
+name = "Create user"
+type = "http"
+method = "post"
+url = "https://api.example.com/users/{{id}}"
+headers = { Authorization = "Bearer {{token}}" }
+body = '{"name": "{{name}}"}'
+"#;
+        let config: config::Config = toml::from_str(raw).unwrap();
+        let mut command = crate::model::command_from_config(&config.commands[0], Path::new("/tmp"));
+        command.params = ["id", "token", "name"]
+            .map(|name| test_param(name, ParamType::Value))
+            .to_vec();
+        let app = app_with(vec![command]);
+        let values = HashMap::from([
+            ("id".to_string(), "7 8".to_string()),
+            ("token".to_string(), "x'; touch pwned; echo '".to_string()),
+            ("name".to_string(), "$(whoami) \"ada\"".to_string()),
+        ]);
+        let request = app.resolve_single_request(0, values, None, true).unwrap();
+
+        let args = Command::new("sh")
+            .arg("-c")
+            .arg(request.command_line.replacen("curl", "printf '%s\\n'", 1))
+            .output()
+            .unwrap();
+        let args = String::from_utf8(args.stdout).unwrap();
+        assert_eq!(
+            args.lines().collect::<Vec<_>>(),
+            [
+                "-sS",
+                "--fail-with-body",
+                "-X",
+                "POST",
+                "-H",
+                "Authorization: Bearer x'; touch pwned; echo '",
+                "--data-raw",
+                r#"{"name": "$(whoami) "ada""}"#,
+                "https://api.example.com/users/7 8",
+            ]
+        );
     }
 
-    fn push_line(&mut self, kind: ChatLineKind, text: String) {
-        self.chat.push(ChatLine { kind, text });
-        if self.active_pane == ActivePane::Commands {
-            self.session_scroll = 0;
-        }
-        if self.chat.len() > MAX_CHAT_LINES {
-            let overflow = self.chat.len() - MAX_CHAT_LINES;
-            self.chat.drain(0..overflow);
-            self.session_scroll = self.session_scroll.saturating_sub(overflow);
-        }
+    #[test]
+    fn headless_values_come_from_flags_answers_and_defaults() {
+        let param = |name: &str, kind, default: Option<&str>| ParamSpec {
+            default_value: default.map(ToString::to_string),
+            ..test_param(name, kind)
+        };
+        let mut command = mock_command("deploy");
+        command.params = vec![
+            param("branch", ParamType::Value, Some("main")),
+            param("force", ParamType::Flag, None),
+            param("region", ParamType::Value, None),
+        ];
+
+        let given = vec![("force".to_string(), "yes".to_string())];
+        let values = resolve_headless_values(&command, None, &given, &mut |param| {
+            Ok((param.name == "region").then(|| "eu-west-1".to_string()))
+        })
+        .unwrap();
+        assert_eq!(values["branch"], "main");
+        assert_eq!(values["force"], "--force");
+        assert_eq!(values["region"], "eu-west-1");
+
+        let err = resolve_headless_values(&command, None, &[], &mut |_| Ok(None)).unwrap_err();
+        assert!(err.to_string().contains("--param region=<value>"));
+        let unknown = vec![("colour".to_string(), "red".to_string())];
+        assert!(resolve_headless_values(&command, None, &unknown, &mut |_| Ok(None)).is_err());
     }
 
-    fn start_loading(&mut self, label: &str) {
-        self.is_loading = true;
-        self.loading_label = Some(label.to_string());
-        self.spinner_index = 0;
+    #[test]
+    fn headless_lookup_accepts_name_or_provider_key() {
+        let mut composer_test = mock_command("test");
+        composer_test.source = CommandSource::Provider("composer".to_string());
+        let commands = vec![mock_command("migrate"), mock_command("test"), composer_test];
+        assert_eq!(find_command_by_name(&commands, "MIGRATE").unwrap(), 0);
+        assert_eq!(
+            find_command_by_name(&commands, "composer::test").unwrap(),
+            2
+        );
+        let err = find_command_by_name(&commands, "test").unwrap_err();
+        assert!(err.to_string().contains("ambiguous"));
+        assert!(find_command_by_name(&commands, "nope").is_err());
     }
 
-    fn stop_loading(&mut self) {
-        self.is_loading = false;
-        self.loading_label = None;
+    #[test]
+    fn history_rerun_prefills_previous_param_values() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy {{branch}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Branch".to_string(),
+            ..test_param("branch", ParamType::Value)
+        }];
+        let key = command_usage_key(&command);
+        let mut app = app_with(vec![command]);
+
+        let entry = HistoryEntry {
+            command: key,
+            name: "deploy".to_string(),
+            command_line: "deploy release".to_string(),
+            timestamp: 0,
+            cwd: PathBuf::from("/"),
+            exit_code: Some(0),
+            environment: None,
+            params: BTreeMap::from([("branch".to_string(), "release".to_string())]),
+            timed_out: false,
+        };
+        app.rerun_history_entry(entry.clone(), false);
+        let Mode::Prompt(prompt) = &app.mode else {
+            panic!("expected the param prompt");
+        };
+        assert_eq!(prompt.input, "release");
+
+        app.mode = Mode::Search;
+        app.rerun_history_entry(
+            HistoryEntry {
+                command: "config::gone".to_string(),
+                ..entry
+            },
+            false,
+        );
+        assert!(matches!(app.mode, Mode::Search));
     }
 
-    fn tick_loading(&mut self) {
-        if self.is_loading {
-            self.spinner_index = (self.spinner_index + 1) % SPINNER_FRAMES.len();
+    #[test]
+    fn history_edit_prompts_for_defaulted_params_and_drops_masked_values() {
+        let param = |name: &str, default: Option<&str>| ParamSpec {
+            default_value: default.map(ToString::to_string),
+            required: default.is_none(),
+            prompt_in_tui: false,
+            ..test_param(name, ParamType::Value)
+        };
+        let mut command = mock_command("deploy");
+        command.template = "deploy {{app}} {{tag}} {{force}}".to_string();
+        command.params = vec![
+            param("app", Some("api")),
+            param("tag", None),
+            ParamSpec {
+                required: false,
+                prompt_in_tui: false,
+                default_flag: Some(false),
+                ..test_param("force", ParamType::Flag)
+            },
+        ];
+        let entry = HistoryEntry {
+            command: command_usage_key(&command),
+            name: "deploy".to_string(),
+            command_line: "deploy web v1".to_string(),
+            timestamp: 0,
+            cwd: PathBuf::from("/"),
+            exit_code: Some(0),
+            environment: None,
+            params: BTreeMap::from([
+                ("app".to_string(), "web".to_string()),
+                ("tag".to_string(), MASK.to_string()),
+                ("force".to_string(), "--force".to_string()),
+            ]),
+            timed_out: false,
+        };
+        let mut app = app_with(vec![command]);
+
+        app.rerun_history_entry(entry.clone(), false);
+        let Mode::Prompt(prompt) = &app.mode else {
+            panic!("expected the tag prompt");
+        };
+        assert_eq!(prompt.pending_params, vec![1, 2]);
+        assert_eq!(prompt.input, "");
+        for ch in "v1".chars() {
+            app.on_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
         }
-    }
+        app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let UiAction::Run(request) = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+        else {
+            panic!("expected a run");
+        };
+        assert_eq!(request.command_line, "deploy web v1 --force");
 
-    fn spinner_frame(&self) -> &'static str {
-        SPINNER_FRAMES[self.spinner_index % SPINNER_FRAMES.len()]
+        app.mode = Mode::Search;
+        app.rerun_history_entry(entry, true);
+        let Mode::Prompt(prompt) = &app.mode else {
+            panic!("expected the app prompt");
+        };
+        assert_eq!(prompt.pending_params, vec![0, 1, 2]);
+        assert_eq!(prompt.input, "web");
+        app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        for ch in "v2".chars() {
+            app.on_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let Mode::Prompt(prompt) = &app.mode else {
+            panic!("expected the force prompt");
+        };
+        assert_eq!(prompt.input, "y");
+        let UiAction::Run(request) = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+        else {
+            panic!("expected a run");
+        };
+        assert_eq!(request.command_line, "deploy web v2 --force");
     }
 
-    fn record_usage(&mut self, key: &str) {
-        let entry = self.usage_counts.entry(key.to_string()).or_insert(0);
-        *entry = entry.saturating_add(1);
-        let _ = persist_usage_store(&self.usage_counts, self.usage_path.as_deref());
+    #[test]
+    fn choice_prompt_filters_options_and_picks_the_highlighted_one() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy {{env}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Environment".to_string(),
+            default_value: Some("production".to_string()),
+            required: false,
+            ..test_param(
+                "env",
+                ParamType::Choice(vec![
+                    "staging".to_string(),
+                    "production".to_string(),
+                    "preview".to_string(),
+                ]),
+            )
+        }];
+        let mut app = app_with(vec![command]);
+
+        app.prepare_command(0, None, true, HashMap::new());
+        let Mode::Prompt(prompt) = &app.mode else {
+            panic!("expected the choice prompt");
+        };
+        assert_eq!(prompt.choice_selected, 1);
+
+        for ch in "prv".chars() {
+            app.on_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        app.on_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        let UiAction::Run(request) = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+        else {
+            panic!("expected run request");
+        };
+        assert_eq!(request.command_line, "deploy preview");
     }
 
-    fn usage_boost_for_command(&self, command: &CommandEntry) -> i64 {
-        if !self.ranking.usage_enabled {
-            return 0;
+    #[test]
+    fn path_prompt_completes_directories_and_glob_matching_files() {
+        let root = std::env::temp_dir().join(format!("fzc-path-param-{}", std::process::id()));
+        fs::create_dir_all(root.join("db")).unwrap();
+        for file in ["db/a.sql", "db/b.sql", "db/notes.txt"] {
+            fs::write(root.join(file), "").unwrap();
         }
+        let mut command = mock_command("import");
+        command.template = "import {{dump}}".to_string();
+        command.working_dir = Some(root.clone());
+        command.params = vec![ParamSpec {
+            prompt: "Dump".to_string(),
+            ..test_param("dump", ParamType::Path(Some("*.sql".to_string())))
+        }];
+        let mut app = app_with(vec![command]);
+        let input = |app: &AppState| match &app.mode {
+            Mode::Prompt(prompt) => prompt.input.clone(),
+            _ => panic!("expected the path prompt"),
+        };
 
-        let usage = self
-            .usage_counts
-            .get(&command_usage_key(command))
-            .copied()
-            .unwrap_or_default();
-        let usage = usage.min(i64::MAX as u64) as i64;
-        usage.saturating_mul(self.ranking.usage_weight.max(0))
+        app.prepare_command(0, None, true, HashMap::new());
+        app.on_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE));
+        app.on_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        assert_eq!(input(&app), "db/");
+        let Mode::Prompt(prompt) = &app.mode else {
+            unreachable!()
+        };
+        assert_eq!(
+            app.prompt_listing(prompt),
+            Some(vec!["db/a.sql".to_string(), "db/b.sql".to_string()])
+        );
+
+        app.on_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        app.on_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        assert_eq!(input(&app), "db/b.sql");
+        let UiAction::Run(request) = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+        else {
+            panic!("expected run request");
+        };
+        assert_eq!(request.command_line, "import db/b.sql");
+        assert_eq!(quote_path_value("db/my dump.sql"), "'db/my dump.sql'");
+        assert_eq!(quote_path_value("~/my dump.sql"), "~/'my dump.sql'");
+
+        let _ = fs::remove_dir_all(root);
     }
 
-    fn is_internal_query(&self) -> bool {
-        self.query.trim_start().starts_with('/')
+    #[test]
+    fn prompt_shows_the_command_line_rendered_so_far() {
+        let param = |name: &str, kind: ParamType| test_param(name, kind);
+        let mut command = mock_command("deploy");
+        command.template = "{{command:prefix}} deploy {{env}} --tag={{tag}} {{cwd}}".to_string();
+        command.params = vec![
+            param(
+                "env",
+                ParamType::Choice(vec!["staging".to_string(), "production".to_string()]),
+            ),
+            param("tag", ParamType::Value),
+        ];
+        let mut prefix = mock_command("prefix");
+        prefix.template = "cd {{dir}} &&".to_string();
+        prefix.params = vec![ParamSpec {
+            default_value: Some("app".to_string()),
+            ..param("dir", ParamType::Value)
+        }];
+        let mut app = app_with(vec![command, prefix]);
+        let cwd = app.runtime.cwd.display().to_string();
+        let rendered = |app: &AppState| match &app.mode {
+            Mode::Prompt(prompt) => app.prompt_command_line(prompt),
+            _ => panic!("expected a prompt"),
+        };
+
+        app.prepare_command(0, None, true, HashMap::new());
+        assert_eq!(
+            rendered(&app),
+            format!("cd app && deploy staging --tag={{{{tag}}}} {cwd}")
+        );
+        app.on_key(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::NONE));
+        assert_eq!(
+            rendered(&app),
+            format!("cd app && deploy production --tag={{{{tag}}}} {cwd}")
+        );
+        app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        app.on_key(KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE));
+        app.on_key(KeyEvent::new(KeyCode::Char('2'), KeyModifiers::NONE));
+        assert_eq!(
+            rendered(&app),
+            format!("cd app && deploy production --tag=v2 {cwd}")
+        );
     }
 
-    fn current_internal_index(&self) -> Option<usize> {
-        match self.filtered.get(self.selected).copied() {
-            Some(SearchItem::Internal(index)) => Some(index),
-            _ => None,
-        }
+    #[test]
+    fn hooks_fall_back_to_global_ones_and_render_run_details() {
+        let mut command = mock_command("deploy");
+        command.hooks =
+            CommandHooks::from_config(None, Some("notify {{name}}' failed: {{exit_code}}'"));
+        let mut runtime = test_runtime();
+        runtime.hooks =
+            CommandHooks::from_config(Some("log {{name}} {{duration}}s"), Some("global"));
+        let mut app = AppState::new(
+            vec![command],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        let UiAction::Run(mut request) = app.build_run_request(0, HashMap::new(), None, true)
+        else {
+            panic!("expected run request");
+        };
+
+        let elapsed = Duration::from_millis(1_250);
+        assert_eq!(
+            hook_command_line(&request, Some(0), elapsed).as_deref(),
+            Some("log deploy 1.25s")
+        );
+        assert_eq!(
+            hook_command_line(&request, Some(2), elapsed).as_deref(),
+            Some("notify deploy' failed: 2'")
+        );
+        assert_eq!(
+            hook_command_line(&request, None, elapsed).as_deref(),
+            Some("notify deploy' failed: '")
+        );
+
+        // Names are quoted, so they cannot break out of the hook's command line.
+        request.display_name = "x'; touch pwned; '".to_string();
+        assert_eq!(
+            hook_command_line(&request, Some(0), elapsed).as_deref(),
+            Some(r"log 'x'\''; touch pwned; '\''' 1.25s")
+        );
     }
 
-    fn refresh_filtered(&mut self) {
-        if self.is_internal_query() {
-            let trimmed = self.query.trim_start();
-            let internal_query = trimmed.trim_start_matches('/').trim();
-            let normalized = internal_query.to_lowercase();
-            let mut scored = Vec::new();
+    #[test]
+    fn hooks_are_checked_against_safety_patterns() {
+        let mut command = mock_command("deploy");
+        command.hooks = CommandHooks::from_config(Some("rm -rf /tmp/{{name}}"), Some("echo ok"));
+        let guard = CommandGuard::from_config(&crate::config::SafetyConfig {
+            confirm: vec!["*rm -rf*".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let mut app = AppState::new(
+            vec![command],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            guard.clone(),
+            test_runtime(),
+        );
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected run request");
+        };
 
-            for (index, command) in self.internal_commands.iter().enumerate() {
-                let haystack = format!("{} {}", command.name, command.description).to_lowercase();
-                let fuzzy = if normalized.is_empty() {
-                    1
-                } else {
-                    self.matcher
-                        .fuzzy_match(&haystack, &normalized)
-                        .unwrap_or_default()
-                };
+        let elapsed = Duration::from_secs(1);
+        assert_eq!(
+            guarded_hook(&guard, &request, Some(0), elapsed),
+            Some(Err("hook skipped: blocked by [safety] patterns".to_string()))
+        );
+        assert_eq!(
+            guarded_hook(&guard, &request, Some(1), elapsed),
+            Some(Ok("echo ok".to_string()))
+        );
+    }
 
-                if normalized.is_empty() || fuzzy > 0 || haystack.contains(&normalized) {
-                    let contains_bonus = if !normalized.is_empty() && haystack.contains(&normalized)
-                    {
-                        10_000
-                    } else {
-                        0
-                    };
-                    scored.push((
-                        index,
-                        fuzzy.saturating_mul(10) + contains_bonus,
-                        command.name,
-                    ));
-                }
-            }
+    #[test]
+    fn command_timeout_overrides_the_default() {
+        let mut deploy = mock_command("deploy");
+        deploy.timeout = Some(Duration::from_secs(30));
+        let output = OutputSettings {
+            timeout: Some(Duration::from_secs(600)),
+            ..default_output()
+        };
+        let mut app = AppState::new(
+            vec![deploy, mock_command("test")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            output,
+            CommandGuard::default(),
+            test_runtime(),
+        );
+        let timeouts: Vec<_> = (0..2)
+            .map(
+                |index| match app.build_run_request(index, HashMap::new(), None, true) {
+                    UiAction::Run(request) => request.timeout,
+                    _ => panic!("expected run request"),
+                },
+            )
+            .collect();
+        assert_eq!(
+            timeouts,
+            [
+                Some(Duration::from_secs(30)),
+                Some(Duration::from_secs(600))
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_working_dirs_are_reported_or_created() {
+        let root = std::env::temp_dir().join(format!("fzc-workdir-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let mut strict = mock_command("build");
+        strict.working_dir = Some(root.join("build"));
+        let mut created = mock_command("scratch");
+        created.working_dir = Some(root.join("scratch/nested"));
+        created.create_working_dir = true;
+        let mut app = app_with(vec![strict, created]);
+        assert!(
+            app.chat
+                .iter()
+                .any(|line| line.text.contains("run /doctor"))
+        );
 
-            scored.sort_by(|a, b| match b.1.cmp(&a.1) {
-                Ordering::Equal => a.2.cmp(b.2),
-                other => other,
-            });
-            self.filtered = scored
-                .into_iter()
-                .map(|(index, _, _)| SearchItem::Internal(index))
-                .collect();
-            self.selected = 0;
-            return;
-        }
+        let Err(message) = app.resolve_run_request(0, HashMap::new(), None, true) else {
+            panic!("missing working_dir should be refused");
+        };
+        assert!(message.contains("does not exist"));
+        app.run_doctor();
+        let reported: Vec<&str> = app
+            .chat
+            .iter()
+            .filter(|line| line.text.starts_with('\''))
+            .map(|line| line.text.as_str())
+            .collect();
+        assert_eq!(reported.len(), 1);
+        assert!(reported[0].starts_with("'build': working_dir"));
 
-        let (provider_filter, query, unknown_alias) = parse_query_provider_filter(
-            self.query.as_str(),
-            &self.provider_aliases,
-            &self.provider_names_without_alias,
+        let request = app
+            .resolve_run_request(1, HashMap::new(), None, true)
+            .unwrap();
+        assert!(prepare_working_dir(&request).unwrap());
+        assert!(root.join("scratch/nested").is_dir());
+        assert!(!prepare_working_dir(&request).unwrap());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn retries_rerun_failed_commands_until_one_succeeds() {
+        let marker = std::env::temp_dir().join(format!("fzc-retry-{}", std::process::id()));
+        let _ = fs::remove_file(&marker);
+        let mut command = mock_command("flaky");
+        command.template = format!(
+            "test -f '{0}' || {{ touch '{0}'; exit 3; }}",
+            marker.display()
         );
-        if unknown_alias {
-            self.filtered.clear();
-            self.selected = 0;
-            return;
-        }
+        let mut app = app_with(vec![command]);
+        let UiAction::Run(mut request) = app.build_run_request(0, HashMap::new(), None, false)
+        else {
+            panic!("expected run request");
+        };
 
-        if query.is_empty() {
-            let mut ordered: Vec<(usize, i64, String)> = self
-                .commands
-                .iter()
-                .enumerate()
-                .filter(|(_, command)| {
-                    provider_filter.is_none_or(|provider| {
-                        command_provider_name(command).eq_ignore_ascii_case(provider)
-                    })
-                })
-                .map(|(index, command)| {
-                    (
-                        index,
-                        self.usage_boost_for_command(command),
-                        command.name.to_lowercase(),
-                    )
-                })
-                .collect();
-            ordered.sort_by(|a, b| match b.1.cmp(&a.1) {
-                Ordering::Equal => a.2.cmp(&b.2),
-                other => other,
-            });
-            self.filtered = ordered
-                .into_iter()
-                .map(|entry| SearchItem::Command(entry.0))
-                .collect();
-            self.selected = 0;
-            return;
-        }
+        assert_eq!(run_inherit_with_retries(&request).unwrap(), (3, false));
+        fs::remove_file(&marker).unwrap();
+        request.retry = RetryPolicy {
+            retries: 2,
+            delay: Duration::ZERO,
+        };
+        assert_eq!(run_inherit_with_retries(&request).unwrap(), (0, false));
 
-        let query_terms = tokenize_for_match(query);
-        let mut scored = Vec::new();
+        let _ = fs::remove_file(marker);
+    }
 
-        for (index, command) in self.commands.iter().enumerate() {
-            if provider_filter.is_some_and(|provider| {
-                !command_provider_name(command).eq_ignore_ascii_case(provider)
-            }) {
-                continue;
-            }
+    #[cfg(unix)]
+    #[test]
+    fn interrupting_lets_the_command_clean_up() {
+        let mut process = Command::new("sh");
+        process
+            .arg("-c")
+            .arg("trap 'echo cleaned up; exit 3' INT; echo ready; while :; do sleep 0.05; done")
+            .stdout(Stdio::piped());
+        own_process_group(&mut process);
+        let mut child = process.spawn().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        assert_eq!(line, "ready\n");
+
+        stop_process_group(&mut child, Stop::Interrupt);
+        let status = child.wait().unwrap();
+        line.clear();
+        stdout.read_line(&mut line).unwrap();
+        assert_eq!(line, "cleaned up\n");
+        assert_eq!(status.code(), Some(3));
+    }
 
-            let mut haystack = command.name.clone();
-            if let Some(desc) = &command.description {
-                haystack.push(' ');
-                haystack.push_str(desc);
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn killing_reaches_processes_the_shell_started() {
+        let mut process = Command::new("sh");
+        process
+            .arg("-c")
+            .arg("sleep 30 & echo $!; wait")
+            .stdout(Stdio::piped());
+        own_process_group(&mut process);
+        let mut child = process.spawn().unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let sleep_pid = line.trim().to_string();
+
+        stop_process_group(&mut child, Stop::Kill);
+        child.wait().unwrap();
+        // Gone, or a zombie until its new parent reaps it.
+        let stopped = (0..100).any(|_| {
+            let state = fs::read_to_string(format!("/proc/{sleep_pid}/stat")).unwrap_or_default();
+            let stopped = state.is_empty() || state.contains(") Z ");
+            if !stopped {
+                thread::sleep(Duration::from_millis(10));
             }
+            stopped
+        });
+        assert!(stopped, "sleep {sleep_pid} survived the group kill");
+    }
 
-            if let Some(score) = score_command_match(&self.matcher, query, &query_terms, command) {
-                let usage_bonus = self.usage_boost_for_command(command);
-                scored.push((
-                    index,
-                    score.total.saturating_add(usage_bonus),
-                    score.fuzzy,
-                    command.name.to_lowercase(),
-                ));
-            }
+    #[test]
+    fn inherit_commands_always_hand_the_terminal_over() {
+        let mut shell = mock_command("ssh box");
+        shell.inherit = true;
+        let mut app = app_with(vec![shell, mock_command("ls")]);
+
+        for (name, return_to_tui) in [("ssh box", false), ("ls", true)] {
+            let index = app.commands.iter().position(|command| command.name == name);
+            let UiAction::Run(request) =
+                app.build_run_request(index.unwrap(), HashMap::new(), None, true)
+            else {
+                panic!("expected run request");
+            };
+            assert_eq!(request.return_to_tui, return_to_tui);
         }
+    }
 
-        scored.sort_by(|a, b| match b.1.cmp(&a.1) {
-            Ordering::Equal => match b.2.cmp(&a.2) {
-                Ordering::Equal => a.3.cmp(&b.3),
-                other => other,
-            },
-            other => other,
+    #[test]
+    fn targets_launch_in_tmux_or_the_terminal_launcher() {
+        let mut server = mock_command("dev server");
+        server.template = "npm run dev".to_string();
+        server.env = vec![("PORT".to_string(), "3000".to_string())];
+        server.target = Some(ExecTarget::TmuxWindow);
+        let output = OutputSettings {
+            target: ExecTarget::Terminal,
+            terminal: Some("term --cwd {{cwd}} -e {{command}}".to_string()),
+            ..default_output()
+        };
+        let mut app = AppState::new(
+            vec![server, mock_command("ls")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            output,
+            CommandGuard::default(),
+            test_runtime(),
+        );
+        let [server, ls] = ["dev server", "ls"].map(|name| {
+            let index = app.commands.iter().position(|command| command.name == name);
+            match app.build_run_request(index.unwrap(), HashMap::new(), None, true) {
+                UiAction::Run(request) => *request,
+                _ => panic!("expected run request"),
+            }
         });
+        let args = |process: &Command| {
+            process
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let cwd = Path::new("/work");
+        let terminal = app.output.terminal.as_deref();
+
+        assert!(target_process(&server, cwd, terminal, false).is_err());
+        let tmux = target_process(&server, cwd, terminal, true).unwrap();
+        assert_eq!(tmux.get_program(), "tmux");
+        assert_eq!(
+            args(&tmux),
+            [
+                "new-window",
+                "-n",
+                "dev server",
+                "-c",
+                "/work",
+                "-e",
+                "PORT=3000",
+                "npm run dev"
+            ]
+        );
 
-        self.filtered = scored
-            .into_iter()
-            .map(|entry| SearchItem::Command(entry.0))
-            .collect();
-        self.selected = 0;
-    }
-
-    fn move_selection(&mut self, direction: isize) {
-        if self.filtered.is_empty() {
-            self.selected = 0;
-            return;
-        }
+        assert_eq!(ls.target, ExecTarget::Terminal);
+        let launcher = target_process(&ls, cwd, terminal, false).unwrap();
+        assert_eq!(
+            args(&launcher).last().unwrap(),
+            "term --cwd /work -e 'php artisan ls'"
+        );
 
-        let len = self.filtered.len() as isize;
-        let next = (self.selected as isize + direction).rem_euclid(len);
-        self.selected = next as usize;
+        let mut secret = server.clone();
+        secret.secrets = vec!["api token".to_string()];
+        secret
+            .env
+            .push((secrets::env_var("api token"), "hunter2-secret".to_string()));
+        let tmux = target_process(&secret, cwd, terminal, true).unwrap();
+        let args = args(&tmux);
+        assert!(args.iter().all(|arg| !arg.contains("hunter2")));
+        assert!(args.contains(&"PORT=3000".to_string()));
+        let file = args
+            .last()
+            .unwrap()
+            .split_whitespace()
+            .find(|word| word.contains("fzc-env-"))
+            .unwrap()
+            .to_string();
+        let written = fs::read_to_string(&file).unwrap();
+        assert!(written.contains("hunter2-secret"));
+        let _ = fs::remove_file(file);
     }
 
-    fn move_selection_by(&mut self, step: isize) {
-        self.move_selection(step);
-    }
+    #[test]
+    fn timeouts_stop_headless_and_background_runs() {
+        let mut hang = mock_command("hang");
+        hang.template = "sleep 30".to_string();
+        hang.timeout = Some(Duration::from_millis(200));
+        let mut app = app_with(vec![hang]);
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, false) else {
+            panic!("expected run request");
+        };
 
-    fn scroll_session(&mut self, delta: isize) {
-        if delta > 0 {
-            self.session_scroll = self
-                .session_scroll
-                .saturating_add(delta as usize)
-                .min(self.chat.len().saturating_sub(1));
-        } else if delta < 0 {
-            self.session_scroll = self.session_scroll.saturating_sub((-delta) as usize);
+        let started = Instant::now();
+        assert_eq!(run_inherit_with_retries(&request).unwrap(), (124, true));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        app.start_background_job(*request);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.jobs[0].is_running() && Instant::now() < deadline {
+            app.poll_jobs();
+            thread::sleep(Duration::from_millis(10));
         }
-    }
+        assert_eq!(app.jobs[0].status, JobStatus::Exited(124));
+        assert!(
+            app.chat
+                .iter()
+                .any(|line| line.text.starts_with("[job 1] hang timed out after"))
+        );
 
-    fn current_command_index(&self) -> Option<usize> {
-        match self.filtered.get(self.selected).copied() {
-            Some(SearchItem::Command(index)) => Some(index),
-            _ => None,
+        let mut tick = mock_command("stuck tick");
+        tick.template = "sleep 30".to_string();
+        tick.timeout = Some(Duration::from_millis(200));
+        tick.schedule = Some(Duration::from_secs(60));
+        let mut app = app_with(vec![tick]);
+        let started = Instant::now();
+        app.poll_schedules(started + Duration::from_secs(61));
+        assert!(app.schedules[0].running);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while app.schedules[0].running && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            app.poll_schedules(started + Duration::from_secs(62));
         }
+        assert!(!app.schedules[0].running);
+        assert!(app.chat.iter().any(|line| {
+            line.text
+                .starts_with("[schedule] stuck tick: timed out after")
+        }));
     }
-}
 
-fn provider_names_without_alias(
-    commands: &[CommandEntry],
-    provider_alias_by_name: &HashMap<String, String>,
-) -> HashSet<String> {
-    commands
-        .iter()
-        .map(command_provider_name)
-        .filter(|provider| !provider_alias_by_name.contains_key(*provider))
-        .map(|provider| provider.to_ascii_lowercase())
-        .collect()
-}
+    #[test]
+    fn background_jobs_report_output_and_can_be_stopped() {
+        let mut quick = mock_command("build");
+        quick.template = "echo built; echo warn >&2".to_string();
+        quick.background = true;
+        let mut server = mock_command("serve");
+        server.template = "sleep 30".to_string();
+        let mut app = app_with(vec![quick, server]);
+        let wait_for = |app: &mut AppState, done: &dyn Fn(&AppState) -> bool| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !done(app) && Instant::now() < deadline {
+                app.poll_jobs();
+                thread::sleep(Duration::from_millis(10));
+            }
+        };
 
-fn command_provider_name(command: &CommandEntry) -> &'static str {
-    match command.source {
-        CommandSource::Config => "config",
-        CommandSource::Provider(name) => name,
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected run request");
+        };
+        assert!(request.background);
+        app.start_background_job(*request);
+        wait_for(&mut app, &|app| {
+            app.jobs[0].status == JobStatus::Exited(0) && app.jobs[0].lines.len() == 2
+        });
+        let mut texts: Vec<&str> = app.jobs[0]
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect();
+        texts.sort_unstable();
+        assert_eq!(texts, ["built", "warn"]);
+
+        app.selected = 1;
+        let UiAction::Run(request) =
+            app.on_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL))
+        else {
+            panic!("expected run request");
+        };
+        assert!(request.background);
+        app.start_background_job(*request);
+        assert!(app.jobs.len() == 2 && app.jobs[1].is_running());
+
+        app.open_jobs();
+        assert!(matches!(app.mode, Mode::Jobs(JobsState { selected: 1 })));
+        assert!(matches!(
+            app.on_key(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::NONE)),
+            UiAction::ForegroundJob(2)
+        ));
+        app.open_jobs();
+        app.on_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(app.jobs[1].status, JobStatus::Stopping);
+        wait_for(&mut app, &|app| !app.jobs[1].is_running());
+        assert_eq!(app.jobs[1].status, JobStatus::Killed);
     }
-}
 
-fn display_command_name(command: &CommandEntry, provider_name: &str) -> String {
-    let prefix = format!("{provider_name} ");
-    if command.name.to_ascii_lowercase().starts_with(&prefix) {
-        return command.name[prefix.len()..].to_string();
-    }
-    command.name.clone()
-}
+    #[test]
+    fn mouse_clicks_select_focus_and_double_click_runs() {
+        let mut app = app_with(vec![
+            mock_command("migrate"),
+            mock_command("seed"),
+            mock_command("serve"),
+        ]);
+        let area = Rect::new(0, 0, 100, 40);
+        let layout = screen_layout(&app, area, false);
+        let mouse = |kind, column, row| MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        let click = MouseEventKind::Down(MouseButton::Left);
 
-fn parse_query_provider_filter<'a>(
-    query: &'a str,
-    provider_aliases: &'a HashMap<String, String>,
-    provider_names_without_alias: &'a HashSet<String>,
-) -> (Option<&'a str>, &'a str, bool) {
-    let trimmed = query.trim_start();
-    if !trimmed.starts_with(':') {
-        return (None, query, false);
+        app.on_mouse(mouse(click, 5, layout.session.y + 2), area);
+        assert!(app.active_pane == ActivePane::Session);
+
+        let second_row = layout.commands.y + 2;
+        let action = app.on_mouse(mouse(click, 5, second_row), area);
+        assert!(matches!(action, UiAction::None));
+        assert!(app.active_pane == ActivePane::Commands);
+        assert_eq!(app.selected, 1);
+        let expected = app.current_command_index().unwrap();
+
+        let UiAction::Run(request) = app.on_mouse(mouse(click, 5, second_row), area) else {
+            panic!("double-click should run the command");
+        };
+        assert_eq!(request.display_name, app.commands[expected].name);
+
+        app.selected = 1;
+        app.on_mouse(mouse(MouseEventKind::ScrollDown, 5, 0), area);
+        assert_eq!(app.selected, 2);
+        // Below the last command.
+        app.on_mouse(mouse(click, 5, layout.commands.y + 6), area);
+        assert_eq!(app.selected, 2);
     }
 
-    let after = &trimmed[1..];
-    let alias_end = after.find(char::is_whitespace).unwrap_or(after.len());
-    let alias = after[..alias_end].trim().to_ascii_lowercase();
-    let remaining = after[alias_end..].trim_start();
+    #[test]
+    fn selection_mode_toggles_and_shows_plain_untruncated_text() {
+        let mut app = app_with(vec![mock_command("git status")]);
+        app.runtime.mouse = true;
+        assert!(app.wants_mouse_capture());
+        app.on_key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert!(matches!(app.mode, Mode::Selection));
+        assert!(!app.wants_mouse_capture());
+        app.on_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(app.mode, Mode::Search));
+        assert!(app.wants_mouse_capture());
 
-    if alias.is_empty() {
-        return (None, query, false);
+        app.push_command("git status".to_string());
+        assert_eq!(
+            selection_text(app.chat.last().unwrap(), None),
+            "$ git status"
+        );
+        let line = ChatLine {
+            kind: ChatLineKind::Stdout,
+            text: "abc…".to_string(),
+            repeat: 1,
+            full_text: Some("abcdef".to_string()),
+            json: None,
+        };
+        assert_eq!(selection_text(&line, None), "abcdef");
     }
 
-    match provider_aliases.get(&alias) {
-        Some(provider) => (Some(provider.as_str()), remaining, false),
-        None => match provider_names_without_alias.get(alias.as_str()) {
-            Some(provider_name) => (Some(provider_name.as_str()), remaining, false),
-            None => (None, remaining, true),
-        },
+    #[test]
+    fn poll_timeout_backs_off_when_idle() {
+        let app = app_with(vec![mock_command("git status")]);
+        let now = app.last_activity;
+        assert_eq!(app.poll_timeout(now), Duration::from_millis(100));
+        assert_eq!(app.poll_timeout(now + IDLE_AFTER), Duration::from_secs(1));
     }
-}
 
-fn parse_internal_command(query: &str) -> Option<InternalCommand> {
-    let trimmed = query.trim();
-    if !trimmed.starts_with('/') {
-        return None;
+    #[test]
+    fn inline_height_uses_share_of_rows_within_layout_minimum() {
+        assert_eq!(inline_height(100), 40);
+        assert_eq!(inline_height(30), INLINE_MIN_HEIGHT);
+        assert_eq!(inline_height(10), 10);
     }
 
-    let mut parts = trimmed[1..].split_whitespace();
-    let name = parts.next().unwrap_or_default().to_ascii_lowercase();
-    if name.is_empty() {
-        return Some(InternalCommand::Unknown(String::new()));
+    #[test]
+    fn color_support_follows_no_color_colorterm_and_term() {
+        assert_eq!(
+            ColorSupport::from_env(Some("1"), Some("truecolor"), Some("xterm-256color"), false),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, Some("truecolor"), Some("screen"), false),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            ColorSupport::from_env(Some(""), None, Some("xterm-256color"), false),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, Some("xterm"), false),
+            ColorSupport::Ansi16
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, Some("dumb"), false),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, None, false),
+            ColorSupport::Monochrome
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, None, true),
+            ColorSupport::Ansi16
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, Some("dumb"), true),
+            ColorSupport::Monochrome
+        );
     }
 
-    match name.as_str() {
-        "reload" => Some(InternalCommand::Reload),
-        "init" => {
-            let force = parts.any(|part| part == "--force" || part == "-f");
-            Some(InternalCommand::Init { force })
-        }
-        _ => Some(InternalCommand::Unknown(name)),
+    #[test]
+    fn rgb_colors_downgrade_to_nearest_palette_entry() {
+        assert_eq!(rgb_to_ansi256(0, 0, 0), 16);
+        assert_eq!(rgb_to_ansi256(255, 0, 0), 196);
+        assert_eq!(rgb_to_ansi256(128, 128, 128), 243);
+        assert_eq!(
+            downgrade_color(Color::Rgb(88, 150, 201), ColorSupport::Ansi256),
+            Color::Indexed(110)
+        );
+        assert_eq!(
+            downgrade_color(Color::Rgb(240, 10, 10), ColorSupport::Ansi16),
+            Color::LightRed
+        );
+        assert_eq!(
+            downgrade_color(Color::Cyan, ColorSupport::Monochrome),
+            Color::Reset
+        );
+        assert_eq!(
+            downgrade_color(Color::Cyan, ColorSupport::Ansi16),
+            Color::Cyan
+        );
     }
-}
 
-fn parse_flag_input(input: &str, default: bool) -> Option<bool> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
-        return Some(default);
+    #[test]
+    fn alias_filter_limits_results_to_provider() {
+        let commands = vec![
+            CommandEntry::new(
+                "artisan cache:clear",
+                "php artisan cache:clear",
+                CommandSource::Provider("artisan".to_string()),
+            ),
+            CommandEntry::new(
+                "just build",
+                "just build",
+                CommandSource::Provider("justfile".to_string()),
+            ),
+        ];
+
+        let mut aliases = HashMap::new();
+        aliases.insert("a".to_string(), "artisan".to_string());
+        aliases.insert("j".to_string(), "justfile".to_string());
+
+        let mut app = AppState::new(
+            commands,
+            None,
+            aliases,
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            test_runtime(),
+        );
+        app.query = ":a cache".to_string();
+        app.refresh_filtered();
+
+        assert_eq!(app.filtered.len(), 1);
+        let SearchItem::Command(index) = app.filtered[0] else {
+            panic!("expected command result");
+        };
+        assert_eq!(app.commands[index].name, "artisan cache:clear");
+    }
+
+    #[test]
+    fn provider_name_filter_works_without_alias() {
+        let commands = vec![
+            CommandEntry::new(
+                "artisan cache:clear",
+                "php artisan cache:clear",
+                CommandSource::Provider("artisan".to_string()),
+            ),
+            CommandEntry::new(
+                "just build",
+                "just build",
+                CommandSource::Provider("justfile".to_string()),
+            ),
+        ];
+
+        let mut aliases = HashMap::new();
+        aliases.insert("a".to_string(), "artisan".to_string());
+
+        let mut app = AppState::new(
+            commands,
+            None,
+            aliases,
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            test_runtime(),
+        );
+        app.query = ":justfile build".to_string();
+        app.refresh_filtered();
+
+        assert_eq!(app.filtered.len(), 1);
+        let SearchItem::Command(index) = app.filtered[0] else {
+            panic!("expected command result");
+        };
+        assert_eq!(app.commands[index].name, "just build");
     }
 
-    match trimmed.to_ascii_lowercase().as_str() {
-        "y" | "yes" | "true" | "1" | "on" => Some(true),
-        "n" | "no" | "false" | "0" | "off" => Some(false),
-        _ => None,
-    }
-}
+    #[test]
+    fn provider_name_filter_is_disabled_when_alias_exists() {
+        let commands = vec![CommandEntry::new(
+            "artisan cache:clear",
+            "php artisan cache:clear",
+            CommandSource::Provider("artisan".to_string()),
+        )];
 
-fn query_has_force_flag(query: &str) -> bool {
-    query
-        .split_whitespace()
-        .any(|part| part == "--force" || part == "-f")
-}
+        let mut aliases = HashMap::new();
+        aliases.insert("a".to_string(), "artisan".to_string());
 
-fn command_usage_key(command: &CommandEntry) -> String {
-    format!("{}::{}", command_provider_name(command), command.name)
-}
+        let mut app = AppState::new(
+            commands,
+            None,
+            aliases,
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            test_runtime(),
+        );
+        app.query = ":artisan cache".to_string();
+        app.refresh_filtered();
 
-fn load_usage_store() -> (HashMap<String, u64>, Option<PathBuf>) {
-    let Some(path) = usage_store_path() else {
-        return (HashMap::new(), None);
-    };
-    if !path.exists() {
-        return (HashMap::new(), Some(path));
+        assert!(app.filtered.is_empty());
     }
 
-    let counts = fs::read_to_string(&path)
-        .ok()
-        .and_then(|content| toml::from_str::<UsageStore>(&content).ok())
-        .map(|store| store.counts)
-        .unwrap_or_default();
-    (counts, Some(path))
-}
+    #[test]
+    fn search_cursor_allows_mid_string_editing() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
+        app.on_search_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        app.on_search_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
+        app.on_search_key(KeyEvent::new(KeyCode::Right, KeyModifiers::NONE));
+        app.on_search_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
+        app.on_search_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
+        assert_eq!(app.query, "abc");
+    }
 
-fn persist_usage_store(counts: &HashMap<String, u64>, path: Option<&Path>) -> Result<()> {
-    let Some(path) = path else {
-        return Ok(());
-    };
+    #[test]
+    fn usage_ranking_prefers_more_frequent_command() {
+        let cmd_a = mock_command("artisan cache:clear");
+        let cmd_b = mock_command("artisan cache:table");
+        let key_b = command_usage_key(&cmd_b);
 
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("failed to create usage directory {}", parent.display()))?;
-    }
+        let mut app = app_with(vec![cmd_a, cmd_b]);
+        app.usage.insert(
+            key_b,
+            UsageRecord {
+                count: 5,
+                score: 5.0,
+                last_used: history::now(),
+            },
+        );
+        app.query = "cache".to_string();
+        app.refresh_filtered();
 
-    let payload = UsageStore {
-        counts: counts.clone(),
-    };
-    let serialized = toml::to_string(&payload).context("failed to serialize usage store")?;
-    fs::write(path, serialized)
-        .with_context(|| format!("failed to write usage store {}", path.display()))?;
-    Ok(())
-}
+        let SearchItem::Command(index) = app.filtered[0] else {
+            panic!("expected command result");
+        };
+        assert_eq!(app.commands[index].name, "artisan cache:table");
+    }
 
-fn usage_store_path() -> Option<PathBuf> {
-    let config_root = dirs::config_dir()?;
-    Some(config_root.join("fzc").join("usage.toml"))
-}
+    #[test]
+    fn help_overlay_searches_keys_and_internal_commands() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
+        app.query = "cache".to_string();
+        app.on_key(KeyEvent::new(KeyCode::Char('?'), KeyModifiers::NONE));
+        assert!(matches!(app.mode, Mode::Help(_)));
+        let titles: Vec<String> = app
+            .help_groups("")
+            .into_iter()
+            .map(|group| group.title)
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                "Running",
+                "Search",
+                "Navigation",
+                "Commands",
+                "General",
+                "Internal commands",
+                "Command palette (Ctrl+P)"
+            ]
+        );
 
-fn insert_char_at(value: &mut String, char_index: usize, ch: char) {
-    let byte_index = byte_index_for_char(value, char_index);
-    value.insert(byte_index, ch);
-}
+        for ch in "job".chars() {
+            app.on_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        let groups = app.help_groups("job");
+        assert_eq!(
+            groups
+                .iter()
+                .map(|group| (group.title.as_str(), group.rows.len()))
+                .collect::<Vec<_>>(),
+            [("Running", 1), ("Navigation", 1), ("Internal commands", 1)]
+        );
+        assert_eq!(groups[2].rows[0].0, "/jobs");
+        // A section title keeps every row of the section.
+        assert_eq!(app.help_groups("navigation")[0].rows.len(), 6);
 
-fn remove_char_at(value: &mut String, char_index: usize) -> bool {
-    let start = byte_index_for_char(value, char_index);
-    if start >= value.len() {
-        return false;
+        app.on_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(&app.mode, Mode::Help(help) if help.query.is_empty()));
+        app.on_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(app.mode, Mode::Search));
+        assert_eq!(app.query, "cache");
     }
-    let end = byte_index_for_char(value, char_index + 1);
-    value.replace_range(start..end, "");
-    true
-}
 
-fn byte_index_for_char(value: &str, char_index: usize) -> usize {
-    if char_index == 0 {
-        return 0;
+    #[test]
+    fn config_line_names_the_global_config_a_project_config_merges_over() {
+        let mut runtime = test_runtime();
+        runtime.global_config_path = Some(PathBuf::from("/home/me/.config/fzc/config.toml"));
+        let mut app = AppState::new(
+            vec![mock_command("deploy")],
+            Some(PathBuf::from("/work/app/fzc.toml")),
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        assert_eq!(
+            app.config_line(),
+            "Config: /work/app/fzc.toml (merged over /home/me/.config/fzc/config.toml)"
+        );
+        app.runtime.global_config_path = None;
+        assert_eq!(app.config_line(), "Config: /work/app/fzc.toml");
     }
-    value
-        .char_indices()
-        .nth(char_index)
-        .map(|(index, _)| index)
-        .unwrap_or(value.len())
-}
-
-#[derive(Debug)]
-struct MatchScore {
-    total: i64,
-    fuzzy: i64,
-}
 
-fn score_command_match(
-    matcher: &SkimMatcherV2,
-    query: &str,
-    query_terms: &[String],
-    command: &CommandEntry,
-) -> Option<MatchScore> {
-    let mut haystack = command.name.to_lowercase();
-    if let Some(desc) = &command.description {
-        haystack.push(' ');
-        haystack.push_str(&desc.to_lowercase());
+    #[test]
+    fn status_lines_follow_the_locale() {
+        let mut runtime = test_runtime();
+        runtime.locale = Locale::De;
+        let mut app = AppState::new(
+            vec![mock_command("deploy")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        app.toggle_staging();
+        app.toggle_staging();
+        let texts: Vec<&str> = app.chat.iter().map(|line| line.text.as_str()).collect();
+        assert!(texts.contains(
+            &"Sammelmodus: ausgewählte Befehle werden eingereiht; Ctrl+Q prüft und führt sie aus"
+        ));
+        assert!(texts.contains(&"Sammelmodus aus (0 eingereihte(r) Befehl(e) bleiben)"));
     }
 
-    let normalized_query = query.to_lowercase();
-    let fuzzy = matcher
-        .fuzzy_match(&haystack, &normalized_query)
-        .unwrap_or_default();
-    if query_terms.is_empty() {
-        return Some(MatchScore {
-            total: fuzzy * 10,
-            fuzzy,
-        });
+    #[test]
+    fn help_keys_come_from_the_keymap_and_only_labels_are_translated() {
+        let mut runtime = test_runtime();
+        runtime.locale = Locale::De;
+        let app = AppState::new(
+            vec![mock_command("deploy")],
+            None,
+            HashMap::new(),
+            default_ranking(),
+            default_output(),
+            CommandGuard::default(),
+            runtime,
+        );
+        let groups = app.help_groups("");
+        assert_eq!(groups[2].title, "Navigation");
+        assert_eq!(
+            groups[2].rows[2],
+            ("Ctrl+J/Ctrl+K".to_string(), "Befehlsauswahl bewegen")
+        );
+        assert_eq!(groups[6].title, "Befehlspalette (Ctrl+P)");
+        // Every Ctrl shortcut the command list handles is listed.
+        for (key, _) in CTRL_KEYMAP {
+            let keys = format!("Ctrl+{}", key.to_ascii_uppercase());
+            assert!(
+                groups[..5]
+                    .iter()
+                    .flat_map(|group| &group.rows)
+                    .any(|(row_keys, _)| row_keys.split('/').any(|part| part == keys)),
+                "{keys} is missing from the help"
+            );
+        }
     }
 
-    let name_terms = tokenize_for_match(&command.name);
-    let haystack_terms = tokenize_for_match(&haystack);
+    #[test]
+    fn tab_switches_focus_to_session_and_typing_returns_to_commands() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
 
-    let mut exact_name_hits = 0i64;
-    let mut partial_name_hits = 0i64;
-    let mut coverage_hits = 0i64;
-    let mut description_hits = 0i64;
+        assert!(matches!(app.active_pane, ActivePane::Commands));
+        app.on_search_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        assert!(matches!(app.active_pane, ActivePane::Session));
 
-    for term in query_terms {
-        let mut matched_name = false;
-        for token in &name_terms {
-            match token_match_quality(token, term) {
-                2 => {
-                    exact_name_hits += 1;
-                    matched_name = true;
-                    break;
-                }
-                1 => {
-                    partial_name_hits += 1;
-                    matched_name = true;
-                    break;
-                }
-                _ => {}
-            }
-        }
-        if matched_name {
-            coverage_hits += 1;
-            continue;
-        }
+        app.on_search_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert!(matches!(app.active_pane, ActivePane::Commands));
+        assert_eq!(app.query, "x");
+    }
 
-        if haystack_terms
-            .iter()
-            .any(|token| token_match_quality(token, term) > 0)
-        {
-            coverage_hits += 1;
-            description_hits += 1;
+    #[test]
+    fn session_focus_allows_scroll_keys() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
+        for i in 0..20 {
+            app.push_info(format!("line {i}"));
         }
-    }
 
-    if fuzzy == 0 && coverage_hits == 0 {
-        return None;
+        app.on_search_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        assert!(matches!(app.active_pane, ActivePane::Session));
+        assert_eq!(app.session_scroll, 0);
+
+        app.on_search_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+        assert!(app.session_scroll > 0);
     }
 
-    let all_terms_in_name = query_terms.iter().all(|term| {
-        name_terms
-            .iter()
-            .any(|token| token_match_quality(token, term) > 0)
-    });
-    let ordered_in_name = terms_in_order(&name_terms, query_terms);
-    let contiguous_in_name = terms_contiguous(&name_terms, query_terms);
-    let query_phrase = query_terms.join(" ");
-    let normalized_name = name_terms.join(" ");
-    let phrase_match = !query_phrase.is_empty() && normalized_name.contains(&query_phrase);
+    #[test]
+    fn enter_does_nothing_when_session_is_active() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
 
-    let total = fuzzy * 10
-        + exact_name_hits * 12_000
-        + partial_name_hits * 6_000
-        + coverage_hits * 10_000
-        + description_hits * 2_500
-        + if all_terms_in_name { 35_000 } else { 0 }
-        + if ordered_in_name { 10_000 } else { 0 }
-        + if contiguous_in_name { 10_000 } else { 0 }
-        + if phrase_match { 15_000 } else { 0 };
+        app.on_search_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        assert!(matches!(app.active_pane, ActivePane::Session));
 
-    Some(MatchScore { total, fuzzy })
-}
+        let action = app.on_search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::Search));
+    }
 
-fn tokenize_for_match(raw: &str) -> Vec<String> {
-    raw.split(|c: char| !c.is_alphanumeric())
-        .filter(|token| !token.is_empty())
-        .map(|token| token.to_lowercase())
-        .collect()
-}
+    #[test]
+    fn parses_internal_reload_command() {
+        let parsed = parse_internal_command("/reload").unwrap();
+        assert!(matches!(parsed, InternalCommand::Reload));
+    }
 
-fn token_match_quality(token: &str, term: &str) -> i64 {
-    if token == term {
-        return 2;
+    #[test]
+    fn parses_internal_init_force_command() {
+        let parsed = parse_internal_command("/init --force").unwrap();
+        assert!(matches!(parsed, InternalCommand::Init { force: true }));
     }
-    if token.starts_with(term) || token.contains(term) {
-        return 1;
+
+    #[test]
+    fn secrets_stay_out_of_the_command_line_and_output() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy --token \"{{secret:deploy-token}}\"".to_string();
+        let mut app = app_with(vec![command]);
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected a run");
+        };
+        #[cfg(not(target_os = "windows"))]
+        assert_eq!(
+            request.command_line,
+            "deploy --token \"${FZC_SECRET_DEPLOY_TOKEN}\""
+        );
+        assert_eq!(request.secrets, ["deploy-token"]);
+        let Err(err) = app.with_secrets(*request) else {
+            panic!("secrets need a backend");
+        };
+        assert!(format!("{err:#}").contains("needs a [secrets] backend"));
+
+        app.secret_values.push("s3cr3t-value".to_string());
+        app.push_line(ChatLineKind::Stdout, "token=s3cr3t-value".to_string());
+        assert_eq!(app.chat.last().unwrap().text, "token=******");
     }
-    0
-}
 
-fn terms_in_order(name_terms: &[String], query_terms: &[String]) -> bool {
-    if query_terms.is_empty() {
-        return false;
+    #[test]
+    fn parses_env_commands() {
+        assert_eq!(parse_env_command("/env"), Some(Ok(EnvCommand::List)));
+        assert_eq!(
+            parse_env_command("/env set GREETING=\"hello world\""),
+            Some(Ok(EnvCommand::Set(
+                "GREETING".to_string(),
+                "hello world".to_string()
+            )))
+        );
+        assert_eq!(
+            parse_env_command("/env unset AWS_PROFILE"),
+            Some(Ok(EnvCommand::Unset("AWS_PROFILE".to_string())))
+        );
+        assert!(matches!(parse_env_command("/env set 1X=2"), Some(Err(_))));
+        assert!(parse_env_command("/envoy").is_none());
     }
 
-    let mut cursor = 0usize;
-    for query in query_terms {
-        let mut found = false;
-        while cursor < name_terms.len() {
-            if token_match_quality(&name_terms[cursor], query) > 0 {
-                found = true;
-                cursor += 1;
-                break;
-            }
-            cursor += 1;
+    #[test]
+    fn session_variables_apply_to_later_runs() {
+        let mut command = mock_command("deploy");
+        command.env = vec![
+            ("APP_ENV".to_string(), "local".to_string()),
+            ("DEBUG".to_string(), "1".to_string()),
+        ];
+        let mut app = app_with(vec![command]);
+        for query in ["/env set APP_ENV=staging", "/env set AWS_PROFILE=ops"] {
+            app.query = query.to_string();
+            app.prepare_selected_internal_command();
         }
+        assert!(app.query.is_empty());
 
-        if !found {
-            return false;
-        }
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected a run");
+        };
+        assert_eq!(
+            request.env,
+            [
+                ("DEBUG".to_string(), "1".to_string()),
+                ("APP_ENV".to_string(), "staging".to_string()),
+                ("AWS_PROFILE".to_string(), "ops".to_string()),
+            ]
+        );
+
+        app.query = "/env unset AWS_PROFILE".to_string();
+        app.prepare_selected_internal_command();
+        let UiAction::Run(request) = app.build_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected a run");
+        };
+        assert_eq!(request.env.len(), 2);
     }
 
-    true
-}
+    #[test]
+    fn internal_init_without_force_opens_prompt() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
+        app.query = "/init".to_string();
+        app.query_cursor = app.query.chars().count();
+        app.refresh_filtered();
 
-fn terms_contiguous(name_terms: &[String], query_terms: &[String]) -> bool {
-    if query_terms.is_empty() || query_terms.len() > name_terms.len() {
-        return false;
+        let action = app.on_search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::InternalPrompt(_)));
     }
 
-    for start in 0..=(name_terms.len() - query_terms.len()) {
-        let mut all_match = true;
-        for offset in 0..query_terms.len() {
-            if token_match_quality(&name_terms[start + offset], &query_terms[offset]) == 0 {
-                all_match = false;
-                break;
-            }
-        }
-        if all_match {
-            return true;
+    #[test]
+    fn staged_commands_queue_up_and_run_in_reviewed_order() {
+        let mut app = app_with(vec![
+            mock_command("build"),
+            mock_command("deploy"),
+            mock_command("test"),
+        ]);
+        let ctrl = |ch| KeyEvent::new(KeyCode::Char(ch), KeyModifiers::CONTROL);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        app.on_key(ctrl('t'));
+        for name in ["build", "deploy", "test"] {
+            app.query = name.to_string();
+            app.refresh_filtered();
+            assert!(matches!(app.on_key(key(KeyCode::Enter)), UiAction::None));
         }
+        assert_eq!(app.queue.len(), 3);
+
+        app.on_key(ctrl('q'));
+        assert!(matches!(app.mode, Mode::QueueReview(_)));
+        app.on_key(KeyEvent::new(KeyCode::Char('J'), KeyModifiers::SHIFT));
+        app.on_key(key(KeyCode::Char('d')));
+        app.on_key(key(KeyCode::Char('d')));
+        assert_eq!(app.queue.len(), 1);
+        app.on_key(key(KeyCode::Char('u')));
+
+        assert!(matches!(app.on_key(key(KeyCode::Enter)), UiAction::None));
+        let UiAction::RunQueue(requests) = app.on_key(key(KeyCode::Enter)) else {
+            panic!("expected queued runs");
+        };
+        let names: Vec<&str> = requests
+            .iter()
+            .map(|request| request.display_name.as_str())
+            .collect();
+        assert_eq!(names, ["deploy", "test"]);
+        assert!(!app.staging && app.queue.is_empty());
     }
 
-    false
-}
+    #[test]
+    fn command_references_expand_with_referenced_defaults_and_reject_cycles() {
+        let mut build = mock_command("Build");
+        build.template = "cargo build --profile {{profile}}".to_string();
+        build.params = vec![ParamSpec {
+            prompt: "Profile".to_string(),
+            default_value: Some("release".to_string()),
+            ..test_param("profile", ParamType::Value)
+        }];
+        let mut deploy = mock_command("Deploy");
+        deploy.template = "{{command:Build}} && ./deploy.sh".to_string();
+        let mut ping = mock_command("Ping");
+        ping.template = "{{command:Pong}}".to_string();
+        let mut pong = mock_command("Pong");
+        pong.template = "{{command:Ping}}".to_string();
+
+        let app = app_with(vec![build, deploy, ping, pong]);
+        let request = app
+            .resolve_run_request(1, HashMap::new(), None, true)
+            .unwrap();
+        assert_eq!(
+            request.command_line,
+            "cargo build --profile release && ./deploy.sh"
+        );
+        let Err(message) = app.resolve_run_request(2, HashMap::new(), None, true) else {
+            panic!("expected a reference cycle");
+        };
+        assert!(message.contains("Ping -> Pong -> Ping"), "{message}");
+    }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
+    #[test]
+    fn pipelines_resolve_their_steps_in_order() {
+        let mut build = mock_command("Build");
+        build.template = "cargo build".to_string();
+        build.then = vec!["Package".to_string()];
+        let mut package = mock_command("Package");
+        package.template = "cargo package".to_string();
+        let mut lint = mock_command("Lint");
+        lint.template = "cargo clippy".to_string();
+        let release = crate::model::pipeline_from_config(&config::PipelineConfig {
+            name: "Release".to_string(),
+            description: None,
+            steps: vec![
+                config::PipelineStepConfig {
+                    command: "Lint".to_string(),
+                    continue_on_error: true,
+                },
+                config::PipelineStepConfig {
+                    command: "Build".to_string(),
+                    continue_on_error: false,
+                },
+            ],
+        });
+        let nested = crate::model::pipeline_from_config(&config::PipelineConfig {
+            name: "Nested".to_string(),
+            description: None,
+            steps: vec![config::PipelineStepConfig {
+                command: "Release".to_string(),
+                continue_on_error: false,
+            }],
+        });
 
-    use super::*;
-    use crate::model::CommandSource;
-    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+        let app = app_with(vec![build, lint, nested, package, release]);
+        let request = app
+            .resolve_run_request(4, HashMap::new(), None, true)
+            .unwrap();
+        let runs: Vec<(&str, bool)> = std::iter::once(&request)
+            .chain(&request.then)
+            .map(|run| (run.command_line.as_str(), run.continue_on_error))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                ("cargo clippy", true),
+                ("cargo build", false),
+                ("cargo package", false)
+            ]
+        );
+        let pipeline = request.pipeline.as_ref().unwrap();
+        assert_eq!(pipeline.usage_key, "config::Release");
 
-    fn mock_command(name: &str) -> CommandEntry {
-        CommandEntry {
-            name: name.to_string(),
-            description: Some("artisan command".to_string()),
-            template: format!("php artisan {name}"),
-            params: Vec::new(),
-            source: CommandSource::Provider("artisan"),
-            working_dir: None,
-        }
+        let Err(message) = app.resolve_run_request(2, HashMap::new(), None, true) else {
+            panic!("expected a nested pipeline error");
+        };
+        assert!(
+            message.contains("cannot run pipeline 'Release'"),
+            "{message}"
+        );
     }
 
-    fn default_ranking() -> RankingSettings {
-        RankingSettings {
-            usage_enabled: true,
-            usage_weight: 8_000,
-        }
+    #[test]
+    fn pipelines_refuse_steps_missing_a_required_param() {
+        let mut deploy = mock_command("Deploy");
+        deploy.template = "deploy {{tag}}".to_string();
+        deploy.params = vec![test_param("tag", ParamType::Value)];
+        let release = crate::model::pipeline_from_config(&config::PipelineConfig {
+            name: "Release".to_string(),
+            description: None,
+            steps: vec![config::PipelineStepConfig {
+                command: "Deploy".to_string(),
+                continue_on_error: false,
+            }],
+        });
+
+        let mut app = app_with(vec![deploy, release]);
+        let Err(message) = app.resolve_run_request(1, HashMap::new(), None, true) else {
+            panic!("expected the pipeline to be refused");
+        };
+        assert_eq!(
+            message,
+            "pipeline 'Release' step 'Deploy': required param 'tag' has no value or default"
+        );
+
+        app.commands[0].params[0].default_value = Some("latest".to_string());
+        let request = app
+            .resolve_run_request(1, HashMap::new(), None, true)
+            .unwrap();
+        assert_eq!(request.command_line, "deploy latest");
     }
 
-    fn test_runtime() -> RuntimeContext {
-        RuntimeContext {
-            cwd: std::env::temp_dir(),
-            explicit_config_path: None,
-        }
+    #[test]
+    fn then_chains_commands_with_shared_param_values() {
+        let mut build = mock_command("Build");
+        build.template = "cargo build --profile {{profile}}".to_string();
+        build.params = vec![ParamSpec {
+            prompt: "Profile".to_string(),
+            default_value: Some("release".to_string()),
+            ..test_param("profile", ParamType::Value)
+        }];
+        build.then = vec!["Deploy".to_string()];
+        let mut deploy = mock_command("Deploy");
+        deploy.template = "./deploy.sh {{profile}}".to_string();
+        deploy.params = build.params.clone();
+        deploy.then = vec!["Smoke".to_string()];
+        let mut smoke = mock_command("Smoke");
+        smoke.template = "./smoke.sh".to_string();
+        let mut ping = mock_command("Ping");
+        ping.then = vec!["Pong".to_string()];
+        let mut pong = mock_command("Pong");
+        pong.then = vec!["Ping".to_string()];
+
+        let app = app_with(vec![build, deploy, ping, pong, smoke]);
+        let values = HashMap::from([("profile".to_string(), "debug".to_string())]);
+        let request = app.resolve_run_request(0, values, None, true).unwrap();
+        assert_eq!(request.command_line, "cargo build --profile debug");
+        let then: Vec<&str> = request
+            .then
+            .iter()
+            .map(|run| run.command_line.as_str())
+            .collect();
+        assert_eq!(then, ["./deploy.sh debug", "./smoke.sh"]);
+
+        let Err(message) = app.resolve_run_request(2, HashMap::new(), None, true) else {
+            panic!("expected a then cycle");
+        };
+        assert!(message.contains("Ping -> Pong -> Ping"), "{message}");
     }
 
-    fn top_name_for(query: &str, commands: Vec<CommandEntry>) -> String {
-        let mut app = AppState::new(
-            commands,
-            None,
-            HashMap::new(),
-            default_ranking(),
-            test_runtime(),
+    #[test]
+    fn then_refuses_chained_commands_missing_a_required_param() {
+        let mut build = mock_command("Build");
+        build.template = "cargo build".to_string();
+        build.then = vec!["Deploy".to_string()];
+        let mut deploy = mock_command("Deploy");
+        deploy.template = "deploy {{tag}}".to_string();
+        deploy.params = vec![test_param("tag", ParamType::Value)];
+
+        let app = app_with(vec![build, deploy]);
+        let Err(message) = app.resolve_run_request(0, HashMap::new(), None, true) else {
+            panic!("expected the chain to be refused");
+        };
+        assert_eq!(
+            message,
+            "'Build' chains to 'Deploy': required param 'tag' has no value or default"
         );
-        app.query = query.to_string();
-        app.refresh_filtered();
+        let values = HashMap::from([("tag".to_string(), "v1".to_string())]);
+        let request = app.resolve_run_request(0, values, None, true).unwrap();
+        assert_eq!(request.then[0].command_line, "deploy v1");
+    }
 
-        let SearchItem::Command(index) = app.filtered[0] else {
-            panic!("expected command result");
+    #[test]
+    fn dry_run_shows_the_resolved_command_before_running_it() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy {{branch}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Branch".to_string(),
+            ..test_param("branch", ParamType::Value)
+        }];
+        command.env = vec![("APP_ENV".to_string(), "{{branch}}".to_string())];
+        let mut app = app_with(vec![command]);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+        app.on_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        assert!(matches!(app.mode, Mode::Prompt(_)));
+        app.on_key(key(KeyCode::Esc));
+        assert!(!app.dry_run);
+
+        app.on_key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        app.on_paste("main");
+        assert!(matches!(app.on_key(key(KeyCode::Enter)), UiAction::None));
+        let Mode::DryRun(dry_run) = &app.mode else {
+            panic!("expected the dry-run popup");
         };
-        app.commands[index].name.clone()
+        assert_eq!(dry_run.request.command_line, "deploy main");
+        assert_eq!(
+            dry_run.request.env,
+            [("APP_ENV".to_string(), "main".to_string())]
+        );
+        assert_eq!(dry_run.verdict, GuardVerdict::Allow);
+
+        let UiAction::Run(request) = app.on_key(key(KeyCode::Enter)) else {
+            panic!("expected Enter to run the previewed command");
+        };
+        assert_eq!(request.command_line, "deploy main");
     }
 
     #[test]
-    fn phrase_query_prefers_cache_clear() {
-        let top = top_name_for(
-            "cache clear",
-            vec![
-                mock_command("artisan cache:clear"),
-                mock_command("artisan clear-compiled"),
-                mock_command("artisan cache:table"),
-            ],
+    fn details_pane_lists_template_params_and_usage() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy {{branch}} {{force}}".to_string();
+        command.working_dir = Some(PathBuf::from("/srv/app"));
+        command.params = vec![
+            ParamSpec {
+                prompt: "Branch".to_string(),
+                default_value: Some("main".to_string()),
+                ..test_param(
+                    "branch",
+                    ParamType::Choice(vec!["main".to_string(), "dev".to_string()]),
+                )
+            },
+            ParamSpec {
+                prompt: "Force?".to_string(),
+                default_flag: Some(false),
+                required: false,
+                ..test_param("force", ParamType::Flag)
+            },
+        ];
+        let mut app = app_with(vec![command]);
+        app.on_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert!(app.show_details);
+
+        let text = |app: &AppState| -> Vec<String> {
+            command_detail_lines(app, &app.commands[0])
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        let lines = text(&app);
+        assert_eq!(lines[0], "$ deploy {{branch}} {{force}}");
+        assert!(lines.contains(&"param     branch (choice) = main [main/dev]".to_string()));
+        assert!(lines.contains(&"param     force (flag) = false".to_string()));
+        assert!(lines.contains(&"directory /srv/app".to_string()));
+        assert_eq!(lines.last().unwrap(), "usage     never run");
+
+        app.record_usage("artisan::deploy");
+        assert!(
+            text(&app)
+                .last()
+                .unwrap()
+                .starts_with("usage     1 run(s), last")
         );
-        assert_eq!(top, "artisan cache:clear");
     }
 
     #[test]
-    fn reversed_query_still_prefers_cache_clear() {
-        let top = top_name_for(
-            "clear cache",
-            vec![
-                mock_command("artisan cache:clear"),
-                mock_command("artisan clear-compiled"),
-                mock_command("artisan cache:table"),
-            ],
+    fn base64_pads_partial_groups() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn cache_command_lists_previews_and_clears_them() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
+        app.preview_cache.insert(
+            "git log".to_string(),
+            PreviewOutput {
+                finished: Instant::now(),
+                lines: vec!["abc123 init".to_string()],
+            },
         );
-        assert_eq!(top, "artisan cache:clear");
+        app.query = "/cache".to_string();
+        app.query_cursor = app.query.chars().count();
+        app.refresh_filtered();
+
+        let action = app.on_search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, UiAction::None));
+        assert!(app.query.is_empty());
+        let Mode::Caches(state) = &app.mode else {
+            panic!("expected the cache list");
+        };
+        assert_eq!(state.items[0], CacheItem::Previews(1));
+
+        app.on_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert!(app.preview_cache.is_empty());
+        assert!(
+            app.chat
+                .iter()
+                .any(|line| line.text.starts_with("Cleared 1 cached preview(s)"))
+        );
+        if let Mode::Caches(state) = &app.mode {
+            assert!(!state.items.contains(&CacheItem::Previews(1)));
+        }
     }
 
     #[test]
-    fn prompt_submit_switches_back_to_search_mode() {
+    fn cache_sizes_use_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(3 * 1024 + 400), "3.4 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn flag_param_prompt_uses_default_on_enter() {
         let mut command = mock_command("deploy");
-        command.template = "deploy --env={{env}}".to_string();
-        command.params = vec![crate::model::ParamSpec {
-            name: "env".to_string(),
-            kind: ParamType::Value,
-            prompt: "Environment".to_string(),
-            placeholder: None,
-            default_value: None,
-            value_value: None,
-            default_flag: None,
-            value_flag: None,
-            required: true,
-            prompt_in_tui: true,
+        command.template = "deploy {{force}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Use --force?".to_string(),
+            default_flag: Some(false),
+            required: false,
+            ..test_param("force", ParamType::Flag)
         }];
 
-        let mut app = AppState::new(
-            vec![command],
-            None,
-            HashMap::new(),
-            default_ranking(),
-            test_runtime(),
-        );
+        let mut app = app_with(vec![command]);
         let action = app.prepare_selected_command(true);
         assert!(matches!(action, UiAction::None));
         assert!(matches!(app.mode, Mode::Prompt(_)));
 
-        if let Mode::Prompt(prompt) = &mut app.mode {
-            prompt.input = "production".to_string();
-        }
-
         let action = app.on_prompt_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
-        assert!(matches!(action, UiAction::Run(_)));
-        assert!(matches!(app.mode, Mode::Search));
+        let UiAction::Run(request) = action else {
+            panic!("expected command run request");
+        };
+        assert_eq!(request.command_line.trim(), "deploy");
     }
 
     #[test]
-    fn alias_filter_limits_results_to_provider() {
-        let commands = vec![
-            CommandEntry {
-                name: "artisan cache:clear".to_string(),
-                description: None,
-                template: "php artisan cache:clear".to_string(),
-                params: Vec::new(),
-                source: CommandSource::Provider("artisan"),
-                working_dir: None,
-            },
-            CommandEntry {
-                name: "just build".to_string(),
-                description: None,
-                template: "just build".to_string(),
-                params: Vec::new(),
-                source: CommandSource::Provider("justfile"),
-                working_dir: None,
-            },
-        ];
-
-        let mut aliases = HashMap::new();
-        aliases.insert("a".to_string(), "artisan".to_string());
-        aliases.insert("j".to_string(), "justfile".to_string());
+    fn flag_param_prompt_accepts_y_without_enter() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy {{force}}".to_string();
+        command.params = vec![ParamSpec {
+            prompt: "Use --force?".to_string(),
+            default_flag: Some(false),
+            required: false,
+            ..test_param("force", ParamType::Flag)
+        }];
 
-        let mut app = AppState::new(commands, None, aliases, default_ranking(), test_runtime());
-        app.query = ":a cache".to_string();
-        app.refresh_filtered();
+        let mut app = app_with(vec![command]);
+        let action = app.prepare_selected_command(true);
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::Prompt(_)));
 
-        assert_eq!(app.filtered.len(), 1);
-        let SearchItem::Command(index) = app.filtered[0] else {
-            panic!("expected command result");
+        let action = app.on_prompt_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
+        let UiAction::Run(request) = action else {
+            panic!("expected command run request");
         };
-        assert_eq!(app.commands[index].name, "artisan cache:clear");
+        assert_eq!(request.command_line.trim(), "deploy --force");
     }
 
     #[test]
-    fn provider_name_filter_works_without_alias() {
-        let commands = vec![
-            CommandEntry {
-                name: "artisan cache:clear".to_string(),
-                description: None,
-                template: "php artisan cache:clear".to_string(),
-                params: Vec::new(),
-                source: CommandSource::Provider("artisan"),
-                working_dir: None,
+    fn environment_picker_applies_env_and_template_vars() {
+        let mut command = mock_command("deploy");
+        command.template = "deploy --region={{region}}".to_string();
+        command.env = vec![
+            ("APP_ENV".to_string(), "dev".to_string()),
+            ("AWS_REGION".to_string(), "{{region}}".to_string()),
+        ];
+        command.environments = vec![
+            crate::model::EnvironmentProfile {
+                name: "staging".to_string(),
+                env: vec![("APP_ENV".to_string(), "staging".to_string())],
+                vars: HashMap::from([("region".to_string(), "eu-west-1".to_string())]),
             },
-            CommandEntry {
-                name: "just build".to_string(),
-                description: None,
-                template: "just build".to_string(),
-                params: Vec::new(),
-                source: CommandSource::Provider("justfile"),
-                working_dir: None,
+            crate::model::EnvironmentProfile {
+                name: "prod".to_string(),
+                env: vec![("APP_ENV".to_string(), "prod".to_string())],
+                vars: HashMap::from([("region".to_string(), "us-east-1".to_string())]),
             },
         ];
 
-        let mut aliases = HashMap::new();
-        aliases.insert("a".to_string(), "artisan".to_string());
-
-        let mut app = AppState::new(commands, None, aliases, default_ranking(), test_runtime());
-        app.query = ":justfile build".to_string();
-        app.refresh_filtered();
-
-        assert_eq!(app.filtered.len(), 1);
-        let SearchItem::Command(index) = app.filtered[0] else {
-            panic!("expected command result");
+        let mut app = app_with(vec![command]);
+        let action = app.prepare_selected_command(true);
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::EnvironmentPicker(_)));
+
+        app.on_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        let action = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let UiAction::Run(request) = action else {
+            panic!("expected command run request");
         };
-        assert_eq!(app.commands[index].name, "just build");
+        assert_eq!(request.command_line, "deploy --region=us-east-1");
+        assert_eq!(request.environment.as_deref(), Some("prod"));
+        assert_eq!(
+            request.env,
+            vec![
+                ("AWS_REGION".to_string(), "us-east-1".to_string()),
+                ("APP_ENV".to_string(), "prod".to_string()),
+            ]
+        );
     }
 
     #[test]
-    fn provider_name_filter_is_disabled_when_alias_exists() {
-        let commands = vec![CommandEntry {
-            name: "artisan cache:clear".to_string(),
-            description: None,
-            template: "php artisan cache:clear".to_string(),
-            params: Vec::new(),
-            source: CommandSource::Provider("artisan"),
-            working_dir: None,
-        }];
-
-        let mut aliases = HashMap::new();
-        aliases.insert("a".to_string(), "artisan".to_string());
-
-        let mut app = AppState::new(commands, None, aliases, default_ranking(), test_runtime());
-        app.query = ":artisan cache".to_string();
-        app.refresh_filtered();
+    fn host_command_is_wrapped_in_ssh() {
+        let mut command = mock_command("uptime");
+        command.template = "uptime".to_string();
+        command.host = Some("deploy@prod-1".to_string());
 
-        assert!(app.filtered.is_empty());
+        let mut app = app_with(vec![command]);
+        let UiAction::Run(request) = app.prepare_selected_command(true) else {
+            panic!("expected command run request");
+        };
+        assert_eq!(request.command_line, "ssh deploy@prod-1 uptime");
     }
 
     #[test]
-    fn search_cursor_allows_mid_string_editing() {
+    fn safety_guard_blocks_or_requires_confirmation() {
+        let mut drop = mock_command("drop");
+        drop.template = "mysql -e 'DROP DATABASE app'".to_string();
+        let mut wipe = mock_command("wipe");
+        wipe.template = "rm -rf /".to_string();
+        let guard = CommandGuard::from_config(&crate::config::SafetyConfig {
+            deny: vec!["rm -rf /".to_string()],
+            confirm: vec!["*drop database*".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
         let mut app = AppState::new(
-            vec![mock_command("artisan cache:clear")],
+            vec![drop, wipe],
             None,
             HashMap::new(),
             default_ranking(),
+            default_output(),
+            guard,
             test_runtime(),
         );
-        app.on_search_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
-        app.on_search_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE));
-        app.on_search_key(KeyEvent::new(KeyCode::Right, KeyModifiers::NONE));
-        app.on_search_key(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE));
-        app.on_search_key(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE));
-        assert_eq!(app.query, "abc");
+
+        app.selected = 1;
+        let action = app.prepare_selected_command(true);
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::Search));
+
+        app.selected = 0;
+        let action = app.prepare_selected_command(true);
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::ConfirmRun(_)));
+
+        for ch in "no".chars() {
+            app.on_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        let action = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::ConfirmRun(_)));
+
+        app.on_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        app.on_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        for ch in "yes".chars() {
+            app.on_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        let action = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, UiAction::Run(_)));
     }
 
     #[test]
-    fn usage_ranking_prefers_more_frequent_command() {
-        let cmd_a = mock_command("artisan cache:clear");
-        let cmd_b = mock_command("artisan cache:table");
-        let key_b = command_usage_key(&cmd_b);
-
+    fn root_commands_need_a_second_enter_after_their_banner() {
+        let mut restart = mock_command("restart");
+        restart.template = "sudo systemctl restart app".to_string();
+        let guard = CommandGuard::from_config(&Default::default()).unwrap();
         let mut app = AppState::new(
-            vec![cmd_a, cmd_b],
+            vec![restart],
             None,
             HashMap::new(),
             default_ranking(),
+            default_output(),
+            guard,
             test_runtime(),
         );
-        app.usage_counts.insert(key_b, 5);
-        app.query = "cache".to_string();
-        app.refresh_filtered();
 
-        let SearchItem::Command(index) = app.filtered[0] else {
-            panic!("expected command result");
-        };
-        assert_eq!(app.commands[index].name, "artisan cache:table");
+        assert!(matches!(app.prepare_selected_command(true), UiAction::None));
+        assert!(matches!(app.mode, Mode::WarnRun(_)));
+        let banner = app.chat.last().unwrap();
+        assert!(banner.kind == ChatLineKind::Banner);
+        assert!(banner.text.contains("runs as root (sudo)"));
+
+        app.on_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert!(matches!(app.mode, Mode::Search));
+
+        app.prepare_selected_command(true);
+        let action = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(action, UiAction::Run(_)));
     }
 
     #[test]
-    fn help_mode_auto_closes_and_allows_typing() {
+    fn confirm_prompts_keep_the_warning_banner() {
+        let mut restart = mock_command("restart");
+        restart.template = "sudo rm -rf /var/cache/app".to_string();
+        let guard = CommandGuard::from_config(&crate::config::SafetyConfig {
+            confirm: vec!["*rm -rf*".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
         let mut app = AppState::new(
-            vec![mock_command("artisan cache:clear")],
+            vec![restart],
             None,
             HashMap::new(),
             default_ranking(),
+            default_output(),
+            guard,
             test_runtime(),
         );
-        app.show_help = true;
-        app.query = "cache".to_string();
-        app.query_cursor = app.query.chars().count();
 
-        let action = app.on_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
-        assert!(matches!(action, UiAction::None));
-        assert_eq!(app.query, "cachex");
-        assert!(!app.show_help);
+        app.prepare_selected_command(true);
+        let Mode::ConfirmRun(confirm) = &app.mode else {
+            panic!("expected a confirmation prompt");
+        };
+        assert_eq!(confirm.pattern, "*rm -rf*");
+        assert_eq!(
+            confirm.warning.as_deref(),
+            Some("WARNING: 'restart' runs as root (sudo)")
+        );
     }
 
     #[test]
-    fn tab_switches_focus_to_session_and_typing_returns_to_commands() {
-        let mut app = AppState::new(
-            vec![mock_command("artisan cache:clear")],
+    fn schedules_and_evals_skip_commands_with_a_warning() {
+        let mut restart = mock_command("restart");
+        restart.template = "sudo systemctl restart app".to_string();
+        restart.schedule = Some(Duration::from_secs(60));
+        let mut deploy = mock_command("deploy");
+        deploy.template = "./deploy.sh".to_string();
+        deploy.host = Some("ops@prod-1".to_string());
+        deploy.schedule = Some(Duration::from_secs(60));
+        let app = AppState::new(
+            vec![restart, deploy],
             None,
             HashMap::new(),
             default_ranking(),
+            default_output(),
+            CommandGuard::from_config(&Default::default()).unwrap(),
             test_runtime(),
         );
 
-        assert!(matches!(app.active_pane, ActivePane::Commands));
-        app.on_search_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
-        assert!(matches!(app.active_pane, ActivePane::Session));
-
-        app.on_search_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
-        assert!(matches!(app.active_pane, ActivePane::Commands));
-        assert_eq!(app.query, "x");
+        assert!(app.schedules.is_empty());
+        for skipped in [
+            "schedule for 'restart' skipped: it runs as root (sudo)",
+            "schedule for 'deploy' skipped: it targets production host 'prod-1'",
+        ] {
+            assert!(app.chat.iter().any(|line| line.text.contains(skipped)));
+        }
+        let error = app
+            .eval_param("$(sudo cat /etc/shadow)", Path::new("."))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "expression runs as root (sudo)");
     }
 
     #[test]
-    fn session_focus_allows_scroll_keys() {
-        let mut app = AppState::new(
-            vec![mock_command("artisan cache:clear")],
-            None,
-            HashMap::new(),
-            default_ranking(),
-            test_runtime(),
-        );
-        for i in 0..20 {
-            app.push_info(format!("line {i}"));
+    fn palette_filters_and_runs_actions() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
+        app.push_info("some output");
+
+        app.on_key(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL));
+        assert!(matches!(app.mode, Mode::Palette(_)));
+        for ch in "clear sess".chars() {
+            app.on_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
         }
+        let action = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
 
-        app.on_search_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
-        assert!(matches!(app.active_pane, ActivePane::Session));
-        assert_eq!(app.session_scroll, 0);
+        assert!(matches!(action, UiAction::None));
+        assert!(matches!(app.mode, Mode::Search));
+        assert!(app.chat.is_empty());
+    }
 
-        app.on_search_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
-        assert!(app.session_scroll > 0);
+    #[test]
+    fn alias_report_lists_aliases_and_conflicts() {
+        let aliases = HashMap::from([
+            ("a".to_string(), "artisan".to_string()),
+            ("composer".to_string(), "justfile".to_string()),
+        ]);
+        let lines = provider_alias_report(&aliases, &["artisan", "composer", "justfile"]);
+
+        assert!(lines.contains(&"  :a -> artisan".to_string()));
+        assert!(lines.contains(&"Usable by provider name: :composer".to_string()));
+        assert!(lines.iter().any(|line| {
+            line.starts_with("Conflict: alias ':composer'") && line.contains("'composer'")
+        }));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("Note: ':artisan'"))
+        );
     }
 
     #[test]
-    fn enter_does_nothing_when_session_is_active() {
+    fn empty_results_suggest_closest_provider_alias() {
+        let mut aliases = HashMap::new();
+        aliases.insert("art".to_string(), "artisan".to_string());
         let mut app = AppState::new(
             vec![mock_command("artisan cache:clear")],
             None,
-            HashMap::new(),
+            aliases,
             default_ranking(),
+            default_output(),
+            CommandGuard::default(),
             test_runtime(),
         );
 
-        app.on_search_key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
-        assert!(matches!(app.active_pane, ActivePane::Session));
+        app.query = ":arr cache".to_string();
+        app.refresh_filtered();
+        assert!(app.filtered.is_empty());
+        assert_eq!(
+            app.empty_result_hint(),
+            "no provider ':arr' — did you mean ':art' (artisan)?"
+        );
 
-        let action = app.on_search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
-        assert!(matches!(action, UiAction::None));
-        assert!(matches!(app.mode, Mode::Search));
+        app.query = "zzz".to_string();
+        app.refresh_filtered();
+        assert_eq!(app.empty_result_hint(), "no commands match 'zzz'");
     }
 
     #[test]
-    fn parses_internal_reload_command() {
-        let parsed = parse_internal_command("/reload").unwrap();
-        assert!(matches!(parsed, InternalCommand::Reload));
+    fn empty_catalog_hint_points_to_init() {
+        let app = app_with(Vec::new());
+        assert!(app.empty_result_hint().contains("run /init"));
     }
 
     #[test]
-    fn parses_internal_init_force_command() {
-        let parsed = parse_internal_command("/init --force").unwrap();
-        assert!(matches!(parsed, InternalCommand::Init { force: true }));
+    fn edit_distance_counts_single_edits() {
+        assert_eq!(edit_distance("relaod", "reload"), 2);
+        assert_eq!(edit_distance("init", "init"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
     }
 
     #[test]
-    fn internal_init_without_force_opens_prompt() {
-        let mut app = AppState::new(
-            vec![mock_command("artisan cache:clear")],
-            None,
-            HashMap::new(),
-            default_ranking(),
-            test_runtime(),
-        );
-        app.query = "/init".to_string();
-        app.query_cursor = app.query.chars().count();
+    fn unknown_internal_command_offers_correction() {
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
+        app.query = "/relaod".to_string();
         app.refresh_filtered();
 
         let action = app.on_search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
         assert!(matches!(action, UiAction::None));
-        assert!(matches!(app.mode, Mode::InternalPrompt(_)));
+        assert_eq!(app.query, "/reload");
+
+        let action = app.on_search_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert!(matches!(
+            action,
+            UiAction::RunInternal(InternalRunRequest {
+                command: InternalCommand::Reload
+            })
+        ));
     }
 
     #[test]
-    fn flag_param_prompt_uses_default_on_enter() {
-        let mut command = mock_command("deploy");
-        command.template = "deploy {{force}}".to_string();
-        command.params = vec![crate::model::ParamSpec {
-            name: "force".to_string(),
-            kind: ParamType::Flag,
-            prompt: "Use --force?".to_string(),
-            placeholder: None,
-            default_value: None,
-            value_value: None,
-            default_flag: Some(false),
-            value_flag: None,
-            required: false,
-            prompt_in_tui: true,
-        }];
+    fn sticky_header_tracks_command_above_viewport() {
+        let chat = vec![
+            ChatLine {
+                kind: ChatLineKind::Info,
+                text: "Loaded".to_string(),
+                repeat: 1,
+                full_text: None,
+                json: None,
+            },
+            ChatLine {
+                kind: ChatLineKind::Command,
+                text: "make build".to_string(),
+                repeat: 1,
+                full_text: None,
+                json: None,
+            },
+            ChatLine {
+                kind: ChatLineKind::Stdout,
+                text: "compiling".to_string(),
+                repeat: 1,
+                full_text: None,
+                json: None,
+            },
+            ChatLine {
+                kind: ChatLineKind::Stdout,
+                text: "done".to_string(),
+                repeat: 1,
+                full_text: None,
+                json: None,
+            },
+        ];
+
+        let rows: Vec<SessionRow> = (0..chat.len())
+            .map(|line| SessionRow {
+                line,
+                json_line: None,
+            })
+            .collect();
+        assert_eq!(sticky_command_index(&chat, &rows, 0), None);
+        assert_eq!(sticky_command_index(&chat, &rows, 1), None);
+        assert_eq!(sticky_command_index(&chat, &rows, 3), Some(1));
+    }
+
+    #[test]
+    fn repeated_output_lines_fold_into_one_entry() {
+        let mut app = app_with(Vec::new());
+        app.chat.clear();
+        for _ in 0..57 {
+            app.push_line(ChatLineKind::Stdout, "retrying...".to_string());
+        }
+        app.push_line(ChatLineKind::Stdout, "ok".to_string());
+
+        assert_eq!(app.chat.len(), 2);
+        assert_eq!(app.chat[0].repeat, 57);
+        assert_eq!(app.session_rows().len(), 2);
+
+        app.expand_repeats = true;
+        assert_eq!(app.session_rows().len(), 58);
+    }
 
+    #[test]
+    fn long_output_lines_are_truncated_and_viewable() {
         let mut app = AppState::new(
-            vec![command],
+            Vec::new(),
             None,
             HashMap::new(),
             default_ranking(),
+            OutputSettings {
+                max_line_bytes: 8,
+                pretty_json: false,
+                color: ColorMode::Force,
+                pty: false,
+                timeout: None,
+                target: ExecTarget::Session,
+                terminal: None,
+            },
+            CommandGuard::default(),
             test_runtime(),
         );
-        let action = app.prepare_selected_command(true);
-        assert!(matches!(action, UiAction::None));
-        assert!(matches!(app.mode, Mode::Prompt(_)));
+        let blob = "aé".repeat(10);
+        app.push_line(ChatLineKind::Stdout, blob.clone());
 
-        let action = app.on_prompt_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
-        let UiAction::Run(request) = action else {
-            panic!("expected command run request");
+        let line = app.chat.last().unwrap();
+        assert_eq!(line.text, "aéaéa… [+23 bytes]");
+        assert_eq!(line.full_text.as_deref(), Some(blob.as_str()));
+
+        app.open_truncated_line_pager();
+        let Mode::Pager(pager) = &app.mode else {
+            panic!("expected pager");
         };
-        assert_eq!(request.command_line.trim(), "deploy");
+        assert_eq!(pager.text, blob);
     }
 
     #[test]
-    fn flag_param_prompt_accepts_y_without_enter() {
-        let mut command = mock_command("deploy");
-        command.template = "deploy {{force}}".to_string();
-        command.params = vec![crate::model::ParamSpec {
-            name: "force".to_string(),
-            kind: ParamType::Flag,
-            prompt: "Use --force?".to_string(),
-            placeholder: None,
-            default_value: None,
-            value_value: None,
-            default_flag: Some(false),
-            value_flag: None,
-            required: false,
-            prompt_in_tui: true,
-        }];
+    fn json_output_expands_into_pretty_rows_when_enabled() {
+        let mut app = app_with(Vec::new());
+        app.chat.clear();
+        app.push_line(
+            ChatLineKind::Stdout,
+            r#"{"name":"fzc","ok":true}"#.to_string(),
+        );
+        app.push_line(ChatLineKind::Stdout, "{not json".to_string());
+        assert_eq!(app.session_rows().len(), 2);
 
-        let mut app = AppState::new(
-            vec![command],
-            None,
-            HashMap::new(),
-            default_ranking(),
-            test_runtime(),
+        app.output.pretty_json = true;
+        let rows = app.session_rows();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(app.chat[0].json.as_ref().unwrap()[1], r#"  "name": "fzc","#);
+    }
+
+    #[test]
+    fn output_filter_hides_non_matching_output_while_typing() {
+        let mut app = app_with(Vec::new());
+        app.chat.clear();
+        app.push_line(ChatLineKind::Command, "make build".to_string());
+        app.push_line(ChatLineKind::Stdout, "compiling core".to_string());
+        app.push_line(ChatLineKind::Stderr, "warning: unused".to_string());
+        app.push_line(ChatLineKind::Stdout, "DONE in 3s".to_string());
+
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert!(!app.on_running_key(key(KeyCode::Char('/'))));
+        for ch in "^DONE".chars() {
+            app.on_running_key(key(KeyCode::Char(ch)));
+        }
+        assert!(!app.on_running_key(key(KeyCode::Enter)));
+        assert!(app.filter_input.is_none());
+        let lines: Vec<usize> = app.session_rows().iter().map(|row| row.line).collect();
+        assert_eq!(lines, vec![0, 3]);
+        assert_eq!(app.chat.len(), 4);
+
+        app.output_filter = Some(OutputFilter::new("unused ("));
+        assert_eq!(app.session_rows().len(), 1);
+
+        assert!(app.on_running_key(key(KeyCode::Esc)));
+    }
+
+    #[test]
+    fn usage_boost_can_be_log_scaled_and_capped() {
+        let mut ranking = default_ranking();
+        assert_eq!(usage_boost(100.0, &ranking), 800_000);
+
+        ranking.usage_scale = UsageScale::Log;
+        assert_eq!(usage_boost(0.0, &ranking), 0);
+        assert_eq!(usage_boost(1.0, &ranking), 8_000);
+        assert_eq!(usage_boost(3.0, &ranking), 16_000);
+        assert!(usage_boost(100.0, &ranking) < 60_000);
+
+        ranking.usage_cap = Some(10_000);
+        assert_eq!(usage_boost(3.0, &ranking), 10_000);
+    }
+
+    #[test]
+    fn frecency_lets_recent_use_outrank_old_frequent_use() {
+        const DAY: u64 = 86_400;
+        let now = 400 * DAY;
+        let mut old = UsageRecord::default();
+        for _ in 0..10 {
+            old.record_use(now - 180 * DAY, 30.0);
+        }
+        let mut recent = UsageRecord::default();
+        recent.record_use(now - DAY, 30.0);
+
+        assert!(recent.frecency(now, 30.0) > old.frecency(now, 30.0));
+        assert!((old.frecency(now - 180 * DAY, 30.0) - 10.0).abs() < 1e-9);
+        assert_eq!(old.frecency(now, 0.0), 10.0);
+    }
+
+    #[test]
+    fn pinned_commands_sort_above_unpinned_ones() {
+        let mut app = app_with(vec![
+            mock_command("migrate"),
+            mock_command("migrate:fresh"),
+            mock_command("queue:work"),
+        ]);
+        app.usage
+            .entry("artisan::migrate".to_string())
+            .or_default()
+            .record_use(history::now(), 30.0);
+        app.selected = 2;
+        app.on_key(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        let names = |app: &AppState| -> Vec<String> {
+            app.filtered
+                .iter()
+                .map(|item| match item {
+                    SearchItem::Command(index) => app.commands[*index].name.clone(),
+                    SearchItem::Internal(_) => String::new(),
+                })
+                .collect()
+        };
+        assert!(app.pinned.contains("artisan::queue:work"));
+        assert!(app.is_pinned(&app.commands[2]) && !app.is_pinned(&app.commands[1]));
+        assert_eq!(names(&app)[0], "queue:work");
+        assert_eq!(app.selected, 0);
+
+        // Every command matches through its description; usage still orders the unpinned rest.
+        app.query = "artisan".to_string();
+        app.refresh_filtered();
+        assert_eq!(names(&app)[..2], ["queue:work", "migrate"]);
+
+        app.on_key(KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL));
+        assert!(app.pinned.is_empty());
+        assert!(app.pins_path.is_none());
+    }
+
+    #[test]
+    fn usage_store_is_untouched_when_persistence_is_disabled() {
+        let mut app = app_with(vec![mock_command("artisan migrate")]);
+        assert!(app.usage_path.is_none());
+
+        app.record_usage("artisan::artisan migrate");
+        assert_eq!(
+            app.usage
+                .get("artisan::artisan migrate")
+                .map(|record| record.count),
+            Some(1)
         );
-        let action = app.prepare_selected_command(true);
-        assert!(matches!(action, UiAction::None));
-        assert!(matches!(app.mode, Mode::Prompt(_)));
+        assert!(app.usage_path.is_none());
+    }
 
-        let action = app.on_prompt_key(KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE));
-        let UiAction::Run(request) = action else {
-            panic!("expected command run request");
+    #[test]
+    fn record_usage_merges_counts_written_by_other_instances() {
+        let mut app = app_with(Vec::new());
+        let path =
+            std::env::temp_dir().join(format!("fzc-usage-merge-{}.toml", std::process::id()));
+        fs::write(&path, "[counts]\n\"just::just build\" = 4\n").unwrap();
+        app.usage_path = Some(path.clone());
+
+        app.record_usage("config::deploy");
+
+        let stored = read_usage_store(&path, 0);
+        assert_eq!(stored.get("just::just build").map(|r| r.count), Some(4));
+        assert_eq!(stored.get("config::deploy").map(|r| r.count), Some(1));
+
+        // Two instances that loaded the same counts each add their own use.
+        let mut other = app_with(Vec::new());
+        other.usage = read_usage_store(&path, 0);
+        other.usage_path = Some(path.clone());
+        app.record_usage("just::just build");
+        other.record_usage("just::just build");
+        let stored = read_usage_store(&path, 0);
+        assert_eq!(stored.get("just::just build").map(|r| r.count), Some(6));
+        assert!(!path.with_extension("toml.lock").exists());
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn recent_projects_are_deduplicated_most_recent_first() {
+        let mut roots = vec![PathBuf::from("/a"), PathBuf::from("/b")];
+        remember_project(&mut roots, Path::new("/b"));
+        assert_eq!(roots, vec![PathBuf::from("/b"), PathBuf::from("/a")]);
+
+        for index in 0..MAX_RECENT_PROJECTS {
+            remember_project(&mut roots, &PathBuf::from(format!("/p{index}")));
+        }
+        assert_eq!(roots.len(), MAX_RECENT_PROJECTS);
+        assert_eq!(
+            roots[0],
+            PathBuf::from(format!("/p{}", MAX_RECENT_PROJECTS - 1))
+        );
+    }
+
+    #[test]
+    fn project_root_walks_up_to_marker() {
+        let root = std::env::temp_dir().join(format!("fzc-project-root-{}", std::process::id()));
+        let nested = root.join("src/deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join("justfile"), "").unwrap();
+
+        assert_eq!(project_root(&nested), root);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn project_picker_selection_switches_project() {
+        let mut app = app_with(Vec::new());
+        app.mode = Mode::ProjectPicker(ProjectPickerState {
+            projects: vec![PathBuf::from("/srv/api"), PathBuf::from("/srv/web")],
+            selected: 0,
+        });
+
+        app.on_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        let action = app.on_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let UiAction::RunInternal(InternalRunRequest {
+            command: InternalCommand::SwitchProject(path),
+        }) = action
+        else {
+            panic!("expected project switch");
         };
-        assert_eq!(request.command_line.trim(), "deploy --force");
+        assert_eq!(path, PathBuf::from("/srv/web"));
+        assert!(matches!(app.mode, Mode::Search));
+    }
+
+    #[test]
+    fn unknown_internal_command_lists_every_registered_one() {
+        let app = app_with(Vec::new());
+        let InternalTaskResult::Error(message) =
+            run_internal_task(&app.runtime, InternalCommand::Unknown("nope".to_string()))
+        else {
+            panic!("expected an error");
+        };
+        for command in INTERNAL_COMMANDS {
+            assert!(message.contains(command.name), "{message}");
+        }
     }
 
     #[test]
     fn slash_query_only_shows_internal_commands() {
-        let mut app = AppState::new(
-            vec![mock_command("artisan cache:clear")],
-            None,
-            HashMap::new(),
-            default_ranking(),
-            test_runtime(),
-        );
+        let mut app = app_with(vec![mock_command("artisan cache:clear")]);
         app.query = "/".to_string();
         app.refresh_filtered();
 
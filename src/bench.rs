@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::app::{self, RankingSettings, RuntimeContext};
+use crate::config::LoadedConfig;
+use crate::model::{CommandCatalog, CommandEntry};
+use crate::provider;
+
+const SAMPLE_QUERIES: &[&str] = &["a", "test", "cache clear", "migrate fresh seed", "zzzz"];
+
+/// The `[[commands]]` of the loaded config and how long they took to load, when the config
+/// provider is enabled. Separate from [`run`] so its errors are reported as config errors.
+pub fn load_config_commands(
+    loaded: &LoadedConfig,
+    cwd: &Path,
+) -> Result<Option<(Vec<CommandEntry>, Duration)>> {
+    if !loaded.config.providers.config.enabled {
+        return Ok(None);
+    }
+    let (commands, elapsed) = timed(|| CommandCatalog::from_config(loaded, cwd))?;
+    Ok(Some((commands.into_vec(), elapsed)))
+}
+
+/// `fzc bench`: catalog load times per provider and search latency over the loaded catalog.
+/// Errors come from loading providers.
+pub fn run(
+    loaded: &LoadedConfig,
+    config_commands: Option<(Vec<CommandEntry>, Duration)>,
+    provider_aliases: HashMap<String, String>,
+    ranking: RankingSettings,
+    runtime: RuntimeContext,
+    queries: &[String],
+    iterations: usize,
+) -> Result<()> {
+    let cwd = runtime.cwd.clone();
+    match &loaded.path {
+        Some(path) => println!("config: {}", path.display()),
+        None => println!("config: none (providers only or defaults)"),
+    }
+    println!("cwd: {}", cwd.display());
+    println!();
+
+    let mut catalog = CommandCatalog::empty();
+    let mut rows = Vec::new();
+    let mut total = Duration::ZERO;
+    if let Some((commands, elapsed)) = config_commands {
+        rows.push(("config", commands.len(), elapsed));
+        total += elapsed;
+        catalog.extend(commands);
+    }
+    let started = Instant::now();
+    // Checked before loading, which writes the caches it misses.
+    let cached: Vec<String> = provider::enabled_provider_names(&loaded.config.providers)
+        .into_iter()
+        .filter(|name| provider::provider_cached_at(name, &cwd).is_some())
+        .collect();
+    let (commands, timings) =
+        provider::load_provider_commands_timed(&loaded.config.providers, &cwd)?;
+    rows.extend(
+        timings
+            .iter()
+            .map(|timing| (timing.name.as_str(), timing.commands, timing.elapsed)),
+    );
+    catalog.extend(commands);
+    total += started.elapsed();
+    let commands = catalog.into_vec();
+
+    println!("providers:");
+    if rows.is_empty() {
+        println!("  (none enabled)");
+    }
+    for (name, count, elapsed) in &rows {
+        let from_cache = if cached.iter().any(|cached| cached == name) {
+            "  (cached)"
+        } else {
+            ""
+        };
+        println!(
+            "  {name:<10} {count:>6} commands  {}{from_cache}",
+            format_duration(*elapsed)
+        );
+    }
+    println!(
+        "  {:<10} {:>6} commands  {}",
+        "total",
+        commands.len(),
+        format_duration(total)
+    );
+    println!();
+
+    let queries: Vec<String> = if queries.is_empty() {
+        SAMPLE_QUERIES
+            .iter()
+            .map(|query| query.to_string())
+            .collect()
+    } else {
+        queries.to_vec()
+    };
+    let count = commands.len();
+    let results = app::measure_match_latency(
+        commands,
+        provider_aliases,
+        ranking,
+        runtime,
+        &queries,
+        iterations,
+    );
+
+    println!("matching ({count} commands, {iterations} iterations per query):");
+    for result in results {
+        println!(
+            "  {:<24} {} avg  {} matches",
+            format!("{:?}", result.query),
+            format_duration(result.average),
+            result.matches
+        );
+    }
+
+    Ok(())
+}
+
+fn timed<T>(load: impl FnOnce() -> Result<T>) -> Result<(T, Duration)> {
+    let started = Instant::now();
+    let value = load()?;
+    Ok((value, started.elapsed()))
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:>9.3} ms", duration.as_secs_f64() * 1_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::format_duration;
+
+    #[test]
+    fn formats_durations_in_milliseconds() {
+        assert_eq!(
+            format_duration(Duration::from_micros(1_500)),
+            "    1.500 ms"
+        );
+        assert_eq!(format_duration(Duration::ZERO), "    0.000 ms");
+    }
+}
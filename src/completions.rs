@@ -0,0 +1,423 @@
+use clap::builder::ValueHint;
+use clap::{Command, ValueEnum};
+
+/// Subcommand whose first positional completes to catalog command names.
+const CATALOG_COMMAND: &str = "run";
+/// Shell snippet printing one catalog command name per line; `--cached` reuses the last list for
+/// the directory so a TAB does not load every provider.
+const CATALOG_NAMES: &str = "fzc list --format names --cached 2>/dev/null";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+struct FlagSpec {
+    short: Option<char>,
+    long: String,
+    help: String,
+    takes_value: bool,
+    values: Vec<String>,
+    path: bool,
+}
+
+struct CommandSpec {
+    name: String,
+    about: String,
+    flags: Vec<FlagSpec>,
+    /// Possible values of the first positional, if it has a fixed set.
+    positional_values: Vec<String>,
+    catalog_names: bool,
+}
+
+/// `fzc completions <shell>`: a completion script generated from the clap definition.
+pub fn render(command: &Command, shell: Shell) -> String {
+    let root = command_spec(command, false);
+    let subcommands: Vec<CommandSpec> = command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .map(|sub| {
+            let mut spec = command_spec(sub, sub.get_name() == CATALOG_COMMAND);
+            // Nested actions (`fzc favorites export`) complete like a fixed positional.
+            if spec.positional_values.is_empty() {
+                spec.positional_values = sub
+                    .get_subcommands()
+                    .filter(|nested| !nested.is_hide_set())
+                    .map(|nested| nested.get_name().to_string())
+                    .collect();
+            }
+            spec
+        })
+        .collect();
+    match shell {
+        Shell::Bash => render_bash(&root, &subcommands),
+        Shell::Zsh => render_zsh(&root, &subcommands),
+        Shell::Fish => render_fish(&root, &subcommands),
+        Shell::Powershell => render_powershell(&root, &subcommands),
+    }
+}
+
+fn command_spec(command: &Command, catalog_names: bool) -> CommandSpec {
+    let mut flags = Vec::new();
+    let mut positional_values = Vec::new();
+    for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let values: Vec<String> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        if arg.is_positional() {
+            if positional_values.is_empty() {
+                positional_values = values;
+            }
+            continue;
+        }
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        flags.push(FlagSpec {
+            short: arg.get_short(),
+            long: long.to_string(),
+            help: arg.get_help().map(ToString::to_string).unwrap_or_default(),
+            takes_value: arg.get_action().takes_values(),
+            values,
+            path: matches!(
+                arg.get_value_hint(),
+                ValueHint::FilePath | ValueHint::AnyPath | ValueHint::DirPath
+            ),
+        });
+    }
+    CommandSpec {
+        name: command.get_name().to_string(),
+        about: command
+            .get_about()
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+        flags,
+        positional_values,
+        catalog_names,
+    }
+}
+
+impl FlagSpec {
+    fn spellings(&self) -> Vec<String> {
+        let mut spellings = vec![format!("--{}", self.long)];
+        if let Some(short) = self.short {
+            spellings.push(format!("-{short}"));
+        }
+        spellings
+    }
+}
+
+fn render_bash(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let names: Vec<&str> = subcommands.iter().map(|sub| sub.name.as_str()).collect();
+    let mut out = String::from("_fzc() {\n");
+    out.push_str(
+        "    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n",
+    );
+    out.push_str("    local sub=\"\" word\n");
+    out.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+    out.push_str(&format!(
+        "        case \"$word\" in {}) sub=\"$word\"; break ;; esac\n",
+        names.join("|")
+    ));
+    out.push_str("    done\n");
+    out.push_str("    case \"$sub\" in\n");
+    for spec in std::iter::once(root).chain(subcommands) {
+        let label = if std::ptr::eq(spec, root) {
+            "\"\""
+        } else {
+            spec.name.as_str()
+        };
+        out.push_str(&format!("        {label})\n"));
+        out.push_str("            case \"$prev\" in\n");
+        for flag in spec.flags.iter().filter(|flag| flag.takes_value) {
+            let action = if !flag.values.is_empty() {
+                format!(
+                    "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                    flag.values.join(" ")
+                )
+            } else if flag.path {
+                "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string()
+            } else {
+                "COMPREPLY=()".to_string()
+            };
+            out.push_str(&format!(
+                "                {}) {action}; return ;;\n",
+                flag.spellings().join("|")
+            ));
+        }
+        out.push_str("            esac\n");
+        let mut words: Vec<String> = spec.flags.iter().map(|f| format!("--{}", f.long)).collect();
+        if std::ptr::eq(spec, root) {
+            words.extend(names.iter().map(ToString::to_string));
+        }
+        words.extend(spec.positional_values.iter().cloned());
+        if spec.catalog_names {
+            // Names may contain spaces and quotes, so match and insert them shell-quoted
+            // rather than through `compgen -W`, which word-splits its list.
+            out.push_str("            if [[ \"$cur\" != -* ]]; then\n");
+            out.push_str("                local name quoted\n");
+            out.push_str("                COMPREPLY=()\n");
+            out.push_str("                while IFS= read -r name; do\n");
+            out.push_str("                    printf -v quoted '%q' \"$name\"\n");
+            out.push_str(
+                "                    [[ \"$quoted\" == \"$cur\"* ]] && COMPREPLY+=(\"$quoted\")\n",
+            );
+            out.push_str(&format!("                done < <({CATALOG_NAMES})\n"));
+            out.push_str("                return\n");
+            out.push_str("            fi\n");
+        }
+        out.push_str(&format!(
+            "            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n",
+            words.join(" ")
+        ));
+    }
+    out.push_str("    esac\n}\ncomplete -F _fzc fzc\n");
+    out
+}
+
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh_arguments(spec: &CommandSpec) -> Vec<String> {
+    let mut lines = Vec::new();
+    for flag in &spec.flags {
+        let value = if !flag.takes_value {
+            String::new()
+        } else if !flag.values.is_empty() {
+            format!(":value:({})", flag.values.join(" "))
+        } else if flag.path {
+            ":file:_files".to_string()
+        } else {
+            ":value: ".to_string()
+        };
+        for spelling in flag.spellings() {
+            lines.push(format!("'{spelling}[{}]{value}'", zsh_escape(&flag.help)));
+        }
+    }
+    lines
+}
+
+fn render_zsh(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let mut out = String::from("#compdef fzc\n\n");
+    out.push_str("_fzc_catalog_names() {\n");
+    out.push_str(&format!(
+        "    local -a names\n    names=(\"${{(@f)$({CATALOG_NAMES})}}\")\n    compadd -a names\n}}\n\n"
+    ));
+    out.push_str("_fzc() {\n    local line state\n    _arguments -C \\\n");
+    for line in zsh_arguments(root) {
+        out.push_str(&format!("        {line} \\\n"));
+    }
+    out.push_str("        '1: :->commands' \\\n        '*:: :->args'\n");
+    out.push_str("    case $state in\n        commands)\n            local -a commands\n");
+    out.push_str("            commands=(\n");
+    for sub in subcommands {
+        out.push_str(&format!(
+            "                '{}:{}'\n",
+            sub.name,
+            zsh_escape(&sub.about)
+        ));
+    }
+    out.push_str("            )\n            _describe 'command' commands ;;\n");
+    out.push_str("        args)\n            case $line[1] in\n");
+    for sub in subcommands {
+        let mut lines = zsh_arguments(sub);
+        if sub.catalog_names {
+            lines.push("'1:command:_fzc_catalog_names'".to_string());
+        } else if !sub.positional_values.is_empty() {
+            lines.push(format!("'1:value:({})'", sub.positional_values.join(" ")));
+        }
+        out.push_str(&format!("                {})\n", sub.name));
+        if lines.is_empty() {
+            out.push_str("                    ;;\n");
+            continue;
+        }
+        out.push_str(&format!(
+            "                    _arguments {} ;;\n",
+            lines.join(" \\\n                        ")
+        ));
+    }
+    out.push_str("            esac ;;\n    esac\n}\n\n_fzc \"$@\"\n");
+    out
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish_flag(condition: &str, flag: &FlagSpec) -> String {
+    let mut line = format!("complete -c fzc -n {}", fish_quote(condition));
+    if let Some(short) = flag.short {
+        line.push_str(&format!(" -s {short}"));
+    }
+    line.push_str(&format!(" -l {}", flag.long));
+    if flag.takes_value {
+        line.push_str(" -r");
+        if !flag.values.is_empty() {
+            line.push_str(&format!(" -f -a {}", fish_quote(&flag.values.join(" "))));
+        } else if flag.path {
+            line.push_str(" -F");
+        }
+    }
+    if !flag.help.is_empty() {
+        line.push_str(&format!(" -d {}", fish_quote(&flag.help)));
+    }
+    line.push('\n');
+    line
+}
+
+fn render_fish(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let mut out = String::from("complete -c fzc -f\n");
+    for flag in &root.flags {
+        out.push_str(&fish_flag("__fish_use_subcommand", flag));
+    }
+    for sub in subcommands {
+        out.push_str(&format!(
+            "complete -c fzc -n '__fish_use_subcommand' -a {} -d {}\n",
+            sub.name,
+            fish_quote(&sub.about)
+        ));
+    }
+    for sub in subcommands {
+        let condition = format!("__fish_seen_subcommand_from {}", sub.name);
+        for flag in &sub.flags {
+            out.push_str(&fish_flag(&condition, flag));
+        }
+        if sub.catalog_names {
+            out.push_str(&format!(
+                "complete -c fzc -n {} -a {}\n",
+                fish_quote(&condition),
+                fish_quote(&format!("({CATALOG_NAMES})"))
+            ));
+        } else if !sub.positional_values.is_empty() {
+            out.push_str(&format!(
+                "complete -c fzc -n {} -a {}\n",
+                fish_quote(&condition),
+                fish_quote(&sub.positional_values.join(" "))
+            ));
+        }
+    }
+    out
+}
+
+fn powershell_list(items: &[String]) -> String {
+    let quoted: Vec<String> = items
+        .iter()
+        .map(|item| format!("'{}'", item.replace('\'', "''")))
+        .collect();
+    format!("@({})", quoted.join(", "))
+}
+
+fn render_powershell(root: &CommandSpec, subcommands: &[CommandSpec]) -> String {
+    let names: Vec<String> = subcommands.iter().map(|sub| sub.name.clone()).collect();
+    let mut out = String::from(
+        "Register-ArgumentCompleter -Native -CommandName fzc -ScriptBlock {\n    \
+         param($wordToComplete, $commandAst, $cursorPosition)\n    \
+         $words = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object { $_.ToString() })\n    \
+         if ($wordToComplete) { $words = @($words | Select-Object -SkipLast 1) }\n    \
+         $prev = if ($words.Count) { $words[-1] } else { '' }\n",
+    );
+    out.push_str(&format!(
+        "    $sub = ''\n    foreach ($word in $words) {{ if ({} -contains $word) {{ $sub = $word; break }} }}\n",
+        powershell_list(&names)
+    ));
+    out.push_str("    $candidates = switch ($sub) {\n");
+    for spec in std::iter::once(root).chain(subcommands) {
+        let label = if std::ptr::eq(spec, root) {
+            String::new()
+        } else {
+            spec.name.clone()
+        };
+        out.push_str(&format!(
+            "        '{label}' {{\n            switch ($prev) {{\n"
+        ));
+        for flag in spec.flags.iter().filter(|flag| flag.takes_value) {
+            for spelling in flag.spellings() {
+                out.push_str(&format!(
+                    "                '{spelling}' {{ {}; break }}\n",
+                    powershell_list(&flag.values)
+                ));
+            }
+        }
+        let mut words: Vec<String> = spec.flags.iter().map(|f| format!("--{}", f.long)).collect();
+        if std::ptr::eq(spec, root) {
+            words.extend(names.iter().cloned());
+        }
+        words.extend(spec.positional_values.iter().cloned());
+        let catalog = if spec.catalog_names {
+            " + @(fzc list --format names --cached 2>$null)"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "                default {{ {}{catalog} }}\n            }}\n        }}\n",
+            powershell_list(&words)
+        ));
+    }
+    out.push_str(
+        "    }\n    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n        \
+         $text = if ($_ -match '[\\s''\"`$;&|(){}@#,]') { \"'\" + ($_ -replace \"'\", \"''\") + \"'\" } else { $_ }\n        \
+         [System.Management.Automation.CompletionResult]::new($text, $_, 'ParameterValue', $_)\n    }\n}\n",
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Arg, ArgAction, Command, value_parser};
+
+    use super::{Shell, render};
+
+    fn sample() -> Command {
+        Command::new("fzc")
+            .arg(
+                Arg::new("config")
+                    .short('c')
+                    .long("config")
+                    .value_hint(clap::ValueHint::FilePath),
+            )
+            .subcommand(
+                Command::new("run")
+                    .about("Run a command")
+                    .arg(Arg::new("name"))
+                    .arg(Arg::new("yes").long("yes").action(ArgAction::SetTrue)),
+            )
+            .subcommand(
+                Command::new("completions")
+                    .arg(Arg::new("shell").value_parser(value_parser!(Shell))),
+            )
+    }
+
+    #[test]
+    fn scripts_cover_subcommands_flags_and_catalog_names() {
+        let bash = render(&sample(), Shell::Bash);
+        assert!(bash.contains("case \"$word\" in run|completions)"));
+        assert!(bash.contains("--config|-c) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;"));
+        assert!(bash.contains("done < <(fzc list --format names --cached 2>/dev/null)"));
+        assert!(bash.contains("printf -v quoted '%q' \"$name\""));
+        assert!(!bash.contains("compgen -W \"$(fzc list"));
+        assert!(bash.contains("compgen -W \"bash zsh fish powershell\""));
+
+        let zsh = render(&sample(), Shell::Zsh);
+        assert!(zsh.contains("'--config[]:file:_files'"));
+        assert!(zsh.contains("'1:command:_fzc_catalog_names'"));
+
+        let fish = render(&sample(), Shell::Fish);
+        assert!(fish.contains("-s c -l config -r -F"));
+        assert!(fish.contains("'__fish_seen_subcommand_from run' -a '(fzc list"));
+
+        let powershell = render(&sample(), Shell::Powershell);
+        assert!(powershell.contains("'--yes'"));
+        assert!(powershell.contains("@('run', 'completions')"));
+        assert!(powershell.contains("CompletionResult]::new($text, $_"));
+    }
+}
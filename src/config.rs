@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Deserializer};
@@ -9,16 +10,42 @@ use serde::{Deserialize, Deserializer};
 pub struct LoadedConfig {
     pub config: Config,
     pub path: Option<PathBuf>,
+    /// The global config that the project config at `path` was merged over.
+    pub global: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct Config {
+    /// In a project config: do not merge it over the global config.
+    #[serde(default)]
+    pub root: bool,
+    /// Config files merged under this one, e.g. `~/.config/fzc/commands/*.toml`.
+    #[serde(default)]
+    pub include: Vec<String>,
     #[serde(default)]
     pub providers: ProvidersConfig,
     #[serde(default)]
     pub ranking: RankingConfig,
     #[serde(default)]
     pub commands: Vec<CommandConfig>,
+    #[serde(default)]
+    pub pipelines: Vec<PipelineConfig>,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub events: EventsConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
 }
 
 fn default_true() -> bool {
@@ -33,11 +60,31 @@ fn default_usage_weight() -> i64 {
     8_000
 }
 
+fn default_decay_half_life_days() -> f64 {
+    30.0
+}
+
+fn default_max_line_bytes() -> usize {
+    4_096
+}
+
+fn default_poll_interval_ms() -> u64 {
+    100
+}
+
+fn default_idle_poll_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_locale() -> String {
+    "auto".to_string()
+}
+
 fn default_justfile_path() -> String {
     "justfile".to_string()
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 pub struct ProvidersConfig {
     #[serde(default, deserialize_with = "deserialize_config_provider")]
     pub config: ConfigProviderConfig,
@@ -47,17 +94,40 @@ pub struct ProvidersConfig {
     pub composer: ComposerProviderConfig,
     #[serde(default, deserialize_with = "deserialize_justfile_provider")]
     pub justfile: JustfileProviderConfig,
-}
-
-impl Default for ProvidersConfig {
-    fn default() -> Self {
-        Self {
-            config: ConfigProviderConfig::default(),
-            artisan: ArtisanProviderConfig::default(),
-            composer: ComposerProviderConfig::default(),
-            justfile: JustfileProviderConfig::default(),
-        }
-    }
+    #[serde(default, deserialize_with = "deserialize_npm_provider")]
+    pub npm: NpmProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_docker_provider")]
+    pub docker: DockerProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_jvm_provider")]
+    pub jvm: JvmProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_git_provider")]
+    pub git: GitProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_kubectl_provider")]
+    pub kubectl: KubectlProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_ruby_provider")]
+    pub ruby: RubyProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_python_provider")]
+    pub python: PythonProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_deno_provider")]
+    pub deno: DenoProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_bun_provider")]
+    pub bun: BunProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_scripts_provider")]
+    pub scripts: ScriptsProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_gh_provider")]
+    pub gh: GhProviderConfig,
+    #[serde(default, deserialize_with = "deserialize_ssh_provider")]
+    pub ssh: SshProviderConfig,
+    #[serde(default)]
+    pub external: Vec<ExternalProviderConfig>,
+    /// Per provider, command-line globs that re-run its discovery after a successful run;
+    /// replaces the built-in triggers, and an empty list turns them off.
+    #[serde(default)]
+    pub refresh: BTreeMap<String, Vec<String>>,
+    /// Providers the TUI loads only once their `:alias` filter is typed; until then they show as
+    /// one "search <provider>…" entry.
+    #[serde(default)]
+    pub lazy: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -66,6 +136,13 @@ pub struct RankingConfig {
     pub usage_enabled: bool,
     #[serde(default = "default_usage_weight")]
     pub usage_weight: i64,
+    #[serde(default)]
+    pub usage_scale: UsageScale,
+    #[serde(default)]
+    pub usage_cap: Option<i64>,
+    /// Days after which a past run counts half as much; 0 ranks by raw run counts.
+    #[serde(default = "default_decay_half_life_days")]
+    pub decay_half_life_days: f64,
 }
 
 impl Default for RankingConfig {
@@ -73,11 +150,240 @@ impl Default for RankingConfig {
         Self {
             usage_enabled: true,
             usage_weight: default_usage_weight(),
+            usage_scale: UsageScale::default(),
+            usage_cap: None,
+            decay_half_life_days: default_decay_half_life_days(),
+        }
+    }
+}
+
+/// How a command's run count turns into a ranking boost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageScale {
+    /// count × weight
+    #[default]
+    Linear,
+    /// log2(1 + count) × weight
+    Log,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutputConfig {
+    #[serde(default = "default_max_line_bytes")]
+    pub max_line_bytes: usize,
+    #[serde(default)]
+    pub pretty_json: bool,
+    #[serde(default)]
+    pub color: ColorMode,
+    /// Run session commands in a pseudo-terminal instead of pipes; ignored on Windows.
+    #[serde(default)]
+    pub pty: bool,
+    /// `<n>s|m|h` after which session commands are stopped; commands can set their own.
+    #[serde(default)]
+    pub timeout: Option<String>,
+    /// Where TUI runs go; commands can set their own `target`.
+    #[serde(default)]
+    pub target: ExecTarget,
+    /// Launcher for `target = "terminal"`, with `{{cwd}}` and `{{command}}` placeholders.
+    #[serde(default)]
+    pub terminal: Option<String>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            max_line_bytes: default_max_line_bytes(),
+            pretty_json: false,
+            color: ColorMode::default(),
+            pty: false,
+            timeout: None,
+            target: ExecTarget::default(),
+            terminal: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrivacyConfig {
+    #[serde(default = "default_true")]
+    pub persist_usage: bool,
+    /// Regexes masked in session output and history; built-in patterns when unset.
+    #[serde(default)]
+    pub redact: Option<Vec<String>>,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            persist_usage: true,
+            redact: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct EventsConfig {
+    /// Unix socket that streams JSON events for commands run in the TUI; `{pid}` is replaced.
+    #[serde(default)]
+    pub socket: Option<String>,
+}
+
+/// Where `{{secret:name}}` values come from; they are fetched for each run.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecretsConfig {
+    #[serde(default)]
+    pub backend: Option<SecretsBackend>,
+    /// `pass`: prepended to the name, e.g. `fzc/` reads `pass show fzc/<name>`.
+    #[serde(default)]
+    pub prefix: String,
+    /// `age`: encrypted TOML file of `name = "value"` pairs.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// `age`: identity (private key) file that decrypts `file`.
+    #[serde(default)]
+    pub identity: Option<String>,
+    /// `keychain`: service the items are stored under; the secret name is the account.
+    #[serde(default = "default_secrets_service")]
+    pub service: String,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            prefix: String::new(),
+            file: None,
+            identity: None,
+            service: default_secrets_service(),
+        }
+    }
+}
+
+fn default_secrets_service() -> String {
+    "fzc".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsBackend {
+    /// `pass show <prefix><name>`, first line.
+    Pass,
+    /// `age --decrypt` of `file`.
+    Age,
+    /// macOS Keychain (`security`) or the Secret Service (`secret-tool`) elsewhere.
+    Keychain,
+}
+
+/// `preset` plus per-color overrides: a name (`cyan`, `light-red`), `#rrggbb`, or a 0-255 index.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ThemeConfig {
+    /// `dark` (default), `light`, or `solarized`.
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub border: Option<String>,
+    #[serde(default)]
+    pub border_active: Option<String>,
+    #[serde(default)]
+    pub highlight_bg: Option<String>,
+    #[serde(default)]
+    pub highlight_fg: Option<String>,
+    #[serde(default)]
+    pub badge: Option<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub popup_bg: Option<String>,
+    #[serde(default)]
+    pub info: Option<String>,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub stdout: Option<String>,
+    #[serde(default)]
+    pub stderr: Option<String>,
+    #[serde(default)]
+    pub header_bg: Option<String>,
+    #[serde(default)]
+    pub banner_fg: Option<String>,
+    #[serde(default)]
+    pub banner_bg: Option<String>,
+    /// Job, history, and safety-verdict status colors.
+    #[serde(default)]
+    pub success: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub warning: Option<String>,
+    /// Badge color per provider name, e.g. `npm = "yellow"`.
+    #[serde(default)]
+    pub providers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UiConfig {
+    /// Event poll interval while typing or while background work is running.
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Poll interval after a few idle seconds.
+    #[serde(default = "default_idle_poll_interval_ms")]
+    pub idle_poll_interval_ms: u64,
+    /// Scheduled and background jobs that get their own Session sub-pane; 0 interleaves all output.
+    #[serde(default)]
+    pub job_panes: usize,
+    /// Capture the mouse for clicking and scrolling; off keeps the terminal's own selection.
+    #[serde(default = "default_true")]
+    pub mouse: bool,
+    /// Language of the TUI: `auto` (from `LANG`), `en`, or `de`.
+    #[serde(default = "default_locale")]
+    pub locale: String,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: default_poll_interval_ms(),
+            idle_poll_interval_ms: default_idle_poll_interval_ms(),
+            job_panes: 0,
+            mouse: true,
+            locale: default_locale(),
         }
     }
 }
 
 #[derive(Debug, Clone, Deserialize)]
+pub struct SafetyConfig {
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub confirm: Vec<String>,
+    /// Show a banner and ask for another Enter before commands that use `sudo` or `doas`.
+    #[serde(default = "default_true")]
+    pub warn_root: bool,
+    /// Environment and host name globs that get the same banner.
+    #[serde(default = "default_production_patterns")]
+    pub production: Vec<String>,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            deny: Vec::new(),
+            confirm: Vec::new(),
+            warn_root: true,
+            production: default_production_patterns(),
+        }
+    }
+}
+
+fn default_production_patterns() -> Vec<String> {
+    ["prod", "prod-*", "prod.*", "production", "live"]
+        .map(str::to_string)
+        .to_vec()
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
 pub struct ConfigProviderConfig {
     #[serde(default = "default_false")]
     pub enabled: bool,
@@ -85,49 +391,240 @@ pub struct ConfigProviderConfig {
     pub alias: Option<String>,
 }
 
-impl Default for ConfigProviderConfig {
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ArtisanProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ComposerProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct NpmProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    /// `npm`, `yarn`, or `pnpm`; detected from the lockfile when unset.
+    #[serde(default)]
+    pub manager: Option<String>,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct DockerProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// Gradle tasks and Maven phases.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct JvmProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// Common git operations, branch switching, and the user's git aliases.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct GitProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// Rake tasks and bundler commands.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct RubyProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// Scripts and tasks of Python projects (poetry, uv, pipenv, poe, tox).
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct PythonProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// `deno task` entries from `deno.json(c)`.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct DenoProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// `bun run` entries for `package.json` scripts in Bun projects.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct BunProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// `ssh <host>` for the `Host` entries of an ssh config file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SshProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default = "default_ssh_config_path")]
+    pub path: String,
+    /// Skip patterns such as `*.internal` or `!bastion`, which are not hosts to connect to.
+    #[serde(default = "default_true")]
+    pub exclude_wildcards: bool,
+}
+
+impl Default for SshProviderConfig {
     fn default() -> Self {
         Self {
             enabled: false,
             alias: None,
+            path: default_ssh_config_path(),
+            exclude_wildcards: true,
         }
     }
 }
 
+fn default_ssh_config_path() -> String {
+    "~/.ssh/config".to_string()
+}
+
+/// GitHub Actions workflows and runs through the `gh` CLI.
 #[derive(Debug, Clone, Deserialize)]
-pub struct ArtisanProviderConfig {
+pub struct GhProviderConfig {
     #[serde(default = "default_false")]
     pub enabled: bool,
     #[serde(default)]
     pub alias: Option<String>,
+    /// How many recent runs get a `gh run watch` command.
+    #[serde(default = "default_gh_runs")]
+    pub runs: usize,
 }
 
-impl Default for ArtisanProviderConfig {
+impl Default for GhProviderConfig {
     fn default() -> Self {
         Self {
             enabled: false,
             alias: None,
+            runs: default_gh_runs(),
         }
     }
 }
 
+fn default_gh_runs() -> usize {
+    10
+}
+
+/// Executable files in script directories.
 #[derive(Debug, Clone, Deserialize)]
-pub struct ComposerProviderConfig {
+pub struct ScriptsProviderConfig {
     #[serde(default = "default_false")]
     pub enabled: bool,
     #[serde(default)]
     pub alias: Option<String>,
+    /// Relative directories are looked up from the working directory upwards.
+    #[serde(default = "default_script_dirs")]
+    pub dirs: Vec<String>,
+    /// Read descriptions from a `# fzc: ...` line near the top of each script.
+    #[serde(default = "default_true")]
+    pub descriptions: bool,
 }
 
-impl Default for ComposerProviderConfig {
+impl Default for ScriptsProviderConfig {
     fn default() -> Self {
         Self {
             enabled: false,
             alias: None,
+            dirs: default_script_dirs(),
+            descriptions: true,
         }
     }
 }
 
+fn default_script_dirs() -> Vec<String> {
+    vec!["scripts".to_string()]
+}
+
+/// kubectl commands for the current kubeconfig and the project's manifests.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KubectlProviderConfig {
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub alias: Option<String>,
+    /// Which commands are listed; `delete` is left out unless named.
+    #[serde(default = "default_kubectl_verbs")]
+    pub verbs: Vec<KubectlVerb>,
+    /// Offer the cluster's namespaces and the kubeconfig's contexts as param choices.
+    #[serde(default = "default_true")]
+    pub populate: bool,
+}
+
+impl Default for KubectlProviderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            alias: None,
+            verbs: default_kubectl_verbs(),
+            populate: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KubectlVerb {
+    Get,
+    Describe,
+    Logs,
+    Exec,
+    Apply,
+    Rollout,
+    UseContext,
+    Delete,
+}
+
+fn default_kubectl_verbs() -> Vec<KubectlVerb> {
+    vec![
+        KubectlVerb::Get,
+        KubectlVerb::Describe,
+        KubectlVerb::Logs,
+        KubectlVerb::Exec,
+        KubectlVerb::Apply,
+        KubectlVerb::Rollout,
+        KubectlVerb::UseContext,
+    ]
+}
+
+/// A user-supplied provider: `command` prints a JSON array of `[[commands]]`-shaped entries.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExternalProviderConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct JustfileProviderConfig {
     #[serde(default)]
@@ -158,6 +655,38 @@ enum ProviderOptionsConfig {
     Many(Vec<String>),
 }
 
+/// A table as `(key, value)` pairs in the order they were written; a map would sort the keys.
+fn deserialize_ordered_table<'de, D, T>(
+    deserializer: D,
+) -> std::result::Result<Vec<(String, T)>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OrderedTable<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for OrderedTable<T> {
+        type Value = Vec<(String, T)>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a table")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> std::result::Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            let mut entries = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(OrderedTable(std::marker::PhantomData))
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 enum ProviderBoolOrTable<T> {
@@ -213,6 +742,198 @@ where
     })
 }
 
+fn deserialize_npm_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<NpmProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<NpmProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => NpmProviderConfig {
+            enabled,
+            ..NpmProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_docker_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<DockerProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<DockerProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => DockerProviderConfig {
+            enabled,
+            ..DockerProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_jvm_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<JvmProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<JvmProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => JvmProviderConfig {
+            enabled,
+            ..JvmProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_git_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<GitProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<GitProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => GitProviderConfig {
+            enabled,
+            ..GitProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_kubectl_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<KubectlProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<KubectlProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => KubectlProviderConfig {
+            enabled,
+            ..KubectlProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_ruby_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<RubyProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<RubyProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => RubyProviderConfig {
+            enabled,
+            ..RubyProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_python_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<PythonProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<PythonProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => PythonProviderConfig {
+            enabled,
+            ..PythonProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_deno_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<DenoProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<DenoProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => DenoProviderConfig {
+            enabled,
+            ..DenoProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_bun_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<BunProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<BunProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => BunProviderConfig {
+            enabled,
+            ..BunProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_ssh_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<SshProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<SshProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => SshProviderConfig {
+            enabled,
+            ..SshProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_gh_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<GhProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<GhProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => GhProviderConfig {
+            enabled,
+            ..GhProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
+fn deserialize_scripts_provider<'de, D>(
+    deserializer: D,
+) -> std::result::Result<ScriptsProviderConfig, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let input = ProviderBoolOrTable::<ScriptsProviderConfig>::deserialize(deserializer)?;
+    Ok(match input {
+        ProviderBoolOrTable::Bool(enabled) => ScriptsProviderConfig {
+            enabled,
+            ..ScriptsProviderConfig::default()
+        },
+        ProviderBoolOrTable::Table(config) => config,
+    })
+}
+
 fn deserialize_justfile_provider<'de, D>(
     deserializer: D,
 ) -> std::result::Result<JustfileProviderConfig, D::Error>
@@ -236,6 +957,21 @@ impl ProvidersConfig {
         insert_alias(&mut aliases, "artisan", self.artisan.alias.as_deref())?;
         insert_alias(&mut aliases, "composer", self.composer.alias.as_deref())?;
         insert_alias(&mut aliases, "justfile", self.justfile.alias.as_deref())?;
+        insert_alias(&mut aliases, "npm", self.npm.alias.as_deref())?;
+        insert_alias(&mut aliases, "docker", self.docker.alias.as_deref())?;
+        insert_alias(&mut aliases, "jvm", self.jvm.alias.as_deref())?;
+        insert_alias(&mut aliases, "git", self.git.alias.as_deref())?;
+        insert_alias(&mut aliases, "kubectl", self.kubectl.alias.as_deref())?;
+        insert_alias(&mut aliases, "ruby", self.ruby.alias.as_deref())?;
+        insert_alias(&mut aliases, "python", self.python.alias.as_deref())?;
+        insert_alias(&mut aliases, "deno", self.deno.alias.as_deref())?;
+        insert_alias(&mut aliases, "bun", self.bun.alias.as_deref())?;
+        insert_alias(&mut aliases, "scripts", self.scripts.alias.as_deref())?;
+        insert_alias(&mut aliases, "gh", self.gh.alias.as_deref())?;
+        insert_alias(&mut aliases, "ssh", self.ssh.alias.as_deref())?;
+        for external in &self.external {
+            insert_alias(&mut aliases, &external.name, external.alias.as_deref())?;
+        }
         Ok(aliases)
     }
 }
@@ -281,7 +1017,197 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::{Config, ParamLiteralConfig, ParamTypeConfig};
+    use std::time::Duration;
+
+    use super::{
+        ColorMode, Config, DiffLine, KubectlVerb, ParamLiteralConfig, ParamTypeConfig, UsageScale,
+        diff_lines, load_config_file, load_layered, parse_duration, parse_schedule,
+    };
+
+    #[test]
+    fn includes_merge_under_the_including_file_and_report_cycles() {
+        let dir = std::env::temp_dir().join(format!("fzc-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("commands")).unwrap();
+        let main = dir.join("config.toml");
+        std::fs::write(
+            &main,
+            r#"
+include = ["commands/*.toml"]
+
+[[commands]]
+name = "Build"
+run = "make release"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("commands/a.toml"),
+            "[[commands]]\nname = \"Build\"\nrun = \"make\"\n\n[[commands]]\nname = \"Lint\"\nrun = \"make lint\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("commands/b.toml"),
+            "[[commands]]\nname = \"Test\"\nrun = \"make test\"\n",
+        )
+        .unwrap();
+
+        let config = load_config_file(&main).unwrap();
+        let commands: Vec<_> = config
+            .commands
+            .iter()
+            .map(|command| (command.name.as_str(), command.run.as_str()))
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                ("Lint", "make lint"),
+                ("Test", "make test"),
+                ("Build", "make release")
+            ]
+        );
+
+        std::fs::write(
+            dir.join("commands/b.toml"),
+            "include = [\"../config.toml\"]\n",
+        )
+        .unwrap();
+        let err = format!("{:#}", load_config_file(&main).unwrap_err());
+        assert!(err.contains("include cycle"), "{err}");
+        assert!(err.contains("b.toml"), "{err}");
+
+        std::fs::write(&main, "include = [\"missing.toml\"]\n").unwrap();
+        let err = format!("{:#}", load_config_file(&main).unwrap_err());
+        assert!(err.contains("missing.toml does not exist"), "{err}");
+
+        // A fragment may refer to a provider the including file defines.
+        std::fs::write(dir.join("lazy.toml"), "[providers]\nlazy = [\"mytool\"]\n").unwrap();
+        std::fs::write(
+            &main,
+            "include = [\"lazy.toml\"]\n\n[[providers.external]]\nname = \"mytool\"\ncommand = \"mytool list\"\n",
+        )
+        .unwrap();
+        assert_eq!(load_config_file(&main).unwrap().providers.lazy, ["mytool"]);
+        std::fs::write(dir.join("lazy.toml"), "[providers]\nlazy = \"mytool\"\n").unwrap();
+        let err = format!("{:#}", load_config_file(&main).unwrap_err());
+        assert!(err.contains("lazy.toml"), "{err}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn project_config_merges_over_the_global_one() {
+        let dir = std::env::temp_dir().join(format!("fzc-layered-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let global = dir.join("global.toml");
+        let local = dir.join("fzc.toml");
+        std::fs::write(
+            &global,
+            r#"
+[providers]
+npm = true
+git = true
+
+[ranking]
+usage_weight = 100
+decay_half_life_days = 7.0
+
+[[commands]]
+name = "Deploy"
+run = "./deploy.sh"
+
+[[commands]]
+name = "Shared"
+run = "echo global"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &local,
+            r#"
+[providers]
+git = false
+
+[ranking]
+usage_weight = 5
+
+[[commands]]
+name = "Shared"
+run = "echo local"
+"#,
+        )
+        .unwrap();
+
+        let loaded = load_layered(Some(&global), &local).unwrap();
+        assert_eq!(loaded.global.as_deref(), Some(global.as_path()));
+        let config = loaded.config;
+        assert!(config.providers.npm.enabled && !config.providers.git.enabled);
+        assert_eq!(config.ranking.usage_weight, 5);
+        assert_eq!(config.ranking.decay_half_life_days, 7.0);
+        let commands: Vec<_> = config
+            .commands
+            .iter()
+            .map(|command| (command.name.as_str(), command.run.as_str()))
+            .collect();
+        assert_eq!(
+            commands,
+            [("Deploy", "./deploy.sh"), ("Shared", "echo local")]
+        );
+
+        // A provider's bool in one file and its table in the other combine.
+        std::fs::write(&local, "[providers.npm]\nmanager = \"pnpm\"\n").unwrap();
+        let npm = load_layered(Some(&global), &local)
+            .unwrap()
+            .config
+            .providers
+            .npm;
+        assert!(npm.enabled);
+        assert_eq!(npm.manager.as_deref(), Some("pnpm"));
+        let table_global = dir.join("table-global.toml");
+        std::fs::write(&table_global, "[providers.npm]\nalias = \"n\"\n").unwrap();
+        std::fs::write(&local, "[providers]\nnpm = true\n").unwrap();
+        let npm = load_layered(Some(&table_global), &local)
+            .unwrap()
+            .config
+            .providers
+            .npm;
+        assert!(npm.enabled);
+        assert_eq!(npm.alias.as_deref(), Some("n"));
+
+        // References between the files are checked on the merged config.
+        std::fs::write(
+            &table_global,
+            "[[providers.external]]\nname = \"mytool\"\ncommand = \"mytool list\"\n",
+        )
+        .unwrap();
+        std::fs::write(&local, "[providers]\nlazy = [\"mytool\"]\n").unwrap();
+        let loaded = load_layered(Some(&table_global), &local).unwrap();
+        assert_eq!(loaded.config.providers.lazy, ["mytool"]);
+        assert!(load_layered(None, &local).is_err());
+
+        std::fs::write(&local, "root = true\n").unwrap();
+        let loaded = load_layered(Some(&global), &local).unwrap();
+        assert!(loaded.global.is_none() && loaded.config.commands.is_empty());
+        // Without a config directory the project config loads on its own.
+        std::fs::write(&local, "[[commands]]\nname = \"Local\"\nrun = \"true\"\n").unwrap();
+        let loaded = load_layered(None, &local).unwrap();
+        assert!(loaded.global.is_none() && loaded.config.commands.len() == 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diff_lines_marks_removed_and_added_lines() {
+        let diff = diff_lines("a\nb\nc\n", "a\nc\nd\n");
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("a".to_string()),
+                DiffLine::Removed("b".to_string()),
+                DiffLine::Same("c".to_string()),
+                DiffLine::Added("d".to_string()),
+            ]
+        );
+    }
 
     #[test]
     fn supports_table_provider_config() {
@@ -303,6 +1229,16 @@ enabled = true
 path = ".justfile"
 options = "--working-directory ."
 alias = "j"
+
+[providers.npm]
+enabled = true
+manager = "pnpm"
+alias = "n"
+
+[[providers.external]]
+name = "make"
+command = "make-commands --json"
+alias = "mk"
 "#;
         let cfg: Config = toml::from_str(raw).unwrap();
         assert!(cfg.providers.config.enabled);
@@ -315,9 +1251,23 @@ alias = "j"
             vec!["--working-directory .".to_string()]
         );
         assert_eq!(cfg.providers.artisan.alias.as_deref(), Some("a"));
-        assert_eq!(cfg.providers.composer.alias.as_deref(), Some("p"));
-        assert_eq!(cfg.providers.config.alias.as_deref(), Some("c"));
+        assert_eq!(cfg.providers.composer.alias.as_deref(), Some("co"));
+        assert_eq!(cfg.providers.config.alias.as_deref(), Some("cf"));
         assert_eq!(cfg.providers.justfile.alias.as_deref(), Some("j"));
+        assert!(cfg.providers.npm.enabled);
+        assert_eq!(cfg.providers.npm.manager.as_deref(), Some("pnpm"));
+        assert_eq!(cfg.providers.npm.alias.as_deref(), Some("n"));
+        assert_eq!(cfg.providers.external.len(), 1);
+        assert_eq!(cfg.providers.external[0].name, "make");
+        assert_eq!(cfg.providers.external[0].command, "make-commands --json");
+        assert_eq!(
+            cfg.providers
+                .alias_map()
+                .unwrap()
+                .get("mk")
+                .map(String::as_str),
+            Some("make")
+        );
         assert!(cfg.ranking.usage_enabled);
     }
 
@@ -340,29 +1290,215 @@ justfile = false
     }
 
     #[test]
-    fn missing_providers_default_to_disabled() {
+    fn missing_providers_default_to_disabled() {
+        let raw = r#"
+[[commands]]
+name = "foo"
+run = "echo foo"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert!(!cfg.providers.config.enabled);
+        assert!(!cfg.providers.artisan.enabled);
+        assert!(!cfg.providers.composer.enabled);
+        assert!(!cfg.providers.justfile.enabled);
+        assert!(!cfg.providers.npm.enabled);
+    }
+
+    #[test]
+    fn kubectl_verbs_default_without_delete_and_reject_unknown_ones() {
+        let cfg: Config = toml::from_str("[providers]\nkubectl = true\n").unwrap();
+        assert!(cfg.providers.kubectl.populate);
+        assert!(!cfg.providers.kubectl.verbs.contains(&KubectlVerb::Delete));
+
+        let raw = "[providers.kubectl]\nenabled = true\nverbs = [\"logs\", \"use-context\"]\n";
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(
+            cfg.providers.kubectl.verbs,
+            vec![KubectlVerb::Logs, KubectlVerb::UseContext]
+        );
+        assert!(toml::from_str::<Config>("[providers.kubectl]\nverbs = [\"scale\"]\n").is_err());
+    }
+
+    #[test]
+    fn parses_schedule_intervals() {
+        assert_eq!(
+            parse_schedule("every 5m").unwrap(),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            parse_schedule("every 30s").unwrap(),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            parse_schedule(" every 2 h ").unwrap(),
+            Duration::from_secs(7200)
+        );
+        assert!(parse_schedule("5m").is_err());
+        assert!(parse_schedule("every 0s").is_err());
+        assert!(parse_schedule("every 5w").is_err());
+        assert_eq!(parse_duration("0s").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(
+            parse_duration("3650d").unwrap(),
+            Duration::from_secs(3650 * 86_400)
+        );
+        assert!(parse_duration("3651d").is_err());
+        assert!(parse_duration("18446744073709551615h").is_err());
+        assert!(parse_schedule("every 99999999999999999h").is_err());
+    }
+
+    #[test]
+    fn retry_delay_is_validated() {
+        let raw = r#"
+[[commands]]
+name = "Deploy"
+run = "./deploy.sh"
+retries = 3
+retry_delay = "soon"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.commands[0].retries, 3);
+        assert!(cfg.commands[0].validate().is_err());
+    }
+
+    #[test]
+    fn timeout_is_validated() {
+        let raw = r#"
+[output]
+timeout = "10m"
+
+[[commands]]
+name = "Deploy"
+run = "./deploy.sh"
+timeout = "30s"
+
+[[commands]]
+name = "Hang"
+run = "sleep 100"
+timeout = "forever"
+
+[[commands]]
+name = "Instant"
+run = "true"
+timeout = "0s"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.output.timeout.as_deref(), Some("10m"));
+        assert!(cfg.commands[0].validate().is_ok());
+        assert!(cfg.commands[1].validate().is_err());
+        assert!(cfg.commands[2].validate().is_err());
+    }
+
+    #[test]
+    fn pipelines_parse_and_need_steps() {
+        let raw = r#"
+[[pipelines]]
+name = "Release"
+steps = [
+    { command = "Lint", continue_on_error = true },
+    { command = "Build" },
+]
+
+[[pipelines]]
+name = "Empty"
+steps = []
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        let release = &cfg.pipelines[0];
+        assert!(release.steps[0].continue_on_error);
+        assert!(!release.steps[1].continue_on_error);
+        assert!(release.validate().is_ok());
+        assert!(cfg.pipelines[1].validate().is_err());
+    }
+
+    #[test]
+    fn ranking_is_configurable() {
+        let raw = r#"
+[ranking]
+usage_enabled = false
+usage_weight = 123
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert!(!cfg.ranking.usage_enabled);
+        assert_eq!(cfg.ranking.usage_weight, 123);
+        assert_eq!(cfg.ranking.usage_scale, UsageScale::Linear);
+        assert_eq!(cfg.ranking.usage_cap, None);
+
+        let cfg: Config =
+            toml::from_str("[ranking]\nusage_scale = \"log\"\nusage_cap = 40000\n").unwrap();
+        assert_eq!(cfg.ranking.usage_scale, UsageScale::Log);
+        assert_eq!(cfg.ranking.usage_cap, Some(40_000));
+        assert_eq!(cfg.ranking.decay_half_life_days, 30.0);
+
+        let cfg: Config = toml::from_str("[ranking]\ndecay_half_life_days = 0\n").unwrap();
+        assert_eq!(cfg.ranking.decay_half_life_days, 0.0);
+    }
+
+    #[test]
+    fn output_max_line_bytes_is_configurable() {
+        let cfg: Config = toml::from_str("").unwrap();
+        assert_eq!(cfg.output.max_line_bytes, 4_096);
+
+        let cfg: Config = toml::from_str("[output]\nmax_line_bytes = 120\n").unwrap();
+        assert_eq!(cfg.output.max_line_bytes, 120);
+    }
+
+    #[test]
+    fn color_mode_defaults_to_force_and_is_overridable_per_command() {
         let raw = r#"
+[output]
+color = "auto"
+
 [[commands]]
-name = "foo"
-run = "echo foo"
+name = "report"
+run = "make report > out.txt"
+color = "never"
+pty = true
 "#;
         let cfg: Config = toml::from_str(raw).unwrap();
-        assert!(!cfg.providers.config.enabled);
-        assert!(!cfg.providers.artisan.enabled);
-        assert!(!cfg.providers.composer.enabled);
-        assert!(!cfg.providers.justfile.enabled);
+        assert_eq!(cfg.output.color, ColorMode::Auto);
+        assert_eq!(cfg.commands[0].color, Some(ColorMode::Never));
+        assert!(!cfg.output.pty);
+        assert_eq!(cfg.commands[0].pty, Some(true));
+        assert_eq!(Config::default().output.color, ColorMode::Force);
     }
 
     #[test]
-    fn ranking_is_configurable() {
+    fn privacy_persists_usage_unless_disabled() {
+        let cfg: Config = toml::from_str("").unwrap();
+        assert!(cfg.privacy.persist_usage);
+
+        let cfg: Config = toml::from_str("[privacy]\npersist_usage = false\n").unwrap();
+        assert!(!cfg.privacy.persist_usage);
+    }
+
+    #[test]
+    fn ui_poll_intervals_default_and_override() {
+        let cfg: Config = toml::from_str("").unwrap();
+        assert_eq!(cfg.ui.poll_interval_ms, 100);
+        assert_eq!(cfg.ui.idle_poll_interval_ms, 1_000);
+
+        let cfg: Config = toml::from_str("[ui]\nidle_poll_interval_ms = 2500\n").unwrap();
+        assert_eq!(cfg.ui.poll_interval_ms, 100);
+        assert_eq!(cfg.ui.idle_poll_interval_ms, 2_500);
+    }
+
+    #[test]
+    fn safety_patterns_default_to_empty() {
+        let cfg: Config = toml::from_str("").unwrap();
+        assert!(cfg.safety.deny.is_empty());
+        assert!(cfg.safety.confirm.is_empty());
+        assert!(cfg.safety.warn_root);
+        assert!(cfg.safety.production.contains(&"production".to_string()));
+
         let raw = r#"
-[ranking]
-usage_enabled = false
-usage_weight = 123
+[safety]
+deny = ["rm -rf /"]
+confirm = ["*drop database*"]
 "#;
         let cfg: Config = toml::from_str(raw).unwrap();
-        assert!(!cfg.ranking.usage_enabled);
-        assert_eq!(cfg.ranking.usage_weight, 123);
+        assert_eq!(cfg.safety.deny, vec!["rm -rf /".to_string()]);
+        assert_eq!(cfg.safety.confirm, vec!["*drop database*".to_string()]);
     }
 
     #[test]
@@ -405,13 +1541,101 @@ default = false
             Some(ParamLiteralConfig::Bool(false))
         ));
     }
+
+    #[test]
+    fn choice_params_need_options_containing_the_default() {
+        let raw = r#"
+[[commands]]
+name = "Deploy"
+run = "./deploy {{env}}"
+
+[[commands.params]]
+name = "env"
+type = "choice"
+options = ["staging", "production"]
+default = "staging"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert_eq!(cfg.commands[0].params[0].r#type, ParamTypeConfig::Choice);
+        assert_eq!(cfg.commands[0].params[0].options, ["staging", "production"]);
+        assert!(cfg.commands[0].validate().is_ok());
+
+        let typo: Config =
+            toml::from_str(&raw.replace("default = \"staging\"", "default = \"stagin\"")).unwrap();
+        assert!(typo.commands[0].validate().is_err());
+        let empty: Config =
+            toml::from_str(&raw.replace("[\"staging\", \"production\"]", "[]")).unwrap();
+        assert!(empty.commands[0].validate().is_err());
+    }
+
+    #[test]
+    fn rejects_hosts_that_ssh_would_read_as_options() {
+        let raw = r#"
+[[commands]]
+name = "Uptime"
+run = "uptime"
+host = "deploy@prod-1"
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        assert!(cfg.commands[0].validate().is_ok());
+        for host in ["-oProxyCommand=/tmp/x", " "] {
+            let bad: Config = toml::from_str(&raw.replace("deploy@prod-1", host)).unwrap();
+            assert!(bad.commands[0].validate().is_err());
+        }
+    }
+
+    #[test]
+    fn supports_command_environments() {
+        let raw = r#"
+[[commands]]
+name = "Deploy"
+run = "./deploy --region={{region}}"
+
+[commands.environments.staging]
+env = { API_URL = "https://staging.example.com" }
+vars = { region = "eu-west-1" }
+
+[commands.environments.prod]
+vars = { region = "us-east-1" }
+"#;
+        let cfg: Config = toml::from_str(raw).unwrap();
+        let environments = &cfg.commands[0].environments;
+        assert_eq!(
+            environments
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["staging", "prod"]
+        );
+        let (staging, prod) = (&environments[0].1, &environments[1].1);
+        assert_eq!(
+            staging.env.get("API_URL").map(String::as_str),
+            Some("https://staging.example.com")
+        );
+        assert!(prod.env.is_empty());
+        assert_eq!(
+            prod.vars.get("region").map(String::as_str),
+            Some("us-east-1")
+        );
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct CommandConfig {
     pub name: String,
-    #[serde(alias = "cmd")]
+    #[serde(default, alias = "cmd")]
     pub run: String,
+    #[serde(default, rename = "type")]
+    pub kind: CommandKind,
+    /// HTTP method for `type = "http"`; defaults to GET.
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
     #[serde(default)]
     pub description: Option<String>,
     #[serde(default)]
@@ -420,6 +1644,252 @@ pub struct CommandConfig {
     pub params: Vec<ParamConfig>,
     #[serde(default)]
     pub working_dir: Option<String>,
+    /// Create a missing `working_dir` (like `mkdir -p`) before running.
+    #[serde(default)]
+    pub create_working_dir: bool,
+    /// Kept in declaration order, which is the order the environment picker lists them in.
+    #[serde(default, deserialize_with = "deserialize_ordered_table")]
+    pub environments: Vec<(String, EnvironmentConfig)>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub color: Option<ColorMode>,
+    /// Overrides `[output] pty` for this command.
+    #[serde(default)]
+    pub pty: Option<bool>,
+    /// Start as a background job (see `/jobs`) instead of streaming into the session.
+    #[serde(default)]
+    pub background: bool,
+    /// Hand the terminal over and exit fzc afterwards, as Option+Enter does.
+    #[serde(default)]
+    pub inherit: bool,
+    /// Overrides `[output] target` for this command.
+    #[serde(default)]
+    pub target: Option<ExecTarget>,
+    /// `every <n>s|m|h`: re-run in the background while fzc is open.
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Read-only command whose output is shown next to the list while this command is selected.
+    #[serde(default)]
+    pub preview: Option<String>,
+    /// Extra attempts after a non-zero exit.
+    #[serde(default)]
+    pub retries: u32,
+    /// `<n>s|m|h` to wait between attempts; defaults to no delay.
+    #[serde(default)]
+    pub retry_delay: Option<String>,
+    /// `<n>s|m|h` after which the command is stopped; overrides `[output] timeout`.
+    #[serde(default)]
+    pub timeout: Option<String>,
+    /// Catalog commands (by name or `provider::name`) run in order after this one exits 0.
+    #[serde(default)]
+    pub then: Vec<String>,
+    /// Run after the command exits 0; overrides `[hooks] on_success`.
+    #[serde(default)]
+    pub on_success: Option<String>,
+    /// Run after the command exits non-zero or fails to start; overrides `[hooks] on_failure`.
+    #[serde(default)]
+    pub on_failure: Option<String>,
+}
+
+/// Catalog commands run in order as one catalog entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PipelineConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub steps: Vec<PipelineStepConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PipelineStepConfig {
+    /// Name of a catalog command, or `provider::name`.
+    pub command: String,
+    /// Run the next step even when this one exits non-zero.
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+impl PipelineConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("pipelines need a `name`");
+        }
+        if self.steps.is_empty() {
+            bail!("pipeline '{}' has no steps", self.name);
+        }
+        if self.steps.iter().any(|step| step.command.trim().is_empty()) {
+            bail!("pipeline '{}' has a step without a `command`", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// Commands run after every catalog command unless it sets its own.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct HooksConfig {
+    #[serde(default)]
+    pub on_success: Option<String>,
+    #[serde(default)]
+    pub on_failure: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandKind {
+    /// `run` is a shell command line.
+    #[default]
+    Shell,
+    /// `method`/`url`/`headers`/`body` describe a request.
+    Http,
+}
+
+impl CommandConfig {
+    pub fn validate(&self) -> Result<()> {
+        match self.kind {
+            CommandKind::Shell if self.run.trim().is_empty() => {
+                bail!("command '{}' needs a `run` line", self.name)
+            }
+            CommandKind::Http if self.url.as_deref().is_none_or(|url| url.trim().is_empty()) => {
+                bail!("http command '{}' needs a `url`", self.name)
+            }
+            _ => {}
+        }
+        // ssh would read `-oProxyCommand=...` as an option and run it locally.
+        if let Some(host) = &self.host
+            && (host.trim().is_empty() || host.starts_with('-'))
+        {
+            bail!("command '{}' has an invalid `host` '{host}'", self.name);
+        }
+        if let Some(schedule) = &self.schedule {
+            parse_schedule(schedule).with_context(|| format!("command '{}'", self.name))?;
+        }
+        if let Some(delay) = &self.retry_delay {
+            parse_duration(delay)
+                .with_context(|| format!("retry_delay of command '{}'", self.name))?;
+        }
+        if let Some(timeout) = &self.timeout {
+            parse_timeout(timeout)
+                .with_context(|| format!("timeout of command '{}'", self.name))?;
+        }
+        for param in &self.params {
+            if let Some(glob) = &param.glob {
+                globset::Glob::new(glob).with_context(|| {
+                    format!("param '{}' of command '{}'", param.name, self.name)
+                })?;
+            }
+            if param.r#type != ParamTypeConfig::Choice {
+                continue;
+            }
+            if param.options.is_empty() {
+                bail!(
+                    "choice param '{}' of command '{}' needs `options`",
+                    param.name,
+                    self.name
+                );
+            }
+            for literal in [&param.default, &param.value].into_iter().flatten() {
+                if let ParamLiteralConfig::String(option) = literal
+                    && !param.options.contains(option)
+                {
+                    bail!(
+                        "'{option}' is not one of the options of param '{}' in command '{}'",
+                        param.name,
+                        self.name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses `every 30s`, `every 5m`, or `every 2h` into the interval between runs.
+pub fn parse_schedule(raw: &str) -> Result<Duration> {
+    let Some(interval) = raw.trim().strip_prefix("every") else {
+        bail!("schedule '{raw}' must look like `every 5m`");
+    };
+    let interval = parse_duration(interval).with_context(|| format!("schedule '{raw}'"))?;
+    if interval.is_zero() {
+        bail!("schedule '{raw}' must be at least 1s");
+    }
+    Ok(interval)
+}
+
+/// Parses a `timeout` like [`parse_duration`]; `0s` would stop every run at once, so it is
+/// rejected.
+pub fn parse_timeout(raw: &str) -> Result<Duration> {
+    let timeout = parse_duration(raw)?;
+    if timeout.is_zero() {
+        bail!("'{raw}' must be longer than 0s");
+    }
+    Ok(timeout)
+}
+
+/// Longest duration [`parse_duration`] accepts, so deadlines computed from it stay in range.
+const MAX_DURATION_DAYS: u64 = 3650;
+
+/// Parses `30s`, `5m`, `2h`, or `7d`, up to 3650 days.
+pub fn parse_duration(raw: &str) -> Result<Duration> {
+    let spec = raw.trim();
+    let split = spec
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(spec.len());
+    let (amount, unit) = spec.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .with_context(|| format!("'{raw}' needs a number, e.g. `5m`"))?;
+    let unit_seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => bail!("'{raw}' must use s, m, h, or d"),
+    };
+    match amount.checked_mul(unit_seconds) {
+        Some(seconds) if seconds <= MAX_DURATION_DAYS * 24 * 60 * 60 => {
+            Ok(Duration::from_secs(seconds))
+        }
+        _ => bail!("'{raw}' is longer than {MAX_DURATION_DAYS}d"),
+    }
+}
+
+/// Whether spawned commands are told to emit ANSI colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Leave color detection to the command.
+    Auto,
+    /// Set CLICOLOR_FORCE / FORCE_COLOR.
+    #[default]
+    Force,
+    /// Set NO_COLOR and clear the force variables.
+    Never,
+}
+
+/// Where commands started from the TUI run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExecTarget {
+    /// Stream into the session pane.
+    #[default]
+    Session,
+    /// A new tmux window in the current tmux session.
+    TmuxWindow,
+    /// A tmux pane split from the current one.
+    TmuxPane,
+    /// The `[output] terminal` launcher, e.g. a new terminal tab.
+    Terminal,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct EnvironmentConfig {
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq, Default)]
@@ -428,6 +1898,10 @@ pub enum ParamTypeConfig {
     #[default]
     Value,
     Flag,
+    /// One of `options`, picked from a list.
+    Choice,
+    /// A file or directory, completed relative to the working dir.
+    Path,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -447,45 +1921,231 @@ pub struct ParamConfig {
     #[serde(default)]
     pub placeholder: Option<String>,
     #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
     pub default: Option<ParamLiteralConfig>,
     #[serde(default)]
     pub value: Option<ParamLiteralConfig>,
     #[serde(default)]
     pub required: bool,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub when: Option<String>,
+    /// Allowed values of a `choice` param.
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// File-name glob that limits the files a `path` param offers, e.g. `*.sql`.
+    #[serde(default)]
+    pub glob: Option<String>,
+    /// Expand the value in the shell before it is substituted, e.g. `$(git rev-parse HEAD)`.
+    #[serde(default)]
+    pub eval: bool,
 }
 
 pub fn load(cwd: &Path, explicit_path: Option<&Path>) -> Result<LoadedConfig> {
     if let Some(path) = explicit_path {
         return Ok(LoadedConfig {
-            config: load_from_path(path)?,
+            config: load_config_file(path)?,
             path: Some(path.to_path_buf()),
+            global: None,
         });
     }
 
+    // Without a config directory there is simply no global config; a project config still loads.
+    let global_path = global_config_path().ok();
     let local_candidates = [cwd.join("fzc.toml"), cwd.join(".fzc.toml")];
-    for path in &local_candidates {
-        if path.exists() {
-            return Ok(LoadedConfig {
-                config: load_from_path(path)?,
-                path: Some(path.to_path_buf()),
-            });
-        }
+    if let Some(path) = local_candidates.iter().find(|path| path.exists()) {
+        return load_layered(global_path.as_deref(), path);
     }
 
-    let global_path = global_config_path()?;
-    if global_path.exists() {
+    if let Some(global_path) = global_path.filter(|path| path.exists()) {
         return Ok(LoadedConfig {
-            config: load_from_path(&global_path)?,
+            config: load_config_file(&global_path)?,
             path: Some(global_path),
+            global: None,
         });
     }
 
     Ok(LoadedConfig {
         config: Config::default(),
         path: None,
+        global: None,
+    })
+}
+
+/// A project config merged over the global one (unless it sets `root = true`): tables merge key by
+/// key with project values winning, `commands`, `pipelines`, and `[[providers.external]]` are
+/// concatenated with project entries shadowing global ones of the same name, and other arrays are
+/// replaced. Each file is checked for syntax and types as it is read, so those errors point at the
+/// file at fault; references between files (such as a lazy provider defined in the other one) are
+/// checked once, on the merged config.
+fn load_layered(global_path: Option<&Path>, local_path: &Path) -> Result<LoadedConfig> {
+    let local = read_config_with_includes(local_path, &mut Vec::new())?;
+    let root = Config::deserialize(toml::Value::Table(local.clone()))
+        .with_context(|| format!("invalid config in {}", local_path.display()))?
+        .root;
+    let global_path = global_path.filter(|path| !root && path.exists());
+    let Some(global_path) = global_path else {
+        return Ok(LoadedConfig {
+            config: config_from_table(local, local_path)?,
+            path: Some(local_path.to_path_buf()),
+            global: None,
+        });
+    };
+    let mut merged = read_config_with_includes(global_path, &mut Vec::new())?;
+    merge_config_tables(&mut merged, local, "");
+    Ok(LoadedConfig {
+        config: config_from_table(merged, local_path)?,
+        path: Some(local_path.to_path_buf()),
+        global: Some(global_path.to_path_buf()),
     })
 }
 
+/// Loads `path` with the files it includes.
+fn load_config_file(path: &Path) -> Result<Config> {
+    let table = read_config_with_includes(path, &mut Vec::new())?;
+    config_from_table(table, path)
+}
+
+/// `path` with the files its `include` patterns match merged under it in order, so the including
+/// file wins. Included files may include others; `stack` holds the files being read, to report
+/// cycles.
+fn read_config_with_includes(path: &Path, stack: &mut Vec<PathBuf>) -> Result<toml::Table> {
+    let canonical =
+        fs::canonicalize(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if let Some(start) = stack.iter().position(|seen| *seen == canonical) {
+        let cycle: Vec<String> = stack[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|path| path.display().to_string())
+            .collect();
+        bail!("include cycle: {}", cycle.join(" -> "));
+    }
+    let mut table = read_config_table(path)?;
+    let includes = Config::deserialize(toml::Value::Table(table.clone()))
+        .with_context(|| format!("invalid config in {}", path.display()))?
+        .include;
+    table.remove("include");
+    if includes.is_empty() {
+        return Ok(table);
+    }
+
+    stack.push(canonical);
+    let mut merged = toml::Table::new();
+    for pattern in &includes {
+        let files = include_paths(pattern, path)
+            .with_context(|| format!("invalid include in {}", path.display()))?;
+        for file in files {
+            let mut included = read_config_with_includes(&file, stack)
+                .with_context(|| format!("included from {}", path.display()))?;
+            included.remove("root");
+            merge_config_tables(&mut merged, included, "");
+        }
+    }
+    stack.pop();
+    merge_config_tables(&mut merged, table, "");
+    Ok(merged)
+}
+
+/// Files an `include` pattern names: `~/` is the home directory, relative paths start at the
+/// including file's directory, and the file name may be a glob (matches sorted by name). A path
+/// without a glob must exist.
+fn include_paths(pattern: &str, from: &Path) -> Result<Vec<PathBuf>> {
+    let mut path = crate::provider::expand_home_shorthand(pattern)
+        .with_context(|| format!("cannot expand '{pattern}' without a home directory"))?;
+    if path.is_relative()
+        && let Some(dir) = from.parent()
+    {
+        path = dir.join(path);
+    }
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !file_name.contains(['*', '?', '[', '{']) {
+        if !path.is_file() {
+            bail!("included file {} does not exist", path.display());
+        }
+        return Ok(vec![path]);
+    }
+    let matcher = globset::Glob::new(&file_name)
+        .with_context(|| format!("invalid include pattern '{pattern}'"))?
+        .compile_matcher();
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| matcher.is_match(entry.file_name()))
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Deserializes a config merged from several files and validates it as a whole.
+fn config_from_table(table: toml::Table, path: &Path) -> Result<Config> {
+    let config = toml::Value::Table(table)
+        .try_into()
+        .with_context(|| format!("invalid config after merging into {}", path.display()))?;
+    validate_config(config, path)
+}
+
+/// Arrays whose entries are identified by `name` when configs are merged.
+const NAMED_CONFIG_LISTS: &[&str] = &["commands", "pipelines", "providers.external"];
+
+fn merge_config_tables(base: &mut toml::Table, overlay: toml::Table, prefix: &str) {
+    for (key, mut value) in overlay {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        if prefix == "providers" {
+            expand_provider_shorthand(&mut value);
+            if let Some(existing) = base.get_mut(&key) {
+                expand_provider_shorthand(existing);
+            }
+        }
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_config_tables(base, overlay, &path);
+            }
+            (Some(toml::Value::Array(base)), toml::Value::Array(overlay))
+                if NAMED_CONFIG_LISTS.contains(&path.as_str()) =>
+            {
+                let name = |entry: &toml::Value| {
+                    entry
+                        .get("name")
+                        .and_then(toml::Value::as_str)
+                        .map(ToString::to_string)
+                };
+                let shadowed: Vec<String> = overlay.iter().filter_map(name).collect();
+                base.retain(|entry| name(entry).is_none_or(|name| !shadowed.contains(&name)));
+                base.extend(overlay);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// `providers.<name> = true` is short for `enabled = true` in its table. Expanded before merging,
+/// so a table in one file keeps the other file's `enabled`, and a bool keeps its table's keys.
+fn expand_provider_shorthand(value: &mut toml::Value) {
+    if let toml::Value::Boolean(enabled) = *value {
+        *value = toml::Value::Table(toml::Table::from_iter([(
+            "enabled".to_string(),
+            toml::Value::Boolean(enabled),
+        )]));
+    }
+}
+
 pub fn global_config_path() -> Result<PathBuf> {
     let config_root = dirs::config_dir().context("unable to resolve OS config directory")?;
     Ok(config_root.join("fzc").join("config.toml"))
@@ -509,22 +2169,240 @@ pub fn write_example_config(path: &Path, force: bool) -> Result<()> {
     Ok(())
 }
 
-fn load_from_path(path: &Path) -> Result<Config> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Diff between an existing config file and the example template, or `None` when
+/// there is nothing to overwrite (missing file or identical content).
+pub fn example_config_overwrite_diff(path: &Path) -> Result<Option<Vec<DiffLine>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let current =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if current == EXAMPLE_CONFIG {
+        return Ok(None);
+    }
+    Ok(Some(diff_lines(&current, EXAMPLE_CONFIG)))
+}
+
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table, filled from the end so the walk below is forward.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0usize, 0usize);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    diff.extend(
+        old[i..]
+            .iter()
+            .map(|line| DiffLine::Removed(line.to_string())),
+    );
+    diff.extend(
+        new[j..]
+            .iter()
+            .map(|line| DiffLine::Added(line.to_string())),
+    );
+    diff
+}
+
+fn read_config_table(path: &Path) -> Result<toml::Table> {
     let content =
         fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
     toml::from_str(&content).with_context(|| format!("invalid TOML in {}", path.display()))
 }
 
+fn validate_config(config: Config, path: &Path) -> Result<Config> {
+    for command in &config.commands {
+        command
+            .validate()
+            .with_context(|| format!("invalid command in {}", path.display()))?;
+    }
+    for pipeline in &config.pipelines {
+        pipeline
+            .validate()
+            .with_context(|| format!("invalid pipeline in {}", path.display()))?;
+    }
+    if let Some(timeout) = &config.output.timeout {
+        parse_timeout(timeout)
+            .with_context(|| format!("invalid [output] timeout in {}", path.display()))?;
+    }
+    for name in &config.providers.lazy {
+        let known = name != "config"
+            && (crate::provider::PROVIDER_NAMES.contains(&name.as_str())
+                || config
+                    .providers
+                    .external
+                    .iter()
+                    .any(|external| external.name == *name));
+        if !known {
+            bail!(
+                "unknown provider '{name}' in [providers] lazy in {}",
+                path.display()
+            );
+        }
+    }
+    for (provider, patterns) in &config.providers.refresh {
+        for pattern in patterns {
+            globset::Glob::new(pattern).with_context(|| {
+                format!(
+                    "invalid refresh trigger '{pattern}' for provider '{provider}' in {}",
+                    path.display()
+                )
+            })?;
+        }
+    }
+    crate::theme::Theme::from_config(&config.theme)
+        .with_context(|| format!("invalid [theme] in {}", path.display()))?;
+    crate::redact::Redactor::from_config(config.privacy.redact.as_deref())
+        .with_context(|| format!("invalid [privacy] in {}", path.display()))?;
+    Ok(config)
+}
+
 const EXAMPLE_CONFIG: &str = r#"# fzc config
 #
+# A project fzc.toml is merged over this file: its tables override keys set here, and its
+# commands are added (replacing ones of the same name). Set `root = true` there to skip this file.
+#
+# Pull in more config files (merged under this one); relative paths start at this file's directory.
+# include = ["~/.config/fzc/commands/*.toml"]
+#
 # Use {{param}} placeholders inside command `run` templates.
 # Parameter types:
 # - value (default): free text
 # - flag: y/n prompt, renders --name when enabled
+# A param with `eval = true` expands its value in the shell first, e.g. `$(git rev-parse HEAD)`.
 
 [ranking]
 usage_enabled = true
 usage_weight = 8000
+# "linear" (count x weight) or "log" (log2(1 + count) x weight)
+usage_scale = "linear"
+# Upper bound for the usage boost so it can't outweigh a clearly better match.
+# usage_cap = 40000
+# A run from this many days ago counts half as much as one from today; 0 uses raw counts.
+decay_half_life_days = 30
+
+# Output lines longer than this many bytes are truncated in the session pane
+# (use the "View truncated line" palette action to see them in full). 0 disables.
+[output]
+max_line_bytes = 4096
+# Pretty-print JSON output lines (toggle at runtime via the Ctrl+P palette).
+pretty_json = false
+# Color env for spawned commands: "force" (CLICOLOR_FORCE/FORCE_COLOR), "auto"
+# (leave it to the command), or "never" (NO_COLOR). Commands can set `color` too.
+color = "force"
+# Run session commands in a pseudo-terminal so progress bars and prompts work;
+# keys go to the command while it runs (Escape still interrupts). Commands can set `pty` too.
+# Unix only: on Windows the setting is ignored and commands keep using pipes.
+pty = false
+# Stop session commands that run longer than this (s/m/h); commands can set `timeout` too.
+# timeout = "10m"
+# Where commands run from the TUI go: "session" (default), "tmux-window", "tmux-pane", or
+# "terminal" (the launcher below). fzc returns to the list right away. Commands can set `target` too.
+# target = "tmux-window"
+# terminal = "wezterm cli spawn --cwd {{cwd}} -- sh -c {{command}}"
+
+# Set to false (or pass --no-usage) to neither read nor write usage counts,
+# the recent projects list, or catalog snapshots.
+[privacy]
+persist_usage = true
+# Output lines and history entries are masked where these regexes match (only capture
+# groups, when a pattern has them). Unset: built-in patterns for bearer tokens,
+# password=/token=/api_key= values, and AWS, GitHub, Slack, and sk- keys; [] turns it off.
+# redact = ['(?i)\bbearer\s+(\S+)', '(?i)password=(\S+)']
+
+# Event polling: `poll_interval_ms` while active, `idle_poll_interval_ms` after a few idle seconds.
+[ui]
+poll_interval_ms = 100
+idle_poll_interval_ms = 1000
+# Give up to this many scheduled commands and background jobs their own pane beside the
+# session (0 = share it).
+job_panes = 0
+# Click to select or focus, double-click to run, wheel to scroll. Turn off to keep the
+# terminal's own text selection (Ctrl+S also shows plain, selectable output).
+mouse = true
+# Language of fzc's own screens: auto (from LC_ALL / LC_MESSAGES / LANG), en, or de.
+# Counts and durations follow it too; command output is never translated.
+locale = "auto"
+
+# Run after every catalog command; {{exit_code}}, {{duration}}, and {{name}} (shell-quoted) are filled in.
+# Commands can set their own `on_success` / `on_failure`.
+# [hooks]
+# on_success = "notify-send fzc {{name}}' finished in {{duration}}s'"
+# on_failure = "notify-send fzc {{name}}' failed ({{exit_code}})'"
+
+# Colors: `preset` is dark (default), light, or solarized; set any color to override it
+# with a name, #rrggbb, or 0-255 index. Keys: border, border_active, highlight_bg,
+# highlight_fg, badge, text, popup_bg, info, command, stdout, stderr, header_bg, banner_fg,
+# banner_bg, success, error, warning.
+# Provider badges get a color derived from the provider's name; `badge` colors them all
+# alike, and [theme.providers] picks one per provider.
+# [theme]
+# preset = "light"
+# badge = "light-blue"
+# [theme.providers]
+# npm = "yellow"
+
+# Serve JSON events (started, output, finished) for commands run in the TUI on a Unix
+# socket, for statusbars, editors, or tmux. `{pid}` makes the path unique per session.
+# [events]
+# socket = "/tmp/fzc-{pid}.sock"
+
+# `{{secret:name}}` in a command is fetched from here when it runs and passed as the variable
+# FZC_SECRET_NAME, so the value never shows up in the command line, history, or session.
+# Backends: pass (`pass show <prefix><name>`), age (a TOML file of name = "value" pairs), or
+# keychain (macOS Keychain / Secret Service; `service` + the name as account).
+# [secrets]
+# backend = "pass"
+# prefix = "fzc/"
+# backend = "age"
+# file = "~/.config/fzc/secrets.toml.age"
+# identity = "~/.config/age/key.txt"
+
+# Commands matching a `deny` pattern are never executed; `confirm` patterns
+# require typing "yes" first. Patterns are case-insensitive globs matched
+# against the whole rendered command line.
+[safety]
+deny = ["rm -rf /", "rm -rf /\\*"]
+confirm = ["*drop database*", "*git push --force*"]
+# Commands using sudo/doas, or run in an environment or on a host matching a
+# `production` glob, show a red banner and need a second Enter.
+warn_root = true
+production = ["prod", "prod-*", "prod.*", "production", "live"]
+
+# Providers with thousands of commands can load on demand: the TUI lists one
+# "search <provider>…" entry until their `:alias` filter is typed.
+[providers]
+lazy = []
 
 # Load commands from this file (`[[commands]]` blocks)
 [providers.config]
@@ -548,6 +2426,99 @@ path = "justfile"
 options = "--working-directory ."
 alias = "j"
 
+# Auto-load package.json scripts; runs them with npm, yarn, or pnpm depending on
+# the lockfile (override with `manager = "pnpm"`).
+[providers.npm]
+enabled = false
+alias = "n"
+
+# List running docker containers with logs, exec, and stop commands per container.
+[providers.docker]
+enabled = false
+alias = "d"
+
+# Gradle lifecycle tasks plus those `gradlew tasks --all` lists (cached until a build
+# file changes), or Maven lifecycle phases, when build.gradle(.kts) or pom.xml is present.
+[providers.jvm]
+enabled = false
+alias = "g"
+
+# Pull, fetch, push, stash, `git switch` with a branch picker, and your [alias] entries from
+# .gitconfig, inside a git repository.
+[providers.git]
+enabled = false
+alias = "gt"
+
+# kubectl get/describe/logs/exec/rollout/use-context, plus apply when k8s manifests are
+# nearby. `verbs` limits the list ("delete" is opt-in); `populate = false` skips asking the
+# cluster for namespaces.
+[providers.kubectl]
+enabled = false
+alias = "k"
+verbs = ["get", "describe", "logs", "exec", "apply", "rollout", "use-context"]
+populate = true
+
+# Rake tasks (`rake -AT`) and bundler commands when a Rakefile or Gemfile is present.
+[providers.ruby]
+enabled = false
+alias = "rb"
+
+# pyproject.toml / Pipfile scripts, [tool.poe.tasks], and tox environments, run through
+# poetry, uv, or pipenv depending on the lockfile.
+[providers.python]
+enabled = false
+alias = "py"
+
+# `deno task <name>` for the tasks in the nearest deno.json / deno.jsonc.
+[providers.deno]
+enabled = false
+alias = "dn"
+
+# `bun run <script>` for package.json scripts in projects with bun.lock(b) or bunfig.toml.
+[providers.bun]
+enabled = false
+alias = "bn"
+
+# Every executable file in these directories; relative ones are searched from
+# the working directory upwards. A `# fzc: <description>` line near the top of
+# a script describes it.
+[providers.scripts]
+enabled = false
+alias = "sh"
+dirs = ["scripts", "~/bin"]
+descriptions = true
+
+# GitHub Actions through the gh CLI: `gh workflow run` for dispatchable
+# workflows, `gh run watch` for recent runs, and `gh pr checks`. Needs `gh`
+# on PATH and a github.com remote.
+[providers.gh]
+enabled = false
+alias = "gh"
+runs = 10
+
+# `ssh <host>` for each Host in your ssh config (Include files too). These
+# take over the terminal, as Option+Enter does.
+[providers.ssh]
+enabled = false
+alias = "ssh"
+path = "~/.ssh/config"
+exclude_wildcards = true
+
+# Providers reload after a successful run matching one of their triggers (globs over the
+# command line). Built in: composer/artisan after `composer install|update|require|remove`,
+# artisan after `php artisan make:command`, docker after container start/stop commands, git after
+# branches or aliases change, kubectl after namespaces or the context change, ruby after
+# `bundle install|update|add|remove`.
+# [providers.refresh]
+# npm = ["npm pkg set scripts.*"]
+# docker = []   # disable
+
+# External providers print a JSON array of commands (name, run, description, params).
+# [[providers.external]]
+# name = "make"
+# command = "my-provider --list"
+# alias = "mk"
+
 # Add your own commands below using `[[commands]]`.
 # Example:
 #
@@ -566,4 +2537,43 @@ alias = "j"
 # name = "no-coverage"
 # type = "flag"
 # default = false
+#
+# Environments add a picker before running; each one sets env vars and
+# template values:
+#
+# [commands.environments.staging]
+# env = { APP_ENV = "staging" }
+# vars = { filter = "Smoke" }
+#
+# Set `host` to run a command over ssh instead of locally:
+#
+# host = "deploy@prod-1"
+#
+# Re-run flaky commands after a non-zero exit, waiting between attempts:
+#
+# retries = 3
+# retry_delay = "5s"
+#
+# Stop a command that hangs; the run shows as timed out in the session and history:
+#
+# timeout = "30s"
+#
+# Run other catalog commands after this one exits 0, in order:
+#
+# then = ["Deploy"]
+#
+# Start long-running commands (dev servers, watchers) as background jobs; see /jobs:
+#
+# background = true
+
+# Pipelines run catalog commands in order as one entry; a failing step stops the
+# rest unless it sets continue_on_error:
+#
+# [[pipelines]]
+# name = "Release"
+# steps = [
+#     { command = "Lint", continue_on_error = true },
+#     { command = "Build" },
+#     { command = "Deploy" },
+# ]
 "#;
@@ -0,0 +1,353 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde_json::{Value, json};
+
+use crate::app;
+use crate::config::LoadedConfig;
+use crate::model::CommandCatalog;
+use crate::provider::{self, ProviderProbe};
+
+/// One provider's detection results; `program`, `version`, and `load` are only checked for
+/// enabled providers.
+struct ProviderReport {
+    name: String,
+    enabled: bool,
+    lazy: bool,
+    probe: ProviderProbe,
+    program_path: Option<PathBuf>,
+    version: Option<String>,
+    load: Option<LoadReport>,
+}
+
+struct LoadReport {
+    commands: usize,
+    elapsed: Duration,
+    error: Option<String>,
+}
+
+struct Report {
+    config: Option<PathBuf>,
+    /// The global config merged under `config`.
+    global_config: Option<PathBuf>,
+    cwd: PathBuf,
+    providers: Vec<ProviderReport>,
+    catalog_commands: usize,
+    /// Missing programs, failed loads, and catalog problems; empty when fzc is ready.
+    problems: Vec<String>,
+}
+
+/// `fzc doctor`: which providers find a project, resolve their program, and load, plus catalog
+/// problems. Returns 1 when there are problems so scripts can check a machine is ready.
+pub fn run(loaded: &LoadedConfig, cwd: &Path, as_json: bool) -> Result<i32> {
+    let report = build_report(loaded, cwd)?;
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&render_json(&report))?);
+    } else {
+        print!("{}", render_text(&report));
+    }
+    Ok(if report.problems.is_empty() { 0 } else { 1 })
+}
+
+fn build_report(loaded: &LoadedConfig, cwd: &Path) -> Result<Report> {
+    let providers_config = &loaded.config.providers;
+    let enabled = provider::enabled_provider_names(providers_config);
+    let names = provider::PROVIDER_NAMES
+        .iter()
+        .map(|name| name.to_string())
+        .chain(
+            providers_config
+                .external
+                .iter()
+                .map(|external| external.name.clone()),
+        );
+
+    let mut providers = Vec::new();
+    let mut problems = Vec::new();
+    let mut catalog = Vec::new();
+    for name in names {
+        let is_config = name == "config";
+        let is_enabled = if is_config {
+            providers_config.config.enabled
+        } else {
+            enabled.contains(&name)
+        };
+        let probe = provider::probe_provider(providers_config, cwd, &name);
+        let mut report = ProviderReport {
+            lazy: providers_config.lazy.contains(&name),
+            enabled: is_enabled,
+            name,
+            probe,
+            program_path: None,
+            version: None,
+            load: None,
+        };
+        if is_enabled {
+            if let Some(program) = &report.probe.program {
+                report.program_path = find_program(program);
+                report.version = report
+                    .program_path
+                    .as_deref()
+                    .and_then(|path| program_version(path, report.probe.version_args));
+                let needed = !report.probe.needs_project || report.probe.root.is_some();
+                if report.program_path.is_none() && needed {
+                    problems.push(format!(
+                        "{}: `{}` was not found on PATH",
+                        report.name,
+                        program.display()
+                    ));
+                }
+            }
+            let started = Instant::now();
+            let loaded_commands = if is_config {
+                CommandCatalog::from_config(loaded, cwd).map(CommandCatalog::into_vec)
+            } else {
+                provider::load_provider(providers_config, cwd, &report.name)
+            };
+            let elapsed = started.elapsed();
+            report.load = Some(match loaded_commands {
+                Ok(commands) => {
+                    let count = commands.len();
+                    catalog.extend(commands);
+                    LoadReport {
+                        commands: count,
+                        elapsed,
+                        error: None,
+                    }
+                }
+                Err(err) => {
+                    problems.push(format!("{}: failed to load: {err:#}", report.name));
+                    LoadReport {
+                        commands: 0,
+                        elapsed,
+                        error: Some(format!("{err:#}")),
+                    }
+                }
+            });
+        }
+        providers.push(report);
+    }
+    problems.extend(app::catalog_problems(&catalog));
+
+    Ok(Report {
+        config: loaded.path.clone(),
+        global_config: loaded.global.clone(),
+        cwd: cwd.to_path_buf(),
+        providers,
+        catalog_commands: catalog.len(),
+        problems,
+    })
+}
+
+/// `program` itself when it is a path, else the first match in `PATH` (with the usual
+/// executable extensions on Windows).
+fn find_program(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return program.is_file().then(|| program.to_path_buf());
+    }
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|dir| {
+        extensions.iter().find_map(|extension| {
+            let mut name = program.as_os_str().to_owned();
+            name.push(extension);
+            let candidate = dir.join(name);
+            candidate.is_file().then_some(candidate)
+        })
+    })
+}
+
+/// The first line the program prints for `args` (some tools print their version to stderr).
+fn program_version(path: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(ToString::to_string)
+}
+
+fn render_json(report: &Report) -> Value {
+    let path = |path: &Option<PathBuf>| path.as_ref().map(|path| path.display().to_string());
+    let providers: Vec<Value> = report
+        .providers
+        .iter()
+        .map(|provider| {
+            let program = provider.probe.program.as_ref().filter(|_| provider.enabled);
+            json!({
+                "name": provider.name,
+                "enabled": provider.enabled,
+                "lazy": provider.lazy,
+                "root": path(&provider.probe.root),
+                "program": program.map(|program| json!({
+                    "name": program.display().to_string(),
+                    "path": path(&provider.program_path),
+                    "version": provider.version,
+                })),
+                "cache": json!({
+                    "path": path(&provider.probe.cache),
+                    "present": provider.probe.cache.as_ref().is_some_and(|cache| cache.is_file()),
+                }),
+                "load": provider.load.as_ref().map(|load| json!({
+                    "commands": load.commands,
+                    "ms": load.elapsed.as_secs_f64() * 1_000.0,
+                    "error": load.error,
+                })),
+            })
+        })
+        .collect();
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "config": path(&report.config),
+        "global_config": path(&report.global_config),
+        "cwd": report.cwd.display().to_string(),
+        "ready": report.problems.is_empty(),
+        "providers": providers,
+        "catalog": { "commands": report.catalog_commands },
+        "problems": report.problems,
+    })
+}
+
+fn render_text(report: &Report) -> String {
+    let mut out = format!("fzc {}\n", env!("CARGO_PKG_VERSION"));
+    match &report.config {
+        Some(path) => match &report.global_config {
+            Some(global) => out.push_str(&format!(
+                "config: {} (merged over {})\n",
+                path.display(),
+                global.display()
+            )),
+            None => out.push_str(&format!("config: {}\n", path.display())),
+        },
+        None => out.push_str("config: none (providers only or defaults)\n"),
+    }
+    out.push_str(&format!("cwd: {}\n\nproviders:\n", report.cwd.display()));
+    for provider in &report.providers {
+        if !provider.enabled {
+            if let Some(root) = &provider.probe.root {
+                out.push_str(&format!(
+                    "  {:<10} off      detected at {}\n",
+                    provider.name,
+                    root.display()
+                ));
+            }
+            continue;
+        }
+        let mut line = format!(
+            "  {:<10} {:<8}",
+            provider.name,
+            if provider.lazy { "lazy" } else { "on" }
+        );
+        if let Some(root) = &provider.probe.root {
+            line.push_str(&format!(" {}", root.display()));
+        }
+        if let Some(program) = &provider.probe.program {
+            match (&provider.program_path, &provider.version) {
+                (Some(_), Some(version)) => line.push_str(&format!("  [{version}]")),
+                (Some(path), None) => line.push_str(&format!("  [{}]", path.display())),
+                (None, _) => line.push_str(&format!("  [{} not found]", program.display())),
+            }
+        }
+        if let Some(cache) = &provider.probe.cache {
+            let state = if cache.is_file() {
+                "cached"
+            } else {
+                "not cached"
+            };
+            line.push_str(&format!("  {state}"));
+        }
+        if let Some(load) = &provider.load {
+            line.push_str(&format!(
+                "  {} commands in {:.1} ms",
+                load.commands,
+                load.elapsed.as_secs_f64() * 1_000.0
+            ));
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "\ncatalog: {} commands\n",
+        report.catalog_commands
+    ));
+    if report.problems.is_empty() {
+        out.push_str("ready: no problems found\n");
+    } else {
+        out.push_str(&format!("problems ({}):\n", report.problems.len()));
+        for problem in &report.problems {
+            out.push_str(&format!("  - {problem}\n"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::config::{Config, ExternalProviderConfig, LoadedConfig};
+
+    use super::{build_report, render_json};
+
+    #[test]
+    fn json_report_lists_detection_loads_and_problems() {
+        let root = std::env::temp_dir().join(format!("fzc-doctor-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("package.json"), r#"{"scripts": {"dev": "vite"}}"#).unwrap();
+        let mut config = Config::default();
+        config.providers.external.push(ExternalProviderConfig {
+            name: "tools".to_string(),
+            command: "fzc-doctor-missing-tool --list".to_string(),
+            alias: None,
+        });
+        let loaded = LoadedConfig {
+            config,
+            path: None,
+            global: None,
+        };
+
+        let report = render_json(&build_report(&loaded, &root).unwrap());
+        let provider = |name: &str| {
+            report["providers"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|provider| provider["name"] == name)
+                .unwrap()
+                .clone()
+        };
+        let npm = provider("npm");
+        assert_eq!(npm["enabled"], false);
+        assert_eq!(npm["root"], root.display().to_string());
+        assert!(npm["program"].is_null() && npm["load"].is_null());
+        assert!(npm["cache"]["path"].is_null());
+        assert_eq!(npm["cache"]["present"], false);
+
+        let tools = provider("tools");
+        assert_eq!(tools["enabled"], true);
+        assert!(tools["program"]["path"].is_null());
+        assert!(tools["load"]["error"].is_string());
+        assert_eq!(report["ready"], false);
+        assert!(
+            report["problems"][0]
+                .as_str()
+                .unwrap()
+                .contains("fzc-doctor-missing-tool")
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
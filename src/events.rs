@@ -0,0 +1,204 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+/// How long a slow reader may hold up the UI before it is disconnected.
+#[cfg_attr(not(unix), allow(dead_code))]
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// What happened to the command fzc is running, sent as one JSON object per line.
+pub enum RunEvent<'a> {
+    Started {
+        name: &'a str,
+        command_line: &'a str,
+        working_dir: Option<&'a Path>,
+    },
+    Output {
+        stderr: bool,
+        line: &'a str,
+    },
+    Finished {
+        name: &'a str,
+        /// `None` when the command could not start.
+        exit_code: Option<i32>,
+        duration: Duration,
+    },
+}
+
+impl RunEvent<'_> {
+    fn to_json(&self) -> Value {
+        match self {
+            RunEvent::Started {
+                name,
+                command_line,
+                working_dir,
+            } => json!({
+                "event": "started",
+                "name": name,
+                "command_line": command_line,
+                "working_dir": working_dir.map(|dir| dir.display().to_string()),
+            }),
+            RunEvent::Output { stderr, line } => json!({
+                "event": "output",
+                "stream": if *stderr { "stderr" } else { "stdout" },
+                "line": line,
+            }),
+            RunEvent::Finished {
+                name,
+                exit_code,
+                duration,
+            } => json!({
+                "event": "finished",
+                "name": name,
+                "exit_code": exit_code,
+                "duration_ms": duration.as_millis() as u64,
+            }),
+        }
+    }
+}
+
+/// `[events] socket`, with `~/` and `{pid}` expanded.
+pub fn socket_path(raw: &str) -> Option<PathBuf> {
+    let raw = raw.trim().replace("{pid}", &std::process::id().to_string());
+    if raw.is_empty() {
+        return None;
+    }
+    crate::provider::expand_home_shorthand(&raw).or_else(|| Some(PathBuf::from(raw)))
+}
+
+#[cfg(unix)]
+pub use unix::EventSocket;
+
+#[cfg(unix)]
+mod unix {
+    use std::io::Write;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::{fs, thread};
+
+    use anyhow::{Context, Result};
+
+    use super::{CLIENT_WRITE_TIMEOUT, RunEvent};
+
+    /// A Unix socket that every connected client reads session events from.
+    pub struct EventSocket {
+        path: PathBuf,
+        pub(super) clients: Arc<Mutex<Vec<UnixStream>>>,
+    }
+
+    impl EventSocket {
+        /// Binds `path`, replacing a stale socket left by an earlier session.
+        pub fn bind(path: &Path) -> Result<Self> {
+            if UnixStream::connect(path).is_ok() {
+                anyhow::bail!("{} is in use by another fzc session", path.display());
+            }
+            let _ = fs::remove_file(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).with_context(|| {
+                    format!("failed to create socket directory {}", parent.display())
+                })?;
+            }
+            let listener = UnixListener::bind(path)
+                .with_context(|| format!("failed to bind event socket {}", path.display()))?;
+            let clients = Arc::new(Mutex::new(Vec::new()));
+            let accepted = Arc::clone(&clients);
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)).is_ok()
+                        && let Ok(mut clients) = accepted.lock()
+                    {
+                        clients.push(stream);
+                    }
+                }
+            });
+            Ok(Self {
+                path: path.to_path_buf(),
+                clients,
+            })
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// Sends `event` to every client; ones that fail to read in time are dropped.
+        pub fn emit(&self, event: &RunEvent<'_>) {
+            let Ok(mut clients) = self.clients.lock() else {
+                return;
+            };
+            if clients.is_empty() {
+                return;
+            }
+            let line = format!("{}\n", event.to_json());
+            clients.retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+        }
+    }
+
+    impl Drop for EventSocket {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// RunEvent sockets need Unix domain sockets; elsewhere binding reports that.
+#[cfg(not(unix))]
+pub struct EventSocket;
+
+#[cfg(not(unix))]
+impl EventSocket {
+    pub fn bind(_path: &Path) -> anyhow::Result<Self> {
+        anyhow::bail!("[events] socket is only supported on Unix platforms")
+    }
+
+    pub fn path(&self) -> &Path {
+        Path::new("")
+    }
+
+    pub fn emit(&self, _event: &RunEvent<'_>) {}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+    use std::time::{Duration, Instant};
+
+    use super::{EventSocket, RunEvent};
+
+    #[test]
+    fn connected_clients_receive_json_lines() {
+        let path = std::env::temp_dir().join(format!("fzc-events-{}.sock", std::process::id()));
+        let socket = EventSocket::bind(&path).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while socket.clients.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        socket.emit(&RunEvent::Output {
+            stderr: true,
+            line: "boom",
+        });
+        socket.emit(&RunEvent::Finished {
+            name: "deploy",
+            exit_code: Some(2),
+            duration: Duration::from_millis(1_500),
+        });
+        let mut lines = BufReader::new(client).lines();
+        let output: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(output["stream"], "stderr");
+        assert_eq!(output["line"], "boom");
+        let finished: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(finished["event"], "finished");
+        assert_eq!(finished["exit_code"], 2);
+        assert_eq!(finished["duration_ms"], 1_500);
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
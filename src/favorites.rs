@@ -0,0 +1,90 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Pinned commands by usage key (`provider::name`). The same shape is used for exports, so a
+/// copied `pins.toml` imports as-is. Tags are not stored here: they are front-matter in the
+/// project's own scripts, so they already move with it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FavoritesStore {
+    #[serde(default)]
+    commands: BTreeSet<String>,
+}
+
+pub fn favorites_path() -> Option<PathBuf> {
+    let config_root = dirs::config_dir()?;
+    Some(config_root.join("fzc").join("pins.toml"))
+}
+
+/// Pinned usage keys; a missing or unreadable file means nothing is pinned.
+pub fn load(path: &Path) -> BTreeSet<String> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str::<FavoritesStore>(&content).ok())
+        .map(|store| store.commands)
+        .unwrap_or_default()
+}
+
+pub fn save(path: &Path, pinned: &BTreeSet<String>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create pins directory {}", parent.display()))?;
+    }
+    fs::write(path, export(pinned)?)
+        .with_context(|| format!("failed to write pinned commands {}", path.display()))?;
+    Ok(())
+}
+
+pub fn export(pinned: &BTreeSet<String>) -> Result<String> {
+    toml::to_string(&FavoritesStore {
+        commands: pinned.clone(),
+    })
+    .context("failed to serialize pinned commands")
+}
+
+/// Merges (or with `replace`, swaps in) the pins from an exported file; returns how many
+/// were new.
+pub fn import(path: &Path, raw: &str, replace: bool) -> Result<usize> {
+    let imported = toml::from_str::<FavoritesStore>(raw)
+        .context("not a pinned commands file")?
+        .commands;
+    let mut pinned = if replace { BTreeSet::new() } else { load(path) };
+    let before = pinned.clone();
+    pinned.extend(imported);
+    save(path, &pinned)?;
+    Ok(pinned.difference(&before).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+
+    use super::{export, import, load, save};
+
+    #[test]
+    fn import_merges_or_replaces_exported_pins() {
+        let dir = std::env::temp_dir().join(format!("fzc-favorites-{}", std::process::id()));
+        let path = dir.join("pins.toml");
+        let _ = fs::remove_dir_all(&dir);
+        save(&path, &BTreeSet::from(["config::deploy".to_string()])).unwrap();
+
+        let other = export(&BTreeSet::from([
+            "config::deploy".to_string(),
+            "npm::npm run dev".to_string(),
+        ]))
+        .unwrap();
+        assert_eq!(import(&path, &other, false).unwrap(), 1);
+        assert_eq!(load(&path).len(), 2);
+
+        let single = export(&BTreeSet::from(["just::test".to_string()])).unwrap();
+        assert_eq!(import(&path, &single, true).unwrap(), 1);
+        assert_eq!(load(&path), BTreeSet::from(["just::test".to_string()]));
+        assert!(import(&path, "commands = 3", false).is_err());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
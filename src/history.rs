@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Oldest entries are dropped once the file holds this many runs.
+const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// One executed catalog command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// `provider::name`, the same key usage counts use.
+    pub command: String,
+    pub name: String,
+    pub command_line: String,
+    /// Unix seconds.
+    pub timestamp: u64,
+    pub cwd: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    /// Stopped for running past its timeout.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryStore {
+    #[serde(default)]
+    entries: Vec<HistoryEntry>,
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    let config_root = dirs::config_dir()?;
+    Some(config_root.join("fzc").join("history.toml"))
+}
+
+/// Recorded runs, oldest first; a missing or unreadable file is an empty history.
+pub fn load(path: &Path) -> Vec<HistoryEntry> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str::<HistoryStore>(&content).ok())
+        .map(|store| store.entries)
+        .unwrap_or_default()
+}
+
+pub fn append(path: &Path, entry: HistoryEntry) -> Result<()> {
+    let mut entries = load(path);
+    entries.push(entry);
+    let overflow = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    entries.drain(..overflow);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create history directory {}", parent.display()))?;
+    }
+    let serialized =
+        toml::to_string(&HistoryStore { entries }).context("failed to serialize history")?;
+    fs::write(path, serialized)
+        .with_context(|| format!("failed to write history {}", path.display()))?;
+    Ok(())
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Compact age such as `42s`, `5m`, `3h`, or `2d`.
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3_600 => format!("{}m", seconds / 60),
+        3_600..86_400 => format!("{}h", seconds / 3_600),
+        _ => format!("{}d", seconds / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{HistoryEntry, HistoryStore, MAX_HISTORY_ENTRIES, append, format_age, load};
+
+    fn entry(timestamp: u64) -> HistoryEntry {
+        HistoryEntry {
+            command: "config::deploy".to_string(),
+            name: "deploy".to_string(),
+            command_line: "deploy --branch main".to_string(),
+            timestamp,
+            cwd: PathBuf::from("/srv/app"),
+            exit_code: Some(0),
+            environment: Some("staging".to_string()),
+            params: BTreeMap::from([("branch".to_string(), "main".to_string())]),
+            timed_out: false,
+        }
+    }
+
+    #[test]
+    fn history_round_trips_and_keeps_newest_entries() {
+        let dir = std::env::temp_dir().join(format!("fzc-history-{}", std::process::id()));
+        let path = dir.join("history.toml");
+        let _ = fs::remove_file(&path);
+
+        let entries = (0..MAX_HISTORY_ENTRIES as u64).map(entry).collect();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, toml::to_string(&HistoryStore { entries }).unwrap()).unwrap();
+        append(&path, entry(MAX_HISTORY_ENTRIES as u64)).unwrap();
+        append(&path, entry(MAX_HISTORY_ENTRIES as u64 + 1)).unwrap();
+        let entries = load(&path);
+        assert_eq!(entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(entries[0].timestamp, 2);
+        assert_eq!(entries.last(), Some(&entry(MAX_HISTORY_ENTRIES as u64 + 1)));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn ages_use_the_largest_whole_unit() {
+        assert_eq!(format_age(42), "42s");
+        assert_eq!(format_age(300), "5m");
+        assert_eq!(format_age(7_200), "2h");
+        assert_eq!(format_age(200_000), "2d");
+    }
+}
@@ -0,0 +1,665 @@
+use anyhow::{Result, bail};
+
+/// Language of the TUI's own strings; command output is never translated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    /// `[ui] locale`: `auto` (from `LC_ALL`, `LC_MESSAGES`, or `LANG`), `en`, or `de`.
+    pub fn from_config(raw: &str) -> Result<Self> {
+        match raw.trim() {
+            "auto" | "" => Ok(Self::from_env(|name| std::env::var(name).ok())),
+            "en" => Ok(Self::En),
+            "de" => Ok(Self::De),
+            other => bail!("unknown locale '{other}' (auto, en, de)"),
+        }
+    }
+
+    /// The first set locale variable wins, as in POSIX; unknown languages fall back to English.
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(var)
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        match value.split(['_', '.', '@']).next().unwrap_or_default() {
+            "de" => Self::De,
+            _ => Self::En,
+        }
+    }
+
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Self::En => &EN,
+            Self::De => &DE,
+        }
+    }
+
+    /// `12,345` in English, `12.345` in German.
+    pub fn format_count(self, count: usize) -> String {
+        let digits = count.to_string();
+        let mut out = String::new();
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                out.push(self.strings().thousands_separator);
+            }
+            out.push(digit);
+        }
+        out
+    }
+
+    /// Compact age in the largest whole unit, like `5m` or `5 Min.`.
+    pub fn format_age(self, seconds: u64) -> String {
+        let [secs, mins, hours, days] = self.strings().age_units;
+        match seconds {
+            0..60 => format!("{seconds}{secs}"),
+            60..3_600 => format!("{}{mins}", seconds / 60),
+            3_600..86_400 => format!("{}{hours}", seconds / 3_600),
+            _ => format!("{}{days}", seconds / 86_400),
+        }
+    }
+}
+
+/// Replaces each `{name}` in a translated template.
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+/// Key rows in the help overlay, one label each in [`Strings::help_labels`].
+pub const HELP_ROWS: usize = 26;
+
+/// Every UI string of one locale; templates use `{name}` placeholders for [`fill`].
+pub struct Strings {
+    /// Titles of the help overlay's key sections.
+    pub help_sections: [&'static str; 5],
+    /// What each key row of the help overlay does, in the order of the keymap's help rows; the
+    /// keys themselves come from the keymap and are not translated.
+    pub help_labels: [&'static str; HELP_ROWS],
+    pub help_title: &'static str,
+    pub help_hint: &'static str,
+    pub help_no_matches: &'static str,
+    pub help_internal_commands: &'static str,
+    pub help_palette: &'static str,
+    pub hint_help: &'static str,
+    pub hint_filter: &'static str,
+    pub hint_running: &'static str,
+    pub hint_foreground: &'static str,
+    /// `{count}`
+    pub hint_jobs_running: &'static str,
+    pub hint_refresh: &'static str,
+    /// `{first}`, `{last}`, `{count}`: the rows the command list shows.
+    pub page_range: &'static str,
+    /// `{age}`
+    pub provider_age: &'static str,
+    pub param_title: &'static str,
+    pub param_default_hint: &'static str,
+    pub param_placeholder_hint: &'static str,
+    /// `{default}` is [`Strings::yes`] or [`Strings::no`].
+    pub param_flag_hint: &'static str,
+    pub param_choice_hint: &'static str,
+    pub param_path_hint: &'static str,
+    /// `{name}`
+    pub param_command: &'static str,
+    pub yes: &'static str,
+    pub no: &'static str,
+    /// `{count}`, `{age}`
+    pub usage: &'static str,
+    pub never_run: &'static str,
+    pub selection_hint: &'static str,
+    pub projects_hint: &'static str,
+    pub history_hint: &'static str,
+    pub jobs_hint: &'static str,
+    pub cache_hint: &'static str,
+    pub environment_hint: &'static str,
+    pub dry_run_hint: &'static str,
+    pub confirm_hint: &'static str,
+    pub init_diff_hint: &'static str,
+    /// `{dir}`
+    pub working_directory: &'static str,
+    /// `{name}`
+    pub environment: &'static str,
+    /// `{attempt}`, `{attempts}`
+    pub attempt: &'static str,
+    /// `{code}`
+    pub exit_code: &'static str,
+    pub interrupted: &'static str,
+    /// `{seconds}`
+    pub interrupt_sent: &'static str,
+    pub force_killed: &'static str,
+    /// `{limit}`
+    pub timed_out: &'static str,
+    /// `{error}`
+    pub execution_failed: &'static str,
+    /// `{delay}`
+    pub retrying: &'static str,
+    pub retry_canceled: &'static str,
+    pub no_matching_commands: &'static str,
+    pub preview_title: &'static str,
+    /// `{command}`
+    pub preview_of: &'static str,
+    /// `{name}`
+    pub details_of: &'static str,
+    pub internal_param_title: &'static str,
+    pub projects_title: &'static str,
+    pub history_title: &'static str,
+    pub jobs_title: &'static str,
+    pub cache_title: &'static str,
+    pub environment_title: &'static str,
+    /// `{name}`
+    pub dry_run_of: &'static str,
+    /// `{count}`
+    pub queue_of: &'static str,
+    pub queue_hint: &'static str,
+    pub confirm_title: &'static str,
+    /// `{path}`
+    pub overwrite_path: &'static str,
+    pub palette_title: &'static str,
+    pub pager_hint: &'static str,
+    /// `{bytes}`
+    pub full_line: &'static str,
+    pub no_truncated_lines: &'static str,
+    pub continuing: &'static str,
+    pub created_working_dir: &'static str,
+    /// `{id}`
+    pub job_finished: &'static str,
+    /// `{id}`
+    pub job_backgrounded: &'static str,
+    /// `{id}`
+    pub job_running: &'static str,
+    pub status_ok: &'static str,
+    pub status_failed: &'static str,
+    /// `{code}`
+    pub status_exit: &'static str,
+    pub status_running: &'static str,
+    pub status_stopping: &'static str,
+    pub status_killed: &'static str,
+    /// `{path}`
+    pub wrote_config: &'static str,
+    /// `{dir}`
+    pub switched_to: &'static str,
+    /// `{path}`
+    pub config_path: &'static str,
+    /// `{path}`, `{global}`
+    pub config_merged: &'static str,
+    pub config_none: &'static str,
+    /// `{path}`
+    pub events_socket: &'static str,
+    pub param_canceled: &'static str,
+    /// `{name}`
+    pub param_required: &'static str,
+    pub answer_yes_no: &'static str,
+    /// `{input}`
+    pub no_option_matches: &'static str,
+    pub no_command_selected: &'static str,
+    pub environment_canceled: &'static str,
+    pub projects_disabled: &'static str,
+    pub no_projects: &'static str,
+    pub history_disabled: &'static str,
+    pub no_history: &'static str,
+    /// `{name}`
+    pub not_in_catalog: &'static str,
+    /// `{text}`
+    pub copied: &'static str,
+    /// `{error}`
+    pub copy_failed: &'static str,
+    pub run_canceled: &'static str,
+    pub type_yes: &'static str,
+    pub internal_canceled: &'static str,
+    /// `{error}`
+    pub init_failed: &'static str,
+    pub init_canceled: &'static str,
+    pub sort_usage: &'static str,
+    pub sort_alphabetical: &'static str,
+    /// `{names}`
+    pub refreshing: &'static str,
+    /// `{provider}`, `{error}`
+    pub provider_failed: &'static str,
+    /// `{count}`
+    pub scheduled: &'static str,
+    /// `{code}`
+    pub hook_exit_code: &'static str,
+    /// `{error}`
+    pub hook_failed: &'static str,
+    /// `{name}`, `{error}`
+    pub start_failed: &'static str,
+    pub no_jobs: &'static str,
+    pub nothing_cached: &'static str,
+    pub no_session_vars: &'static str,
+    pub session_vars: &'static str,
+    /// `{key}`, `{value}`
+    pub session_var_set: &'static str,
+    /// `{key}`
+    pub session_var_unset: &'static str,
+    /// `{key}`
+    pub no_session_var: &'static str,
+    pub staging_on: &'static str,
+    /// `{count}`
+    pub staging_off: &'static str,
+    pub queue_empty: &'static str,
+    /// `{id}`
+    pub job_foreground: &'static str,
+    /// `{count}`
+    pub queue_confirm: &'static str,
+    /// `{banner}`
+    pub warn_run: &'static str,
+    /// `{name}`, `{suggestion}`
+    pub did_you_mean: &'static str,
+    /// `{name}`, `{error}`
+    pub job_start_failed: &'static str,
+    /// `{id}`, `{name}`
+    pub job_started: &'static str,
+    /// `{count}`, `{seconds}`
+    pub previews_cleared: &'static str,
+    /// `{count}`
+    pub cache_files_removed: &'static str,
+    /// `{path}`
+    pub cache_remove_failed: &'static str,
+    /// `{count}`
+    pub doctor_ok: &'static str,
+    /// `{count}`
+    pub doctor_problems: &'static str,
+    /// `{name}`, `{count}`
+    pub queued: &'static str,
+    /// `{name}`, `{code}`
+    pub schedule_exit: &'static str,
+    /// `{name}`, `{error}`
+    pub schedule_failed: &'static str,
+    pub worker_disconnected: &'static str,
+    /// `{count}`
+    pub loaded: &'static str,
+    /// `{count}`, `{provider}`
+    pub loaded_provider: &'static str,
+    /// `{count}`
+    pub reloaded: &'static str,
+    /// Suffixes for seconds, minutes, hours, and days.
+    pub age_units: [&'static str; 4],
+    pub thousands_separator: char,
+}
+
+static EN: Strings = Strings {
+    help_sections: ["Running", "Search", "Navigation", "Commands", "General"],
+    help_labels: [
+        "Run selected command",
+        "Run selected command and exit",
+        "Dry run: show the resolved command without running it",
+        "Run selected command as a background job (/jobs)",
+        "Toggle staging (queue commands instead of running)",
+        "Review and run the command queue",
+        "Interrupt the running command",
+        "Filter a running command's output by substring or regex",
+        "Move cursor in search input",
+        "Jump cursor in search input",
+        "Edit search input",
+        "Filter by provider",
+        "Internal commands",
+        "Clear search, or quit when it is empty",
+        "Toggle command/session focus",
+        "Scroll active pane",
+        "Move the command selection",
+        "Scroll active pane faster",
+        "Focus the next scheduled job pane",
+        "Plain session text for copying with the mouse",
+        "Command palette",
+        "Pin/unpin selected command",
+        "Toggle the details pane for the selected command",
+        "Reload the selected command's provider",
+        "Search this help",
+        "Quit",
+    ],
+    help_title: "Help",
+    help_hint: "type to search, Up/Down/PgUp/PgDn scroll, Esc clears the search or closes",
+    help_no_matches: "no matching keys or commands",
+    help_internal_commands: "Internal commands",
+    help_palette: "Command palette",
+    hint_help: "? for help",
+    hint_refresh: "F5 refreshes",
+    page_range: "{first}–{last} of {count}",
+    provider_age: "loaded {age} ago",
+    hint_filter: "Enter to keep filter, Esc to clear it",
+    hint_running: "Esc to interrupt, / to filter output",
+    hint_foreground: "Esc to send the job back to the background, Ctrl+C to stop it",
+    hint_jobs_running: "{count} background job(s) running (/jobs)",
+    param_title: "Parameter",
+    param_default_hint: "Enter = default, Tab = edit default",
+    param_placeholder_hint: "Tab = start from placeholder",
+    param_flag_hint: "answer: y/n (Enter = {default})",
+    param_choice_hint: "type to filter, Up/Down = move, Enter = pick",
+    param_path_hint: "Tab = complete, Up/Down = pick completion",
+    param_command: "command: {name}",
+    yes: "yes",
+    no: "no",
+    usage: "{count} run(s), last {age} ago",
+    never_run: "never run",
+    selection_hint: "selection mode: drag to select, Up/Down/PgUp/PgDn scroll, Esc returns",
+    projects_hint: "Up/Down or 1-9 to pick, Enter to switch, Esc to cancel",
+    history_hint: "Up/Down to pick, Enter to re-run with the same params, e to edit them, Esc to cancel",
+    jobs_hint: "Enter/o output, f foreground, x stop (again to kill), Esc to close",
+    cache_hint: "Enter/x clear the selected entry, a clear all, Esc to close",
+    environment_hint: "Up/Down or 1-9 to pick, Enter to run",
+    dry_run_hint: "Nothing was run. Enter to run, c to copy the command line, Esc to close",
+    confirm_hint: "Type 'yes' and press Enter to run, Esc to cancel",
+    init_diff_hint: "y = overwrite, n/Esc = keep current config, Up/Down/PgUp/PgDn = scroll",
+    working_directory: "working directory: {dir}",
+    environment: "environment: {name}",
+    attempt: "[attempt {attempt}/{attempts}]",
+    exit_code: "exit code: {code}",
+    interrupted: "Interrupted by user (Escape)",
+    interrupt_sent: "Asked the command to stop; Esc again after {seconds}s kills it",
+    force_killed: "Killed the command (Escape)",
+    timed_out: "Timed out after {limit}",
+    execution_failed: "execution failed: {error}",
+    retrying: "retrying in {delay} (Esc cancels)",
+    retry_canceled: "Retry canceled by user (Escape)",
+    no_matching_commands: "No matching commands",
+    preview_title: "Preview",
+    preview_of: "Preview: {command}",
+    details_of: "Details: {name}",
+    internal_param_title: "Internal Parameter",
+    projects_title: "Recent projects",
+    history_title: "History",
+    jobs_title: "Jobs",
+    cache_title: "Cache",
+    environment_title: "Environment",
+    dry_run_of: "Dry run: {name}",
+    queue_of: "Queue ({count})",
+    queue_hint: "Enter run all, Shift+Up/Down or K/J reorder, d remove, u undo, Esc close (queue is kept)",
+    confirm_title: "Confirm",
+    overwrite_path: "Overwrite {path}?",
+    palette_title: "Command Palette",
+    pager_hint: "Up/Down/PgUp/PgDn scroll, Esc/q close",
+    full_line: "Full line ({bytes} bytes)",
+    no_truncated_lines: "No truncated output lines in session",
+    continuing: "continuing (continue_on_error)",
+    created_working_dir: "created working directory",
+    job_finished: "[job {id}] already finished",
+    job_backgrounded: "[job {id}] back in the background",
+    job_running: "job {id} running",
+    status_ok: "ok",
+    status_failed: "failed",
+    status_exit: "exit {code}",
+    status_running: "running",
+    status_stopping: "stopping",
+    status_killed: "killed",
+    wrote_config: "Wrote example config: {path}",
+    switched_to: "Switched to {dir}",
+    config_path: "Config: {path}",
+    config_merged: "Config: {path} (merged over {global})",
+    config_none: "Config: none (providers only or defaults)",
+    events_socket: "Events: {path}",
+    param_canceled: "Parameter entry canceled",
+    param_required: "'{name}' is required",
+    answer_yes_no: "Please enter y or n",
+    no_option_matches: "No option matches '{input}'",
+    no_command_selected: "No command selected",
+    environment_canceled: "Environment selection canceled",
+    projects_disabled: "Recent projects are disabled (--no-usage or [privacy])",
+    no_projects: "No recent projects yet",
+    history_disabled: "History is disabled (--no-usage or [privacy])",
+    no_history: "No history yet",
+    not_in_catalog: "'{name}' is no longer in the catalog",
+    copied: "Copied to clipboard: {text}",
+    copy_failed: "copy failed: {error}",
+    run_canceled: "Run canceled",
+    type_yes: "Type 'yes' to confirm or Esc to cancel",
+    internal_canceled: "Internal command canceled",
+    init_failed: "init failed: {error}",
+    init_canceled: "Init canceled, existing config kept",
+    sort_usage: "Sort mode: usage-weighted",
+    sort_alphabetical: "Sort mode: alphabetical",
+    refreshing: "Refreshing {names}",
+    provider_failed: "provider '{provider}' failed: {error}",
+    scheduled: "Scheduled {count} command(s)",
+    hook_exit_code: "hook exit code: {code}",
+    hook_failed: "hook failed: {error}",
+    start_failed: "{name} failed to start: {error}",
+    no_jobs: "No background jobs; Ctrl+B starts the selected command as one",
+    nothing_cached: "Nothing is cached; provider results load fresh on start, /reload refreshes them all, F5 the selected one's",
+    no_session_vars: "No session variables; /env set KEY=value adds one",
+    session_vars: "Session variables (added to every command run):",
+    session_var_set: "Session variable set: {key}={value}",
+    session_var_unset: "Session variable unset: {key}",
+    no_session_var: "No session variable {key}",
+    staging_on: "Staging: selected commands are queued; Ctrl+Q reviews and runs them",
+    staging_off: "Staging off ({count} queued command(s) kept)",
+    queue_empty: "Queue is empty; Ctrl+T starts staging commands",
+    job_foreground: "[job {id}] in the foreground: Esc sends it back, Ctrl+C stops it",
+    queue_confirm: "Run {count} command(s) in order, stopping at the first failure? Enter to run, any key to go back",
+    warn_run: "{banner} — press Enter to run it, any other key cancels",
+    did_you_mean: "Unknown internal command '/{name}' — did you mean {suggestion}? Press Enter to accept",
+    job_start_failed: "[job] {name} failed to start: {error}",
+    job_started: "[job {id}] {name} started in the background (/jobs to manage it)",
+    previews_cleared: "Cleared {count} cached preview(s) (previews are kept for {seconds}s)",
+    cache_files_removed: "Removed {count} cache file(s); the next load asks the tool again",
+    cache_remove_failed: "could not remove {path}",
+    doctor_ok: "/doctor: no problems found in {count} command(s)",
+    doctor_problems: "/doctor: {count} problem(s)",
+    queued: "Queued {name} ({count} in queue)",
+    schedule_exit: "[schedule] {name} (exit code: {code})",
+    schedule_failed: "[schedule] {name}: {error}",
+    worker_disconnected: "internal command worker disconnected unexpectedly",
+    loaded: "Loaded {count} commands",
+    loaded_provider: "Loaded {count} {provider} commands",
+    reloaded: "Reloaded {count} commands",
+    age_units: ["s", "m", "h", "d"],
+    thousands_separator: ',',
+};
+
+static DE: Strings = Strings {
+    help_sections: ["Ausführen", "Suche", "Navigation", "Befehle", "Allgemein"],
+    help_labels: [
+        "Ausgewählten Befehl ausführen",
+        "Ausgewählten Befehl ausführen und beenden",
+        "Probelauf: aufgelösten Befehl zeigen, ohne ihn auszuführen",
+        "Ausgewählten Befehl als Hintergrundjob starten (/jobs)",
+        "Sammelmodus umschalten (Befehle einreihen statt ausführen)",
+        "Warteschlange prüfen und ausführen",
+        "Laufenden Befehl abbrechen",
+        "Ausgabe eines laufenden Befehls nach Text oder Regex filtern",
+        "Cursor in der Suche bewegen",
+        "Cursor an Anfang/Ende der Suche",
+        "Suche bearbeiten",
+        "Nach Provider filtern",
+        "Interne Befehle",
+        "Suche leeren, bei leerer Suche beenden",
+        "Fokus zwischen Befehlen und Sitzung wechseln",
+        "Aktiven Bereich scrollen",
+        "Befehlsauswahl bewegen",
+        "Aktiven Bereich schneller scrollen",
+        "Nächsten Bereich eines geplanten Jobs fokussieren",
+        "Sitzungstext zum Kopieren mit der Maus",
+        "Befehlspalette",
+        "Ausgewählten Befehl anheften/lösen",
+        "Detailbereich für den ausgewählten Befehl umschalten",
+        "Provider des ausgewählten Befehls neu laden",
+        "Diese Hilfe durchsuchen",
+        "Beenden",
+    ],
+    help_title: "Hilfe",
+    help_hint: "Tippen sucht, Hoch/Runter/Bild↑/Bild↓ scrollen, Esc leert die Suche oder schließt",
+    help_no_matches: "keine passenden Tasten oder Befehle",
+    help_internal_commands: "Interne Befehle",
+    help_palette: "Befehlspalette",
+    hint_help: "? für Hilfe",
+    hint_refresh: "F5 aktualisiert",
+    page_range: "{first}–{last} von {count}",
+    provider_age: "vor {age} geladen",
+    hint_filter: "Enter behält den Filter, Esc entfernt ihn",
+    hint_running: "Esc bricht ab, / filtert die Ausgabe",
+    hint_foreground: "Esc schickt den Job in den Hintergrund, Strg+C beendet ihn",
+    hint_jobs_running: "{count} Hintergrundjob(s) laufen (/jobs)",
+    param_title: "Parameter",
+    param_default_hint: "Enter = Standardwert, Tab = Standardwert bearbeiten",
+    param_placeholder_hint: "Tab = mit Platzhalter beginnen",
+    param_flag_hint: "Antwort: y/n (Enter = {default})",
+    param_choice_hint: "Tippen filtert, Hoch/Runter = bewegen, Enter = wählen",
+    param_path_hint: "Tab = vervollständigen, Hoch/Runter = Vorschlag wählen",
+    param_command: "Befehl: {name}",
+    yes: "ja",
+    no: "nein",
+    usage: "{count}× ausgeführt, zuletzt vor {age}",
+    never_run: "nie ausgeführt",
+    selection_hint: "Auswahlmodus: mit der Maus markieren, Hoch/Runter/Bild↑/Bild↓ scrollen, Esc zurück",
+    projects_hint: "Hoch/Runter oder 1-9 wählen, Enter wechselt, Esc bricht ab",
+    history_hint: "Hoch/Runter wählen, Enter führt mit denselben Parametern erneut aus, e bearbeitet sie, Esc bricht ab",
+    jobs_hint: "Enter/o Ausgabe, f Vordergrund, x stoppen (erneut: beenden), Esc schließt",
+    cache_hint: "Enter/x leert den gewählten Eintrag, a leert alle, Esc schließt",
+    environment_hint: "Hoch/Runter oder 1-9 wählen, Enter führt aus",
+    dry_run_hint: "Nichts wurde ausgeführt. Enter führt aus, c kopiert die Befehlszeile, Esc schließt",
+    confirm_hint: "'yes' eingeben und Enter drücken zum Ausführen, Esc bricht ab",
+    init_diff_hint: "y = überschreiben, n/Esc = Konfiguration behalten, Hoch/Runter/Bild↑/Bild↓ = scrollen",
+    working_directory: "Arbeitsverzeichnis: {dir}",
+    environment: "Umgebung: {name}",
+    attempt: "[Versuch {attempt}/{attempts}]",
+    exit_code: "Exit-Code: {code}",
+    interrupted: "Vom Benutzer abgebrochen (Escape)",
+    interrupt_sent: "Befehl zum Beenden aufgefordert; Esc nach {seconds} s beendet ihn sofort",
+    force_killed: "Befehl zwangsweise beendet (Escape)",
+    timed_out: "Zeitlimit von {limit} überschritten",
+    execution_failed: "Ausführung fehlgeschlagen: {error}",
+    retrying: "neuer Versuch in {delay} (Esc bricht ab)",
+    retry_canceled: "Neuer Versuch vom Benutzer abgebrochen (Escape)",
+    no_matching_commands: "Keine passenden Befehle",
+    preview_title: "Vorschau",
+    preview_of: "Vorschau: {command}",
+    details_of: "Details: {name}",
+    internal_param_title: "Interner Parameter",
+    projects_title: "Letzte Projekte",
+    history_title: "Verlauf",
+    jobs_title: "Jobs",
+    cache_title: "Cache",
+    environment_title: "Umgebung",
+    dry_run_of: "Probelauf: {name}",
+    queue_of: "Warteschlange ({count})",
+    queue_hint: "Enter führt alle aus, Umschalt+Hoch/Runter oder K/J sortiert um, d entfernt, u macht rückgängig, Esc schließt (Warteschlange bleibt)",
+    confirm_title: "Bestätigen",
+    overwrite_path: "{path} überschreiben?",
+    palette_title: "Befehlspalette",
+    pager_hint: "Hoch/Runter/Bild↑/Bild↓ scrollen, Esc/q schließt",
+    full_line: "Ganze Zeile ({bytes} Bytes)",
+    no_truncated_lines: "Keine gekürzten Ausgabezeilen in der Sitzung",
+    continuing: "weiter (continue_on_error)",
+    created_working_dir: "Arbeitsverzeichnis angelegt",
+    job_finished: "[Job {id}] bereits beendet",
+    job_backgrounded: "[Job {id}] wieder im Hintergrund",
+    job_running: "Job {id} läuft",
+    status_ok: "ok",
+    status_failed: "fehlgeschlagen",
+    status_exit: "Exit {code}",
+    status_running: "läuft",
+    status_stopping: "stoppt",
+    status_killed: "beendet",
+    wrote_config: "Beispielkonfiguration geschrieben: {path}",
+    switched_to: "Gewechselt nach {dir}",
+    config_path: "Konfiguration: {path}",
+    config_merged: "Konfiguration: {path} (über {global} gelegt)",
+    config_none: "Konfiguration: keine (nur Provider oder Standardwerte)",
+    events_socket: "Ereignisse: {path}",
+    param_canceled: "Parametereingabe abgebrochen",
+    param_required: "'{name}' ist erforderlich",
+    answer_yes_no: "Bitte y oder n eingeben",
+    no_option_matches: "Keine Option passt zu '{input}'",
+    no_command_selected: "Kein Befehl ausgewählt",
+    environment_canceled: "Umgebungsauswahl abgebrochen",
+    projects_disabled: "Letzte Projekte sind deaktiviert (--no-usage oder [privacy])",
+    no_projects: "Noch keine letzten Projekte",
+    history_disabled: "Der Verlauf ist deaktiviert (--no-usage oder [privacy])",
+    no_history: "Noch kein Verlauf",
+    not_in_catalog: "'{name}' ist nicht mehr im Katalog",
+    copied: "In die Zwischenablage kopiert: {text}",
+    copy_failed: "Kopieren fehlgeschlagen: {error}",
+    run_canceled: "Ausführung abgebrochen",
+    type_yes: "'yes' eingeben zum Bestätigen oder Esc zum Abbrechen",
+    internal_canceled: "Interner Befehl abgebrochen",
+    init_failed: "init fehlgeschlagen: {error}",
+    init_canceled: "init abgebrochen, bestehende Konfiguration bleibt",
+    sort_usage: "Sortierung: nach Nutzung gewichtet",
+    sort_alphabetical: "Sortierung: alphabetisch",
+    refreshing: "Aktualisiere {names}",
+    provider_failed: "Provider '{provider}' fehlgeschlagen: {error}",
+    scheduled: "{count} Befehl(e) geplant",
+    hook_exit_code: "Hook-Exit-Code: {code}",
+    hook_failed: "Hook fehlgeschlagen: {error}",
+    start_failed: "{name} konnte nicht starten: {error}",
+    no_jobs: "Keine Hintergrundjobs; Ctrl+B startet den ausgewählten Befehl als einen",
+    nothing_cached: "Nichts im Cache; Provider-Ergebnisse werden beim Start neu geladen, /reload aktualisiert alle, F5 die des ausgewählten",
+    no_session_vars: "Keine Sitzungsvariablen; /env set KEY=value fügt eine hinzu",
+    session_vars: "Sitzungsvariablen (bei jeder Ausführung gesetzt):",
+    session_var_set: "Sitzungsvariable gesetzt: {key}={value}",
+    session_var_unset: "Sitzungsvariable entfernt: {key}",
+    no_session_var: "Keine Sitzungsvariable {key}",
+    staging_on: "Sammelmodus: ausgewählte Befehle werden eingereiht; Ctrl+Q prüft und führt sie aus",
+    staging_off: "Sammelmodus aus ({count} eingereihte(r) Befehl(e) bleiben)",
+    queue_empty: "Warteschlange ist leer; Ctrl+T startet den Sammelmodus",
+    job_foreground: "[Job {id}] im Vordergrund: Esc schickt ihn zurück, Ctrl+C stoppt ihn",
+    queue_confirm: "{count} Befehl(e) der Reihe nach ausführen und beim ersten Fehler anhalten? Enter führt aus, jede andere Taste geht zurück",
+    warn_run: "{banner} — Enter führt ihn aus, jede andere Taste bricht ab",
+    did_you_mean: "Unbekannter interner Befehl '/{name}' — meintest du {suggestion}? Enter übernimmt",
+    job_start_failed: "[Job] {name} konnte nicht starten: {error}",
+    job_started: "[Job {id}] {name} im Hintergrund gestartet (/jobs verwaltet ihn)",
+    previews_cleared: "{count} Vorschau(en) aus dem Cache entfernt (Vorschauen bleiben {seconds}s)",
+    cache_files_removed: "{count} Cache-Datei(en) entfernt; beim nächsten Laden wird das Werkzeug neu gefragt",
+    cache_remove_failed: "{path} konnte nicht entfernt werden",
+    doctor_ok: "/doctor: keine Probleme in {count} Befehl(en) gefunden",
+    doctor_problems: "/doctor: {count} Problem(e)",
+    queued: "{name} eingereiht ({count} in der Warteschlange)",
+    schedule_exit: "[Zeitplan] {name} (Exit-Code: {code})",
+    schedule_failed: "[Zeitplan] {name}: {error}",
+    worker_disconnected: "Worker für interne Befehle unerwartet getrennt",
+    loaded: "{count} Befehle geladen",
+    loaded_provider: "{count} {provider}-Befehle geladen",
+    reloaded: "{count} Befehle neu geladen",
+    age_units: [" s", " Min.", " Std.", " T."],
+    thousands_separator: '.',
+};
+
+#[cfg(test)]
+mod tests {
+    use super::{Locale, fill};
+
+    #[test]
+    fn locale_comes_from_config_or_environment() {
+        assert_eq!(Locale::from_config("de").unwrap(), Locale::De);
+        assert!(Locale::from_config("fr-ish").is_err());
+        let env = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(
+            Locale::from_env(env(&[("LANG", "de_DE.UTF-8")])),
+            Locale::De
+        );
+        assert_eq!(
+            Locale::from_env(env(&[("LC_ALL", "C"), ("LANG", "de_DE.UTF-8")])),
+            Locale::En
+        );
+        assert_eq!(
+            Locale::from_env(env(&[("LC_ALL", ""), ("LC_MESSAGES", "de_AT")])),
+            Locale::De
+        );
+    }
+
+    #[test]
+    fn counts_and_durations_follow_the_locale() {
+        assert_eq!(Locale::En.format_count(1_234_567), "1,234,567");
+        assert_eq!(Locale::De.format_count(1_234_567), "1.234.567");
+        assert_eq!(Locale::De.format_count(999), "999");
+        assert_eq!(Locale::En.format_age(300), "5m");
+        assert_eq!(Locale::De.format_age(7_200), "2 Std.");
+        assert_eq!(
+            fill(Locale::De.strings().exit_code, &[("code", "3")]),
+            "Exit-Code: 3"
+        );
+    }
+}
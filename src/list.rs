@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::app::command_provider_name;
+use crate::model::CommandEntry;
+
+/// How long `fzc list --cached` reuses the names it last listed for a directory.
+const NAMES_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    /// Aligned columns for reading
+    Human,
+    /// One JSON array of objects
+    Json,
+    /// Tab-separated: name, provider, description, template, working dir, comma-separated tags
+    Tsv,
+    /// One command name per line, for shell completions
+    Names,
+}
+
+/// `fzc list`: the resolved catalog, sorted by name.
+pub fn render(commands: &mut [CommandEntry], format: ListFormat) -> String {
+    commands.sort_by_key(|command| command.name.to_lowercase());
+    match format {
+        ListFormat::Human => render_human(commands),
+        ListFormat::Json => render_json(commands),
+        ListFormat::Tsv => render_tsv(commands),
+        ListFormat::Names => commands
+            .iter()
+            .map(|command| format!("{}\n", command.name))
+            .collect(),
+    }
+}
+
+/// Where `fzc list --format names --cached` keeps its lists, under the platform cache directory
+/// (`~/.cache/fzc/names/`).
+pub fn names_cache_dir() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("fzc").join("names"))
+}
+
+/// The cached names listed for `cwd`.
+pub fn names_cache_path(cwd: &Path) -> Option<PathBuf> {
+    Some(names_cache_dir()?.join(format!("{}.txt", crate::snapshots::project_file_name(cwd))))
+}
+
+/// The cached names at `path` while they are younger than [`NAMES_TTL`].
+pub fn cached_names(path: &Path) -> Option<String> {
+    let age = fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()?;
+    (age < NAMES_TTL)
+        .then(|| fs::read_to_string(path).ok())
+        .flatten()
+}
+
+/// Best effort: completions still work, just slower, when the cache cannot be written.
+pub fn write_names_cache(path: &Path, names: &str) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let _ = fs::write(path, names);
+}
+
+fn render_human(commands: &[CommandEntry]) -> String {
+    let provider_width = commands
+        .iter()
+        .map(|command| command_provider_name(command).len())
+        .max()
+        .unwrap_or_default();
+    let name_width = commands
+        .iter()
+        .map(|command| command.name.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    for command in commands {
+        let mut line = format!(
+            "{:<provider_width$}  {:<name_width$}  {}",
+            command_provider_name(command),
+            command.name,
+            command.description.as_deref().unwrap_or_default()
+        );
+        line.truncate(line.trim_end().len());
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn render_json(commands: &[CommandEntry]) -> String {
+    let entries: Vec<_> = commands
+        .iter()
+        .map(|command| {
+            json!({
+                "name": command.name,
+                "provider": command_provider_name(command),
+                "description": command.description,
+                "template": command.template,
+                "working_dir": command.working_dir,
+                "tags": command.tags,
+            })
+        })
+        .collect();
+    format!("{}\n", serde_json::Value::Array(entries))
+}
+
+fn render_tsv(commands: &[CommandEntry]) -> String {
+    let mut out = String::new();
+    for command in commands {
+        let working_dir = command
+            .working_dir
+            .as_ref()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let tags = command.tags.join(",");
+        let fields = [
+            command.name.as_str(),
+            command_provider_name(command),
+            command.description.as_deref().unwrap_or_default(),
+            command.template.as_str(),
+            working_dir.as_str(),
+            tags.as_str(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| tsv_field(field)).collect();
+        out.push_str(&fields.join("\t"));
+        out.push('\n');
+    }
+    out
+}
+
+/// Escapes the characters that would break a TSV row.
+fn tsv_field(raw: &str) -> String {
+    raw.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::model::{CommandEntry, CommandSource};
+
+    use super::{ListFormat, render};
+
+    fn command(name: &str, source: CommandSource) -> CommandEntry {
+        CommandEntry {
+            description: Some("runs\tthings\nfast".to_string()),
+            working_dir: Some(PathBuf::from("/srv/app")),
+            ..CommandEntry::new(name.to_string(), format!("{name} --now"), source)
+        }
+    }
+
+    #[test]
+    fn renders_catalog_as_json_and_tsv() {
+        let mut commands = vec![
+            command("zeta", CommandSource::Config),
+            CommandEntry {
+                tags: vec!["ops".to_string(), "deploy".to_string()],
+                ..command("alpha", CommandSource::Provider("npm".to_string()))
+            },
+        ];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&mut commands, ListFormat::Json)).unwrap();
+        assert_eq!(json[0]["name"], "alpha");
+        assert_eq!(json[0]["provider"], "npm");
+        assert_eq!(json[1]["provider"], "config");
+        assert_eq!(json[1]["working_dir"], "/srv/app");
+        assert_eq!(json[0]["tags"], serde_json::json!(["ops", "deploy"]));
+        assert_eq!(json[1]["tags"], serde_json::json!([]));
+
+        let tsv = render(&mut commands, ListFormat::Tsv);
+        assert_eq!(
+            tsv.lines().next().unwrap(),
+            "alpha\tnpm\truns\\tthings\\nfast\talpha --now\t/srv/app\tops,deploy"
+        );
+        assert_eq!(tsv.lines().count(), 2);
+
+        assert_eq!(render(&mut commands, ListFormat::Names), "alpha\nzeta\n");
+    }
+}
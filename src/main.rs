@@ -1,58 +1,534 @@
 mod app;
+mod bench;
+mod completions;
 mod config;
+mod doctor;
+mod events;
+mod favorites;
+mod history;
+mod i18n;
+mod list;
 mod model;
+mod pick;
 mod provider;
+#[cfg(unix)]
+mod pty;
+mod redact;
+mod secrets;
+mod snapshots;
+mod theme;
 
 use std::env;
 use std::path::PathBuf;
 use std::process;
+use std::time::Duration;
 
-use anyhow::Result;
-use clap::Parser;
-use model::CommandCatalog;
+use anyhow::{Context, Error, anyhow};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
+use model::{CommandCatalog, CommandGuard, CommandHooks};
 
 #[derive(Debug, Parser)]
 #[command(name = "fzc", version, about = "Fuzzy terminal command launcher")]
 struct Cli {
     /// Override config path. If omitted, fzc checks ./fzc.toml, ./.fzc.toml, and then ~/.config/fzc/config.toml
-    #[arg(short, long)]
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
     config: Option<PathBuf>,
+
+    /// Do not read or write usage counts and recent projects for this session
+    #[arg(long)]
+    no_usage: bool,
+
+    /// Start even when launched from a command running inside another fzc session
+    #[arg(long)]
+    allow_nested: bool,
+
+    /// Draw the picker below the prompt instead of on the alternate screen
+    #[arg(long)]
+    inline: bool,
+
+    /// How fatal errors are written to stderr
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    errors: ErrorFormat,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
 }
 
-fn main() {
-    if let Err(err) = run() {
-        eprintln!("error: {err:#}");
-        process::exit(1);
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Report catalog load times per provider and match latency for sample queries
+    Bench {
+        /// Query to measure (repeatable); defaults to a built-in sample set
+        #[arg(short, long = "query")]
+        queries: Vec<String>,
+
+        /// Searches per query used for the average
+        #[arg(long, default_value_t = 50)]
+        iterations: usize,
+    },
+    /// Check which providers detect a project, find their programs, and load; exits 1 on problems
+    Doctor {
+        /// Print a machine-readable JSON report
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run a catalog command by name without opening the TUI
+    Run {
+        /// Command name, or `provider::name` when several providers share it
+        name: String,
+
+        /// Param value (repeatable); missing params use defaults or are asked for on a terminal
+        #[arg(short, long = "param", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        params: Vec<(String, String)>,
+
+        /// Environment to use for commands that define `[commands.environments]`
+        #[arg(short, long)]
+        environment: Option<String>,
+
+        /// Run commands matching `[safety] confirm` patterns without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Print the resolved command catalog, e.g. to pipe into fzf, rofi, or CI tooling
+    List {
+        #[arg(long, value_enum, default_value_t = list::ListFormat::Human)]
+        format: list::ListFormat,
+
+        /// Reuse the list printed for this directory in the last few minutes instead of
+        /// loading providers (used by completion scripts)
+        #[arg(long, hide = true)]
+        cached: bool,
+    },
+    /// Print a completion script; `fzc run` completes catalog names via `fzc list`
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    /// Pick one line of a file in a minimal one-pane picker and print it, e.g. in scripts and git hooks
+    Pick {
+        /// Lines to choose from; `-` reads them from stdin
+        #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+        from_file: PathBuf,
+
+        /// Print the 0-based line index instead of the line
+        #[arg(long)]
+        print_index: bool,
+    },
+    /// Export or import pinned commands to carry them between machines
+    Favorites {
+        #[command(subcommand)]
+        action: FavoritesAction,
+    },
+    /// Record the project's catalog or compare it with an earlier snapshot
+    Catalog {
+        #[command(subcommand)]
+        action: CatalogAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CatalogAction {
+    /// Save the current catalog if it changed since the last snapshot
+    Snapshot,
+    /// Show commands added, removed, or changed since an earlier snapshot
+    Diff {
+        /// Compare with the catalog as of this long ago (e.g. `7d`, `12h`) instead of the
+        /// previous snapshot
+        #[arg(long, value_name = "AGE")]
+        since: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum FavoritesAction {
+    /// Write pinned commands as TOML to stdout or a file
+    Export {
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+    /// Add pinned commands from an exported file
+    Import {
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+
+        /// Replace the current pins instead of merging
+        #[arg(long)]
+        replace: bool,
+    },
+}
+
+fn parse_key_value(raw: &str) -> Result<(String, String), String> {
+    match raw.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got '{raw}'")),
     }
 }
 
-fn run() -> Result<()> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+/// Exit codes: 1 runtime error, 2 config error, 3 provider failure, 130 user cancel;
+/// a command run on exit passes its own code through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorKind {
+    Runtime,
+    Config,
+    Provider,
+}
+
+impl ErrorKind {
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::Config => "config",
+            ErrorKind::Provider => "provider",
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Runtime => 1,
+            ErrorKind::Config => 2,
+            ErrorKind::Provider => 3,
+        }
+    }
+}
+
+struct Failure {
+    kind: ErrorKind,
+    error: Error,
+}
+
+impl Failure {
+    fn runtime(error: Error) -> Self {
+        Self {
+            kind: ErrorKind::Runtime,
+            error,
+        }
+    }
+
+    fn config(error: Error) -> Self {
+        Self {
+            kind: ErrorKind::Config,
+            error,
+        }
+    }
+
+    fn provider(error: Error) -> Self {
+        Self {
+            kind: ErrorKind::Provider,
+            error,
+        }
+    }
+
+    fn render(&self, format: ErrorFormat) -> String {
+        match format {
+            ErrorFormat::Text => format!("error: {:#}", self.error),
+            ErrorFormat::Json => serde_json::json!({
+                "error": {
+                    "kind": self.kind.name(),
+                    "code": self.kind.exit_code(),
+                    "message": format!("{:#}", self.error),
+                }
+            })
+            .to_string(),
+        }
+    }
+}
+
+const CANCELED_EXIT_CODE: i32 = 130;
+
+fn main() {
     let cli = Cli::parse();
+    let errors = cli.errors;
+    match run(cli) {
+        Ok(code) => process::exit(code),
+        Err(failure) => {
+            eprintln!("{}", failure.render(errors));
+            process::exit(failure.kind.exit_code());
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<i32, Failure> {
+    if let Some(CliCommand::Completions { shell }) = cli.command {
+        print!("{}", completions::render(&Cli::command(), shell));
+        return Ok(0);
+    }
+    if let Some(CliCommand::Pick {
+        from_file,
+        print_index,
+    }) = &cli.command
+    {
+        return pick::run(from_file, *print_index).map_err(Failure::runtime);
+    }
+    if let Some(CliCommand::Favorites { action }) = &cli.command {
+        return run_favorites(action).map_err(Failure::runtime);
+    }
     let explicit_config = cli.config.clone();
-    let cwd = env::current_dir()?;
-    let loaded = config::load(&cwd, explicit_config.as_deref())?;
-    let provider_aliases = loaded.config.providers.alias_map()?;
+    let cwd = env::current_dir().map_err(|err| Failure::runtime(err.into()))?;
+    if cli.command.is_none()
+        && !cli.allow_nested
+        && let Ok(pid) = env::var(app::SESSION_ENV)
+    {
+        return Err(Failure::runtime(anyhow!(
+            "already running inside fzc (pid {pid}); pass --allow-nested to start anyway"
+        )));
+    }
+    let loaded = config::load(&cwd, explicit_config.as_deref()).map_err(Failure::config)?;
+    let provider_aliases = loaded
+        .config
+        .providers
+        .alias_map()
+        .map_err(Failure::config)?;
+    let guard = CommandGuard::from_config(&loaded.config.safety).map_err(Failure::config)?;
+    let provider_hints = provider::disabled_provider_hints(&loaded.config.providers, &cwd);
+    let ranking = app::RankingSettings {
+        usage_enabled: loaded.config.ranking.usage_enabled,
+        usage_weight: loaded.config.ranking.usage_weight,
+        usage_scale: loaded.config.ranking.usage_scale,
+        usage_cap: loaded.config.ranking.usage_cap,
+        decay_half_life_days: loaded.config.ranking.decay_half_life_days,
+    };
+    let runtime = app::RuntimeContext {
+        cwd: cwd.clone(),
+        explicit_config_path: explicit_config,
+        global_config_path: loaded.global.clone(),
+        provider_hints,
+        persist_usage: !cli.no_usage && loaded.config.privacy.persist_usage,
+        color_support: app::ColorSupport::detect(),
+        providers: loaded.config.providers.clone(),
+        inline: cli.inline,
+        events_socket: loaded
+            .config
+            .events
+            .socket
+            .as_deref()
+            .and_then(events::socket_path),
+        job_panes: loaded.config.ui.job_panes,
+        mouse: loaded.config.ui.mouse,
+        secrets: loaded.config.secrets.clone(),
+        hooks: CommandHooks::from_config(
+            loaded.config.hooks.on_success.as_deref(),
+            loaded.config.hooks.on_failure.as_deref(),
+        ),
+        locale: i18n::Locale::from_config(&loaded.config.ui.locale).map_err(Failure::config)?,
+        theme: theme::Theme::from_config(&loaded.config.theme).map_err(Failure::config)?,
+        redactor: redact::Redactor::from_config(loaded.config.privacy.redact.as_deref())
+            .map_err(Failure::config)?,
+        poll: app::PollSettings {
+            interval: Duration::from_millis(loaded.config.ui.poll_interval_ms.max(1)),
+            idle_interval: Duration::from_millis(loaded.config.ui.idle_poll_interval_ms.max(1)),
+        },
+    };
+
+    if let Some(CliCommand::Bench {
+        queries,
+        iterations,
+    }) = &cli.command
+    {
+        let config_commands =
+            bench::load_config_commands(&loaded, &cwd).map_err(Failure::config)?;
+        bench::run(
+            &loaded,
+            config_commands,
+            provider_aliases,
+            ranking,
+            runtime,
+            queries,
+            *iterations,
+        )
+        .map_err(Failure::provider)?;
+        return Ok(0);
+    }
+    if let Some(CliCommand::Doctor { json }) = &cli.command {
+        return doctor::run(&loaded, &runtime.cwd, *json).map_err(Failure::runtime);
+    }
+
+    let output = app::OutputSettings {
+        max_line_bytes: loaded.config.output.max_line_bytes,
+        pretty_json: loaded.config.output.pretty_json,
+        color: loaded.config.output.color,
+        pty: loaded.config.output.pty,
+        timeout: loaded
+            .config
+            .output
+            .timeout
+            .as_deref()
+            .and_then(|raw| config::parse_timeout(raw).ok()),
+        target: loaded.config.output.target,
+        terminal: loaded.config.output.terminal.clone(),
+    };
 
     let mut catalog = CommandCatalog::empty();
     if loaded.config.providers.config.enabled {
-        catalog.extend(CommandCatalog::from_config(&loaded, &cwd)?.into_vec());
+        catalog.extend(
+            CommandCatalog::from_config(&loaded, &cwd)
+                .map_err(Failure::config)?
+                .into_vec(),
+        );
+    }
+
+    match cli.command {
+        Some(CliCommand::Run {
+            name,
+            params,
+            environment,
+            yes,
+        }) => {
+            catalog.extend(
+                provider::load_provider_commands(&loaded.config.providers, &cwd)
+                    .map_err(Failure::provider)?,
+            );
+            let run = app::HeadlessRun {
+                name,
+                params,
+                environment,
+                assume_yes: yes,
+            };
+            return app::run_headless(catalog.into_vec(), ranking, output, guard, runtime, run)
+                .map_err(Failure::runtime);
+        }
+        Some(CliCommand::List { format, cached }) => {
+            let cache = (cached && format == list::ListFormat::Names)
+                .then(|| list::names_cache_path(&cwd))
+                .flatten();
+            if let Some(names) = cache.as_deref().and_then(list::cached_names) {
+                print!("{names}");
+                return Ok(0);
+            }
+            catalog.extend(
+                provider::load_provider_commands(&loaded.config.providers, &cwd)
+                    .map_err(Failure::provider)?,
+            );
+            let rendered = list::render(&mut catalog.into_vec(), format);
+            if let Some(cache) = &cache {
+                list::write_names_cache(cache, &rendered);
+            }
+            print!("{rendered}");
+            return Ok(0);
+        }
+        Some(CliCommand::Catalog { action }) => {
+            catalog.extend(
+                provider::load_provider_commands(&loaded.config.providers, &cwd)
+                    .map_err(Failure::provider)?,
+            );
+            return run_catalog(&action, &catalog.into_vec(), &runtime).map_err(Failure::runtime);
+        }
+        _ => {}
     }
-    catalog.extend(provider::load_provider_commands(
-        &loaded.config.providers,
-        &cwd,
-    )?);
 
     app::run_tui(
         catalog.into_vec(),
         loaded.path.as_deref(),
         provider_aliases,
-        app::RankingSettings {
-            usage_enabled: loaded.config.ranking.usage_enabled,
-            usage_weight: loaded.config.ranking.usage_weight,
-        },
-        app::RuntimeContext {
-            cwd,
-            explicit_config_path: explicit_config,
-        },
+        ranking,
+        output,
+        guard,
+        runtime,
     )
+    .map(|exit| match exit {
+        app::SessionExit::Canceled => CANCELED_EXIT_CODE,
+        app::SessionExit::Executed(code) => code,
+    })
+    .map_err(Failure::runtime)
+}
+
+fn run_favorites(action: &FavoritesAction) -> anyhow::Result<i32> {
+    let path = favorites::favorites_path().context("unable to resolve OS config directory")?;
+    match action {
+        FavoritesAction::Export { output } => {
+            let exported = favorites::export(&favorites::load(&path))?;
+            match output {
+                Some(output) => std::fs::write(output, exported)
+                    .with_context(|| format!("failed to write {}", output.display()))?,
+                None => print!("{exported}"),
+            }
+        }
+        FavoritesAction::Import { file, replace } => {
+            let raw = std::fs::read_to_string(file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            let added = favorites::import(&path, &raw, *replace)
+                .with_context(|| format!("failed to import {}", file.display()))?;
+            println!(
+                "Imported {added} new pin(s); {} pinned in total",
+                favorites::load(&path).len()
+            );
+        }
+    }
+    Ok(0)
+}
+
+fn run_catalog(
+    action: &CatalogAction,
+    commands: &[model::CommandEntry],
+    runtime: &app::RuntimeContext,
+) -> anyhow::Result<i32> {
+    let path =
+        snapshots::snapshots_path(&runtime.cwd).context("unable to resolve OS config directory")?;
+    let now = history::now();
+    let current = snapshots::CatalogSnapshot::new(commands, &runtime.providers.lazy, now);
+    match action {
+        CatalogAction::Snapshot => {
+            if !runtime.persist_usage {
+                anyhow::bail!("catalog snapshots are disabled (--no-usage or [privacy])");
+            }
+            let count = current.commands.len();
+            if snapshots::record(&path, current)? {
+                println!("Recorded {count} command(s) in {}", path.display());
+            } else {
+                println!("Catalog unchanged since the last snapshot");
+            }
+        }
+        CatalogAction::Diff { since } => {
+            let age = since
+                .as_deref()
+                .map(config::parse_duration)
+                .transpose()?
+                .map(|age| age.as_secs());
+            let recorded = snapshots::load(&path);
+            let Some(baseline) = snapshots::baseline(&recorded, &current, age, now) else {
+                anyhow::bail!(match since {
+                    Some(since) => format!("no catalog snapshot from at least {since} ago"),
+                    None => "no earlier catalog snapshot that differs from the current one \
+                             (record one with `fzc catalog snapshot`)"
+                        .to_string(),
+                });
+            };
+            print!("{}", snapshots::render_diff(baseline, &current, now));
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::{ErrorFormat, Failure};
+
+    #[test]
+    fn renders_failures_as_text_or_json() {
+        let failure = Failure::config(anyhow!("bad toml").context("failed to parse config"));
+        assert_eq!(failure.kind.exit_code(), 2);
+        assert_eq!(
+            failure.render(ErrorFormat::Text),
+            "error: failed to parse config: bad toml"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&failure.render(ErrorFormat::Json)).unwrap();
+        assert_eq!(json["error"]["kind"], "config");
+        assert_eq!(json["error"]["code"], 2);
+        assert_eq!(json["error"]["message"], "failed to parse config: bad toml");
+        assert_eq!(
+            Failure::provider(anyhow!("just failed")).kind.exit_code(),
+            3
+        );
+    }
 }
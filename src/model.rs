@@ -1,23 +1,34 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
-use globset::Glob;
+use globset::{Glob, GlobBuilder, GlobMatcher};
 
 use crate::config::{
-    CommandConfig, LoadedConfig, ParamConfig, ParamLiteralConfig, ParamTypeConfig,
+    ColorMode, CommandConfig, CommandKind, EnvironmentConfig, ExecTarget, LoadedConfig,
+    ParamConfig, ParamLiteralConfig, ParamTypeConfig, PipelineConfig, SafetyConfig, parse_duration,
+    parse_schedule, parse_timeout,
 };
+use crate::provider::shell_escape_arg;
 
 #[derive(Debug, Clone)]
 pub enum CommandSource {
     Config,
-    Provider(&'static str),
+    Provider(String),
+    /// Stands in for a lazy provider's commands until its filter loads them.
+    Lazy(String),
 }
 
 #[derive(Debug, Clone)]
 pub enum ParamType {
     Value,
     Flag,
+    /// A value restricted to these options.
+    Choice(Vec<String>),
+    /// A file or directory path; files are limited to the optional file-name glob.
+    Path(Option<String>),
 }
 
 #[derive(Debug, Clone)]
@@ -26,18 +37,26 @@ pub struct ParamSpec {
     pub kind: ParamType,
     pub prompt: String,
     pub placeholder: Option<String>,
+    /// Longer help shown under the prompt; may span several lines.
+    pub description: Option<String>,
     pub default_value: Option<String>,
     pub value_value: Option<String>,
     pub default_flag: Option<bool>,
     pub value_flag: Option<bool>,
     pub required: bool,
     pub prompt_in_tui: bool,
+    /// Heading shown above the prompt, e.g. "Database".
+    pub group: Option<String>,
+    /// Name of a flag param; this param is skipped (and left empty) when that flag is off.
+    pub when: Option<String>,
+    /// The value is a shell expression, expanded locally before it is substituted.
+    pub eval: bool,
 }
 
 impl ParamSpec {
     pub fn requires_input(&self) -> bool {
         match self.kind {
-            ParamType::Value => {
+            ParamType::Value | ParamType::Choice(_) | ParamType::Path(_) => {
                 self.value_value.is_none()
                     && (self.prompt_in_tui || self.required || self.default_value.is_none())
             }
@@ -63,6 +82,135 @@ pub struct CommandEntry {
     pub params: Vec<ParamSpec>,
     pub source: CommandSource,
     pub working_dir: Option<PathBuf>,
+    /// Create a missing `working_dir` before running instead of refusing to.
+    pub create_working_dir: bool,
+    pub environments: Vec<EnvironmentProfile>,
+    /// Env vars set on every run; values may use `{{param}}` placeholders.
+    pub env: Vec<(String, String)>,
+    /// Substitute `{{name}}` values shell-quoted, for templates that place them as whole or
+    /// partial arguments rather than as shell code.
+    pub quote_params: bool,
+    pub host: Option<String>,
+    /// Overrides the global `[output] color` mode for this command.
+    pub color: Option<ColorMode>,
+    /// Overrides the global `[output] pty` setting for this command.
+    pub pty: Option<bool>,
+    /// Always start as a background job.
+    pub background: bool,
+    /// Always hand the terminal over (as with Option+Enter), for interactive programs.
+    pub inherit: bool,
+    /// Overrides `[output] target` (tmux window, pane, or terminal tab) for this command.
+    pub target: Option<ExecTarget>,
+    /// Interval for background re-runs while fzc is open.
+    pub schedule: Option<Duration>,
+    /// Template run to fill the preview pane while this command is selected.
+    pub preview: Option<String>,
+    /// Shown after the provider in the badge, e.g. an npm workspace package.
+    pub badge: Option<String>,
+    /// Front-matter `@tags`; the TUI shows (and searches) them at the end of the description.
+    pub tags: Vec<String>,
+    pub hooks: CommandHooks,
+    pub retry: RetryPolicy,
+    /// Stop the command once it has run this long; falls back to `[output] timeout`.
+    pub timeout: Option<Duration>,
+    /// Catalog commands run after this one exits 0, in order.
+    pub then: Vec<String>,
+    /// Set for `[[pipelines]]` entries, which run these commands instead of a template.
+    pub steps: Vec<PipelineStep>,
+}
+
+impl CommandEntry {
+    /// A plain command with every optional setting left at its default.
+    pub fn new(
+        name: impl Into<String>,
+        template: impl Into<String>,
+        source: CommandSource,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            template: template.into(),
+            params: Vec::new(),
+            source,
+            working_dir: None,
+            create_working_dir: false,
+            environments: Vec::new(),
+            env: Vec::new(),
+            quote_params: false,
+            host: None,
+            color: None,
+            pty: None,
+            background: false,
+            inherit: false,
+            target: None,
+            schedule: None,
+            preview: None,
+            tags: Vec::new(),
+            badge: None,
+            hooks: CommandHooks::default(),
+            retry: RetryPolicy::default(),
+            timeout: None,
+            then: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PipelineStep {
+    pub command: String,
+    pub continue_on_error: bool,
+}
+
+/// How often a command is re-run after a non-zero exit, and how long to wait in between.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+/// Shell commands run after a command finishes; `{{exit_code}}`, `{{duration}}`, and `{{name}}`
+/// are filled in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandHooks {
+    pub on_success: Option<String>,
+    pub on_failure: Option<String>,
+}
+
+impl CommandHooks {
+    pub fn from_config(on_success: Option<&str>, on_failure: Option<&str>) -> Self {
+        let clean = |hook: Option<&str>| {
+            hook.map(str::trim)
+                .filter(|hook| !hook.is_empty())
+                .map(ToString::to_string)
+        };
+        Self {
+            on_success: clean(on_success),
+            on_failure: clean(on_failure),
+        }
+    }
+
+    /// These hooks, with unset ones taken from `fallback`.
+    pub fn or(&self, fallback: &CommandHooks) -> CommandHooks {
+        CommandHooks {
+            on_success: self
+                .on_success
+                .clone()
+                .or_else(|| fallback.on_success.clone()),
+            on_failure: self
+                .on_failure
+                .clone()
+                .or_else(|| fallback.on_failure.clone()),
+        }
+    }
+}
+
+/// Named environment (e.g. dev/staging/prod) picked right before a command runs.
+#[derive(Debug, Clone)]
+pub struct EnvironmentProfile {
+    pub name: String,
+    pub env: Vec<(String, String)>,
+    pub vars: HashMap<String, String>,
 }
 
 pub struct CommandCatalog {
@@ -84,6 +232,7 @@ impl CommandCatalog {
             }
             commands.push(command_from_config(command, cwd));
         }
+        commands.extend(loaded.config.pipelines.iter().map(pipeline_from_config));
         Ok(Self { commands })
     }
 
@@ -96,6 +245,119 @@ impl CommandCatalog {
     }
 }
 
+/// Deny/confirm patterns checked against every rendered command line before it runs.
+#[derive(Debug, Clone, Default)]
+pub struct CommandGuard {
+    deny: Vec<(String, GlobMatcher)>,
+    confirm: Vec<(String, GlobMatcher)>,
+    warn_root: bool,
+    production: Vec<(String, GlobMatcher)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuardVerdict {
+    Allow,
+    /// Runs after a banner with this reason and one more keypress.
+    Warn(String),
+    Confirm(String),
+    Deny(String),
+}
+
+impl CommandGuard {
+    pub fn from_config(config: &SafetyConfig) -> Result<Self> {
+        Ok(Self {
+            deny: compile_guard_patterns(&config.deny)?,
+            confirm: compile_guard_patterns(&config.confirm)?,
+            warn_root: config.warn_root,
+            production: compile_guard_patterns(&config.production)?,
+        })
+    }
+
+    /// Why a run deserves a banner: it uses `sudo`/`doas`, or its environment or host (without
+    /// `user@`) matches a `production` pattern.
+    pub fn warning(
+        &self,
+        command_line: &str,
+        environment: Option<&str>,
+        host: Option<&str>,
+    ) -> Option<String> {
+        if self.warn_root
+            && let Some(tool) = command_line
+                .split(|ch: char| ch.is_whitespace() || matches!(ch, ';' | '&' | '|' | '(' | ')'))
+                .find(|word| matches!(*word, "sudo" | "doas"))
+        {
+            return Some(format!("runs as root ({tool})"));
+        }
+        let production = |name: &str| {
+            self.production
+                .iter()
+                .any(|(_, matcher)| matcher.is_match(name))
+        };
+        if let Some(environment) = environment.filter(|name| production(name)) {
+            return Some(format!("targets production environment '{environment}'"));
+        }
+        let host = host.map(|host| host.rsplit_once('@').map_or(host, |(_, name)| name));
+        host.filter(|name| production(name))
+            .map(|host| format!("targets production host '{host}'"))
+    }
+
+    /// Deny beats confirm, which beats a warning (see [`CommandGuard::warning`]); runs that
+    /// cannot show a banner or ask, like schedules and previews, should only go ahead on `Allow`.
+    pub fn check(
+        &self,
+        command_line: &str,
+        environment: Option<&str>,
+        host: Option<&str>,
+    ) -> GuardVerdict {
+        let candidates = guard_candidates(command_line);
+        let matches = |matcher: &GlobMatcher| candidates.iter().any(|text| matcher.is_match(text));
+        if let Some((pattern, _)) = self.deny.iter().find(|(_, matcher)| matches(matcher)) {
+            return GuardVerdict::Deny(pattern.clone());
+        }
+        if let Some((pattern, _)) = self.confirm.iter().find(|(_, matcher)| matches(matcher)) {
+            return GuardVerdict::Confirm(pattern.clone());
+        }
+        match self.warning(command_line, environment, host) {
+            Some(reason) => GuardVerdict::Warn(reason),
+            None => GuardVerdict::Allow,
+        }
+    }
+}
+
+/// What a safety pattern is matched against: the whole line, and each command chained by `;`,
+/// `&`, or `|` without a trailing `#` comment, from its first word and from every later one, so
+/// `rm -rf /` also catches `sudo rm -rf /` and `cd / && rm -rf / # x` but not `rm -rf /tmp`.
+fn guard_candidates(command_line: &str) -> Vec<&str> {
+    let mut candidates = vec![command_line.trim()];
+    for segment in command_line.split([';', '&', '|', '\n']) {
+        let segment = segment
+            .split_once(" #")
+            .map_or(segment, |(command, _)| command)
+            .trim();
+        let mut previous = ' ';
+        for (index, ch) in segment.char_indices() {
+            if previous.is_whitespace() && !ch.is_whitespace() {
+                candidates.push(&segment[index..]);
+            }
+            previous = ch;
+        }
+    }
+    candidates
+}
+
+fn compile_guard_patterns(patterns: &[String]) -> Result<Vec<(String, GlobMatcher)>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            let glob = GlobBuilder::new(pattern.trim())
+                .case_insensitive(true)
+                .build()
+                .with_context(|| format!("invalid safety pattern: {pattern}"))?;
+            Ok((pattern.clone(), glob.compile_matcher()))
+        })
+        .collect()
+}
+
 pub fn render_template(template: &str, params: &HashMap<String, String>) -> String {
     let mut output = template.to_owned();
     for (key, value) in params {
@@ -105,7 +367,97 @@ pub fn render_template(template: &str, params: &HashMap<String, String>) -> Stri
     output
 }
 
-fn command_from_config(command: &CommandConfig, cwd: &Path) -> CommandEntry {
+/// Fills `{{cwd}}`, `{{git_branch}}`, `{{git_root}}`, `{{date}}` (UTC, `YYYY-MM-DD`), and
+/// `{{project_name}}` when `template` uses them and no param of that name is set. Git values
+/// stay unset outside a repository, so the run reports them as unresolved.
+pub fn insert_context_values(template: &str, dir: &Path, values: &mut HashMap<String, String>) {
+    let wanted =
+        |name: &str| !values.contains_key(name) && template.contains(&format!("{{{{{name}}}}}"));
+    let git_root = (wanted("git_root") || wanted("project_name"))
+        .then(|| git_output(dir, &["rev-parse", "--show-toplevel"]))
+        .flatten();
+    let mut context = Vec::new();
+    if wanted("cwd") {
+        context.push(("cwd", Some(dir.display().to_string())));
+    }
+    if wanted("git_branch") {
+        context.push((
+            "git_branch",
+            git_output(dir, &["rev-parse", "--abbrev-ref", "HEAD"]),
+        ));
+    }
+    if wanted("date") {
+        context.push(("date", Some(utc_date(SystemTime::now()))));
+    }
+    if wanted("project_name") {
+        let root = git_root.as_deref().map(Path::new).unwrap_or(dir);
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        context.push(("project_name", name));
+    }
+    if wanted("git_root") {
+        context.push(("git_root", git_root));
+    }
+    for (name, value) in context {
+        if let Some(value) = value {
+            values.insert(name.to_string(), value);
+        }
+    }
+}
+
+/// Trimmed stdout of `git -C dir args`; `None` when git fails or prints nothing.
+pub fn git_output(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// Civil date from the days since 1970-01-01 (Howard Hinnant's `civil_from_days`).
+fn utc_date(now: SystemTime) -> String {
+    let days = now
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or_default() as i64;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Wraps a rendered command so it runs on `host` via ssh. Env vars are exported
+/// inside the remote shell because the local ssh process would not forward them.
+pub fn remote_command_line(host: &str, command: &str, env: &[(String, String)]) -> String {
+    let mut script = String::new();
+    if !env.is_empty() {
+        let assignments: Vec<String> = env
+            .iter()
+            .map(|(key, value)| format!("{key}={}", shell_escape_arg(value)))
+            .collect();
+        script.push_str(&format!("export {}; ", assignments.join(" ")));
+    }
+    script.push_str(command);
+    format!(
+        "ssh {} {}",
+        shell_escape_arg(host),
+        shell_escape_arg(&script)
+    )
+}
+
+pub fn command_from_config(command: &CommandConfig, cwd: &Path) -> CommandEntry {
     let working_dir = command.working_dir.as_ref().map(|raw| {
         let path = PathBuf::from(raw);
         if path.is_absolute() {
@@ -116,12 +468,166 @@ fn command_from_config(command: &CommandConfig, cwd: &Path) -> CommandEntry {
     });
 
     CommandEntry {
-        name: command.name.clone(),
         description: command.description.clone(),
-        template: command.run.clone(),
         params: command.params.iter().map(param_from_config).collect(),
-        source: CommandSource::Config,
         working_dir,
+        create_working_dir: command.create_working_dir,
+        environments: command
+            .environments
+            .iter()
+            .map(|(name, environment)| environment_from_config(name, environment))
+            .collect(),
+        env: command
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+        quote_params: command.kind == CommandKind::Http,
+        host: command
+            .host
+            .as_deref()
+            .map(str::trim)
+            .filter(|host| !host.is_empty())
+            .map(ToString::to_string),
+        color: command.color,
+        pty: command.pty,
+        background: command.background,
+        inherit: command.inherit,
+        target: command.target,
+        schedule: command
+            .schedule
+            .as_deref()
+            .and_then(|raw| parse_schedule(raw).ok()),
+        preview: command
+            .preview
+            .as_deref()
+            .map(str::trim)
+            .filter(|preview| !preview.is_empty())
+            .map(ToString::to_string),
+        hooks: CommandHooks::from_config(
+            command.on_success.as_deref(),
+            command.on_failure.as_deref(),
+        ),
+        retry: RetryPolicy {
+            retries: command.retries,
+            delay: command
+                .retry_delay
+                .as_deref()
+                .and_then(|raw| parse_duration(raw).ok())
+                .unwrap_or_default(),
+        },
+        timeout: command
+            .timeout
+            .as_deref()
+            .and_then(|raw| parse_timeout(raw).ok()),
+        then: command.then.clone(),
+        ..CommandEntry::new(
+            command.name.clone(),
+            match command.kind {
+                CommandKind::Shell => command.run.clone(),
+                CommandKind::Http => http_command_line(command),
+            },
+            CommandSource::Config,
+        )
+    }
+}
+
+/// A pipeline entry; its template only lists the steps for the details pane and search.
+pub fn pipeline_from_config(pipeline: &PipelineConfig) -> CommandEntry {
+    let names: Vec<&str> = pipeline
+        .steps
+        .iter()
+        .map(|step| step.command.as_str())
+        .collect();
+    CommandEntry {
+        description: pipeline.description.clone(),
+        badge: Some("pipeline".to_string()),
+        steps: pipeline
+            .steps
+            .iter()
+            .map(|step| PipelineStep {
+                command: step.command.clone(),
+                continue_on_error: step.continue_on_error,
+            })
+            .collect(),
+        ..CommandEntry::new(
+            pipeline.name.clone(),
+            names.join(" → "),
+            CommandSource::Config,
+        )
+    }
+}
+
+/// Renders an http command as a curl invocation. `{{name}}` placeholders are kept outside the
+/// quoting so their values can be substituted shell-quoted (see `CommandEntry::quote_params`).
+fn http_command_line(command: &CommandConfig) -> String {
+    let method = command
+        .method
+        .as_deref()
+        .map(str::trim)
+        .filter(|method| !method.is_empty())
+        .unwrap_or("GET")
+        .to_ascii_uppercase();
+    let mut pieces = vec![
+        "curl".to_string(),
+        "-sS".to_string(),
+        "--fail-with-body".to_string(),
+        "-X".to_string(),
+        shell_escape_arg(&method),
+    ];
+    for (name, value) in &command.headers {
+        pieces.push("-H".to_string());
+        pieces.push(quote_template_arg(&format!("{name}: {value}")));
+    }
+    if let Some(body) = &command.body {
+        pieces.push("--data-raw".to_string());
+        pieces.push(quote_template_arg(body));
+    }
+    pieces.push(quote_template_arg(
+        command.url.as_deref().unwrap_or_default(),
+    ));
+    pieces.join(" ")
+}
+
+/// Shell-quotes the text around `{{...}}` placeholders in one argument; `{{secret:...}}` is
+/// double-quoted so the variable it becomes expands as one word.
+fn quote_template_arg(arg: &str) -> String {
+    let mut quoted = String::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        if start > 0 {
+            quoted.push_str(&shell_escape_arg(&rest[..start]));
+        }
+        let placeholder = &rest[start..start + len + 2];
+        if placeholder.starts_with("{{secret:") {
+            quoted.push_str(&format!("\"{placeholder}\""));
+        } else {
+            quoted.push_str(placeholder);
+        }
+        rest = &rest[start + len + 2..];
+    }
+    if !rest.is_empty() || quoted.is_empty() {
+        quoted.push_str(&shell_escape_arg(rest));
+    }
+    quoted
+}
+
+fn environment_from_config(name: &str, environment: &EnvironmentConfig) -> EnvironmentProfile {
+    EnvironmentProfile {
+        name: name.to_string(),
+        env: environment
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+        vars: environment
+            .vars
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
     }
 }
 
@@ -129,6 +635,8 @@ fn param_from_config(param: &ParamConfig) -> ParamSpec {
     let kind = match param.r#type {
         ParamTypeConfig::Value => ParamType::Value,
         ParamTypeConfig::Flag => ParamType::Flag,
+        ParamTypeConfig::Choice => ParamType::Choice(param.options.clone()),
+        ParamTypeConfig::Path => ParamType::Path(param.glob.clone()),
     };
 
     let default_value = literal_as_string(param.default.as_ref());
@@ -136,7 +644,9 @@ fn param_from_config(param: &ParamConfig) -> ParamSpec {
     let default_flag = literal_as_bool(param.default.as_ref());
     let value_flag = literal_as_bool(param.value.as_ref());
     let default_prompt = match kind {
-        ParamType::Value => format!("{}:", param.name),
+        ParamType::Value | ParamType::Choice(_) | ParamType::Path(_) => {
+            format!("{}:", param.name)
+        }
         ParamType::Flag => {
             let token = if param.name.starts_with('-') {
                 param.name.clone()
@@ -152,12 +662,16 @@ fn param_from_config(param: &ParamConfig) -> ParamSpec {
         prompt: param.prompt.clone().unwrap_or(default_prompt),
         kind,
         placeholder: param.placeholder.clone(),
+        description: param.description.clone(),
         default_value,
         value_value,
         default_flag,
         value_flag,
         required: param.required,
         prompt_in_tui: param.prompt.is_some(),
+        group: param.group.clone(),
+        when: param.when.clone(),
+        eval: param.eval,
     }
 }
 
@@ -268,6 +782,56 @@ mod tests {
     use std::fs;
     use std::time::{SystemTime, UNIX_EPOCH};
 
+    #[test]
+    fn context_placeholders_fill_only_used_and_unset_names() {
+        let dir = std::env::temp_dir().join(format!("fzc-context-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut values = HashMap::from([("date".to_string(), "yesterday".to_string())]);
+        insert_context_values("tar czf {{date}}.tgz {{cwd}}", &dir, &mut values);
+
+        assert_eq!(values["date"], "yesterday");
+        assert_eq!(values["cwd"], dir.display().to_string());
+        assert!(!values.contains_key("git_branch"));
+        assert_eq!(
+            utc_date(UNIX_EPOCH + Duration::from_secs(1_709_251_200)),
+            "2024-03-01"
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn http_commands_render_as_curl_with_placeholders() {
+        let raw = r#"
+[[commands]]
+name = "Create user"
+type = "http"
+method = "post"
+url = "https://api.example.com/users"
+headers = { Authorization = "Bearer {{token}}" }
+body = '{"name": "{{name}}"}'
+"#;
+        let config: crate::config::Config = toml::from_str(raw).unwrap();
+        let command = command_from_config(&config.commands[0], Path::new("/tmp"));
+        assert!(command.quote_params);
+        assert_eq!(
+            command.template,
+            "curl -sS --fail-with-body -X POST -H 'Authorization: Bearer '{{token}} \
+             --data-raw '{\"name\": \"'{{name}}'\"}' https://api.example.com/users"
+        );
+        assert_eq!(quote_template_arg("{{url}}"), "{{url}}");
+        assert_eq!(
+            quote_template_arg("token={{secret:api}}&x"),
+            "token=\"{{secret:api}}\"'&x'"
+        );
+
+        let missing_url = crate::config::CommandConfig {
+            url: None,
+            ..config.commands[0].clone()
+        };
+        assert!(missing_url.validate().is_err());
+    }
+
     #[test]
     fn template_replacement_works() {
         let mut params = HashMap::new();
@@ -278,6 +842,90 @@ mod tests {
         assert_eq!(rendered, "deploy --env=production --region=us-east-1");
     }
 
+    #[test]
+    fn remote_command_line_quotes_command_and_exports_env() {
+        assert_eq!(
+            remote_command_line("deploy@prod-1", "ls -la", &[]),
+            "ssh deploy@prod-1 'ls -la'"
+        );
+        assert_eq!(
+            remote_command_line(
+                "prod",
+                "echo 'hi' && uptime",
+                &[("APP_ENV".to_string(), "prod".to_string())]
+            ),
+            r#"ssh prod 'export APP_ENV=prod; echo '\''hi'\'' && uptime'"#
+        );
+    }
+
+    #[test]
+    fn command_guard_denies_before_confirming() {
+        let guard = CommandGuard::from_config(&SafetyConfig {
+            deny: vec!["rm -rf /".to_string()],
+            confirm: vec!["*drop database*".to_string(), "rm -rf*".to_string()],
+            ..SafetyConfig::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            guard.check("rm -rf /", None, None),
+            GuardVerdict::Deny("rm -rf /".to_string())
+        );
+        assert_eq!(
+            guard.check("mysql -e 'DROP DATABASE app'", None, None),
+            GuardVerdict::Confirm("*drop database*".to_string())
+        );
+        assert_eq!(
+            guard.check("rm -rf /tmp/cache", None, None),
+            GuardVerdict::Confirm("rm -rf*".to_string())
+        );
+        assert_eq!(guard.check("ls -la", None, None), GuardVerdict::Allow);
+        for chained in [
+            "sudo rm -rf /",
+            "cd / && rm -rf /",
+            "rm -rf / # x",
+            "true; rm -rf /",
+        ] {
+            assert_eq!(
+                guard.check(chained, None, None),
+                GuardVerdict::Deny("rm -rf /".to_string()),
+                "{chained}"
+            );
+        }
+        assert_eq!(
+            guard.check("cd /srv && rm -rf /srv/cache", None, None),
+            GuardVerdict::Confirm("rm -rf*".to_string())
+        );
+        assert_eq!(
+            guard.check("sudo ls", None, None),
+            GuardVerdict::Warn("runs as root (sudo)".to_string())
+        );
+        assert_eq!(
+            guard.check("sudo mysql -e 'DROP DATABASE app'", None, None),
+            GuardVerdict::Confirm("*drop database*".to_string())
+        );
+    }
+
+    #[test]
+    fn command_guard_warns_about_root_and_production_targets() {
+        let guard = CommandGuard::from_config(&SafetyConfig::default()).unwrap();
+
+        assert_eq!(
+            guard.warning("cd /srv && sudo systemctl restart app", None, None),
+            Some("runs as root (sudo)".to_string())
+        );
+        assert_eq!(guard.warning("echo pseudo", None, None), None);
+        assert_eq!(
+            guard.warning("deploy", Some("Production"), None),
+            Some("targets production environment 'Production'".to_string())
+        );
+        assert_eq!(
+            guard.warning("uptime", Some("staging"), Some("deploy@prod-1")),
+            Some("targets production host 'prod-1'".to_string())
+        );
+        assert_eq!(guard.warning("uptime", None, Some("products.local")), None);
+    }
+
     #[test]
     fn scope_matching_works() {
         let cwd = Path::new("/Users/me/projects/laravel-app");
@@ -0,0 +1,236 @@
+use std::fs;
+use std::io::{self, Read, Stderr};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{List, ListItem, ListState, Paragraph};
+
+/// A one-pane fuzzy picker over plain lines, for scripts and git hooks: it draws on stderr so
+/// stdout carries only the choice.
+struct Picker {
+    lines: Vec<String>,
+    query: String,
+    /// Indexes into `lines` that match `query`, best first.
+    filtered: Vec<usize>,
+    selected: usize,
+    matcher: SkimMatcherV2,
+}
+
+enum PickerAction {
+    None,
+    Accept,
+    Cancel,
+}
+
+impl Picker {
+    fn new(lines: Vec<String>) -> Self {
+        let mut picker = Self {
+            lines,
+            query: String::new(),
+            filtered: Vec::new(),
+            selected: 0,
+            matcher: SkimMatcherV2::default(),
+        };
+        picker.refilter();
+        picker
+    }
+
+    /// Lines in file order while the query is empty, else by fuzzy score with ties kept in order.
+    fn refilter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                if self.query.is_empty() {
+                    return Some((0, index));
+                }
+                self.matcher
+                    .fuzzy_match(line, &self.query)
+                    .map(|score| (score, index))
+            })
+            .collect();
+        scored.sort_by(|left, right| right.0.cmp(&left.0).then(left.1.cmp(&right.1)));
+        self.filtered = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.filtered.get(self.selected).copied()
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.filtered.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> PickerAction {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return PickerAction::Cancel,
+            KeyCode::Char('c') if ctrl => return PickerAction::Cancel,
+            KeyCode::Enter if self.current().is_some() => return PickerAction::Accept,
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::Backspace if self.query.pop().is_some() => self.refilter(),
+            KeyCode::Char(ch) if !ctrl => {
+                self.query.push(ch);
+                self.refilter();
+            }
+            _ => {}
+        }
+        PickerAction::None
+    }
+}
+
+/// Lets the user pick one line of `path` (`-` reads stdin) and prints it, or its 0-based
+/// line index with `print_index`. Returns the exit code: 0 on a pick, 130 when canceled.
+pub fn run(path: &Path, print_index: bool) -> Result<i32> {
+    let raw = if path == Path::new("-") {
+        let mut raw = String::new();
+        io::stdin()
+            .read_to_string(&mut raw)
+            .context("failed to read lines from stdin")?;
+        raw
+    } else {
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?
+    };
+    let lines: Vec<String> = raw.lines().map(str::to_string).collect();
+    if lines.is_empty() {
+        bail!("nothing to pick: {} has no lines", path.display());
+    }
+
+    let mut picker = Picker::new(lines);
+    enable_raw_mode().context("failed to enable raw mode")?;
+    let mut stderr = io::stderr();
+    execute!(stderr, EnterAlternateScreen).context("failed to enter alternate screen")?;
+    let mut terminal =
+        Terminal::new(CrosstermBackend::new(stderr)).context("failed to create terminal")?;
+    let picked = pick_loop(&mut terminal, &mut picker);
+    disable_raw_mode().context("failed to disable raw mode")?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)
+        .context("failed to leave alternate screen")?;
+    terminal.show_cursor().context("failed to show cursor")?;
+
+    match picked? {
+        Some(index) if print_index => println!("{index}"),
+        Some(index) => println!("{}", picker.lines[index]),
+        None => return Ok(crate::CANCELED_EXIT_CODE),
+    }
+    Ok(0)
+}
+
+fn pick_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stderr>>,
+    picker: &mut Picker,
+) -> Result<Option<usize>> {
+    loop {
+        terminal
+            .draw(|frame| {
+                let [search, list] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)])
+                    .areas(frame.area());
+                let count = format!("  {}/{}", picker.filtered.len(), picker.lines.len());
+                frame.render_widget(
+                    Paragraph::new(Line::from(vec![
+                        Span::styled("> ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(picker.query.as_str()),
+                        Span::styled(count, Style::default().add_modifier(Modifier::DIM)),
+                    ])),
+                    search,
+                );
+                frame.set_cursor_position((
+                    search.x + 2 + picker.query.chars().count() as u16,
+                    search.y,
+                ));
+                let items: Vec<ListItem> = picker
+                    .filtered
+                    .iter()
+                    .map(|index| ListItem::new(picker.lines[*index].as_str()))
+                    .collect();
+                let mut state =
+                    ListState::default().with_selected(picker.current().map(|_| picker.selected));
+                frame.render_stateful_widget(
+                    List::new(items)
+                        .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+                    list,
+                    &mut state,
+                );
+            })
+            .context("failed to draw picker")?;
+
+        if let Event::Key(key) = event::read().context("failed to read terminal event")?
+            && key.kind != KeyEventKind::Release
+        {
+            match picker.handle_key(key) {
+                PickerAction::None => {}
+                PickerAction::Accept => return Ok(picker.current()),
+                PickerAction::Cancel => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Picker, PickerAction};
+
+    fn press(picker: &mut Picker, code: KeyCode) -> PickerAction {
+        picker.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn typing_filters_lines_and_keeps_their_original_index() {
+        let mut picker = Picker::new(
+            ["main", "feature/login", "feature/logout", "release"]
+                .map(str::to_string)
+                .to_vec(),
+        );
+        assert_eq!(picker.filtered, vec![0, 1, 2, 3]);
+
+        for ch in "logout".chars() {
+            press(&mut picker, KeyCode::Char(ch));
+        }
+        assert_eq!(picker.current(), Some(2));
+
+        press(&mut picker, KeyCode::Backspace);
+        press(&mut picker, KeyCode::Backspace);
+        press(&mut picker, KeyCode::Backspace);
+        assert_eq!(picker.filtered.len(), 2);
+        press(&mut picker, KeyCode::Down);
+        assert!(matches!(
+            press(&mut picker, KeyCode::Enter),
+            PickerAction::Accept
+        ));
+    }
+
+    #[test]
+    fn enter_without_a_match_does_nothing() {
+        let mut picker = Picker::new(vec!["main".to_string()]);
+        press(&mut picker, KeyCode::Char('z'));
+        assert_eq!(picker.current(), None);
+        assert!(matches!(
+            press(&mut picker, KeyCode::Enter),
+            PickerAction::None
+        ));
+        assert!(matches!(
+            press(&mut picker, KeyCode::Esc),
+            PickerAction::Cancel
+        ));
+    }
+}
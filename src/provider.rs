@@ -2,118 +2,2384 @@ use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use globset::GlobBuilder;
 
 use crate::config::{
-    ArtisanProviderConfig, ComposerProviderConfig, JustfileProviderConfig, ProvidersConfig,
+    ArtisanProviderConfig, BunProviderConfig, CommandConfig, ComposerProviderConfig,
+    DenoProviderConfig, DockerProviderConfig, ExternalProviderConfig, GhProviderConfig,
+    GitProviderConfig, JustfileProviderConfig, JvmProviderConfig, KubectlProviderConfig,
+    KubectlVerb, NpmProviderConfig, ProvidersConfig, PythonProviderConfig, RubyProviderConfig,
+    ScriptsProviderConfig, SshProviderConfig,
 };
-use crate::model::{CommandEntry, CommandSource};
+use crate::model::{
+    CommandEntry, CommandSource, ParamSpec, ParamType, command_from_config, git_output,
+};
+
+/// Names of all built-in providers, usable as `:name` filters when they have no alias.
+pub const PROVIDER_NAMES: &[&str] = &[
+    "config", "artisan", "composer", "justfile", "npm", "docker", "jvm", "git", "kubectl", "ruby",
+    "python", "deno", "bun", "scripts", "gh", "ssh",
+];
+
+/// How long one enabled provider took to load, as reported by `fzc bench`.
+pub struct ProviderTiming {
+    pub name: String,
+    pub commands: usize,
+    pub elapsed: Duration,
+}
+
+pub fn load_provider_commands(config: &ProvidersConfig, cwd: &Path) -> Result<Vec<CommandEntry>> {
+    load_provider_commands_timed(config, cwd).map(|(commands, _)| commands)
+}
+
+pub fn load_provider_commands_timed(
+    config: &ProvidersConfig,
+    cwd: &Path,
+) -> Result<(Vec<CommandEntry>, Vec<ProviderTiming>)> {
+    let mut commands = Vec::new();
+    let mut timings = Vec::new();
+    for name in enabled_provider_names(config) {
+        let started = Instant::now();
+        let loaded = load_provider(config, cwd, &name)?;
+        timings.push(ProviderTiming {
+            name,
+            commands: loaded.len(),
+            elapsed: started.elapsed(),
+        });
+        commands.extend(loaded);
+    }
+
+    Ok((commands, timings))
+}
+
+/// Enabled providers other than `config`, in load order.
+pub fn enabled_provider_names(config: &ProvidersConfig) -> Vec<String> {
+    let mut names = Vec::new();
+    for (name, enabled) in [
+        ("artisan", config.artisan.enabled),
+        ("composer", config.composer.enabled),
+        ("justfile", config.justfile.enabled),
+        ("npm", config.npm.enabled),
+        ("docker", config.docker.enabled),
+        ("jvm", config.jvm.enabled),
+        ("git", config.git.enabled),
+        ("kubectl", config.kubectl.enabled),
+        ("ruby", config.ruby.enabled),
+        ("python", config.python.enabled),
+        ("deno", config.deno.enabled),
+        ("bun", config.bun.enabled),
+        ("scripts", config.scripts.enabled),
+        ("gh", config.gh.enabled),
+        ("ssh", config.ssh.enabled),
+    ] {
+        if enabled {
+            names.push(name.to_string());
+        }
+    }
+    names.extend(config.external.iter().map(|external| external.name.clone()));
+    names
+}
+
+/// Enabled providers listed in `[providers] lazy`.
+pub fn lazy_provider_names(config: &ProvidersConfig) -> Vec<String> {
+    enabled_provider_names(config)
+        .into_iter()
+        .filter(|name| config.lazy.contains(name))
+        .collect()
+}
+
+/// The single entry a lazy provider shows until typing `:filter` (its alias or name) loads it.
+pub fn lazy_placeholder(provider: &str, filter: &str) -> CommandEntry {
+    CommandEntry {
+        description: Some(format!("type :{filter} to load and search its commands")),
+        ..CommandEntry::new(
+            format!("search {provider}…"),
+            "",
+            CommandSource::Lazy(provider.to_string()),
+        )
+    }
+}
+
+const COMPOSER_CHANGES: &[&str] = &[
+    "composer install*",
+    "composer update*",
+    "composer require *",
+    "composer remove *",
+];
+
+/// Command-line globs after which a provider's commands are likely to have changed.
+fn default_refresh_triggers(name: &str) -> Vec<&'static str> {
+    match name {
+        "composer" => COMPOSER_CHANGES.to_vec(),
+        // Packages register artisan commands, and make:command adds one.
+        "artisan" => [COMPOSER_CHANGES, &["php artisan make:command *"]].concat(),
+        "docker" => vec![
+            "docker run *",
+            "docker start *",
+            "docker stop *",
+            "docker rm *",
+            "docker compose up*",
+            "docker compose down*",
+        ],
+        // Branch choices and alias commands.
+        "git" => vec![
+            "git branch *",
+            "git switch -c *",
+            "git checkout -b *",
+            "git fetch*",
+            "git pull*",
+            "git config *",
+        ],
+        // Gems can add rake tasks.
+        "ruby" => vec![
+            "bundle install*",
+            "bundle update*",
+            "bundle add *",
+            "bundle remove *",
+        ],
+        // New runs to watch.
+        "gh" => vec!["gh workflow run *", "git push*"],
+        // Namespace and context choices.
+        "kubectl" => vec![
+            "kubectl create namespace *",
+            "kubectl delete namespace *",
+            "kubectl config use-context *",
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Enabled providers whose refresh triggers (from `[providers.refresh]`, else the built-in
+/// ones) match a command line that just finished.
+pub fn providers_to_refresh(config: &ProvidersConfig, command_line: &str) -> Vec<String> {
+    let command_line = command_line.trim();
+    enabled_provider_names(config)
+        .into_iter()
+        .filter(|name| {
+            let patterns: Vec<&str> = match config.refresh.get(name) {
+                Some(patterns) => patterns.iter().map(String::as_str).collect(),
+                None => default_refresh_triggers(name),
+            };
+            patterns.iter().any(|pattern| {
+                GlobBuilder::new(pattern)
+                    .build()
+                    .is_ok_and(|glob| glob.compile_matcher().is_match(command_line))
+            })
+        })
+        .collect()
+}
+
+/// Loads one provider by name; see [`enabled_provider_names`].
+pub fn load_provider(
+    config: &ProvidersConfig,
+    cwd: &Path,
+    name: &str,
+) -> Result<Vec<CommandEntry>> {
+    match name {
+        "artisan" => load_artisan_provider(cwd, &config.artisan),
+        "composer" => load_composer_provider(cwd, &config.composer),
+        "justfile" => load_justfile_provider(cwd, &config.justfile),
+        "npm" => load_npm_provider(cwd, &config.npm, config.bun.enabled),
+        "docker" => load_docker_provider(cwd, &config.docker),
+        "jvm" => load_jvm_provider(cwd, &config.jvm),
+        "git" => load_git_provider(cwd, &config.git),
+        "kubectl" => load_kubectl_provider(cwd, &config.kubectl),
+        "ruby" => load_ruby_provider(cwd, &config.ruby),
+        "python" => load_python_provider(cwd, &config.python),
+        "deno" => load_deno_provider(cwd, &config.deno),
+        "bun" => load_bun_provider(cwd, &config.bun),
+        "scripts" => load_scripts_provider(cwd, &config.scripts),
+        "gh" => load_gh_provider(cwd, &config.gh),
+        "ssh" => load_ssh_provider(&config.ssh),
+        _ => match config
+            .external
+            .iter()
+            .find(|external| external.name == name)
+        {
+            Some(external) => load_external_provider(cwd, external),
+            None => bail!("unknown provider '{name}'"),
+        },
+    }
+}
+
+/// What `fzc doctor` checks for one provider: the project it would read, the program its
+/// commands run (a name to look up on `PATH`, or a path such as a Gradle wrapper), and its cache.
+#[derive(Debug, Default)]
+pub struct ProviderProbe {
+    pub root: Option<PathBuf>,
+    /// The provider lists nothing without `root`, so a missing program only matters with one.
+    pub needs_project: bool,
+    pub program: Option<PathBuf>,
+    /// Arguments that make `program` print its version.
+    pub version_args: &'static [&'static str],
+    pub cache: Option<PathBuf>,
+}
+
+impl ProviderProbe {
+    /// A provider that lists nothing outside `root` and runs `program --version`.
+    fn project(root: Option<PathBuf>, program: impl Into<PathBuf>) -> Self {
+        Self {
+            root,
+            needs_project: true,
+            program: Some(program.into()),
+            version_args: &["--version"],
+            cache: None,
+        }
+    }
+}
+
+/// Probes one provider by name with the detection its loader uses; see [`load_provider`].
+pub fn probe_provider(config: &ProvidersConfig, cwd: &Path, name: &str) -> ProviderProbe {
+    let probe = match name {
+        "config" => ProviderProbe::default(),
+        "artisan" => probe_artisan_provider(cwd),
+        "composer" => probe_composer_provider(cwd),
+        "justfile" => probe_justfile_provider(cwd, &config.justfile),
+        "npm" => probe_npm_provider(cwd, &config.npm),
+        "docker" => probe_docker_provider(),
+        "jvm" => probe_jvm_provider(cwd),
+        "git" => probe_git_provider(cwd),
+        "kubectl" => probe_kubectl_provider(cwd),
+        "ruby" => probe_ruby_provider(cwd),
+        "python" => probe_python_provider(cwd),
+        "deno" => probe_deno_provider(cwd),
+        "bun" => probe_bun_provider(cwd),
+        "scripts" => probe_scripts_provider(cwd, &config.scripts),
+        "gh" => probe_gh_provider(cwd),
+        "ssh" => probe_ssh_provider(&config.ssh),
+        _ => config
+            .external
+            .iter()
+            .find(|external| external.name == name)
+            .map(probe_external_provider)
+            .unwrap_or_default(),
+    };
+    ProviderProbe {
+        cache: provider_cache_path(name, cwd),
+        ..probe
+    }
+}
+
+/// Startup hints for disabled providers whose project markers exist around `cwd`.
+pub fn disabled_provider_hints(config: &ProvidersConfig, cwd: &Path) -> Vec<String> {
+    let mut detected = Vec::new();
+    if !config.artisan.enabled && detect_laravel_root(cwd).is_some() {
+        detected.push(("artisan", "artisan"));
+    }
+    if !config.composer.enabled && detect_composer_root(cwd).is_some() {
+        detected.push(("composer", "composer.json"));
+    }
+    if !config.justfile.enabled && resolve_provider_path(cwd, &config.justfile.path).is_some() {
+        detected.push(("justfile", "justfile"));
+    }
+    if !config.npm.enabled && detect_npm_root(cwd).is_some() {
+        detected.push(("npm", "package.json"));
+    }
+    if !config.ruby.enabled && detect_ruby_root(cwd).is_some() {
+        detected.push(("ruby", "Gemfile/Rakefile"));
+    }
+    if !config.python.enabled && detect_python_root(cwd).is_some() {
+        detected.push(("python", "pyproject.toml"));
+    }
+    if !config.deno.enabled && detect_deno_manifest(cwd).is_some() {
+        detected.push(("deno", "deno.json"));
+    }
+    if !config.bun.enabled && detect_bun_root(cwd).is_some() {
+        detected.push(("bun", "bun.lock"));
+    }
+    if !config.jvm.enabled {
+        if detect_gradle_root(cwd).is_some() {
+            detected.push(("jvm", "build.gradle"));
+        } else if detect_maven_root(cwd).is_some() {
+            detected.push(("jvm", "pom.xml"));
+        }
+    }
+    if !config.gh.enabled
+        && detect_git_root(cwd).is_some_and(|root| root.join(".github/workflows").is_dir())
+    {
+        detected.push(("gh", ".github/workflows"));
+    }
+    if !config.git.enabled && detect_git_root(cwd).is_some() {
+        detected.push(("git", ".git"));
+    }
+    // A kubeconfig alone is too common to hint at; manifests mean this project deploys to k8s.
+    if !config.kubectl.enabled && detect_k8s_manifests(cwd).is_some() {
+        detected.push(("kubectl", "k8s manifests"));
+    }
+
+    detected
+        .into_iter()
+        .map(|(provider, marker)| {
+            format!("{marker} detected - enable it with `{provider} = true` under [providers]")
+        })
+        .collect()
+}
+
+fn probe_artisan_provider(cwd: &Path) -> ProviderProbe {
+    ProviderProbe::project(detect_laravel_root(cwd), "php")
+}
+
+fn load_artisan_provider(cwd: &Path, _config: &ArtisanProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_laravel_root(cwd) else {
+        return Ok(Vec::new());
+    };
+
+    let raw_list = artisan_list_raw(&root).unwrap_or_default();
+    let command_names = parse_artisan_commands(&raw_list);
+    let command_descriptions = artisan_descriptions(&root).unwrap_or_default();
+
+    let commands = command_names
+        .into_iter()
+        .map(|name| {
+            let params = artisan_generator_params(&name);
+            let mut template = format!("php artisan {name}");
+            for param in &params {
+                template.push_str(&format!(" {{{{{}}}}}", param.name));
+            }
+            template.push_str(" --ansi");
+            CommandEntry {
+                description: command_descriptions
+                    .get(&name)
+                    .filter(|desc| !desc.trim().is_empty())
+                    .cloned()
+                    .or_else(|| Some("Laravel artisan command".to_string())),
+                params,
+                working_dir: Some(root.clone()),
+                ..CommandEntry::new(
+                    format!("artisan {name}"),
+                    template,
+                    CommandSource::Provider("artisan".to_string()),
+                )
+            }
+        })
+        .collect();
+
+    Ok(commands)
+}
+
+fn probe_justfile_provider(cwd: &Path, config: &JustfileProviderConfig) -> ProviderProbe {
+    ProviderProbe::project(resolve_provider_path(cwd, &config.path), "just")
+}
+
+fn load_justfile_provider(
+    cwd: &Path,
+    config: &JustfileProviderConfig,
+) -> Result<Vec<CommandEntry>> {
+    let Some(justfile_path) = resolve_provider_path(cwd, &config.path) else {
+        return Ok(Vec::new());
+    };
+
+    let option_tokens = tokenize_provider_options(&config.options);
+    let raw_list = just_list_summary_raw(&justfile_path, cwd, &option_tokens).unwrap_or_default();
+    let recipes = parse_just_recipes(&raw_list);
+    let mut front_matter = fs::read_to_string(&justfile_path)
+        .map(|raw| parse_just_front_matter(&raw))
+        .unwrap_or_default();
+
+    let commands = recipes
+        .into_iter()
+        .map(|recipe| {
+            let meta = front_matter.remove(&recipe).unwrap_or_default();
+            let mut template = build_just_command_template(&justfile_path, &option_tokens, &recipe);
+            for param in &meta.params {
+                template.push_str(&format!(" {{{{{}}}}}", param.name));
+            }
+            CommandEntry {
+                description: Some(
+                    meta.description
+                        .unwrap_or_else(|| "just recipe".to_string()),
+                )
+                .map(|description| with_tags(description, &meta.tags)),
+                params: meta.params,
+                tags: meta.tags,
+                working_dir: Some(cwd.to_path_buf()),
+                quote_params: true,
+                ..CommandEntry::new(
+                    meta.name.unwrap_or_else(|| format!("just {recipe}")),
+                    template,
+                    CommandSource::Provider("justfile".to_string()),
+                )
+            }
+        })
+        .collect();
+
+    Ok(commands)
+}
+
+/// The first word of the provider's command, which is what has to be installed.
+fn probe_external_provider(config: &ExternalProviderConfig) -> ProviderProbe {
+    ProviderProbe {
+        program: config.command.split_whitespace().next().map(PathBuf::from),
+        ..ProviderProbe::default()
+    }
+}
+
+fn load_external_provider(
+    cwd: &Path,
+    config: &ExternalProviderConfig,
+) -> Result<Vec<CommandEntry>> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&config.command)
+        .current_dir(cwd)
+        .output()
+        .with_context(|| format!("failed to run provider '{}'", config.name))?;
+    if !output.status.success() {
+        bail!(
+            "provider '{}' exited with {}: {}",
+            config.name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    parse_external_commands(&String::from_utf8_lossy(&output.stdout), &config.name, cwd)
+        .with_context(|| format!("provider '{}' printed invalid JSON", config.name))
+}
+
+fn parse_external_commands(raw: &str, provider: &str, cwd: &Path) -> Result<Vec<CommandEntry>> {
+    let definitions: Vec<CommandConfig> = serde_json::from_str(raw)?;
+    for definition in &definitions {
+        definition.validate()?;
+    }
+    Ok(definitions
+        .iter()
+        .map(|definition| CommandEntry {
+            source: CommandSource::Provider(provider.to_string()),
+            ..command_from_config(definition, cwd)
+        })
+        .collect())
+}
+
+fn probe_composer_provider(cwd: &Path) -> ProviderProbe {
+    ProviderProbe::project(detect_composer_root(cwd), "composer")
+}
+
+fn load_composer_provider(
+    cwd: &Path,
+    _config: &ComposerProviderConfig,
+) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_composer_root(cwd) else {
+        return Ok(Vec::new());
+    };
+
+    let mut commands = Vec::new();
+
+    for (name, description) in basic_composer_commands() {
+        commands.push(CommandEntry {
+            description: Some(description.to_string()),
+            working_dir: Some(root.clone()),
+            ..CommandEntry::new(
+                format!("composer {name}"),
+                format!("composer {name}"),
+                CommandSource::Provider("composer".to_string()),
+            )
+        });
+    }
+
+    let package = ParamSpec {
+        placeholder: Some("vendor/package".to_string()),
+        ..front_matter_param("package", "Package")
+    };
+    let dev = ParamSpec {
+        kind: ParamType::Flag,
+        required: false,
+        default_flag: Some(false),
+        ..front_matter_param("dev", "Add to require-dev (--dev)?")
+    };
+    for (name, description, params) in [
+        (
+            "require",
+            "Add a package to composer.json and install it",
+            vec![package.clone(), dev],
+        ),
+        (
+            "remove",
+            "Remove a package from composer.json",
+            vec![package],
+        ),
+    ] {
+        let placeholders: Vec<String> = params
+            .iter()
+            .map(|param| format!("{{{{{}}}}}", param.name))
+            .collect();
+        commands.push(CommandEntry {
+            description: Some(description.to_string()),
+            params,
+            working_dir: Some(root.clone()),
+            ..CommandEntry::new(
+                format!("composer {name}"),
+                format!("composer {name} {}", placeholders.join(" ")),
+                CommandSource::Provider("composer".to_string()),
+            )
+        });
+    }
+
+    for script in composer_scripts(&root) {
+        commands.push(CommandEntry {
+            description: Some("composer script".to_string()),
+            working_dir: Some(root.clone()),
+            ..CommandEntry::new(
+                format!("composer script:{script}"),
+                format!("composer run-script {script}"),
+                CommandSource::Provider("composer".to_string()),
+            )
+        });
+    }
+
+    Ok(commands)
+}
+
+fn probe_ruby_provider(cwd: &Path) -> ProviderProbe {
+    let root = detect_ruby_root(cwd);
+    let program = if root.as_deref().is_some_and(is_bundled) {
+        "bundle"
+    } else {
+        "rake"
+    };
+    ProviderProbe::project(root, program)
+}
+
+fn load_ruby_provider(cwd: &Path, _config: &RubyProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_ruby_root(cwd) else {
+        return Ok(Vec::new());
+    };
+    let bundled = is_bundled(&root);
+
+    let ruby_command = |name: String, description: String, template: String| CommandEntry {
+        description: Some(description),
+        working_dir: Some(root.clone()),
+        ..CommandEntry::new(name, template, CommandSource::Provider("ruby".to_string()))
+    };
+
+    let mut commands = Vec::new();
+    if bundled {
+        for (name, description) in basic_bundler_commands() {
+            commands.push(ruby_command(
+                format!("bundle {name}"),
+                description.to_string(),
+                format!("bundle {name}"),
+            ));
+        }
+        let gem = ParamSpec {
+            placeholder: Some("rails".to_string()),
+            ..front_matter_param("gem", "Gem")
+        };
+        for (name, description) in [
+            ("add", "Add a gem to the Gemfile and install it"),
+            ("remove", "Remove a gem from the Gemfile"),
+        ] {
+            let mut command = ruby_command(
+                format!("bundle {name}"),
+                description.to_string(),
+                format!("bundle {name} {{{{gem}}}}"),
+            );
+            command.params = vec![gem.clone()];
+            commands.push(command);
+        }
+    }
+
+    let rake = if bundled { "bundle exec rake" } else { "rake" };
+    let raw = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{rake} -AT"))
+        .current_dir(&root)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default();
+    for (task, description) in parse_rake_tasks(&raw) {
+        let description = if description.is_empty() {
+            "rake task".to_string()
+        } else {
+            description
+        };
+        commands.push(ruby_command(
+            format!("rake {task}"),
+            description,
+            format!("{rake} {task}"),
+        ));
+    }
+
+    Ok(commands)
+}
+
+fn probe_python_provider(cwd: &Path) -> ProviderProbe {
+    let root = detect_python_root(cwd);
+    let tool = root.as_deref().and_then(|root| {
+        let (pyproject, pipfile) = read_python_manifests(root);
+        python_tool(root, &pyproject, pipfile.is_some())
+    });
+    ProviderProbe::project(root, tool.unwrap_or("python3"))
+}
+
+/// Project scripts, poe tasks, and tox environments, run through the project's tool (poetry, uv,
+/// or pipenv, picked by lockfile) when it has one.
+fn load_python_provider(cwd: &Path, _config: &PythonProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_python_root(cwd) else {
+        return Ok(Vec::new());
+    };
+    let (pyproject, pipfile) = read_python_manifests(&root);
+    let tool = python_tool(&root, &pyproject, pipfile.is_some());
+    let run = |target: &str| match tool {
+        Some(tool) => format!("{tool} run {target}"),
+        None => target.to_string(),
+    };
+
+    let python_command = |template: String, description: String| CommandEntry {
+        description: Some(description),
+        working_dir: Some(root.clone()),
+        ..CommandEntry::new(
+            template.clone(),
+            template,
+            CommandSource::Provider("python".to_string()),
+        )
+    };
+
+    let mut commands = Vec::new();
+    let basics: &[(&str, &str)] = match tool {
+        Some("poetry") => &[
+            ("poetry install", "Install the project and its dependencies"),
+            ("poetry update", "Update dependencies and poetry.lock"),
+        ],
+        Some("uv") => &[
+            ("uv sync", "Sync the environment with uv.lock"),
+            ("uv lock", "Update uv.lock"),
+        ],
+        Some("pipenv") => &[
+            ("pipenv install", "Install the Pipfile's packages"),
+            ("pipenv update", "Update packages and Pipfile.lock"),
+        ],
+        _ => &[],
+    };
+    for (template, description) in basics {
+        commands.push(python_command(
+            template.to_string(),
+            description.to_string(),
+        ));
+    }
+
+    let table_keys = |table: Option<&toml::Value>| -> Vec<String> {
+        table
+            .and_then(toml::Value::as_table)
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default()
+    };
+    let mut scripts = table_keys(
+        pyproject
+            .get("project")
+            .and_then(|project| project.get("scripts")),
+    );
+    for script in table_keys(
+        pyproject
+            .get("tool")
+            .and_then(|tool| tool.get("poetry"))
+            .and_then(|poetry| poetry.get("scripts")),
+    ) {
+        if !scripts.contains(&script) {
+            scripts.push(script);
+        }
+    }
+    for script in scripts.iter().filter(|script| is_shell_safe_arg(script)) {
+        commands.push(python_command(run(script), "project script".to_string()));
+    }
+
+    if let Some(scripts) = pipfile
+        .as_ref()
+        .and_then(|pipfile| pipfile.get("scripts"))
+        .and_then(toml::Value::as_table)
+    {
+        for (name, command) in scripts {
+            if is_shell_safe_arg(name) {
+                let description = command.as_str().unwrap_or("Pipfile script").to_string();
+                commands.push(python_command(format!("pipenv run {name}"), description));
+            }
+        }
+    }
+
+    if let Some(tasks) = pyproject
+        .get("tool")
+        .and_then(|tool| tool.get("poe"))
+        .and_then(|poe| poe.get("tasks"))
+        .and_then(toml::Value::as_table)
+    {
+        for (name, task) in tasks {
+            // Poe hides tasks starting with `_`; they only serve as building blocks.
+            if name.starts_with('_') || !is_shell_safe_arg(name) {
+                continue;
+            }
+            commands.push(python_command(
+                run(&format!("poe {name}")),
+                poe_task_description(task),
+            ));
+        }
+    }
+
+    let tox = fs::read_to_string(root.join("tox.ini")).unwrap_or_default();
+    let environments = parse_tox_environments(&tox);
+    if !environments.is_empty() {
+        commands.push(python_command(
+            "tox".to_string(),
+            "Run every tox environment".to_string(),
+        ));
+    }
+    for environment in environments {
+        commands.push(python_command(
+            format!("tox -e {environment}"),
+            "tox environment".to_string(),
+        ));
+    }
+
+    Ok(commands)
+}
+
+/// `poetry`, `uv`, or `pipenv`, judged by lockfile (and `[tool.poetry]`); `None` for plain
+/// pyproject projects.
+/// `pyproject.toml` (empty when missing or invalid) and `Pipfile` at the project root.
+fn read_python_manifests(root: &Path) -> (toml::Table, Option<toml::Table>) {
+    let read_toml = |name: &str| {
+        fs::read_to_string(root.join(name))
+            .ok()
+            .and_then(|raw| raw.parse::<toml::Table>().ok())
+    };
+    (
+        read_toml("pyproject.toml").unwrap_or_default(),
+        read_toml("Pipfile"),
+    )
+}
+
+fn python_tool(root: &Path, pyproject: &toml::Table, has_pipfile: bool) -> Option<&'static str> {
+    let tool_table = |name: &str| {
+        pyproject
+            .get("tool")
+            .and_then(|tool| tool.get(name))
+            .is_some()
+    };
+    if root.join("poetry.lock").is_file() || tool_table("poetry") {
+        Some("poetry")
+    } else if root.join("uv.lock").is_file() || tool_table("uv") {
+        Some("uv")
+    } else if has_pipfile {
+        Some("pipenv")
+    } else {
+        None
+    }
+}
+
+/// A poe task's `help`, else the command it runs.
+fn poe_task_description(task: &toml::Value) -> String {
+    let text = match task {
+        toml::Value::String(command) => Some(command.as_str()),
+        toml::Value::Table(table) => ["help", "cmd", "shell", "script", "ref"]
+            .iter()
+            .find_map(|key| table.get(*key).and_then(toml::Value::as_str)),
+        toml::Value::Array(_) => Some("sequence of tasks"),
+        _ => None,
+    };
+    text.map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .unwrap_or_else(|| "poe task".to_string())
+}
+
+/// Environments from `tox.ini`: the `envlist` entries plus `[testenv:name]` sections. Entries
+/// with generative `{a,b}` factors are skipped, since tox expands them itself.
+fn parse_tox_environments(raw: &str) -> Vec<String> {
+    let mut environments: Vec<String> = Vec::new();
+    let mut push = |name: &str| {
+        let name = name.trim();
+        if is_shell_safe_arg(name) && !environments.iter().any(|known| known == name) {
+            environments.push(name.to_string());
+        }
+    };
+    let mut section = String::new();
+    let mut in_envlist = false;
+    for line in raw.lines() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            section = name.trim().to_string();
+            in_envlist = false;
+            if let Some(environment) = section.strip_prefix("testenv:") {
+                push(environment);
+            }
+            continue;
+        }
+        if section != "tox" {
+            continue;
+        }
+        let continued = in_envlist && line.starts_with(char::is_whitespace);
+        let value = match trimmed.split_once('=') {
+            Some((key, value)) if !continued && matches!(key.trim(), "envlist" | "env_list") => {
+                in_envlist = true;
+                value
+            }
+            _ if continued => trimmed,
+            _ => {
+                in_envlist = false;
+                continue;
+            }
+        };
+        for name in value.split(',') {
+            if !name.contains(['{', '}']) {
+                push(name);
+            }
+        }
+    }
+    environments
+}
+
+fn probe_deno_provider(cwd: &Path) -> ProviderProbe {
+    let root = detect_deno_manifest(cwd).map(|manifest| deno_root(&manifest, cwd));
+    ProviderProbe::project(root, "deno")
+}
+
+/// `deno task <name>` for each task in the nearest `deno.json` or `deno.jsonc`.
+fn load_deno_provider(cwd: &Path, _config: &DenoProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(manifest) = detect_deno_manifest(cwd) else {
+        return Ok(Vec::new());
+    };
+    let root = deno_root(&manifest, cwd);
+    let content = fs::read_to_string(&manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+
+    Ok(parse_deno_tasks(&content)
+        .into_iter()
+        .map(|(task, description)| {
+            js_runtime_command(
+                "deno",
+                format!("deno task {task}"),
+                format!("deno task {}", shell_escape_arg(&task)),
+                description,
+                &root,
+            )
+        })
+        .collect())
+}
+
+fn probe_bun_provider(cwd: &Path) -> ProviderProbe {
+    ProviderProbe::project(detect_bun_root(cwd), "bun")
+}
+
+/// `bun run <script>` for `package.json` scripts in the nearest Bun project.
+fn load_bun_provider(cwd: &Path, _config: &BunProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_bun_root(cwd) else {
+        return Ok(Vec::new());
+    };
+    let content = fs::read_to_string(root.join("package.json")).unwrap_or_default();
+
+    Ok(parse_package_scripts_json(&content)
+        .into_iter()
+        .map(|(script, body)| {
+            js_runtime_command(
+                "bun",
+                format!("bun run {script}"),
+                format!("bun run {}", shell_escape_arg(&script)),
+                body,
+                &root,
+            )
+        })
+        .collect())
+}
+
+/// The first configured directory that exists; scripts run themselves, so there is no program.
+fn probe_scripts_provider(cwd: &Path, config: &ScriptsProviderConfig) -> ProviderProbe {
+    ProviderProbe {
+        root: config
+            .dirs
+            .iter()
+            .find_map(|dir| resolve_script_dir(cwd, dir))
+            .map(|(dir, _)| dir),
+        ..ProviderProbe::default()
+    }
+}
+
+/// Executable files in the configured directories, sorted by name within each directory. Scripts
+/// in project directories run from the directory that holds the script directory.
+fn load_scripts_provider(cwd: &Path, config: &ScriptsProviderConfig) -> Result<Vec<CommandEntry>> {
+    let mut commands = Vec::new();
+    for raw_dir in &config.dirs {
+        let Some((dir, working_dir)) = resolve_script_dir(cwd, raw_dir) else {
+            continue;
+        };
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?;
+        let mut scripts: Vec<(String, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                (
+                    entry.file_name().to_string_lossy().into_owned(),
+                    entry.path(),
+                )
+            })
+            .filter(|(name, path)| !name.starts_with('.') && is_executable_file(path))
+            .collect();
+        scripts.sort();
+
+        for (name, path) in scripts {
+            let meta = parse_script_front_matter(&script_header(&path));
+            let description = meta
+                .description
+                .or_else(|| {
+                    config
+                        .descriptions
+                        .then(|| script_description(&path))
+                        .flatten()
+                })
+                .unwrap_or_else(|| raw_dir.clone());
+            let mut template = shell_escape_arg(&path.to_string_lossy());
+            for param in &meta.params {
+                template.push_str(&format!(" {{{{{}}}}}", param.name));
+            }
+            commands.push(CommandEntry {
+                description: Some(with_tags(description, &meta.tags)),
+                params: meta.params,
+                tags: meta.tags,
+                working_dir: working_dir.clone(),
+                quote_params: true,
+                ..CommandEntry::new(
+                    meta.name.unwrap_or(name),
+                    template,
+                    CommandSource::Provider("scripts".to_string()),
+                )
+            });
+        }
+    }
+    Ok(commands)
+}
+
+/// The script directory for `raw_dir`, with the project directory to run its scripts from when
+/// it was found relative to `cwd` (absolute and `~` directories run from `cwd`).
+fn resolve_script_dir(cwd: &Path, raw_dir: &str) -> Option<(PathBuf, Option<PathBuf>)> {
+    let candidate = expand_home_shorthand(raw_dir)?;
+    if candidate.is_absolute() {
+        return candidate.is_dir().then_some((candidate, None));
+    }
+    cwd.ancestors().find_map(|dir| {
+        let joined = dir.join(&candidate);
+        joined.is_dir().then(|| (joined, Some(dir.to_path_buf())))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable_file(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                ["exe", "bat", "cmd", "ps1", "com"]
+                    .iter()
+                    .any(|known| extension.eq_ignore_ascii_case(known))
+            })
+}
+
+/// The first lines of a script, enough for its front-matter block; empty for binaries.
+fn script_header(path: &Path) -> String {
+    let Ok(file) = fs::File::open(path) else {
+        return String::new();
+    };
+    std::io::BufRead::lines(std::io::BufReader::new(file))
+        .take(50)
+        .map_while(Result::ok)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The text after `fzc:` in a `#` or `//` comment within the first 10 lines of the script.
+fn script_description(path: &Path) -> Option<String> {
+    let file = fs::File::open(path).ok()?;
+    std::io::BufRead::lines(std::io::BufReader::new(file))
+        .take(10)
+        .map_while(Result::ok)
+        .find_map(|line| {
+            let comment = line.trim_start();
+            let comment = comment
+                .strip_prefix('#')
+                .or_else(|| comment.strip_prefix("//"))?;
+            let description = comment.trim_start().strip_prefix("fzc:")?.trim();
+            (!description.is_empty()).then(|| description.to_string())
+        })
+}
+
+fn js_runtime_command(
+    provider: &str,
+    name: String,
+    template: String,
+    description: String,
+    root: &Path,
+) -> CommandEntry {
+    CommandEntry {
+        description: (!description.is_empty()).then_some(description),
+        working_dir: Some(root.to_path_buf()),
+        ..CommandEntry::new(
+            name,
+            template,
+            CommandSource::Provider(provider.to_string()),
+        )
+    }
+}
+
+/// Task names with their descriptions, sorted by name. A task is either a command string or
+/// (Deno 2) an object with `command` and an optional `description`.
+fn parse_deno_tasks(raw: &str) -> Vec<(String, String)> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(&strip_jsonc(raw)) else {
+        return Vec::new();
+    };
+    let Some(map) = value.get("tasks").and_then(|value| value.as_object()) else {
+        return Vec::new();
+    };
+
+    let mut tasks: Vec<(String, String)> = map
+        .iter()
+        .filter(|(name, _)| !name.trim().is_empty())
+        .map(|(name, task)| {
+            let description = match task {
+                serde_json::Value::String(command) => command.as_str(),
+                task => task
+                    .get("description")
+                    .or_else(|| task.get("command"))
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default(),
+            };
+            (name.trim().to_string(), description.to_string())
+        })
+        .collect();
+    tasks.sort();
+    tasks
+}
+
+/// Drops `//` and `/* */` comments and trailing commas so JSONC parses as JSON; string contents
+/// are left alone.
+fn strip_jsonc(raw: &str) -> String {
+    let mut stripped = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    let mut in_string = false;
+    while let Some(ch) = chars.next() {
+        if in_string {
+            stripped.push(ch);
+            match ch {
+                '\\' => stripped.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (ch, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                stripped.push(ch);
+            }
+            ('/', Some('/')) => while chars.next_if(|next| *next != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            (']' | '}', _) => {
+                let kept = stripped.trim_end().len();
+                if stripped[..kept].ends_with(',') {
+                    stripped.remove(kept - 1);
+                }
+                stripped.push(ch);
+            }
+            _ => stripped.push(ch),
+        }
+    }
+    stripped
+}
+
+fn probe_npm_provider(cwd: &Path, config: &NpmProviderConfig) -> ProviderProbe {
+    let root = detect_npm_root(cwd);
+    let manager = match &root {
+        Some(root) => npm_manager(root, config),
+        None => config.manager.clone().unwrap_or_else(|| "npm".to_string()),
+    };
+    ProviderProbe::project(root, manager)
+}
+
+/// With `bun_enabled`, a Bun project is left to the bun provider so its scripts are not listed
+/// twice.
+fn load_npm_provider(
+    cwd: &Path,
+    config: &NpmProviderConfig,
+    bun_enabled: bool,
+) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_npm_root(cwd) else {
+        return Ok(Vec::new());
+    };
+    if bun_enabled && detect_bun_root(cwd).as_ref() == Some(&root) {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(root.join("package.json")).unwrap_or_default();
+    let manager = npm_manager(&root, config);
+
+    let mut commands: Vec<CommandEntry> = parse_package_scripts_json(&content)
+        .into_iter()
+        .map(|(script, body)| {
+            npm_script_command(
+                format!("{manager} run {script}"),
+                body,
+                format!("{manager} run {}", shell_escape_arg(&script)),
+                &root,
+                None,
+            )
+        })
+        .collect();
+
+    for (package_name, relative, dir) in workspace_packages(&root, &content) {
+        let package_content = fs::read_to_string(dir.join("package.json")).unwrap_or_default();
+        let package = package_name.clone().unwrap_or_else(|| relative.clone());
+        let package_arg = shell_escape_arg(&package);
+        for (script, body) in parse_package_scripts_json(&package_content) {
+            let script_arg = shell_escape_arg(&script);
+            // yarn and pnpm select by name; a package without one is reached by its directory.
+            let (name, template) = match (manager.as_str(), &package_name) {
+                ("yarn", Some(_)) => (
+                    format!("yarn workspace {package} run {script}"),
+                    format!("yarn workspace {package_arg} run {script_arg}"),
+                ),
+                ("yarn", None) => (
+                    format!("yarn --cwd {package} run {script}"),
+                    format!("yarn --cwd {package_arg} run {script_arg}"),
+                ),
+                ("pnpm", Some(_)) => (
+                    format!("pnpm --filter {package} run {script}"),
+                    format!("pnpm --filter {package_arg} run {script_arg}"),
+                ),
+                ("bun", _) => (
+                    format!("bun run --filter {package} {script}"),
+                    format!("bun run --filter {package_arg} {script_arg}"),
+                ),
+                ("pnpm", None) => (
+                    format!("pnpm --filter ./{package} run {script}"),
+                    format!(
+                        "pnpm --filter {} run {script_arg}",
+                        shell_escape_arg(&format!("./{package}"))
+                    ),
+                ),
+                _ => (
+                    format!("{manager} run {script} -w {package}"),
+                    format!("{manager} run {script_arg} -w {package_arg}"),
+                ),
+            };
+            commands.push(npm_script_command(
+                name,
+                body,
+                template,
+                &root,
+                Some(package.clone()),
+            ));
+        }
+    }
+
+    Ok(commands)
+}
+
+fn npm_script_command(
+    name: String,
+    body: String,
+    template: String,
+    root: &Path,
+    package: Option<String>,
+) -> CommandEntry {
+    CommandEntry {
+        description: Some(body),
+        working_dir: Some(root.to_path_buf()),
+        badge: package,
+        ..CommandEntry::new(name, template, CommandSource::Provider("npm".to_string()))
+    }
+}
+
+/// Workspace packages declared in the root package.json as `(package name, directory relative to
+/// the root, directory)`, sorted by directory.
+fn workspace_packages(root: &Path, raw: &str) -> Vec<(Option<String>, String, PathBuf)> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(raw) else {
+        return Vec::new();
+    };
+    // Either `"workspaces": [...]` or yarn's `"workspaces": {"packages": [...]}`.
+    let Some(patterns) = value
+        .get("workspaces")
+        .and_then(|workspaces| workspaces.get("packages").or(Some(workspaces)))
+        .and_then(|patterns| patterns.as_array())
+    else {
+        return Vec::new();
+    };
+
+    let mut dirs = BTreeSet::new();
+    for pattern in patterns.iter().filter_map(|pattern| pattern.as_str()) {
+        let pattern = pattern
+            .trim()
+            .trim_start_matches("./")
+            .trim_end_matches('/');
+        if pattern.is_empty() || pattern.starts_with('!') {
+            continue;
+        }
+        let Ok(glob) = GlobBuilder::new(pattern).literal_separator(true).build() else {
+            continue;
+        };
+        let matcher = glob.compile_matcher();
+        let depth = if pattern.contains("**") {
+            MAX_WORKSPACE_DEPTH
+        } else {
+            pattern.split('/').count()
+        };
+        collect_workspace_dirs(root, depth, &mut |dir| {
+            if let Ok(relative) = dir.strip_prefix(root)
+                && matcher.is_match(relative)
+                && dir.join("package.json").is_file()
+            {
+                dirs.insert(dir.to_path_buf());
+            }
+        });
+    }
+
+    dirs.into_iter()
+        .map(|dir| {
+            let content = fs::read_to_string(dir.join("package.json")).unwrap_or_default();
+            let name = serde_json::from_str::<serde_json::Value>(&content)
+                .ok()
+                .and_then(|value| value.get("name")?.as_str().map(str::to_string))
+                .filter(|name| !name.trim().is_empty());
+            let relative = dir
+                .strip_prefix(root)
+                .unwrap_or(&dir)
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (name, relative, dir)
+        })
+        .collect()
+}
+
+/// How deep `**` workspace patterns are followed below the root.
+const MAX_WORKSPACE_DEPTH: usize = 4;
+
+fn collect_workspace_dirs(dir: &Path, depth: usize, visit: &mut dyn FnMut(&Path)) {
+    if depth == 0 {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !path.is_dir() || name == "node_modules" || name.starts_with('.') {
+            continue;
+        }
+        visit(&path);
+        collect_workspace_dirs(&path, depth - 1, visit);
+    }
+}
+
+fn probe_docker_provider() -> ProviderProbe {
+    ProviderProbe {
+        needs_project: false,
+        ..ProviderProbe::project(None, "docker")
+    }
+}
+
+/// Logs, shell, and stop commands for each running container; empty when docker is unavailable.
+fn load_docker_provider(cwd: &Path, _config: &DockerProviderConfig) -> Result<Vec<CommandEntry>> {
+    let raw = docker_ps_raw(cwd).unwrap_or_default();
+    let mut commands = Vec::new();
+    for container in parse_docker_ps(&raw) {
+        let description = format!("{} · {}", container.image, container.status);
+        for (action, template) in [
+            ("logs", format!("docker logs -f {}", container.id)),
+            ("exec", format!("docker exec -it {} sh", container.id)),
+            ("stop", format!("docker stop {}", container.id)),
+        ] {
+            commands.push(CommandEntry {
+                description: Some(description.clone()),
+                // An interactive shell needs the terminal, not the output pane.
+                inherit: action == "exec",
+                ..CommandEntry::new(
+                    format!("docker {action} {}", container.name),
+                    template,
+                    CommandSource::Provider("docker".to_string()),
+                )
+            });
+        }
+    }
+
+    Ok(commands)
+}
+
+struct DockerContainer {
+    id: String,
+    name: String,
+    image: String,
+    status: String,
+}
+
+fn docker_ps_raw(cwd: &Path) -> Option<String> {
+    let output = Command::new("docker")
+        .arg("ps")
+        .arg("--format")
+        .arg("{{.ID}}\t{{.Names}}\t{{.Image}}\t{{.Status}}")
+        .current_dir(cwd)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+fn parse_docker_ps(raw: &str) -> Vec<DockerContainer> {
+    raw.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t').map(str::trim);
+            let id = fields.next().filter(|id| is_shell_safe_arg(id))?;
+            let name = fields.next().filter(|name| !name.is_empty()).unwrap_or(id);
+            Some(DockerContainer {
+                id: id.to_string(),
+                name: name.to_string(),
+                image: fields.next().unwrap_or_default().to_string(),
+                status: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+const GIT_COMMANDS: &[(&str, &str)] = &[
+    ("status", "Show the working tree status"),
+    (
+        "pull --rebase",
+        "Fetch and rebase local commits onto upstream",
+    ),
+    (
+        "fetch --prune",
+        "Fetch and drop branches deleted on the remote",
+    ),
+    ("push", "Push the current branch"),
+    ("stash", "Stash uncommitted changes"),
+    ("stash pop", "Apply and drop the latest stash"),
+    ("log --oneline --graph -20", "Show the last 20 commits"),
+];
+
+fn probe_git_provider(cwd: &Path) -> ProviderProbe {
+    ProviderProbe::project(detect_git_root(cwd), "git")
+}
+
+/// Common git operations, `git switch` over the local branches, and one command per git alias;
+/// empty outside a repository.
+fn load_git_provider(cwd: &Path, _config: &GitProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_git_root(cwd) else {
+        return Ok(Vec::new());
+    };
+    let mut commands: Vec<CommandEntry> = GIT_COMMANDS
+        .iter()
+        .map(|(args, description)| {
+            git_command(format!("git {args}"), description.to_string(), &root)
+        })
+        .collect();
+
+    let branches: Vec<String> = git_output(
+        &root,
+        &["for-each-ref", "--format=%(refname:short)", "refs/heads"],
+    )
+    .unwrap_or_default()
+    .lines()
+    .filter(|branch| is_shell_safe_arg(branch))
+    .map(str::to_string)
+    .collect();
+    let current = git_output(&root, &["rev-parse", "--abbrev-ref", "HEAD"]);
+    let mut switch = git_command(
+        "git switch".to_string(),
+        "Switch to a local branch".to_string(),
+        &root,
+    );
+    switch.template = "git switch {{branch}}".to_string();
+    switch.params = vec![ParamSpec {
+        kind: if branches.is_empty() {
+            ParamType::Value
+        } else {
+            ParamType::Choice(branches)
+        },
+        default_value: current,
+        ..front_matter_param("branch", "Branch")
+    }];
+    commands.push(switch);
+
+    let aliases = git_output(&root, &["config", "--get-regexp", r"^alias\."]).unwrap_or_default();
+    for (alias, expansion) in parse_git_aliases(&aliases) {
+        let description = match expansion.strip_prefix('!') {
+            Some(shell) => format!("git alias: runs `{}`", shell.trim()),
+            None => format!("git alias for `git {expansion}`"),
+        };
+        commands.push(git_command(format!("git {alias}"), description, &root));
+    }
+
+    Ok(commands)
+}
+
+fn probe_ssh_provider(config: &SshProviderConfig) -> ProviderProbe {
+    ProviderProbe {
+        version_args: &["-V"],
+        ..ProviderProbe::project(ssh_config_path(config), "ssh")
+    }
+}
+
+/// `ssh <host>` for each `Host` in the ssh config, described by its `User`, `HostName`, and `Port`.
+/// They hand the terminal over, since ssh sessions are interactive.
+fn load_ssh_provider(config: &SshProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(path) = ssh_config_path(config) else {
+        return Ok(Vec::new());
+    };
+    let mut hosts = Vec::new();
+    read_ssh_config(&path, &mut hosts, 0);
+
+    let mut seen = BTreeSet::new();
+    let mut commands = Vec::new();
+    for host in hosts {
+        let wildcard = host.name.contains(['*', '?']);
+        let skip = if wildcard {
+            config.exclude_wildcards
+        } else {
+            !is_shell_safe_arg(&host.name)
+        };
+        if skip || host.name.starts_with('!') || !seen.insert(host.name.clone()) {
+            continue;
+        }
+        let target = format!(
+            "{}{}{}",
+            host.user.map(|user| format!("{user}@")).unwrap_or_default(),
+            host.hostname.as_deref().unwrap_or(&host.name),
+            host.port.map(|port| format!(":{port}")).unwrap_or_default(),
+        );
+        // A pattern is not a host to connect to, so ask for one it matches.
+        let (template, params) = if wildcard {
+            let prompt = format!("Host ({})", host.name);
+            (
+                "ssh {{host}}".to_string(),
+                vec![front_matter_param("host", &prompt)],
+            )
+        } else {
+            (format!("ssh {}", host.name), Vec::new())
+        };
+        commands.push(CommandEntry {
+            description: Some(target),
+            params,
+            inherit: true,
+            ..CommandEntry::new(
+                format!("ssh {}", host.name),
+                template,
+                CommandSource::Provider("ssh".to_string()),
+            )
+        });
+    }
+    Ok(commands)
+}
+
+#[derive(Debug, Default)]
+struct SshHost {
+    name: String,
+    user: Option<String>,
+    hostname: Option<String>,
+    port: Option<String>,
+}
+
+/// The configured ssh config file, when it exists.
+fn ssh_config_path(config: &SshProviderConfig) -> Option<PathBuf> {
+    expand_home_shorthand(&config.path).filter(|path| path.is_file())
+}
+
+/// Collects `Host` entries in file order, following `Include` (relative paths are under
+/// `~/.ssh`; `*` and `?` may appear in the file name) up to a few levels deep. Settings apply
+/// to every name of the `Host` line they follow.
+fn read_ssh_config(path: &Path, hosts: &mut Vec<SshHost>, depth: usize) {
+    let Ok(raw) = fs::read_to_string(path) else {
+        return;
+    };
+    let mut current = 0..0;
+    for line in raw.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((keyword, value)) = line
+            .split_once(|ch: char| ch.is_whitespace() || ch == '=')
+            .map(|(keyword, value)| (keyword, value.trim_start_matches([' ', '\t', '=']).trim()))
+        else {
+            continue;
+        };
+        match keyword.to_ascii_lowercase().as_str() {
+            "host" => {
+                let start = hosts.len();
+                hosts.extend(value.split_whitespace().map(|name| SshHost {
+                    name: name.trim_matches('"').to_string(),
+                    ..SshHost::default()
+                }));
+                current = start..hosts.len();
+            }
+            "match" => current = hosts.len()..hosts.len(),
+            "include" if depth < 4 => {
+                for pattern in value.split_whitespace() {
+                    for included in ssh_include_paths(pattern) {
+                        read_ssh_config(&included, hosts, depth + 1);
+                    }
+                }
+                current = hosts.len()..hosts.len();
+            }
+            setting @ ("user" | "hostname" | "port") => {
+                for host in &mut hosts[current.clone()] {
+                    let field = match setting {
+                        "user" => &mut host.user,
+                        "hostname" => &mut host.hostname,
+                        _ => &mut host.port,
+                    };
+                    // As in ssh, the first value wins.
+                    field.get_or_insert_with(|| value.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn ssh_include_paths(pattern: &str) -> Vec<PathBuf> {
+    let Some(mut path) = expand_home_shorthand(pattern) else {
+        return Vec::new();
+    };
+    if path.is_relative() {
+        let Some(home) = dirs::home_dir() else {
+            return Vec::new();
+        };
+        path = home.join(".ssh").join(path);
+    }
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !file_name.contains(['*', '?']) {
+        return vec![path];
+    }
+    let (Some(dir), Ok(glob)) = (path.parent(), GlobBuilder::new(&file_name).build()) else {
+        return Vec::new();
+    };
+    let matcher = glob.compile_matcher();
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| matcher.is_match(entry.file_name()))
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
+
+fn probe_gh_provider(cwd: &Path) -> ProviderProbe {
+    ProviderProbe::project(detect_git_root(cwd), "gh")
+}
+
+/// `gh workflow run` for each dispatchable workflow, `gh run watch` for recent runs, and
+/// `gh pr checks`, in a git repository with a github.com remote when `gh` is installed.
+fn load_gh_provider(cwd: &Path, config: &GhProviderConfig) -> Result<Vec<CommandEntry>> {
+    let Some(root) = detect_git_root(cwd) else {
+        return Ok(Vec::new());
+    };
+    let has_github_remote =
+        git_output(&root, &["remote", "-v"]).is_some_and(|remotes| remotes.contains("github.com"));
+    if !has_github_remote || Command::new("gh").arg("--version").output().is_err() {
+        return Ok(Vec::new());
+    }
+    let gh_command = |template: String, description: String| CommandEntry {
+        source: CommandSource::Provider("gh".to_string()),
+        ..git_command(template, description, &root)
+    };
+
+    let mut commands: Vec<CommandEntry> = github_workflows(&root)
+        .into_iter()
+        .map(|(file, name)| gh_command(format!("gh workflow run {file}"), name))
+        .collect();
+
+    if config.runs > 0 {
+        let output = Command::new("gh")
+            .args(["run", "list", "--limit", &config.runs.to_string(), "--json"])
+            .arg("databaseId,workflowName,displayTitle,headBranch,status,conclusion")
+            .current_dir(&root)
+            .output()
+            .ok()
+            .filter(|output| output.status.success());
+        let raw = output
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default();
+        for run in parse_gh_runs(&raw) {
+            commands.push(gh_command(
+                format!("gh run watch {}", run.id),
+                run.description,
+            ));
+        }
+    }
+
+    commands.push(gh_command(
+        "gh pr checks".to_string(),
+        "Show CI checks for the current branch's pull request".to_string(),
+    ));
+    Ok(commands)
+}
+
+/// `(file name, workflow name)` for workflows in `.github/workflows` that can be started by hand
+/// (they have a `workflow_dispatch` trigger), sorted by file name.
+fn github_workflows(root: &Path) -> Vec<(String, String)> {
+    let Ok(entries) = fs::read_dir(root.join(".github/workflows")) else {
+        return Vec::new();
+    };
+    let mut workflows: Vec<(String, String)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file = entry.file_name().to_string_lossy().into_owned();
+            let is_yaml = file.ends_with(".yml") || file.ends_with(".yaml");
+            if !is_yaml || !is_shell_safe_arg(&file) {
+                return None;
+            }
+            let raw = fs::read_to_string(entry.path()).ok()?;
+            if !raw.contains("workflow_dispatch") {
+                return None;
+            }
+            let name = raw
+                .lines()
+                .find_map(|line| line.strip_prefix("name:"))
+                .map(|name| name.trim().trim_matches(['"', '\'']).to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| file.clone());
+            Some((file, name))
+        })
+        .collect();
+    workflows.sort();
+    workflows
+}
+
+struct GhRun {
+    id: u64,
+    description: String,
+}
+
+/// Runs from `gh run list --json`, described as `workflow: title (branch) · status`.
+fn parse_gh_runs(raw: &str) -> Vec<GhRun> {
+    let Ok(serde_json::Value::Array(runs)) = serde_json::from_str(raw) else {
+        return Vec::new();
+    };
+    runs.iter()
+        .filter_map(|run| {
+            let field = |name: &str| run.get(name).and_then(|value| value.as_str()).unwrap_or("");
+            let status = match field("conclusion") {
+                "" => field("status"),
+                conclusion => conclusion,
+            };
+            Some(GhRun {
+                id: run.get("databaseId")?.as_u64()?,
+                description: format!(
+                    "{}: {} ({}) · {status}",
+                    field("workflowName"),
+                    field("displayTitle"),
+                    field("headBranch")
+                ),
+            })
+        })
+        .collect()
+}
+
+fn git_command(template: String, description: String, root: &Path) -> CommandEntry {
+    CommandEntry {
+        description: Some(description),
+        working_dir: Some(root.to_path_buf()),
+        ..CommandEntry::new(
+            template.clone(),
+            template,
+            CommandSource::Provider("git".to_string()),
+        )
+    }
+}
+
+/// The nearest directory with a `.git` entry (a directory, or a file in worktrees and submodules).
+fn detect_git_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// `(alias, expansion)` pairs from `git config --get-regexp ^alias\.`; aliases whose names are
+/// not safe to put on a command line are skipped.
+fn parse_git_aliases(raw: &str) -> Vec<(String, String)> {
+    raw.lines()
+        .filter_map(|line| {
+            let (key, expansion) = line.split_once(char::is_whitespace)?;
+            let alias = key.strip_prefix("alias.")?;
+            is_shell_safe_arg(alias).then(|| (alias.to_string(), expansion.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Where the project keeps Kubernetes manifests, and whether it is a kustomization.
+struct K8sManifests {
+    path: PathBuf,
+    kustomize: bool,
+}
+
+/// Manifests are the project, but a kubeconfig alone is enough for the provider to list commands.
+fn probe_kubectl_provider(cwd: &Path) -> ProviderProbe {
+    ProviderProbe {
+        needs_project: !kubeconfig_exists(),
+        version_args: &["version", "--client"],
+        ..ProviderProbe::project(
+            detect_k8s_manifests(cwd).map(|manifests| manifests.path),
+            "kubectl",
+        )
+    }
+}
 
-pub fn load_provider_commands(config: &ProvidersConfig, cwd: &Path) -> Result<Vec<CommandEntry>> {
-    let mut commands = Vec::new();
+/// kubectl commands when a kubeconfig or manifests are found; namespaces and contexts are asked
+/// from kubectl when `populate` is on.
+fn load_kubectl_provider(cwd: &Path, config: &KubectlProviderConfig) -> Result<Vec<CommandEntry>> {
+    let manifests = detect_k8s_manifests(cwd);
+    if manifests.is_none() && !kubeconfig_exists() {
+        return Ok(Vec::new());
+    }
+    let kubectl_lines = |args: &[&str]| -> Vec<String> {
+        if !config.populate {
+            return Vec::new();
+        }
+        Command::new("kubectl")
+            .args(args)
+            .current_dir(cwd)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(|line| line.trim().to_string())
+                    .filter(|line| is_shell_safe_arg(line))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let namespaces: Vec<String> =
+        kubectl_lines(&["get", "namespaces", "-o", "name", "--request-timeout=3s"])
+            .into_iter()
+            .map(|name| name.trim_start_matches("namespace/").to_string())
+            .collect();
+    let contexts = kubectl_lines(&["config", "get-contexts", "-o", "name"]);
+    let current_context = kubectl_lines(&["config", "current-context"])
+        .into_iter()
+        .next();
+    Ok(kubectl_commands(
+        &config.verbs,
+        manifests.as_ref(),
+        namespaces,
+        contexts,
+        current_context,
+    ))
+}
+
+fn kubectl_commands(
+    verbs: &[KubectlVerb],
+    manifests: Option<&K8sManifests>,
+    namespaces: Vec<String>,
+    contexts: Vec<String>,
+    current_context: Option<String>,
+) -> Vec<CommandEntry> {
+    let choice_or_value = |options: Vec<String>| {
+        if options.is_empty() {
+            ParamType::Value
+        } else {
+            ParamType::Choice(options)
+        }
+    };
+    let namespace = ParamSpec {
+        kind: choice_or_value(namespaces),
+        default_value: Some("default".to_string()),
+        ..front_matter_param("namespace", "Namespace")
+    };
+    let resource = ParamSpec {
+        placeholder: Some("deployment".to_string()),
+        ..front_matter_param("resource", "Resource type")
+    };
+    let name = front_matter_param("name", "Name");
+    let pod = front_matter_param("pod", "Pod");
+    let deployment = front_matter_param("deployment", "Deployment");
+    let context = ParamSpec {
+        kind: choice_or_value(contexts),
+        default_value: current_context,
+        ..front_matter_param("context", "Context")
+    };
+
+    let mut entries = Vec::new();
+    for verb in verbs {
+        let (command, description, template, params) = match verb {
+            KubectlVerb::Get => (
+                "kubectl get pods",
+                "List pods in a namespace",
+                "kubectl get pods -n {{namespace}}".to_string(),
+                vec![namespace.clone()],
+            ),
+            KubectlVerb::Describe => (
+                "kubectl describe",
+                "Show details of a resource",
+                "kubectl describe {{resource}} {{name}} -n {{namespace}}".to_string(),
+                vec![resource.clone(), name.clone(), namespace.clone()],
+            ),
+            KubectlVerb::Logs => (
+                "kubectl logs",
+                "Follow a pod's logs",
+                "kubectl logs -f {{pod}} -n {{namespace}}".to_string(),
+                vec![pod.clone(), namespace.clone()],
+            ),
+            KubectlVerb::Exec => (
+                "kubectl exec",
+                "Open a shell in a pod",
+                "kubectl exec -it {{pod}} -n {{namespace}} -- sh".to_string(),
+                vec![pod.clone(), namespace.clone()],
+            ),
+            KubectlVerb::Apply => {
+                let Some(manifests) = manifests else {
+                    continue;
+                };
+                let flag = if manifests.kustomize { "-k" } else { "-R -f" };
+                (
+                    "kubectl apply",
+                    "Apply the project's manifests",
+                    format!(
+                        "kubectl apply {flag} {}",
+                        shell_escape_arg(&manifests.path.to_string_lossy())
+                    ),
+                    Vec::new(),
+                )
+            }
+            KubectlVerb::Rollout => (
+                "kubectl rollout restart",
+                "Restart a deployment's pods",
+                "kubectl rollout restart deployment/{{deployment}} -n {{namespace}}".to_string(),
+                vec![deployment.clone(), namespace.clone()],
+            ),
+            KubectlVerb::UseContext => (
+                "kubectl use-context",
+                "Switch the current kubeconfig context",
+                "kubectl config use-context {{context}}".to_string(),
+                vec![context.clone()],
+            ),
+            KubectlVerb::Delete => (
+                "kubectl delete",
+                "Delete a resource",
+                "kubectl delete {{resource}} {{name}} -n {{namespace}}".to_string(),
+                vec![resource.clone(), name.clone(), namespace.clone()],
+            ),
+        };
+        entries.push(CommandEntry {
+            description: Some(description.to_string()),
+            params,
+            inherit: *verb == KubectlVerb::Exec,
+            ..CommandEntry::new(
+                command,
+                template,
+                CommandSource::Provider("kubectl".to_string()),
+            )
+        });
+    }
+    entries
+}
 
-    if config.artisan.enabled {
-        commands.extend(load_artisan_provider(cwd, &config.artisan)?);
+/// `$KUBECONFIG` (any of its files) or `~/.kube/config`.
+fn kubeconfig_exists() -> bool {
+    match std::env::var_os("KUBECONFIG") {
+        Some(paths) if !paths.is_empty() => {
+            std::env::split_paths(&paths).any(|path| path.is_file())
+        }
+        _ => dirs::home_dir().is_some_and(|home| home.join(".kube").join("config").is_file()),
     }
-    if config.composer.enabled {
-        commands.extend(load_composer_provider(cwd, &config.composer)?);
+}
+
+/// The nearest kustomization, or a `k8s`/`kubernetes` directory next to the project files.
+fn detect_k8s_manifests(start: &Path) -> Option<K8sManifests> {
+    start.ancestors().find_map(|dir| {
+        if ["kustomization.yaml", "kustomization.yml"]
+            .iter()
+            .any(|name| dir.join(name).is_file())
+        {
+            return Some(K8sManifests {
+                path: dir.to_path_buf(),
+                kustomize: true,
+            });
+        }
+        ["k8s", "kubernetes"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_dir())
+            .map(|path| K8sManifests {
+                kustomize: path.join("kustomization.yaml").is_file()
+                    || path.join("kustomization.yml").is_file(),
+                path,
+            })
+    })
+}
+
+const GRADLE_LIFECYCLE_TASKS: &[(&str, &str)] = &[
+    ("build", "Assembles and tests this project"),
+    ("clean", "Deletes the build directory"),
+    ("assemble", "Assembles the outputs of this project"),
+    ("check", "Runs all checks"),
+    ("test", "Runs the tests"),
+];
+
+const MAVEN_PHASES: &[(&str, &str)] = &[
+    ("validate", "Validate the project is correct"),
+    ("compile", "Compile the source code"),
+    ("test", "Run the unit tests"),
+    ("package", "Package the compiled code, e.g. as a JAR"),
+    ("verify", "Run integration tests and checks"),
+    ("install", "Install the package into the local repository"),
+    ("deploy", "Copy the package to the remote repository"),
+    ("clean", "Remove the build output"),
+    (
+        "clean install",
+        "Rebuild and install into the local repository",
+    ),
+];
+
+/// How deep subprojects are searched for build files below the Gradle root.
+const MAX_GRADLE_DEPTH: usize = 6;
+
+/// Gradle before Maven, as [`load_jvm_provider`] picks them.
+fn probe_jvm_provider(cwd: &Path) -> ProviderProbe {
+    let (root, program) = if let Some(root) = detect_gradle_root(cwd) {
+        let (program, _) = jvm_runner(&root, "gradlew", "gradle");
+        (Some(root), program)
+    } else if let Some(root) = detect_maven_root(cwd) {
+        let (program, _) = jvm_runner(&root, "mvnw", "mvn");
+        (Some(root), program)
+    } else {
+        (None, PathBuf::from("mvn"))
+    };
+    ProviderProbe::project(root, program)
+}
+
+/// Gradle lifecycle tasks plus the project's own, or Maven phases; Gradle wins when a project
+/// has both build files.
+fn load_jvm_provider(cwd: &Path, _config: &JvmProviderConfig) -> Result<Vec<CommandEntry>> {
+    if let Some(root) = detect_gradle_root(cwd) {
+        let (program, runner) = jvm_runner(&root, "gradlew", "gradle");
+        let mut tasks: Vec<(String, String)> = GRADLE_LIFECYCLE_TASKS
+            .iter()
+            .map(|(name, description)| (name.to_string(), description.to_string()))
+            .collect();
+        let cache = gradle_tasks_cache_path(&root);
+        for (name, description) in gradle_tasks(&root, &program, cache.as_deref())? {
+            match tasks.iter_mut().find(|(known, _)| *known == name) {
+                Some(task) if !description.is_empty() => task.1 = description,
+                Some(_) => {}
+                None => tasks.push((name, description)),
+            }
+        }
+        return Ok(tasks
+            .into_iter()
+            .map(|(name, description)| {
+                let description = if description.is_empty() {
+                    "Gradle task".to_string()
+                } else {
+                    description
+                };
+                jvm_command(
+                    format!("gradle {name}"),
+                    description,
+                    format!("{runner} {name}"),
+                    &root,
+                )
+            })
+            .collect());
     }
-    if config.justfile.enabled {
-        commands.extend(load_justfile_provider(cwd, &config.justfile)?);
+    if let Some(root) = detect_maven_root(cwd) {
+        let (_, runner) = jvm_runner(&root, "mvnw", "mvn");
+        return Ok(MAVEN_PHASES
+            .iter()
+            .map(|(phase, description)| {
+                jvm_command(
+                    format!("maven {phase}"),
+                    description.to_string(),
+                    format!("{runner} {phase}"),
+                    &root,
+                )
+            })
+            .collect());
     }
+    Ok(Vec::new())
+}
 
-    Ok(commands)
+fn jvm_command(name: String, description: String, template: String, root: &Path) -> CommandEntry {
+    CommandEntry {
+        description: Some(description),
+        working_dir: Some(root.to_path_buf()),
+        ..CommandEntry::new(name, template, CommandSource::Provider("jvm".to_string()))
+    }
 }
 
-fn load_artisan_provider(cwd: &Path, _config: &ArtisanProviderConfig) -> Result<Vec<CommandEntry>> {
-    let Some(root) = detect_laravel_root(cwd) else {
-        return Ok(Vec::new());
+/// The project's wrapper script when it has one, else the tool from PATH: the program to
+/// spawn and how templates call it.
+fn jvm_runner(root: &Path, wrapper: &str, tool: &str) -> (PathBuf, String) {
+    let wrapper = if cfg!(windows) {
+        format!("{wrapper}.cmd")
+    } else {
+        wrapper.to_string()
     };
+    let path = root.join(&wrapper);
+    if !path.is_file() {
+        return (PathBuf::from(tool), tool.to_string());
+    }
+    if cfg!(windows) {
+        (path, wrapper)
+    } else {
+        (path, format!("./{wrapper}"))
+    }
+}
 
-    let raw_list = artisan_list_raw(&root).unwrap_or_default();
-    let command_names = parse_artisan_commands(&raw_list);
-    let command_descriptions = artisan_descriptions(&root).unwrap_or_default();
+/// Discovered Gradle tasks per project, under the platform cache directory (`~/.cache/fzc/gradle-tasks/`).
+fn gradle_tasks_cache_dir() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("fzc").join("gradle-tasks"))
+}
 
-    let commands = command_names
-        .into_iter()
-        .map(|name| CommandEntry {
-            name: format!("artisan {name}"),
-            description: command_descriptions
-                .get(&name)
-                .filter(|desc| !desc.trim().is_empty())
-                .cloned()
-                .or_else(|| Some("Laravel artisan command".to_string())),
-            template: format!("php artisan {name} --ansi"),
-            params: Vec::new(),
-            source: CommandSource::Provider("artisan"),
-            working_dir: Some(root.clone()),
-        })
-        .collect();
+fn gradle_tasks_cache_path(root: &Path) -> Option<PathBuf> {
+    gradle_tasks_cache_dir()
+        .map(|dir| dir.join(format!("{}.txt", crate::snapshots::project_file_name(root))))
+}
 
-    Ok(commands)
+/// The file `name` keeps its results for the project at `cwd` in, for providers with a cache.
+fn provider_cache_path(name: &str, cwd: &Path) -> Option<PathBuf> {
+    match name {
+        "jvm" => gradle_tasks_cache_path(&detect_gradle_root(cwd)?),
+        _ => None,
+    }
 }
 
-fn load_justfile_provider(
-    cwd: &Path,
-    config: &JustfileProviderConfig,
-) -> Result<Vec<CommandEntry>> {
-    let Some(justfile_path) = resolve_provider_path(cwd, &config.path) else {
-        return Ok(Vec::new());
-    };
+/// When `name`'s cached results for `cwd` were written, in Unix seconds; `None` without a cache.
+pub fn provider_cached_at(name: &str, cwd: &Path) -> Option<u64> {
+    let modified = fs::metadata(provider_cache_path(name, cwd)?)
+        .ok()?
+        .modified()
+        .ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
 
-    let option_tokens = tokenize_provider_options(&config.options);
-    let raw_list = just_list_summary_raw(&justfile_path, cwd, &option_tokens).unwrap_or_default();
-    let recipes = parse_just_recipes(&raw_list);
+/// Deletes `name`'s cached results for `cwd`, so its next load asks the tool again.
+pub fn invalidate_provider_cache(name: &str, cwd: &Path) {
+    if let Some(path) = provider_cache_path(name, cwd) {
+        let _ = fs::remove_file(path);
+    }
+}
 
-    let commands = recipes
-        .into_iter()
-        .map(|recipe| CommandEntry {
-            name: format!("just {recipe}"),
-            description: Some("just recipe".to_string()),
-            template: build_just_command_template(&justfile_path, &option_tokens, &recipe),
-            params: Vec::new(),
-            source: CommandSource::Provider("justfile"),
-            working_dir: Some(cwd.to_path_buf()),
+/// A file fzc keeps results in, as listed by `/cache`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// What the file holds: `jvm` for Gradle tasks, `names` for the completion list.
+    pub kind: &'static str,
+    /// The directory name of the project it belongs to.
+    pub project: String,
+    /// When it was written, in Unix seconds.
+    pub written: u64,
+    pub size: u64,
+    pub path: PathBuf,
+}
+
+/// Every cache file of every project, newest first.
+pub fn cache_entries() -> Vec<CacheEntry> {
+    let dirs = [
+        ("jvm", gradle_tasks_cache_dir()),
+        ("names", crate::list::names_cache_dir()),
+    ];
+    let mut entries = Vec::new();
+    for (kind, dir) in dirs {
+        let Some(files) = dir.and_then(|dir| fs::read_dir(dir).ok()) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let Ok(metadata) = file.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let written = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since_epoch| since_epoch.as_secs());
+            entries.push(CacheEntry {
+                kind,
+                project: crate::snapshots::project_label(&file.file_name().to_string_lossy())
+                    .to_string(),
+                written,
+                size: metadata.len(),
+                path: file.path(),
+            });
+        }
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.written));
+    entries
+}
+
+/// Deletes one cache file so its next use asks the tool again; false when it could not be
+/// removed.
+pub fn remove_cache_entry(entry: &CacheEntry) -> bool {
+    fs::remove_file(&entry.path).is_ok()
+}
+
+/// The settings file's directory (the multi-project root), else the nearest build file's.
+fn detect_gradle_root(start: &Path) -> Option<PathBuf> {
+    let has_any = |dir: &Path, names: &[&str]| names.iter().any(|name| dir.join(name).is_file());
+    start
+        .ancestors()
+        .find(|dir| has_any(dir, &["settings.gradle", "settings.gradle.kts"]))
+        .or_else(|| {
+            start
+                .ancestors()
+                .find(|dir| has_any(dir, &["build.gradle", "build.gradle.kts"]))
         })
-        .collect();
+        .map(Path::to_path_buf)
+}
 
-    Ok(commands)
+fn detect_maven_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join("pom.xml").is_file())
+        .map(Path::to_path_buf)
 }
 
-fn load_composer_provider(
-    cwd: &Path,
-    _config: &ComposerProviderConfig,
-) -> Result<Vec<CommandEntry>> {
-    let Some(root) = detect_composer_root(cwd) else {
-        return Ok(Vec::new());
+/// Tasks from `gradle tasks --all`, which takes seconds; its output is kept in `cache` until a
+/// build file changes.
+fn gradle_tasks(
+    root: &Path,
+    program: &Path,
+    cache: Option<&Path>,
+) -> Result<Vec<(String, String)>> {
+    let key = gradle_cache_key(root);
+    if let Some(cached) = cache.and_then(|path| fs::read_to_string(path).ok())
+        && let Some((cached_key, raw)) = cached.split_once('\n')
+        && cached_key == key
+    {
+        return Ok(parse_gradle_tasks(raw));
+    }
+
+    let output = Command::new(program)
+        .args(["tasks", "--all", "--console=plain", "-q"])
+        .current_dir(root)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .with_context(|| format!("failed to run {} tasks", program.display()))?;
+    if !output.status.success() {
+        bail!(
+            "{} tasks exited with {}: {}",
+            program.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let raw = String::from_utf8_lossy(&output.stdout);
+    if let Some(path) = cache {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = fs::write(path, format!("{key}\n{raw}"));
+    }
+    Ok(parse_gradle_tasks(&raw))
+}
+
+/// A hash of every file whose change can add or remove Gradle tasks, by path, mtime, and size:
+/// the `*.gradle(.kts)` and settings files of all subprojects, `gradle.properties`, version
+/// catalogs, and everything in `buildSrc`.
+fn gradle_cache_key(root: &Path) -> String {
+    let mut files = Vec::new();
+    collect_gradle_build_files(root, root, MAX_GRADLE_DEPTH, &mut files);
+    files.sort_unstable();
+    format!(
+        "{:016x}",
+        crate::snapshots::fnv1a(files.join("\n").as_bytes())
+    )
+}
+
+fn collect_gradle_build_files(root: &Path, dir: &Path, depth: usize, files: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
     };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let in_build_src = relative.starts_with("buildSrc");
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            // Sources outside `buildSrc` and build output cannot declare tasks.
+            let skipped = name.starts_with('.')
+                || matches!(name.as_ref(), "build" | "node_modules" | "out")
+                || (name == "src" && !in_build_src);
+            if depth > 1 && !skipped {
+                collect_gradle_build_files(root, &path, depth - 1, files);
+            }
+            continue;
+        }
+        let build_file = name.ends_with(".gradle")
+            || name.ends_with(".gradle.kts")
+            || name.ends_with(".versions.toml")
+            || name == "gradle.properties";
+        if !build_file && !in_build_src {
+            continue;
+        }
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_nanos())
+            .unwrap_or_default();
+        files.push(format!(
+            "{}={modified}:{}",
+            relative.display(),
+            metadata.len()
+        ));
+    }
+}
 
-    let mut commands = Vec::new();
+/// Task names and descriptions from `gradle tasks` output, skipping section titles (underlined
+/// with dashes), rules, and hints.
+fn parse_gradle_tasks(raw: &str) -> Vec<(String, String)> {
+    let is_underline = |line: &str| {
+        let line = line.trim();
+        !line.is_empty() && line.chars().all(|ch| ch == '-')
+    };
+    let lines: Vec<&str> = raw.lines().collect();
+    let mut tasks = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim().is_empty()
+            || line.starts_with(char::is_whitespace)
+            || is_underline(line)
+            || lines.get(index + 1).is_some_and(|next| is_underline(next))
+        {
+            continue;
+        }
+        let (name, description) = match line.split_once(" - ") {
+            Some((name, description)) => (name, description.trim()),
+            None => (line.trim_end(), ""),
+        };
+        let valid = name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, ':' | '_' | '-' | '.'));
+        if valid && !tasks.iter().any(|(known, _)| known == name) {
+            tasks.push((name.to_string(), description.to_string()));
+        }
+    }
+    tasks
+}
 
-    for (name, description) in basic_composer_commands() {
-        commands.push(CommandEntry {
-            name: format!("composer {name}"),
-            description: Some(description.to_string()),
-            template: format!("composer {name}"),
-            params: Vec::new(),
-            source: CommandSource::Provider("composer"),
-            working_dir: Some(root.clone()),
-        });
+fn detect_npm_root(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        if dir.join("package.json").is_file() {
+            return Some(dir.to_path_buf());
+        }
     }
+    None
+}
 
-    for script in composer_scripts(&root) {
-        commands.push(CommandEntry {
-            name: format!("composer script:{script}"),
-            description: Some("composer script".to_string()),
-            template: format!("composer run-script {script}"),
-            params: Vec::new(),
-            source: CommandSource::Provider("composer"),
-            working_dir: Some(root.clone()),
-        });
+fn detect_deno_manifest(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find_map(|dir| {
+        ["deno.json", "deno.jsonc"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// The nearest `package.json` directory that also has a Bun lockfile or `bunfig.toml`.
+/// The directory holding the deno manifest, where its tasks run.
+fn deno_root(manifest: &Path, cwd: &Path) -> PathBuf {
+    manifest.parent().unwrap_or(cwd).to_path_buf()
+}
+
+fn detect_bun_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| {
+            dir.join("package.json").is_file()
+                && ["bun.lock", "bun.lockb", "bunfig.toml"]
+                    .iter()
+                    .any(|name| dir.join(name).is_file())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// The configured package manager, else the one the project's lockfile belongs to.
+fn npm_manager(root: &Path, config: &NpmProviderConfig) -> String {
+    config
+        .manager
+        .clone()
+        .unwrap_or_else(|| detect_package_manager(root).to_string())
+}
+
+fn detect_package_manager(root: &Path) -> &'static str {
+    if root.join("bun.lock").is_file() || root.join("bun.lockb").is_file() {
+        "bun"
+    } else if root.join("pnpm-lock.yaml").is_file() {
+        "pnpm"
+    } else if root.join("yarn.lock").is_file() {
+        "yarn"
+    } else {
+        "npm"
     }
+}
 
-    Ok(commands)
+/// Script names with their command bodies, sorted by name.
+fn parse_package_scripts_json(raw: &str) -> Vec<(String, String)> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(raw) else {
+        return Vec::new();
+    };
+    let Some(map) = value.get("scripts").and_then(|value| value.as_object()) else {
+        return Vec::new();
+    };
+
+    let mut scripts: Vec<(String, String)> = map
+        .iter()
+        .filter(|(name, _)| !name.trim().is_empty())
+        .map(|(name, body)| {
+            (
+                name.trim().to_string(),
+                body.as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect();
+    scripts.sort();
+    scripts
 }
 
 fn basic_composer_commands() -> &'static [(&'static str, &'static str)] {
@@ -128,6 +2394,58 @@ fn basic_composer_commands() -> &'static [(&'static str, &'static str)] {
     ]
 }
 
+fn basic_bundler_commands() -> &'static [(&'static str, &'static str)] {
+    &[
+        ("install", "Install the gems in the Gemfile"),
+        ("update", "Update gems to their latest allowed versions"),
+        ("outdated", "List gems with newer versions available"),
+        ("exec rspec", "Run the RSpec suite"),
+    ]
+}
+
+fn detect_python_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| {
+            ["pyproject.toml", "Pipfile", "tox.ini"]
+                .iter()
+                .any(|name| dir.join(name).is_file())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Whether a ruby project's commands go through Bundler.
+fn is_bundled(root: &Path) -> bool {
+    root.join("Gemfile").is_file()
+}
+
+fn detect_ruby_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| {
+            ["Gemfile", "Rakefile", "rakefile", "Rakefile.rb"]
+                .iter()
+                .any(|name| dir.join(name).is_file())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// `(task, description)` from `rake -AT`, whose lines look like `rake db:migrate  # Migrate`;
+/// tasks with arguments (`rake build[target]`) keep only the name.
+fn parse_rake_tasks(raw: &str) -> Vec<(String, String)> {
+    raw.lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("rake ")?;
+            let (spec, description) = match rest.split_once('#') {
+                Some((spec, description)) => (spec.trim(), description.trim()),
+                None => (rest.trim(), ""),
+            };
+            let task = spec.split(['[', ' ']).next()?;
+            is_shell_safe_arg(task).then(|| (task.to_string(), description.to_string()))
+        })
+        .collect()
+}
+
 fn detect_laravel_root(start: &Path) -> Option<PathBuf> {
     for dir in start.ancestors() {
         if dir.join("artisan").is_file() {
@@ -176,6 +2494,39 @@ fn parse_composer_scripts_json(raw: &str) -> Vec<String> {
     scripts.into_iter().collect()
 }
 
+/// A required `name` argument for `make:*` generators, plus the common option flags of the
+/// most used ones.
+fn artisan_generator_params(command: &str) -> Vec<ParamSpec> {
+    if !command.starts_with("make:") {
+        return Vec::new();
+    }
+    let flags: &[(&str, &str)] = match command {
+        "make:model" => &[
+            ("-m", "Create a migration?"),
+            ("-f", "Create a factory?"),
+            ("-s", "Create a seeder?"),
+            ("-c", "Create a controller?"),
+            ("-r", "Make the controller a resource controller?"),
+        ],
+        "make:controller" => &[
+            ("-r", "Resource controller?"),
+            ("--api", "API resource controller (no create/edit)?"),
+            ("-i", "Single-action invokable controller?"),
+        ],
+        _ => &[],
+    };
+
+    let mut params = vec![front_matter_param("name", "Name")];
+    params.extend(flags.iter().map(|(flag, prompt)| ParamSpec {
+        kind: ParamType::Flag,
+        required: false,
+        default_flag: Some(false),
+        group: Some("Options".to_string()),
+        ..front_matter_param(flag, prompt)
+    }));
+    params
+}
+
 fn artisan_list_raw(root: &Path) -> Option<String> {
     let output = Command::new("php")
         .arg("artisan")
@@ -327,7 +2678,289 @@ fn parse_just_recipes(raw: &str) -> Vec<String> {
         }
     }
 
-    recipes.into_iter().collect()
+    recipes.into_iter().collect()
+}
+
+/// Metadata read from `# @key value` comment lines directly above a recipe or at the top of a
+/// script.
+#[derive(Debug, Default)]
+struct FrontMatter {
+    name: Option<String>,
+    description: Option<String>,
+    params: Vec<ParamSpec>,
+    tags: Vec<String>,
+    /// Allowed values per param from `# @choices <param> a, b`.
+    choices: HashMap<String, Vec<String>>,
+}
+
+/// Parses front-matter blocks such as
+///
+/// ```text
+/// # @name Deploy app
+/// # @description Deploy to an environment
+/// # @param env Target environment
+/// # @choices env staging, production
+/// # @tags deploy, ops
+/// deploy env='staging':
+/// ```
+///
+/// keyed by recipe name, along with recipes whose header declares parameters. Params become
+/// positional recipe arguments; header defaults are pre-filled and `@choices` make a picker.
+fn parse_just_front_matter(raw: &str) -> HashMap<String, FrontMatter> {
+    let mut recipes = HashMap::new();
+    let mut pending = FrontMatter::default();
+    let mut has_pending = false;
+
+    for line in raw.lines() {
+        if let Some(comment) = line.strip_prefix('#') {
+            if parse_front_matter_comment(comment, &mut pending) {
+                has_pending = true;
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && has_pending {
+            continue;
+        }
+        if let Some(recipe) = just_recipe_header(line) {
+            let header_params = just_recipe_params(line);
+            if has_pending || !header_params.is_empty() {
+                let mut meta = std::mem::take(&mut pending);
+                meta.params = merge_just_params(header_params, meta.params, &meta.choices);
+                recipes.insert(recipe, meta);
+            }
+        }
+        pending = FrontMatter::default();
+        has_pending = false;
+    }
+
+    recipes
+}
+
+/// Applies one `@key value` comment (without its comment marker) to `meta`; false for other
+/// comments.
+fn parse_front_matter_comment(comment: &str, meta: &mut FrontMatter) -> bool {
+    let Some(rest) = comment.trim().strip_prefix('@') else {
+        return false;
+    };
+    let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let value = value.trim();
+    match key {
+        "name" if !value.is_empty() => meta.name = Some(value.to_string()),
+        "description" if !value.is_empty() => meta.description = Some(value.to_string()),
+        "param" => {
+            let (name, prompt) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+            if !name.is_empty() {
+                meta.params.push(front_matter_param(name, prompt.trim()));
+            }
+        }
+        "choices" => {
+            let (name, options) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+            let options: Vec<String> = options
+                .split(|ch: char| ch == ',' || ch.is_whitespace())
+                .map(|option| option.trim_matches(|ch| ch == '\'' || ch == '"'))
+                .filter(|option| !option.is_empty())
+                .map(str::to_string)
+                .collect();
+            if !name.is_empty() && !options.is_empty() {
+                meta.choices.insert(name.to_string(), options);
+            }
+        }
+        "tags" => meta.tags.extend(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string),
+        ),
+        _ => return false,
+    }
+    true
+}
+
+/// Front-matter in the comment block at the top of a script, after its shebang. Params are
+/// passed as positional arguments.
+fn parse_script_front_matter(raw: &str) -> FrontMatter {
+    let mut meta = FrontMatter::default();
+    for line in raw.lines().skip_while(|line| line.starts_with("#!")) {
+        let line = line.trim_start();
+        if line.is_empty() {
+            continue;
+        }
+        let Some(comment) = line.strip_prefix('#').or_else(|| line.strip_prefix("//")) else {
+            break;
+        };
+        parse_front_matter_comment(comment, &mut meta);
+    }
+    meta.params = merge_just_params(Vec::new(), std::mem::take(&mut meta.params), &meta.choices);
+    meta
+}
+
+fn just_recipe_header(line: &str) -> Option<String> {
+    if line.starts_with(char::is_whitespace) || line.contains(":=") {
+        return None;
+    }
+    let (head, _) = line.split_once(':')?;
+    let name = head.split_whitespace().next()?.trim_start_matches('@');
+    if name.is_empty()
+        || matches!(name, "set" | "alias" | "export" | "import" | "mod")
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// A recipe parameter from the header line and its default, if it is a plain literal.
+struct JustRecipeParam {
+    name: String,
+    default: Option<String>,
+    /// Defaulted or `*variadic`; `just` runs without it.
+    optional: bool,
+}
+
+/// Parameters declared after the recipe name, e.g. `deploy target='staging' *flags:`.
+fn just_recipe_params(line: &str) -> Vec<JustRecipeParam> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quote = None;
+    let mut depth = 0usize;
+    for ch in line.chars() {
+        match (quote, ch) {
+            (Some(open), _) if ch == open => {
+                quote = None;
+                token.push(ch);
+            }
+            (Some(_), _) => token.push(ch),
+            (None, '\'' | '"' | '`') => {
+                quote = Some(ch);
+                token.push(ch);
+            }
+            (None, '(') => {
+                depth += 1;
+                token.push(ch);
+            }
+            (None, ')') => {
+                depth = depth.saturating_sub(1);
+                token.push(ch);
+            }
+            (None, ':') if depth == 0 => break,
+            (None, _) if ch.is_whitespace() && depth == 0 => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            (None, _) => token.push(ch),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+        .into_iter()
+        .skip(1)
+        .filter_map(|token| {
+            let variadic = token.starts_with('*');
+            let token = token.trim_start_matches(['*', '+', '$']);
+            let (name, default) = match token.split_once('=') {
+                Some((name, raw)) => (name, Some(raw)),
+                None => (token, None),
+            };
+            if name.is_empty() {
+                return None;
+            }
+            let literal = default.and_then(|raw| {
+                let inner = raw
+                    .strip_prefix('\'')
+                    .and_then(|rest| rest.strip_suffix('\''))
+                    .or_else(|| {
+                        raw.strip_prefix('"')
+                            .and_then(|rest| rest.strip_suffix('"'))
+                    });
+                match inner {
+                    Some(inner) => Some(inner.to_string()),
+                    None if is_shell_safe_arg(raw) => Some(raw.to_string()),
+                    None => None,
+                }
+            });
+            Some(JustRecipeParam {
+                name: name.to_string(),
+                default: literal,
+                optional: variadic || default.is_some(),
+            })
+        })
+        .collect()
+}
+
+/// Header params in order, prompted with their `@param` text; `@param`s missing from the
+/// header keep their old place at the end.
+fn merge_just_params(
+    header: Vec<JustRecipeParam>,
+    mut declared: Vec<ParamSpec>,
+    choices: &HashMap<String, Vec<String>>,
+) -> Vec<ParamSpec> {
+    let mut params = Vec::new();
+    for recipe_param in header {
+        let mut param = match declared
+            .iter()
+            .position(|param| param.name == recipe_param.name)
+        {
+            Some(index) => declared.remove(index),
+            None => front_matter_param(&recipe_param.name, ""),
+        };
+        param.required = !recipe_param.optional;
+        param.default_value = recipe_param.default;
+        params.push(param);
+    }
+    params.extend(declared);
+
+    for param in &mut params {
+        if let Some(options) = choices.get(&param.name) {
+            let mut options = options.clone();
+            if let Some(default) = &param.default_value
+                && !options.contains(default)
+            {
+                options.insert(0, default.clone());
+            }
+            param.kind = ParamType::Choice(options);
+        }
+    }
+    params
+}
+
+fn front_matter_param(name: &str, prompt: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        kind: ParamType::Value,
+        prompt: if prompt.is_empty() {
+            name.to_string()
+        } else {
+            prompt.to_string()
+        },
+        placeholder: None,
+        description: None,
+        default_value: None,
+        value_value: None,
+        default_flag: None,
+        value_flag: None,
+        required: true,
+        prompt_in_tui: true,
+        group: None,
+        when: None,
+        eval: false,
+    }
+}
+
+fn with_tags(description: String, tags: &[String]) -> String {
+    if tags.is_empty() {
+        description
+    } else {
+        format!("{description} [{}]", tags.join(", "))
+    }
 }
 
 fn resolve_provider_path(cwd: &Path, raw_path: &str) -> Option<PathBuf> {
@@ -346,7 +2979,7 @@ fn resolve_provider_path(cwd: &Path, raw_path: &str) -> Option<PathBuf> {
     None
 }
 
-fn expand_home_shorthand(raw_path: &str) -> Option<PathBuf> {
+pub fn expand_home_shorthand(raw_path: &str) -> Option<PathBuf> {
     #[cfg(windows)]
     let starts_with_home =
         raw_path == "~" || raw_path.starts_with("~/") || raw_path.starts_with("~\\");
@@ -400,7 +3033,7 @@ fn build_just_command_template(
     pieces.join(" ")
 }
 
-fn shell_escape_arg(input: &str) -> String {
+pub fn shell_escape_arg(input: &str) -> String {
     if is_shell_safe_arg(input) {
         return input.to_string();
     }
@@ -421,11 +3054,15 @@ mod tests {
     use std::path::{Path, PathBuf};
     use std::time::{SystemTime, UNIX_EPOCH};
 
-    use crate::config::ComposerProviderConfig;
+    use crate::config::{
+        ComposerProviderConfig, ExternalProviderConfig, NpmProviderConfig, ProvidersConfig,
+    };
+    use crate::model::{CommandSource, ParamType};
 
     use super::{
-        build_just_command_template, expand_home_shorthand, parse_artisan_commands,
-        parse_artisan_descriptions_json, parse_composer_scripts_json, parse_just_recipes,
+        build_just_command_template, expand_home_shorthand, load_provider_commands,
+        parse_artisan_commands, parse_artisan_descriptions_json, parse_composer_scripts_json,
+        parse_external_commands, parse_just_front_matter, parse_just_recipes, providers_to_refresh,
         resolve_provider_path, shell_escape_arg, tokenize_provider_options,
     };
 
@@ -441,6 +3078,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn synthesizes_params_for_artisan_generators() {
+        let model = super::artisan_generator_params("make:model");
+        assert_eq!(model[0].name, "name");
+        assert!(model[0].required);
+        assert_eq!(model[1].flag_token(), "-m");
+        assert_eq!(super::artisan_generator_params("make:migration").len(), 1);
+        assert!(super::artisan_generator_params("migrate").is_empty());
+    }
+
     #[test]
     fn parses_artisan_command_descriptions_from_json() {
         let input = r#"{
@@ -464,6 +3111,75 @@ mod tests {
         assert_eq!(recipes, vec!["build", "check", "lint", "modx::task"]);
     }
 
+    #[test]
+    fn parses_just_front_matter_above_recipes() {
+        let input = "\
+# @name Deploy app
+# @description Deploy to an environment
+# @param env Target environment
+# @tags deploy, ops
+[no-cd]
+deploy env:
+    ./deploy.sh {{env}}
+
+# plain comment
+lint:
+    cargo clippy
+
+# @description stale block
+version := \"1\"
+test:
+    cargo test
+";
+        let meta = parse_just_front_matter(input);
+        assert_eq!(meta.len(), 1);
+        let deploy = &meta["deploy"];
+        assert_eq!(deploy.name.as_deref(), Some("Deploy app"));
+        assert_eq!(
+            deploy.description.as_deref(),
+            Some("Deploy to an environment")
+        );
+        assert_eq!(deploy.params.len(), 1);
+        assert_eq!(deploy.params[0].name, "env");
+        assert_eq!(deploy.params[0].prompt, "Target environment");
+        assert_eq!(deploy.tags, vec!["deploy", "ops"]);
+    }
+
+    #[test]
+    fn maps_just_header_defaults_and_choices_to_params() {
+        let input = "\
+# @param target Where to deploy
+# @choices target staging, production
+deploy target='staging' region=\"eu-west-1\" *flags: build
+    ./deploy.sh {{target}} {{region}} {{flags}}
+
+serve port url='http://localhost:8000':
+    ./serve {{port}}
+";
+        let meta = parse_just_front_matter(input);
+        let deploy = &meta["deploy"].params;
+        assert_eq!(deploy.len(), 3);
+        assert_eq!(deploy[0].prompt, "Where to deploy");
+        assert!(matches!(
+            &deploy[0].kind,
+            ParamType::Choice(options) if options == &["staging", "production"]
+        ));
+        assert_eq!(deploy[0].default_value.as_deref(), Some("staging"));
+        assert!(!deploy[0].required);
+        assert!(matches!(deploy[1].kind, ParamType::Value));
+        assert_eq!(deploy[1].default_value.as_deref(), Some("eu-west-1"));
+        assert_eq!(deploy[2].name, "flags");
+        assert!(!deploy[2].required && deploy[2].default_value.is_none());
+
+        let serve = &meta["serve"].params;
+        assert_eq!(serve[0].name, "port");
+        assert!(serve[0].required);
+        assert_eq!(
+            serve[1].default_value.as_deref(),
+            Some("http://localhost:8000")
+        );
+    }
+
     #[test]
     fn resolves_relative_provider_path_from_ancestors() {
         let root = make_temp_dir();
@@ -564,6 +3280,13 @@ mod tests {
                 .iter()
                 .any(|command| command.name == "composer script:test")
         );
+        let require = commands
+            .iter()
+            .find(|command| command.name == "composer require")
+            .unwrap();
+        assert_eq!(require.template, "composer require {{package}} {{dev}}");
+        assert!(require.params[0].required);
+        assert_eq!(require.params[1].flag_token(), "--dev");
         assert!(
             commands
                 .iter()
@@ -573,6 +3296,746 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    #[test]
+    fn parses_package_scripts_with_bodies() {
+        let raw = r#"{"scripts":{"test":"vitest","build":"vite build","":"x"}}"#;
+        assert_eq!(
+            super::parse_package_scripts_json(raw),
+            vec![
+                ("build".to_string(), "vite build".to_string()),
+                ("test".to_string(), "vitest".to_string()),
+            ]
+        );
+        assert!(super::parse_package_scripts_json("{}").is_empty());
+    }
+
+    #[test]
+    fn loads_npm_scripts_with_lockfile_manager() {
+        let root = make_temp_dir();
+        let nested = root.join("src/components");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join("package.json"), r#"{"scripts":{"dev":"vite"}}"#).unwrap();
+        fs::write(root.join("yarn.lock"), "").unwrap();
+
+        let config = NpmProviderConfig {
+            enabled: true,
+            ..NpmProviderConfig::default()
+        };
+        let commands = super::load_npm_provider(&nested, &config, false).unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].name, "yarn run dev");
+        assert_eq!(commands[0].template, "yarn run dev");
+        assert_eq!(commands[0].working_dir.as_ref(), Some(&root));
+        let probe = super::probe_npm_provider(&nested, &config);
+        assert_eq!(probe.root.as_ref(), Some(&root));
+        assert_eq!(probe.program, Some(PathBuf::from("yarn")));
+
+        let config = NpmProviderConfig {
+            manager: Some("pnpm".to_string()),
+            ..config
+        };
+        let commands = super::load_npm_provider(&nested, &config, false).unwrap();
+        assert_eq!(commands[0].name, "pnpm run dev");
+        let probe = super::probe_npm_provider(&nested, &config);
+        assert_eq!(probe.program, Some(PathBuf::from("pnpm")));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn loads_npm_workspace_scripts_with_package_badges() {
+        let root = make_temp_dir();
+        fs::create_dir_all(root.join("packages/web")).unwrap();
+        fs::create_dir_all(root.join("packages/api")).unwrap();
+        fs::create_dir_all(root.join("packages/web/node_modules/dep")).unwrap();
+        fs::write(
+            root.join("package.json"),
+            r#"{"workspaces":["packages/*"],"scripts":{"lint":"eslint ."}}"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("packages/web/package.json"),
+            r#"{"name":"@acme/web","scripts":{"dev":"vite"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("packages/api/package.json"),
+            r#"{"scripts":{"start":"node ."}}"#,
+        )
+        .unwrap();
+
+        let config = NpmProviderConfig {
+            enabled: true,
+            ..NpmProviderConfig::default()
+        };
+        let commands = super::load_npm_provider(&root, &config, false).unwrap();
+        let summary: Vec<(&str, &str, Option<&str>)> = commands
+            .iter()
+            .map(|command| {
+                (
+                    command.name.as_str(),
+                    command.template.as_str(),
+                    command.badge.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("npm run lint", "npm run lint", None),
+                (
+                    "npm run start -w packages/api",
+                    "npm run start -w packages/api",
+                    Some("packages/api")
+                ),
+                (
+                    "npm run dev -w @acme/web",
+                    "npm run dev -w @acme/web",
+                    Some("@acme/web")
+                ),
+            ]
+        );
+
+        fs::write(root.join("pnpm-lock.yaml"), "").unwrap();
+        let commands = super::load_npm_provider(&root, &config, false).unwrap();
+        assert_eq!(
+            commands[1].template,
+            "pnpm --filter ./packages/api run start"
+        );
+        assert_eq!(commands[2].template, "pnpm --filter @acme/web run dev");
+        fs::remove_file(root.join("pnpm-lock.yaml")).unwrap();
+        fs::write(root.join("yarn.lock"), "").unwrap();
+        let commands = super::load_npm_provider(&root, &config, false).unwrap();
+        assert_eq!(commands[1].template, "yarn --cwd packages/api run start");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn parses_docker_ps_rows_and_skips_unsafe_ids() {
+        let raw =
+            "3f2a9c\tweb\tnginx:1.27\tUp 5 minutes\n\nbad id\tx\ty\tz\n7b1e\t\tredis\tUp 1 hour\n";
+        let containers = super::parse_docker_ps(raw);
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0].id, "3f2a9c");
+        assert_eq!(containers[0].name, "web");
+        assert_eq!(containers[0].image, "nginx:1.27");
+        assert_eq!(containers[0].status, "Up 5 minutes");
+        assert_eq!(containers[1].name, "7b1e");
+    }
+
+    #[test]
+    fn parses_external_provider_json_into_labeled_commands() {
+        let raw = r#"[
+            {"name": "Build docs", "run": "make docs OUT={{out}}", "description": "Render docs",
+             "params": [{"name": "out", "prompt": "Output dir"}]}
+        ]"#;
+        let commands = parse_external_commands(raw, "make", Path::new("/tmp")).unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].name, "Build docs");
+        assert_eq!(commands[0].template, "make docs OUT={{out}}");
+        assert_eq!(commands[0].params[0].prompt, "Output dir");
+        assert!(matches!(&commands[0].source, CommandSource::Provider(name) if name == "make"));
+        assert!(parse_external_commands("not json", "make", Path::new("/tmp")).is_err());
+    }
+
+    #[test]
+    fn refresh_triggers_use_overrides_before_built_in_globs() {
+        let mut config = ProvidersConfig::default();
+        config.composer.enabled = true;
+        config.artisan.enabled = true;
+        config.docker.enabled = true;
+        assert_eq!(
+            providers_to_refresh(&config, "composer require laravel/horizon --dev"),
+            ["artisan", "composer"]
+        );
+        assert!(providers_to_refresh(&config, "composer test").is_empty());
+        assert_eq!(providers_to_refresh(&config, "docker stop web"), ["docker"]);
+
+        config.refresh.insert("docker".to_string(), Vec::new());
+        assert!(providers_to_refresh(&config, "docker stop web").is_empty());
+    }
+
+    #[test]
+    fn failing_external_provider_reports_its_name() {
+        let config = ProvidersConfig {
+            external: vec![ExternalProviderConfig {
+                name: "broken".to_string(),
+                command: "echo nope >&2; exit 3".to_string(),
+                alias: None,
+            }],
+            ..ProvidersConfig::default()
+        };
+        let err = load_provider_commands(&config, Path::new("/")).unwrap_err();
+        let message = format!("{err:#}");
+        assert!(message.contains("provider 'broken'"), "{message}");
+        assert!(message.contains("nope"), "{message}");
+    }
+
+    #[test]
+    fn hints_only_for_detected_disabled_providers() {
+        let root = make_temp_dir();
+        fs::write(root.join("composer.json"), "{}").unwrap();
+        fs::write(root.join("justfile"), "build:\n\techo hi\n").unwrap();
+
+        let mut config = ProvidersConfig::default();
+        config.justfile.enabled = true;
+        let hints = super::disabled_provider_hints(&config, &root);
+
+        assert_eq!(
+            hints,
+            vec!["composer.json detected - enable it with `composer = true` under [providers]"]
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn parses_git_aliases_and_skips_unsafe_names() {
+        let raw = "alias.co checkout\nalias.lg log --oneline --graph\nalias.up !git pull --rebase && git push\nalias.a;b status\n";
+        assert_eq!(
+            super::parse_git_aliases(raw),
+            vec![
+                ("co".to_string(), "checkout".to_string()),
+                ("lg".to_string(), "log --oneline --graph".to_string()),
+                (
+                    "up".to_string(),
+                    "!git pull --rebase && git push".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn git_provider_offers_common_commands_inside_a_repository() {
+        let root = make_temp_dir();
+        let nested = root.join("src");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(&nested).unwrap();
+
+        let entries = super::load_git_provider(&nested, &Default::default()).unwrap();
+        let pull = entries
+            .iter()
+            .find(|entry| entry.name == "git pull --rebase")
+            .unwrap();
+        assert_eq!(pull.working_dir.as_deref(), Some(root.as_path()));
+        let switch = entries
+            .iter()
+            .find(|entry| entry.name == "git switch")
+            .unwrap();
+        assert_eq!(switch.template, "git switch {{branch}}");
+        assert_eq!(switch.params[0].name, "branch");
+
+        fs::remove_dir_all(&root).unwrap();
+        let outside = make_temp_dir();
+        assert!(
+            super::load_git_provider(&outside, &Default::default())
+                .unwrap()
+                .is_empty()
+        );
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn kubectl_commands_follow_the_configured_verbs() {
+        use crate::config::KubectlVerb;
+
+        let manifests = super::K8sManifests {
+            path: PathBuf::from("/srv/app/k8s"),
+            kustomize: false,
+        };
+        let entries = super::kubectl_commands(
+            &[
+                KubectlVerb::Logs,
+                KubectlVerb::Apply,
+                KubectlVerb::UseContext,
+                KubectlVerb::Exec,
+            ],
+            Some(&manifests),
+            vec!["default".to_string(), "staging".to_string()],
+            vec!["dev".to_string(), "prod".to_string()],
+            Some("dev".to_string()),
+        );
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "kubectl logs",
+                "kubectl apply",
+                "kubectl use-context",
+                "kubectl exec"
+            ]
+        );
+        assert_eq!(
+            entries[0].template,
+            "kubectl logs -f {{pod}} -n {{namespace}}"
+        );
+        assert!(matches!(
+            &entries[0].params[1].kind,
+            ParamType::Choice(options) if options == &["default", "staging"]
+        ));
+        assert_eq!(entries[1].template, "kubectl apply -R -f /srv/app/k8s");
+        assert_eq!(entries[2].params[0].default_value.as_deref(), Some("dev"));
+        assert!(entries[3].inherit);
+        assert!(!entries[0].inherit);
+
+        let without_manifests =
+            super::kubectl_commands(&[KubectlVerb::Apply], None, Vec::new(), Vec::new(), None);
+        assert!(without_manifests.is_empty());
+    }
+
+    #[test]
+    fn parses_rake_tasks_with_and_without_descriptions() {
+        let raw = "\
+rake about                 # List versions of all Rails frameworks
+rake assets:precompile     # Compile all the assets named in config.assets.precompile
+rake build[target]         # Build a target
+rake internal:cleanup
+(in /srv/app)
+";
+        assert_eq!(
+            super::parse_rake_tasks(raw),
+            vec![
+                (
+                    "about".to_string(),
+                    "List versions of all Rails frameworks".to_string()
+                ),
+                (
+                    "assets:precompile".to_string(),
+                    "Compile all the assets named in config.assets.precompile".to_string()
+                ),
+                ("build".to_string(), "Build a target".to_string()),
+                ("internal:cleanup".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ruby_provider_lists_bundler_commands_for_a_gemfile() {
+        let root = make_temp_dir();
+        fs::write(root.join("Gemfile"), "source 'https://rubygems.org'\n").unwrap();
+        let nested = root.join("lib");
+        fs::create_dir_all(&nested).unwrap();
+
+        let entries = super::load_ruby_provider(&nested, &Default::default()).unwrap();
+        let add = entries
+            .iter()
+            .find(|entry| entry.name == "bundle add")
+            .unwrap();
+        assert_eq!(add.template, "bundle add {{gem}}");
+        assert_eq!(add.working_dir.as_deref(), Some(root.as_path()));
+        assert!(
+            entries
+                .iter()
+                .any(|entry| entry.name == "bundle exec rspec")
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn python_provider_reads_scripts_poe_tasks_and_tox_envs() {
+        let root = make_temp_dir();
+        fs::write(
+            root.join("pyproject.toml"),
+            r#"
+[project]
+name = "app"
+
+[project.scripts]
+serve = "app.cli:serve"
+
+[tool.poe.tasks]
+lint = "ruff check ."
+test = { cmd = "pytest", help = "Run the tests" }
+_setup = "echo hidden"
+"#,
+        )
+        .unwrap();
+        fs::write(root.join("poetry.lock"), "").unwrap();
+        fs::write(
+            root.join("tox.ini"),
+            "[tox]\nenvlist = py311, lint,\n    py{38,39}\n\n[testenv:docs]\ncommands = sphinx-build\n",
+        )
+        .unwrap();
+
+        let entries = super::load_python_provider(&root, &Default::default()).unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "poetry install",
+                "poetry update",
+                "poetry run serve",
+                "poetry run poe lint",
+                "poetry run poe test",
+                "tox",
+                "tox -e py311",
+                "tox -e lint",
+                "tox -e docs",
+            ]
+        );
+        assert_eq!(entries[4].description.as_deref(), Some("Run the tests"));
+        assert_eq!(entries[0].working_dir.as_deref(), Some(root.as_path()));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn deno_and_bun_providers_read_tasks_from_ancestor_manifests() {
+        let root = make_temp_dir();
+        let nested = root.join("src/routes");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            root.join("deno.jsonc"),
+            r#"{
+  // dev server
+  "tasks": {
+    "dev": "deno run -A --watch main.ts", /* inline */
+    "test": { "command": "deno test", "description": "Run the tests" },
+  },
+  "imports": { "std/": "https://jsr.io/@std//" },
+}"#,
+        )
+        .unwrap();
+        fs::write(
+            root.join("package.json"),
+            r#"{"scripts": {"build": "bun build ./index.ts"}}"#,
+        )
+        .unwrap();
+
+        let deno = super::load_deno_provider(&nested, &Default::default()).unwrap();
+        let names: Vec<&str> = deno.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["deno task dev", "deno task test"]);
+        assert_eq!(
+            deno[0].description.as_deref(),
+            Some("deno run -A --watch main.ts")
+        );
+        assert_eq!(deno[1].description.as_deref(), Some("Run the tests"));
+        assert_eq!(deno[0].working_dir.as_deref(), Some(root.as_path()));
+
+        assert!(
+            super::load_bun_provider(&nested, &Default::default())
+                .unwrap()
+                .is_empty()
+        );
+        fs::write(root.join("bun.lock"), "").unwrap();
+        let bun = super::load_bun_provider(&nested, &Default::default()).unwrap();
+        assert_eq!(bun[0].template, "bun run build");
+        assert_eq!(bun[0].working_dir.as_deref(), Some(root.as_path()));
+
+        // npm runs a Bun project's scripts with bun, and leaves them to the bun provider when
+        // that is enabled.
+        let npm = super::load_npm_provider(&nested, &Default::default(), false).unwrap();
+        assert_eq!(npm[0].template, "bun run build");
+        assert!(
+            super::load_npm_provider(&nested, &Default::default(), true)
+                .unwrap()
+                .is_empty()
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn parses_gradle_tasks_output() {
+        let raw = "\
+Application tasks
+-----------------
+bootRun - Runs this project as a Spring Boot application.
+
+Build tasks
+-----------
+assemble - Assembles the outputs of this project.
+app:jar - Assembles a jar archive containing the classes of the 'main' feature.
+
+Other tasks
+-----------
+compileJava - Compiles main Java source.
+prepareKotlinBuildScriptModel
+
+Rules
+-----
+Pattern: clean<TaskName>: Cleans the output files of a task.
+";
+        let tasks = super::parse_gradle_tasks(raw);
+        let names: Vec<&str> = tasks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "bootRun",
+                "assemble",
+                "app:jar",
+                "compileJava",
+                "prepareKotlinBuildScriptModel"
+            ]
+        );
+        assert_eq!(
+            tasks[0].1,
+            "Runs this project as a Spring Boot application."
+        );
+        assert_eq!(tasks[4].1, "");
+    }
+
+    #[test]
+    fn ssh_provider_lists_hosts_from_config_and_includes() {
+        let root = make_temp_dir();
+        let included = root.join("config.d");
+        fs::create_dir_all(&included).unwrap();
+        fs::write(
+            included.join("work.conf"),
+            "Host build\n  HostName build.internal\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("config"),
+            format!(
+                "# personal\nHost box vps\n  User me\n  HostName=203.0.113.7\n  Port 2222\n\
+                 Host *.example.com !bastion\n  User ops\n\
+                 Include {}/*.conf\nHost box\n  User other\n",
+                included.display()
+            ),
+        )
+        .unwrap();
+
+        let mut config = crate::config::SshProviderConfig {
+            path: root.join("config").to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let entries = super::load_ssh_provider(&config).unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["ssh box", "ssh vps", "ssh build"]);
+        assert_eq!(
+            entries[1].description.as_deref(),
+            Some("me@203.0.113.7:2222")
+        );
+        assert_eq!(entries[2].description.as_deref(), Some("build.internal"));
+        assert!(entries.iter().all(|entry| entry.inherit));
+
+        config.exclude_wildcards = false;
+        let entries = super::load_ssh_provider(&config).unwrap();
+        assert_eq!(entries[2].name, "ssh *.example.com");
+        assert_eq!(entries[2].template, "ssh {{host}}");
+        assert_eq!(entries[2].params[0].name, "host");
+        assert_eq!(entries.len(), 4, "negated patterns are always skipped");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn gh_workflows_need_dispatch_and_runs_are_described() {
+        let root = make_temp_dir();
+        let workflows = root.join(".github/workflows");
+        fs::create_dir_all(&workflows).unwrap();
+        fs::write(
+            workflows.join("deploy.yml"),
+            "name: \"Deploy\"\non:\n  workflow_dispatch:\njobs:\n  ship:\n    name: Ship\n",
+        )
+        .unwrap();
+        fs::write(workflows.join("ci.yaml"), "name: CI\non: [push]\n").unwrap();
+        fs::write(workflows.join("README.md"), "workflow_dispatch").unwrap();
+        assert_eq!(
+            super::github_workflows(&root),
+            vec![("deploy.yml".to_string(), "Deploy".to_string())]
+        );
+
+        let runs = super::parse_gh_runs(
+            r#"[
+  {"databaseId": 42, "workflowName": "CI", "displayTitle": "Fix login", "headBranch": "main",
+   "status": "completed", "conclusion": "failure"},
+  {"databaseId": 43, "workflowName": "Deploy", "displayTitle": "Ship", "headBranch": "main",
+   "status": "in_progress", "conclusion": ""},
+  {"workflowName": "no id"}
+]"#,
+        );
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, 42);
+        assert_eq!(runs[0].description, "CI: Fix login (main) · failure");
+        assert_eq!(runs[1].description, "Deploy: Ship (main) · in_progress");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scripts_provider_lists_executables_with_header_descriptions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = make_temp_dir();
+        let scripts = root.join("scripts");
+        let nested = root.join("app/src");
+        fs::create_dir_all(&scripts).unwrap();
+        fs::create_dir_all(&nested).unwrap();
+        let write = |name: &str, content: &str, mode: u32| {
+            fs::write(scripts.join(name), content).unwrap();
+            fs::set_permissions(scripts.join(name), fs::Permissions::from_mode(mode)).unwrap();
+        };
+        write(
+            "deploy.sh",
+            "#!/bin/sh\n# fzc: Deploy to staging\necho deploy\n",
+            0o755,
+        );
+        write("bootstrap", "#!/bin/sh\necho hi\n", 0o755);
+        write("notes.md", "# fzc: not a script\n", 0o644);
+        write(
+            "release",
+            "#!/bin/sh\n# @name Release app\n# @description Tag and push\n# @param version Version\n\
+             # @param channel\n# @choices channel stable, beta\n# @tags ops\necho \"$1\"\n\
+             # @param ignored after the header\n",
+            0o755,
+        );
+
+        let config = crate::config::ScriptsProviderConfig {
+            dirs: vec!["scripts".to_string(), "missing".to_string()],
+            ..Default::default()
+        };
+        let entries = super::load_scripts_provider(&nested, &config).unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["bootstrap", "deploy.sh", "Release app"]);
+        assert_eq!(entries[0].description.as_deref(), Some("scripts"));
+        assert_eq!(entries[1].description.as_deref(), Some("Deploy to staging"));
+        assert_eq!(entries[1].working_dir.as_deref(), Some(root.as_path()));
+        assert!(entries[1].template.ends_with("deploy.sh"));
+
+        let release = &entries[2];
+        assert_eq!(release.description.as_deref(), Some("Tag and push [ops]"));
+        assert!(
+            release
+                .template
+                .ends_with("release {{version}} {{channel}}")
+        );
+        assert!(release.quote_params);
+        assert_eq!(release.tags, vec!["ops"]);
+        let params: Vec<&str> = release
+            .params
+            .iter()
+            .map(|param| param.name.as_str())
+            .collect();
+        assert_eq!(params, vec!["version", "channel"]);
+        assert!(
+            matches!(&release.params[1].kind, ParamType::Choice(options) if options == &["stable", "beta"])
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gradle_tasks_are_cached_until_a_build_file_changes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = make_temp_dir();
+        fs::write(root.join("build.gradle"), "plugins { id 'java' }\n").unwrap();
+        let gradlew = root.join("gradlew");
+        fs::write(
+            &gradlew,
+            "#!/bin/sh\necho run >> runs.log\nprintf 'Build tasks\\n-----------\\nbootJar - Fat jar\\n'\n",
+        )
+        .unwrap();
+        fs::set_permissions(&gradlew, fs::Permissions::from_mode(0o755)).unwrap();
+        let cache = root.join("cache").join("tasks.txt");
+        let runs = || {
+            fs::read_to_string(root.join("runs.log"))
+                .unwrap()
+                .lines()
+                .count()
+        };
+
+        let tasks = super::gradle_tasks(&root, &gradlew, Some(&cache)).unwrap();
+        assert_eq!(tasks, vec![("bootJar".to_string(), "Fat jar".to_string())]);
+        assert_eq!(
+            super::gradle_tasks(&root, &gradlew, Some(&cache)).unwrap(),
+            tasks
+        );
+        assert_eq!(runs(), 1);
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(root.join("build.gradle"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        super::gradle_tasks(&root, &gradlew, Some(&cache)).unwrap();
+        assert_eq!(runs(), 2);
+
+        // Subprojects and buildSrc count too; sources elsewhere do not.
+        fs::create_dir_all(root.join("app/src/main")).unwrap();
+        fs::write(root.join("app/src/main/App.java"), "class App {}\n").unwrap();
+        super::gradle_tasks(&root, &gradlew, Some(&cache)).unwrap();
+        assert_eq!(runs(), 2);
+        fs::write(root.join("app/build.gradle.kts"), "plugins { java }\n").unwrap();
+        super::gradle_tasks(&root, &gradlew, Some(&cache)).unwrap();
+        assert_eq!(runs(), 3);
+        fs::create_dir_all(root.join("buildSrc/src/main/kotlin")).unwrap();
+        fs::write(root.join("buildSrc/src/main/kotlin/Conventions.kt"), "\n").unwrap();
+        super::gradle_tasks(&root, &gradlew, Some(&cache)).unwrap();
+        assert_eq!(runs(), 4);
+
+        fs::write(
+            &gradlew,
+            "#!/bin/sh\necho 'Could not resolve plugin' >&2\nexit 1\n",
+        )
+        .unwrap();
+        fs::write(root.join("settings.gradle"), "include 'app'\n").unwrap();
+        let err = super::gradle_tasks(&root, &gradlew, Some(&cache)).unwrap_err();
+        assert!(
+            err.to_string().contains("Could not resolve plugin"),
+            "{err}"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn gradle_cache_age_comes_from_the_cache_file_until_it_is_invalidated() {
+        let root = make_temp_dir();
+        fs::write(root.join("build.gradle"), "plugins { id 'java' }\n").unwrap();
+        let Some(cache) = super::gradle_tasks_cache_path(&root) else {
+            return;
+        };
+        fs::create_dir_all(cache.parent().unwrap()).unwrap();
+        fs::write(&cache, "key\n").unwrap();
+        let written = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&cache)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+
+        assert_eq!(super::provider_cached_at("jvm", &root), Some(1_700_000_000));
+        assert_eq!(super::provider_cached_at("npm", &root), None);
+        super::invalidate_provider_cache("jvm", &root);
+        assert!(!cache.exists());
+        assert_eq!(super::provider_cached_at("jvm", &root), None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn maven_projects_get_lifecycle_phases_from_the_pom_directory() {
+        let root = make_temp_dir();
+        let module = root.join("src").join("main");
+        fs::create_dir_all(&module).unwrap();
+        fs::write(root.join("pom.xml"), "<project/>").unwrap();
+
+        let entries = super::load_jvm_provider(&module, &Default::default()).unwrap();
+        let package = entries
+            .iter()
+            .find(|entry| entry.name == "maven package")
+            .unwrap();
+        assert_eq!(package.template, "mvn package");
+        assert_eq!(package.working_dir.as_deref(), Some(root.as_path()));
+        assert!(
+            entries
+                .iter()
+                .any(|entry| entry.name == "maven clean install")
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
     fn make_temp_dir() -> PathBuf {
         let nonce = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -0,0 +1,309 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+/// Spawns `command` with a new pseudo-terminal of `rows` x `cols` as its controlling terminal and
+/// stdin/stdout/stderr. Returns the child and the master side, which reads the child's output and
+/// takes its input; reads fail with EIO once the child and its descendants have exited.
+pub fn spawn(mut command: Command, rows: u16, cols: u16) -> Result<(Child, File)> {
+    // SAFETY: plain libc calls on a descriptor this function owns from here on.
+    let master = unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if fd < 0 {
+            return Err(io::Error::last_os_error()).context("failed to open a pseudo-terminal");
+        }
+        File::from_raw_fd(fd)
+    };
+    let fd = master.as_raw_fd();
+    // SAFETY: `fd` is the open master; ptsname's static buffer is copied out immediately.
+    let slave_path = unsafe {
+        if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+            return Err(io::Error::last_os_error()).context("failed to unlock the pseudo-terminal");
+        }
+        let name = libc::ptsname(fd);
+        if name.is_null() {
+            bail!("failed to name the pseudo-terminal");
+        }
+        std::ffi::CStr::from_ptr(name)
+            .to_string_lossy()
+            .into_owned()
+    };
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(&slave_path)
+        .with_context(|| format!("failed to open {slave_path}"))?;
+    resize(&slave, rows, cols);
+
+    command
+        .stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave));
+    // SAFETY: only async-signal-safe calls between fork and exec.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn()?;
+    // `command` still holds the slave descriptors; dropping it lets reads end with the child.
+    drop(command);
+    Ok((child, master))
+}
+
+/// Sets the terminal size; on the master side this also sends SIGWINCH to the command, so it
+/// redraws for the new size.
+pub fn resize(terminal: &File, rows: u16, cols: u16) {
+    let size = libc::winsize {
+        ws_row: rows.max(1),
+        ws_col: cols.max(1),
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCSWINSZ reads a winsize from the pointer; failure just keeps the default size.
+    unsafe {
+        libc::ioctl(terminal.as_raw_fd(), libc::TIOCSWINSZ, &size);
+    }
+}
+
+/// What the terminal shows after a chunk of output.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PtyLine {
+    /// A line ended by a newline.
+    Done(String),
+    /// The line the cursor is on, as redrawn so far (e.g. a progress bar after `\r`).
+    Partial(String),
+}
+
+/// Turns raw terminal output into lines: `\r\n` ends a line, a lone `\r` starts it over,
+/// backspace erases, and escape sequences other than colors are dropped.
+#[derive(Default)]
+pub struct LineAssembler {
+    current: Vec<u8>,
+    pending_cr: bool,
+    escape: Escape,
+}
+
+#[derive(Default)]
+enum Escape {
+    #[default]
+    None,
+    /// Just saw ESC.
+    Start,
+    /// Inside `ESC [`, with the bytes kept so far.
+    Csi(Vec<u8>),
+    /// Inside `ESC ]`, until BEL or ESC \.
+    Osc,
+}
+
+impl LineAssembler {
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<PtyLine> {
+        let mut lines = Vec::new();
+        let mut touched = false;
+        for &byte in bytes {
+            if self.take_escape_byte(byte) {
+                touched = true;
+                continue;
+            }
+            if std::mem::take(&mut self.pending_cr) && byte != b'\n' {
+                self.current.clear();
+            }
+            match byte {
+                b'\n' => {
+                    lines.push(PtyLine::Done(self.text()));
+                    self.current.clear();
+                    touched = false;
+                    continue;
+                }
+                b'\r' => self.pending_cr = true,
+                0x08 => self.erase_last_char(),
+                0x1b => self.escape = Escape::Start,
+                0x07 => {}
+                _ => self.current.push(byte),
+            }
+            touched = true;
+        }
+        if touched {
+            lines.push(PtyLine::Partial(self.text()));
+        }
+        lines
+    }
+
+    /// The unfinished last line once output has ended.
+    pub fn finish(&mut self) -> Option<String> {
+        (!self.current.is_empty()).then(|| {
+            let text = self.text();
+            self.current.clear();
+            text
+        })
+    }
+
+    /// Backspace: drops the last character, all of its UTF-8 bytes, but not color sequences
+    /// after it.
+    fn erase_last_char(&mut self) {
+        let mut last = None;
+        let mut index = 0;
+        while index < self.current.len() {
+            if self.current[index] == 0x1b {
+                // Kept sequences are always complete `ESC [ ... m`.
+                index += self.current[index..]
+                    .iter()
+                    .position(|&byte| byte == b'm')
+                    .map_or(self.current.len() - index, |end| end + 1);
+                continue;
+            }
+            let len = match self.current[index] {
+                0xf0.. => 4,
+                0xe0.. => 3,
+                0xc0.. => 2,
+                _ => 1,
+            };
+            let end = (index + len).min(self.current.len());
+            last = Some(index..end);
+            index = end;
+        }
+        if let Some(range) = last {
+            self.current.drain(range);
+        }
+    }
+
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.current).into_owned()
+    }
+
+    /// Consumes `byte` if it belongs to an escape sequence; SGR color sequences are kept.
+    fn take_escape_byte(&mut self, byte: u8) -> bool {
+        match &mut self.escape {
+            Escape::None => return false,
+            Escape::Start => {
+                self.escape = match byte {
+                    b'[' => Escape::Csi(Vec::new()),
+                    b']' => Escape::Osc,
+                    _ => Escape::None,
+                };
+            }
+            Escape::Csi(params) => {
+                if (0x40..=0x7e).contains(&byte) {
+                    // Cursor moves and erases are dropped: after `\r` the line is redrawn anyway.
+                    if byte == b'm' {
+                        self.current.extend_from_slice(b"\x1b[");
+                        self.current.extend_from_slice(params);
+                        self.current.push(b'm');
+                    }
+                    self.escape = Escape::None;
+                } else {
+                    params.push(byte);
+                }
+            }
+            Escape::Osc => {
+                if byte == 0x07 || byte == b'\\' {
+                    self.escape = Escape::None;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Bytes a key sends to a terminal program, or `None` for keys fzc keeps for itself.
+pub fn key_bytes(key: crossterm::event::KeyEvent) -> Option<Vec<u8>> {
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    let bytes = match key.code {
+        KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let upper = ch.to_ascii_uppercase();
+            if !upper.is_ascii_uppercase() {
+                return None;
+            }
+            vec![upper as u8 - b'@']
+        }
+        KeyCode::Char(ch) => ch.to_string().into_bytes(),
+        KeyCode::Enter => b"\r".to_vec(),
+        KeyCode::Tab => b"\t".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Up => b"\x1b[A".to_vec(),
+        KeyCode::Down => b"\x1b[B".to_vec(),
+        KeyCode::Right => b"\x1b[C".to_vec(),
+        KeyCode::Left => b"\x1b[D".to_vec(),
+        KeyCode::Home => b"\x1b[H".to_vec(),
+        KeyCode::End => b"\x1b[F".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::process::Command;
+
+    use super::{LineAssembler, PtyLine, resize, spawn};
+
+    #[test]
+    fn carriage_returns_redraw_the_current_line() {
+        let mut lines = LineAssembler::default();
+        assert_eq!(
+            lines.feed(b"fetching\r\nprogress 10%"),
+            [
+                PtyLine::Done("fetching".to_string()),
+                PtyLine::Partial("progress 10%".to_string()),
+            ]
+        );
+        assert_eq!(
+            lines.feed(b"\r\x1b[2Kprogress \x1b[32m100%\x1b[0m"),
+            [PtyLine::Partial("progress \x1b[32m100%\x1b[0m".to_string())]
+        );
+        assert_eq!(
+            lines.feed(b"\r\n"),
+            [PtyLine::Done("progress \x1b[32m100%\x1b[0m".to_string())]
+        );
+        assert_eq!(lines.feed(b"ab\x08c"), [PtyLine::Partial("ac".to_string())]);
+        assert_eq!(lines.finish().as_deref(), Some("ac"));
+        assert_eq!(
+            lines.feed("grün\x1b[0m\x08e".as_bytes()),
+            [PtyLine::Partial("grü\x1b[0me".to_string())]
+        );
+        assert_eq!(
+            lines.feed("\r\n→\x08".as_bytes()),
+            [
+                PtyLine::Done("grü\x1b[0me".to_string()),
+                PtyLine::Partial(String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_see_a_terminal() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("test -t 0 && test -t 1 && stty size");
+        let (mut child, mut master) = spawn(command, 24, 100).unwrap();
+        let mut output = Vec::new();
+        // Reading ends with EIO once the child has exited.
+        let _ = master.read_to_end(&mut output);
+        assert!(child.wait().unwrap().success());
+        assert_eq!(String::from_utf8_lossy(&output).trim(), "24 100");
+    }
+
+    #[test]
+    fn resizing_the_master_reaches_the_command() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("stty -echo; read line; stty size");
+        let (mut child, mut master) = spawn(command, 24, 100).unwrap();
+        resize(&master, 30, 120);
+        master.write_all(b"go\n").unwrap();
+        let mut output = Vec::new();
+        let _ = master.read_to_end(&mut output);
+        assert!(child.wait().unwrap().success());
+        assert!(String::from_utf8_lossy(&output).trim().ends_with("30 120"));
+    }
+}
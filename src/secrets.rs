@@ -0,0 +1,149 @@
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::{SecretsBackend, SecretsConfig};
+use crate::provider::expand_home_shorthand;
+
+const PREFIX: &str = "{{secret:";
+
+/// Names of the `{{secret:name}}` placeholders in `template`, in order of first use.
+pub fn names(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(PREFIX) {
+        let after = &rest[start + PREFIX.len()..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        let name = after[..end].to_string();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &after[end + 2..];
+    }
+    names
+}
+
+/// The variable a secret is passed in: `deploy-token` becomes `FZC_SECRET_DEPLOY_TOKEN`.
+pub fn env_var(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("FZC_SECRET_{name}")
+}
+
+/// Replaces each `{{secret:name}}` with a reference to its variable, so the command line that
+/// is shown, recorded, and passed to the shell never holds the value itself.
+pub fn substitute(template: &str) -> String {
+    let mut output = template.to_string();
+    for name in names(template) {
+        #[cfg(target_os = "windows")]
+        let reference = format!("%{}%", env_var(&name));
+        #[cfg(not(target_os = "windows"))]
+        let reference = format!("${{{}}}", env_var(&name));
+        output = output.replace(&format!("{PREFIX}{name}}}}}"), &reference);
+    }
+    output
+}
+
+/// Fetches one secret from the configured backend.
+pub fn fetch(config: &SecretsConfig, name: &str) -> Result<String> {
+    let Some(backend) = config.backend else {
+        bail!("{{{{secret:{name}}}}} needs a [secrets] backend (pass, age, or keychain)");
+    };
+    let value = match backend {
+        SecretsBackend::Pass => {
+            let entry = format!("{}{name}", config.prefix);
+            let output = run(Command::new("pass").arg("show").arg(&entry), "pass")?;
+            output.lines().next().unwrap_or_default().to_string()
+        }
+        SecretsBackend::Age => {
+            let file = config_path(config.file.as_deref(), "file")?;
+            let identity = config_path(config.identity.as_deref(), "identity")?;
+            let output = run(
+                Command::new("age")
+                    .arg("--decrypt")
+                    .arg("--identity")
+                    .arg(identity)
+                    .arg(&file),
+                "age",
+            )?;
+            let table: toml::Table = toml::from_str(&output)
+                .with_context(|| format!("{} does not decrypt to TOML", file.display()))?;
+            match table.get(name) {
+                Some(toml::Value::String(value)) => value.clone(),
+                Some(_) => bail!("secret '{name}' in {} is not a string", file.display()),
+                None => bail!("no secret '{name}' in {}", file.display()),
+            }
+        }
+        SecretsBackend::Keychain => {
+            #[cfg(target_os = "macos")]
+            let output = run(
+                Command::new("security")
+                    .args(["find-generic-password", "-w", "-s", &config.service, "-a"])
+                    .arg(name),
+                "security",
+            )?;
+            #[cfg(not(target_os = "macos"))]
+            let output = run(
+                Command::new("secret-tool")
+                    .args(["lookup", "service", &config.service, "account"])
+                    .arg(name),
+                "secret-tool",
+            )?;
+            output.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if value.is_empty() {
+        bail!("secret '{name}' is empty");
+    }
+    Ok(value)
+}
+
+fn config_path(raw: Option<&str>, key: &str) -> Result<std::path::PathBuf> {
+    let raw = raw.with_context(|| format!("[secrets] backend = \"age\" needs `{key}`"))?;
+    expand_home_shorthand(raw).with_context(|| format!("cannot expand [secrets] {key}: {raw}"))
+}
+
+/// Runs a backend tool and returns its stdout; its stderr (e.g. "not in the password store")
+/// becomes the error.
+fn run(command: &mut Command, tool: &str) -> Result<String> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("failed to run {tool}; is it installed?"))?;
+    if !output.status.success() {
+        bail!(
+            "{tool} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{env_var, names, substitute};
+
+    #[test]
+    fn secret_placeholders_become_variable_references() {
+        let template = "curl -H \"Authorization: Bearer {{secret:deploy-token}}\" {{url}} \
+                        && echo {{secret:deploy-token}} {{secret:other}}";
+        assert_eq!(names(template), ["deploy-token", "other"]);
+        assert_eq!(env_var("deploy-token"), "FZC_SECRET_DEPLOY_TOKEN");
+        #[cfg(not(target_os = "windows"))]
+        assert_eq!(
+            substitute(template),
+            "curl -H \"Authorization: Bearer ${FZC_SECRET_DEPLOY_TOKEN}\" {{url}} \
+             && echo ${FZC_SECRET_DEPLOY_TOKEN} ${FZC_SECRET_OTHER}"
+        );
+    }
+}
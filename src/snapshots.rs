@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{command_provider_name, command_usage_key};
+use crate::history;
+use crate::model::CommandEntry;
+
+/// Oldest snapshots are dropped once a project has this many.
+const MAX_SNAPSHOTS: usize = 50;
+
+/// The catalog of one project at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatalogSnapshot {
+    /// Unix seconds.
+    pub timestamp: u64,
+    /// Command template by usage key (`provider::name`).
+    pub commands: BTreeMap<String, String>,
+}
+
+impl CatalogSnapshot {
+    /// The catalog without the `lazy` providers, which load on demand and so would come and go
+    /// between snapshots.
+    pub fn new(commands: &[CommandEntry], lazy: &[String], timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            commands: commands
+                .iter()
+                .filter(|command| {
+                    !lazy
+                        .iter()
+                        .any(|name| name == command_provider_name(command))
+                })
+                .map(|command| (command_usage_key(command), command.template.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SnapshotStore {
+    #[serde(default)]
+    snapshots: Vec<CatalogSnapshot>,
+}
+
+/// One file per project under `~/.config/fzc/snapshots/`, named after its path.
+pub fn snapshots_path(project: &Path) -> Option<PathBuf> {
+    let config_root = dirs::config_dir()?;
+    Some(
+        config_root
+            .join("fzc")
+            .join("snapshots")
+            .join(format!("{}.toml", project_file_name(project))),
+    )
+}
+
+/// A file name that stands for `project`'s path, for per-project state files: its directory name
+/// for readability and a hash of its canonical path, so projects with the same name (or paths that
+/// only differ in punctuation) get their own files.
+pub fn project_file_name(project: &Path) -> String {
+    let canonical = project
+        .canonicalize()
+        .unwrap_or_else(|_| project.to_path_buf());
+    let name: String = canonical
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('_');
+    let name = if name.is_empty() { "root" } else { name };
+    format!(
+        "{name}-{:016x}",
+        fnv1a(canonical.to_string_lossy().as_bytes())
+    )
+}
+
+/// The directory name a [`project_file_name`] (with or without an extension) was made from.
+pub fn project_label(file_name: &str) -> &str {
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+    stem.rsplit_once('-')
+        .filter(|(_, hash)| hash.len() == 16 && hash.chars().all(|ch| ch.is_ascii_hexdigit()))
+        .map_or(stem, |(name, _)| name)
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` stays the same across Rust releases, so file names
+/// derived from it do too.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Recorded snapshots, oldest first; a missing or unreadable file has none.
+pub fn load(path: &Path) -> Vec<CatalogSnapshot> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str::<SnapshotStore>(&content).ok())
+        .map(|store| store.snapshots)
+        .unwrap_or_default()
+}
+
+/// Appends `snapshot` unless the catalog is unchanged since the newest one; returns whether it
+/// was written.
+pub fn record(path: &Path, snapshot: CatalogSnapshot) -> Result<bool> {
+    let mut snapshots = load(path);
+    if snapshots
+        .last()
+        .is_some_and(|last| last.commands == snapshot.commands)
+    {
+        return Ok(false);
+    }
+    snapshots.push(snapshot);
+    let overflow = snapshots.len().saturating_sub(MAX_SNAPSHOTS);
+    snapshots.drain(..overflow);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create snapshot directory {}", parent.display()))?;
+    }
+    let serialized = toml::to_string(&SnapshotStore { snapshots })
+        .context("failed to serialize catalog snapshots")?;
+    fs::write(path, serialized)
+        .with_context(|| format!("failed to write catalog snapshots {}", path.display()))?;
+    Ok(true)
+}
+
+/// The snapshot to compare `current` with: the newest one taken at least `age` seconds before
+/// `now`, or without `age` the newest one that differs from `current`.
+pub fn baseline<'a>(
+    snapshots: &'a [CatalogSnapshot],
+    current: &CatalogSnapshot,
+    age: Option<u64>,
+    now: u64,
+) -> Option<&'a CatalogSnapshot> {
+    match age {
+        Some(age) => snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.timestamp.saturating_add(age) <= now),
+        None => snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.commands != current.commands),
+    }
+}
+
+/// `fzc catalog diff`: added (`+`), removed (`-`), and changed (`~`) commands.
+pub fn render_diff(old: &CatalogSnapshot, new: &CatalogSnapshot, now: u64) -> String {
+    let mut out = format!(
+        "Changes since the snapshot from {} ago ({} commands then, {} now)\n",
+        history::format_age(now.saturating_sub(old.timestamp)),
+        old.commands.len(),
+        new.commands.len()
+    );
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for (key, template) in &new.commands {
+        match old.commands.get(key) {
+            None => {
+                added += 1;
+                out.push_str(&format!("+ {key}  {template}\n"));
+            }
+            Some(before) if before != template => {
+                changed += 1;
+                out.push_str(&format!(
+                    "~ {key}\n    was: {before}\n    now: {template}\n"
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, template) in &old.commands {
+        if !new.commands.contains_key(key) {
+            removed += 1;
+            out.push_str(&format!("- {key}  {template}\n"));
+        }
+    }
+    out.push_str(&format!(
+        "{added} added, {removed} removed, {changed} changed\n"
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use super::{
+        CatalogSnapshot, baseline, load, project_file_name, project_label, record, render_diff,
+    };
+    use crate::model::{CommandEntry, CommandSource};
+
+    fn snapshot(timestamp: u64, commands: &[(&str, &str)]) -> CatalogSnapshot {
+        CatalogSnapshot {
+            timestamp,
+            commands: commands
+                .iter()
+                .map(|(key, template)| (key.to_string(), template.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn project_file_names_tell_similar_paths_apart() {
+        let name = project_file_name(std::path::Path::new("/srv/a-b/api"));
+        assert!(name.starts_with("api-"), "{name}");
+        assert_ne!(
+            name,
+            project_file_name(std::path::Path::new("/srv/a/b/api"))
+        );
+        assert_ne!(
+            name,
+            project_file_name(std::path::Path::new("/srv/a_b/api"))
+        );
+        assert_eq!(
+            name,
+            project_file_name(std::path::Path::new("/srv/a-b/api"))
+        );
+        assert!(project_file_name(std::path::Path::new("/")).starts_with("root-"));
+        assert_eq!(project_label(&format!("{name}.txt")), "api");
+        assert_eq!(project_label("my-app-0123456789abcdef.txt"), "my-app");
+        assert_eq!(project_label("legacy_name.txt"), "legacy_name");
+    }
+
+    #[test]
+    fn snapshots_leave_out_lazy_providers() {
+        let commands = [
+            CommandEntry::new("deploy", "deploy", CommandSource::Config),
+            CommandEntry::new(
+                "bazel build //app",
+                "bazel build //app",
+                CommandSource::Provider("bazel".to_string()),
+            ),
+        ];
+        assert_eq!(
+            CatalogSnapshot::new(&commands, &["bazel".to_string()], 100),
+            snapshot(100, &[("config::deploy", "deploy")])
+        );
+    }
+
+    #[test]
+    fn unchanged_catalogs_are_not_recorded_twice() {
+        let dir = std::env::temp_dir().join(format!("fzc-snapshots-{}", std::process::id()));
+        let path = dir.join("project.toml");
+        let _ = fs::remove_dir_all(&dir);
+
+        let first = snapshot(100, &[("config::deploy", "deploy")]);
+        assert!(record(&path, first.clone()).unwrap());
+        assert!(!record(&path, snapshot(200, &[("config::deploy", "deploy")])).unwrap());
+        assert!(record(&path, snapshot(300, &[])).unwrap());
+        let snapshots = load(&path);
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0], first);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn diffs_against_the_previous_or_an_older_snapshot() {
+        let snapshots = vec![
+            snapshot(0, &[("artisan::queue:work", "php artisan queue:work")]),
+            snapshot(
+                86_400 * 5,
+                &[("config::deploy", "deploy"), ("npm::lint", "npm run lint")],
+            ),
+            snapshot(
+                86_400 * 9,
+                &[
+                    ("config::deploy", "deploy --env={{env}}"),
+                    ("npm::lint", "npm run lint"),
+                ],
+            ),
+        ];
+        let now = 86_400 * 10;
+        let current = snapshots[2].clone();
+
+        let previous = baseline(&snapshots, &current, None, now).unwrap();
+        assert_eq!(previous.timestamp, 86_400 * 5);
+        let diff = render_diff(previous, &current, now);
+        assert!(diff.contains("~ config::deploy\n    was: deploy\n    now: deploy --env={{env}}"));
+        assert!(diff.ends_with("0 added, 0 removed, 1 changed\n"));
+
+        let week = baseline(&snapshots, &current, Some(86_400 * 7), now).unwrap();
+        assert_eq!(week.timestamp, 0);
+        let diff = render_diff(week, &current, now);
+        assert!(diff.starts_with("Changes since the snapshot from 10d ago"));
+        assert!(diff.contains("+ npm::lint  npm run lint\n"));
+        assert!(diff.contains("- artisan::queue:work  php artisan queue:work\n"));
+        assert!(baseline(&snapshots, &current, Some(86_400 * 30), now).is_none());
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, bail};
+use ratatui::style::Color;
+
+use crate::config::ThemeConfig;
+
+/// Colors the TUI draws with. RGB values are downgraded on terminals with fewer colors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub border: Color,
+    pub border_active: Color,
+    /// Background of the selected row in lists and pickers.
+    pub highlight_bg: Color,
+    pub highlight_fg: Color,
+    /// Provider badge in front of command names: config commands, and every provider when
+    /// `badge_palette` is empty.
+    pub badge: Color,
+    /// Badge colors other providers are spread over by name, so they stay apart.
+    pub badge_palette: Vec<Color>,
+    /// `[theme.providers]`: fixed badge colors by provider name.
+    pub provider_badges: BTreeMap<String, Color>,
+    /// Command names and other primary text.
+    pub text: Color,
+    pub popup_bg: Color,
+    pub info: Color,
+    pub command: Color,
+    pub stdout: Color,
+    pub stderr: Color,
+    /// Background of the sticky command header above scrolled output.
+    pub header_bg: Color,
+    /// `[safety]` warning banners.
+    pub banner_fg: Color,
+    pub banner_bg: Color,
+    /// Status of jobs, job panes, history entries, and dry-run safety verdicts.
+    pub success: Color,
+    pub error: Color,
+    pub warning: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            border: Color::Rgb(70, 84, 96),
+            border_active: Color::Rgb(88, 150, 201),
+            highlight_bg: Color::Rgb(42, 88, 116),
+            highlight_fg: Color::White,
+            badge: Color::LightCyan,
+            badge_palette: vec![
+                Color::LightGreen,
+                Color::LightYellow,
+                Color::LightMagenta,
+                Color::LightBlue,
+                Color::LightRed,
+                Color::Rgb(255, 175, 95),
+                Color::Rgb(135, 215, 175),
+                Color::Rgb(215, 175, 255),
+            ],
+            provider_badges: BTreeMap::new(),
+            text: Color::White,
+            popup_bg: Color::Black,
+            info: Color::Gray,
+            command: Color::Cyan,
+            stdout: Color::White,
+            stderr: Color::LightRed,
+            header_bg: Color::Rgb(30, 38, 46),
+            banner_fg: Color::White,
+            banner_bg: Color::Red,
+            success: Color::Green,
+            error: Color::Red,
+            warning: Color::Yellow,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            border: Color::Rgb(170, 178, 186),
+            border_active: Color::Rgb(36, 110, 180),
+            highlight_bg: Color::Rgb(200, 222, 242),
+            highlight_fg: Color::Black,
+            badge: Color::Rgb(0, 118, 140),
+            badge_palette: vec![
+                Color::Rgb(38, 128, 46),
+                Color::Rgb(160, 90, 0),
+                Color::Rgb(140, 50, 150),
+                Color::Rgb(36, 90, 180),
+                Color::Rgb(180, 40, 70),
+                Color::Rgb(90, 100, 20),
+                Color::Rgb(100, 70, 170),
+                Color::Rgb(0, 120, 100),
+            ],
+            provider_badges: BTreeMap::new(),
+            text: Color::Black,
+            popup_bg: Color::Rgb(248, 248, 248),
+            info: Color::Rgb(96, 96, 96),
+            command: Color::Rgb(0, 92, 160),
+            stdout: Color::Black,
+            stderr: Color::Rgb(180, 30, 30),
+            header_bg: Color::Rgb(228, 234, 240),
+            banner_fg: Color::White,
+            banner_bg: Color::Rgb(180, 30, 30),
+            success: Color::Rgb(38, 128, 46),
+            error: Color::Rgb(180, 30, 30),
+            warning: Color::Rgb(160, 90, 0),
+        }
+    }
+
+    pub fn solarized() -> Self {
+        Self {
+            border: Color::Rgb(88, 110, 117),
+            border_active: Color::Rgb(38, 139, 210),
+            highlight_bg: Color::Rgb(7, 54, 66),
+            highlight_fg: Color::Rgb(238, 232, 213),
+            badge: Color::Rgb(42, 161, 152),
+            badge_palette: vec![
+                Color::Rgb(133, 153, 0),
+                Color::Rgb(181, 137, 0),
+                Color::Rgb(203, 75, 22),
+                Color::Rgb(211, 54, 130),
+                Color::Rgb(108, 113, 196),
+                Color::Rgb(38, 139, 210),
+                Color::Rgb(220, 50, 47),
+            ],
+            provider_badges: BTreeMap::new(),
+            text: Color::Rgb(147, 161, 161),
+            popup_bg: Color::Rgb(0, 43, 54),
+            info: Color::Rgb(131, 148, 150),
+            command: Color::Rgb(181, 137, 0),
+            stdout: Color::Rgb(147, 161, 161),
+            stderr: Color::Rgb(220, 50, 47),
+            header_bg: Color::Rgb(7, 54, 66),
+            banner_fg: Color::Rgb(253, 246, 227),
+            banner_bg: Color::Rgb(220, 50, 47),
+            success: Color::Rgb(133, 153, 0),
+            error: Color::Rgb(220, 50, 47),
+            warning: Color::Rgb(181, 137, 0),
+        }
+    }
+
+    /// Starts from `preset` (default `dark`) and applies each color set in `[theme]`.
+    pub fn from_config(config: &ThemeConfig) -> Result<Self> {
+        let mut theme = match config.preset.as_deref().map(str::trim) {
+            None | Some("dark") => Self::dark(),
+            Some("light") => Self::light(),
+            Some("solarized") => Self::solarized(),
+            Some(other) => bail!("unknown theme preset '{other}' (dark, light, solarized)"),
+        };
+        let overrides = [
+            ("border", &config.border, &mut theme.border),
+            (
+                "border_active",
+                &config.border_active,
+                &mut theme.border_active,
+            ),
+            (
+                "highlight_bg",
+                &config.highlight_bg,
+                &mut theme.highlight_bg,
+            ),
+            (
+                "highlight_fg",
+                &config.highlight_fg,
+                &mut theme.highlight_fg,
+            ),
+            ("badge", &config.badge, &mut theme.badge),
+            ("text", &config.text, &mut theme.text),
+            ("popup_bg", &config.popup_bg, &mut theme.popup_bg),
+            ("info", &config.info, &mut theme.info),
+            ("command", &config.command, &mut theme.command),
+            ("stdout", &config.stdout, &mut theme.stdout),
+            ("stderr", &config.stderr, &mut theme.stderr),
+            ("header_bg", &config.header_bg, &mut theme.header_bg),
+            ("banner_fg", &config.banner_fg, &mut theme.banner_fg),
+            ("banner_bg", &config.banner_bg, &mut theme.banner_bg),
+            ("success", &config.success, &mut theme.success),
+            ("error", &config.error, &mut theme.error),
+            ("warning", &config.warning, &mut theme.warning),
+        ];
+        for (key, raw, slot) in overrides {
+            if let Some(raw) = raw {
+                *slot = parse_color(raw).with_context(|| format!("[theme] {key}"))?;
+            }
+        }
+        if config.badge.is_some() {
+            theme.badge_palette.clear();
+        }
+        for (provider, raw) in &config.providers {
+            let color =
+                parse_color(raw).with_context(|| format!("[theme.providers] {provider}"))?;
+            theme.provider_badges.insert(provider.clone(), color);
+        }
+        Ok(theme)
+    }
+
+    /// The provider's `[theme.providers]` color, else one picked from `badge_palette` by a hash
+    /// of its name, so it is the same in every session.
+    pub fn badge_color(&self, provider: &str) -> Color {
+        if let Some(color) = self.provider_badges.get(provider) {
+            return *color;
+        }
+        if provider == "config" || self.badge_palette.is_empty() {
+            return self.badge;
+        }
+        // FNV-1a: std's hasher may change between Rust releases.
+        let hash = provider
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        self.badge_palette[(hash % self.badge_palette.len() as u64) as usize]
+    }
+}
+
+/// A color name (`cyan`, `light-red`), `#rrggbb`, or a 0-255 palette index.
+fn parse_color(raw: &str) -> Result<Color> {
+    raw.trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("'{raw}' is not a color name, #rrggbb, or 0-255 index"))
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Color;
+
+    use super::Theme;
+    use crate::config::ThemeConfig;
+
+    #[test]
+    fn presets_take_overrides_and_reject_bad_colors() {
+        let config = ThemeConfig {
+            preset: Some("light".to_string()),
+            badge: Some("#ff8800".to_string()),
+            stderr: Some("magenta".to_string()),
+            border: Some("240".to_string()),
+            ..ThemeConfig::default()
+        };
+        let theme = Theme::from_config(&config).unwrap();
+        assert_eq!(theme.badge, Color::Rgb(255, 136, 0));
+        assert_eq!(theme.stderr, Color::Magenta);
+        assert_eq!(theme.border, Color::Indexed(240));
+        assert_eq!(theme.text, Theme::light().text);
+        assert_eq!(theme.success, Theme::light().success);
+        let warning = Theme::from_config(&ThemeConfig {
+            warning: Some("light-yellow".to_string()),
+            ..ThemeConfig::default()
+        })
+        .unwrap();
+        assert_eq!(warning.warning, Color::LightYellow);
+        assert_eq!(warning.error, Theme::dark().error);
+
+        assert_eq!(
+            Theme::from_config(&ThemeConfig::default()).unwrap(),
+            Theme::dark()
+        );
+        let bad = ThemeConfig {
+            info: Some("not-a-color".to_string()),
+            ..ThemeConfig::default()
+        };
+        assert!(Theme::from_config(&bad).is_err());
+        let unknown = ThemeConfig {
+            preset: Some("neon".to_string()),
+            ..ThemeConfig::default()
+        };
+        assert!(Theme::from_config(&unknown).is_err());
+    }
+
+    #[test]
+    fn provider_badges_are_derived_from_the_name_unless_overridden() {
+        let theme = Theme::dark();
+        assert_eq!(theme.badge_color("config"), theme.badge);
+        assert_eq!(theme.badge_color("npm"), theme.badge_color("npm"));
+        let colors: std::collections::BTreeSet<String> =
+            ["npm", "composer", "docker", "git", "artisan", "justfile"]
+                .iter()
+                .map(|provider| format!("{:?}", theme.badge_color(provider)))
+                .collect();
+        assert!(colors.len() > 2);
+
+        let config = ThemeConfig {
+            providers: [("npm".to_string(), "red".to_string())].into(),
+            ..ThemeConfig::default()
+        };
+        let theme = Theme::from_config(&config).unwrap();
+        assert_eq!(theme.badge_color("npm"), Color::Red);
+
+        let config = ThemeConfig {
+            badge: Some("blue".to_string()),
+            ..ThemeConfig::default()
+        };
+        let theme = Theme::from_config(&config).unwrap();
+        assert_eq!(theme.badge_color("docker"), Color::Blue);
+    }
+}